@@ -0,0 +1,112 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// Bind Address Configuration
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    env::Env,
+    settings::{ConfigEntry, SetupVarsEntry, ValueType},
+    util::{Error, ErrorKind}
+};
+
+/// Get the address and port for the API to listen on. They are read from
+/// `SetupVarsEntry::ApiBindAddress` and `SetupVarsEntry::ApiPort` when the API
+/// starts, so changing them requires a restart. If they are not set, the
+/// values from the API config are used. Invalid values are an error instead
+/// of falling back, so a typo does not silently bind to every interface.
+pub fn bind_address(env: &Env) -> Result<(String, u16), Error> {
+    let address = SetupVarsEntry::ApiBindAddress.read(env)?;
+    let address = if address.is_empty() {
+        env.config().address().to_owned()
+    } else if ValueType::IpAddress.is_valid(&address) {
+        address
+    } else {
+        return Err(Error::from(ErrorKind::BindConfig(format!(
+            "{} is not a valid IP address",
+            address
+        ))));
+    };
+
+    let port = SetupVarsEntry::ApiPort.read(env)?;
+    let port = if port.is_empty() {
+        env.config().port().to_string()
+    } else {
+        port
+    };
+
+    // Port 0 would bind to a random port
+    match port.parse::<u16>() {
+        Ok(port) if port != 0 => Ok((address, port)),
+        _ => Err(Error::from(ErrorKind::BindConfig(format!(
+            "{} is not a port from 1 to 65535",
+            port
+        ))))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::bind_address;
+    use crate::{
+        env::{Config, Env, PiholeFile},
+        testing::TestEnvBuilder,
+        util::ErrorKind
+    };
+
+    /// Create a test environment with the setupVars.conf contents
+    fn test_env(setup_vars: &str) -> Env {
+        Env::Test(
+            Config::default(),
+            TestEnvBuilder::new()
+                .file(PiholeFile::SetupVars, setup_vars)
+                .build()
+        )
+    }
+
+    /// The API config is used if the address and port are not set
+    #[test]
+    fn default_address() {
+        assert_eq!(
+            bind_address(&test_env("")).unwrap(),
+            ("0.0.0.0".to_owned(), 80)
+        );
+    }
+
+    /// The configured address and port override the API config
+    #[test]
+    fn configured_address() {
+        assert_eq!(
+            bind_address(&test_env("API_BIND_ADDRESS=::1\nAPI_PORT=8080")).unwrap(),
+            ("::1".to_owned(), 8080)
+        );
+    }
+
+    /// An address which is not an IP address is an error
+    #[test]
+    fn invalid_address() {
+        let error = bind_address(&test_env("API_BIND_ADDRESS=localhost")).unwrap_err();
+
+        assert_eq!(
+            error.kind(),
+            ErrorKind::BindConfig("localhost is not a valid IP address".to_owned())
+        );
+    }
+
+    /// Ports must be from 1 to 65535
+    #[test]
+    fn invalid_port() {
+        for port in &["0", "65536", "http"] {
+            let error = bind_address(&test_env(&format!("API_PORT={}", port))).unwrap_err();
+
+            assert_eq!(
+                error.kind(),
+                ErrorKind::BindConfig(format!("{} is not a port from 1 to 65535", port))
+            );
+        }
+    }
+}
@@ -0,0 +1,230 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// Response Compression
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use flate2::{
+    write::{GzEncoder, ZlibEncoder},
+    Compression as CompressionLevel
+};
+use rocket::{
+    fairing::{Fairing, Info, Kind},
+    Request, Response
+};
+use std::io::{self, Cursor, Write};
+
+/// Responses smaller than this many bytes are not compressed, because the
+/// savings would not be worth the overhead
+pub const COMPRESSION_THRESHOLD: usize = 1024;
+
+/// The encodings which the API can compress responses with
+#[derive(Copy, Clone, PartialEq, Debug)]
+enum Encoding {
+    Gzip,
+    Deflate
+}
+
+impl Encoding {
+    /// Get the name of the encoding, as used in the `Content-Encoding` header
+    fn name(self) -> &'static str {
+        match self {
+            Encoding::Gzip => "gzip",
+            Encoding::Deflate => "deflate"
+        }
+    }
+
+    /// Compress the data with this encoding
+    fn compress(self, data: &[u8]) -> io::Result<Vec<u8>> {
+        match self {
+            Encoding::Gzip => {
+                let mut encoder = GzEncoder::new(Vec::new(), CompressionLevel::default());
+                encoder.write_all(data)?;
+                encoder.finish()
+            }
+            Encoding::Deflate => {
+                let mut encoder = ZlibEncoder::new(Vec::new(), CompressionLevel::default());
+                encoder.write_all(data)?;
+                encoder.finish()
+            }
+        }
+    }
+}
+
+/// A fairing which compresses responses if the client accepts gzip or deflate
+/// encoding. Responses under [`COMPRESSION_THRESHOLD`] bytes, and responses
+/// which are already encoded, are left alone.
+///
+/// [`COMPRESSION_THRESHOLD`]: constant.COMPRESSION_THRESHOLD.html
+pub struct Compression;
+
+impl Fairing for Compression {
+    fn info(&self) -> Info {
+        Info {
+            name: "Response Compression",
+            kind: Kind::Response
+        }
+    }
+
+    fn on_response(&self, request: &Request, response: &mut Response) {
+        if response.headers().contains("Content-Encoding") {
+            return;
+        }
+
+        let encoding = match accepted_encoding(request) {
+            Some(encoding) => encoding,
+            None => return
+        };
+
+        let body = match response.body_bytes() {
+            Some(body) => body,
+            None => return
+        };
+
+        // The response varies by the accepted encodings, even if it ends up
+        // not being compressed
+        response.adjoin_raw_header("Vary", "Accept-Encoding");
+
+        if body.len() < COMPRESSION_THRESHOLD {
+            response.set_sized_body(Cursor::new(body));
+            return;
+        }
+
+        match encoding.compress(&body) {
+            Ok(compressed) => {
+                response.set_raw_header("Content-Encoding", encoding.name());
+                response.set_sized_body(Cursor::new(compressed));
+            }
+            // Fall back to the uncompressed body
+            Err(_) => response.set_sized_body(Cursor::new(body))
+        }
+    }
+}
+
+/// Find the encoding to compress the response with, according to the
+/// `Accept-Encoding` header. Gzip is preferred over deflate. Encodings with a
+/// quality value of zero are not accepted.
+fn accepted_encoding(request: &Request) -> Option<Encoding> {
+    let accepted: Vec<String> = request
+        .headers()
+        .get("Accept-Encoding")
+        .flat_map(|header| header.split(','))
+        .filter_map(|item| {
+            let mut parts = item.split(';').map(str::trim);
+            let name = parts.next()?.to_lowercase();
+            let refused = parts.any(|param| {
+                param.starts_with("q=")
+                    && param[2..]
+                        .parse::<f32>()
+                        .map_or(false, |quality| quality <= 0.0)
+            });
+
+            if refused {
+                None
+            } else {
+                Some(name)
+            }
+        })
+        .collect();
+
+    [Encoding::Gzip, Encoding::Deflate]
+        .iter()
+        .cloned()
+        .find(|encoding| accepted.iter().any(|name| name == encoding.name()))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Compression, COMPRESSION_THRESHOLD};
+    use flate2::read::{GzDecoder, ZlibDecoder};
+    use rocket::{
+        http::{Header, Status},
+        local::Client
+    };
+    use std::io::Read;
+
+    #[get("/small")]
+    fn small() -> &'static str {
+        "small"
+    }
+
+    #[get("/large")]
+    fn large() -> String {
+        "a".repeat(COMPRESSION_THRESHOLD)
+    }
+
+    /// Request the endpoint, and get the `Content-Encoding` header and body
+    fn request(endpoint: &str, accept_encoding: Option<&'static str>) -> (Option<String>, Vec<u8>) {
+        let client = Client::new(
+            rocket::ignite()
+                .attach(Compression)
+                .mount("/", routes![small, large])
+        )
+        .unwrap();
+        let mut request = client.get(endpoint.to_owned());
+
+        if let Some(accept_encoding) = accept_encoding {
+            request.add_header(Header::new("Accept-Encoding", accept_encoding));
+        }
+
+        let mut response = request.dispatch();
+        assert_eq!(response.status(), Status::Ok);
+
+        let encoding = response
+            .headers()
+            .get_one("Content-Encoding")
+            .map(ToOwned::to_owned);
+        let body = response.body_bytes().unwrap();
+
+        (encoding, body)
+    }
+
+    /// Large responses are compressed with gzip if it is accepted
+    #[test]
+    fn gzip() {
+        let (encoding, body) = request("/large", Some("deflate, gzip"));
+        assert_eq!(encoding, Some("gzip".to_owned()));
+
+        let mut decompressed = String::new();
+        GzDecoder::new(body.as_slice())
+            .read_to_string(&mut decompressed)
+            .unwrap();
+        assert_eq!(decompressed, "a".repeat(COMPRESSION_THRESHOLD));
+    }
+
+    /// Large responses are compressed with deflate if gzip is not accepted
+    #[test]
+    fn deflate() {
+        let (encoding, body) = request("/large", Some("gzip;q=0, deflate"));
+        assert_eq!(encoding, Some("deflate".to_owned()));
+
+        let mut decompressed = String::new();
+        ZlibDecoder::new(body.as_slice())
+            .read_to_string(&mut decompressed)
+            .unwrap();
+        assert_eq!(decompressed, "a".repeat(COMPRESSION_THRESHOLD));
+    }
+
+    /// Responses are not compressed if the client does not accept compression
+    #[test]
+    fn not_accepted() {
+        let (encoding, body) = request("/large", None);
+        assert_eq!(encoding, None);
+        assert_eq!(body, "a".repeat(COMPRESSION_THRESHOLD).into_bytes());
+
+        let (encoding, _) = request("/large", Some("br"));
+        assert_eq!(encoding, None);
+    }
+
+    /// Responses under the threshold are not compressed
+    #[test]
+    fn small_response() {
+        let (encoding, body) = request("/small", Some("gzip"));
+        assert_eq!(encoding, None);
+        assert_eq!(body, b"small".to_vec());
+    }
+}
@@ -0,0 +1,127 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// Cross-Origin Resource Sharing
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    env::Env,
+    settings::{ConfigEntry, SetupVarsEntry},
+    util::{Error, ErrorKind}
+};
+use rocket_cors::{AllowedOrigins, Cors};
+
+/// The origin which allows every origin
+const WILDCARD_ORIGIN: &str = "*";
+
+/// Create the CORS fairing from `SetupVarsEntry::ApiCorsOrigins`, or `None`
+/// if no origins are allowed. Exact origins may send credentials (the session
+/// cookie). The wildcard origin can not send credentials, so it must be the
+/// only origin.
+pub fn cors_fairing(env: &Env) -> Result<Option<Cors>, Error> {
+    let origins = SetupVarsEntry::ApiCorsOrigins.read_list(env)?;
+
+    if origins.is_empty() {
+        return Ok(None);
+    }
+
+    if origins.iter().any(|origin| origin == WILDCARD_ORIGIN) {
+        if origins.len() > 1 {
+            return Err(Error::from(ErrorKind::CorsConfig(
+                "the wildcard origin does not allow credentials, so it can not be combined with \
+                 other origins"
+                    .to_owned()
+            )));
+        }
+
+        return Ok(Some(Cors {
+            allowed_origins: AllowedOrigins::all(),
+            allow_credentials: false,
+            send_wildcard: true,
+            ..Cors::default()
+        }));
+    }
+
+    let origins: Vec<&str> = origins.iter().map(String::as_str).collect();
+    let (allowed_origins, failed_origins) = AllowedOrigins::some(&origins);
+
+    if let Some(origin) = failed_origins.keys().next() {
+        return Err(Error::from(ErrorKind::CorsConfig(format!(
+            "{} is not a valid origin",
+            origin
+        ))));
+    }
+
+    Ok(Some(Cors {
+        allowed_origins,
+        allow_credentials: true,
+        ..Cors::default()
+    }))
+}
+
+#[cfg(test)]
+mod test {
+    use super::cors_fairing;
+    use crate::{
+        env::{Config, Env, PiholeFile},
+        testing::TestEnvBuilder,
+        util::ErrorKind
+    };
+
+    /// Create a test environment with the CORS origins setting
+    fn test_env(origins: &str) -> Env {
+        Env::Test(
+            Config::default(),
+            TestEnvBuilder::new()
+                .file(
+                    PiholeFile::SetupVars,
+                    &format!("API_CORS_ORIGINS={}", origins)
+                )
+                .build()
+        )
+    }
+
+    /// CORS is disabled by default
+    #[test]
+    fn disabled() {
+        assert!(cors_fairing(&test_env("")).unwrap().is_none());
+    }
+
+    /// Exact origins are allowed to send credentials
+    #[test]
+    fn exact_origins() {
+        let cors = cors_fairing(&test_env("https://a.example.com,http://b.example.com:8080"))
+            .unwrap()
+            .unwrap();
+
+        assert!(cors.allow_credentials);
+        assert!(!cors.allowed_origins.is_all());
+    }
+
+    /// The wildcard origin allows every origin, without credentials
+    #[test]
+    fn wildcard() {
+        let cors = cors_fairing(&test_env("*")).unwrap().unwrap();
+
+        assert!(!cors.allow_credentials);
+        assert!(cors.send_wildcard);
+        assert!(cors.allowed_origins.is_all());
+    }
+
+    /// The wildcard origin can not be combined with other origins
+    #[test]
+    fn wildcard_with_origins() {
+        let error = cors_fairing(&test_env("https://a.example.com,*"))
+            .err()
+            .unwrap();
+
+        match error.kind() {
+            ErrorKind::CorsConfig(_) => (),
+            kind => panic!("Unexpected error: {:?}", kind)
+        }
+    }
+}
@@ -8,7 +8,7 @@
 // This file is copyright under the latest version of the EUPL.
 // Please see LICENSE file for your rights under this license.
 
-use crate::ftl::{FtlDnssecType, FtlQueryReplyType};
+use crate::ftl::{FtlDnssecType, FtlQueryReplyType, FtlQueryStatus};
 use rocket_contrib::json::JsonValue;
 
 #[database("ftl_database")]
@@ -45,10 +45,13 @@ impl Into<JsonValue> for FtlDbQuery {
             "timestamp": self.timestamp,
             "type": self.query_type as u8,
             "status": self.status as u8,
+            "status_name": FtlQueryStatus::name_from_number(self.status as isize),
             "domain": self.domain,
             "client": self.client,
+            "upstream": self.upstream,
             "dnssec": FtlDnssecType::Unknown as u8,
             "reply": FtlQueryReplyType::Unknown as u8,
+            "reply_name": FtlQueryReplyType::Unknown.get_name(),
             "response_time": 0
         })
     }
@@ -0,0 +1,219 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// Gravity Domains Access
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    env::{Env, PiholeFile},
+    settings::{ConfigEntry, SetupVarsEntry},
+    util::Error
+};
+use std::{
+    sync::Mutex,
+    time::{Duration, Instant}
+};
+
+/// Caches the number of domains blocked by gravity and when gravity was last
+/// updated, so that the gravity list does not need to be read on every
+/// request. The info is refreshed lazily, at most once per
+/// `API_GRAVITY_COUNT_TTL` seconds.
+pub struct GravityCountCache {
+    cached: Mutex<Option<(Instant, GravityInfo)>>
+}
+
+/// Information about the gravity list
+#[derive(Copy, Clone, Serialize)]
+#[cfg_attr(test, derive(PartialEq, Debug))]
+pub struct GravityInfo {
+    /// When gravity was last updated, as a Unix timestamp
+    pub updated: Option<u64>,
+    /// The number of domains blocked by gravity
+    pub domain_count: Option<usize>
+}
+
+impl GravityCountCache {
+    /// Create a new, empty cache
+    pub fn new() -> GravityCountCache {
+        GravityCountCache {
+            cached: Mutex::new(None)
+        }
+    }
+
+    /// Get the number of domains blocked by gravity. `None` is returned if the
+    /// gravity list is missing or can not be read.
+    pub fn get(&self, env: &Env) -> Option<usize> {
+        self.get_info(env).domain_count
+    }
+
+    /// Get the gravity info. If there is no cached info, or it is older than
+    /// the TTL, it is read again. The fields are `None` if the gravity list is
+    /// missing or can not be read.
+    pub fn get_info(&self, env: &Env) -> GravityInfo {
+        // Fall back to the default TTL if the setting is invalid
+        let ttl = Duration::from_secs(
+            SetupVarsEntry::ApiGravityCountTtl
+                .read_as(env)
+                .unwrap_or(60)
+        );
+        let mut cached = self.cached.lock().unwrap();
+
+        if let Some((updated, info)) = *cached {
+            if updated.elapsed() < ttl {
+                return info;
+            }
+        }
+
+        let info = GravityInfo {
+            updated: env.file_modified(PiholeFile::Gravity),
+            domain_count: count_gravity_domains(env)
+        };
+        *cached = Some((Instant::now(), info));
+
+        info
+    }
+}
+
+/// Count the domains in the gravity list
+fn count_gravity_domains(env: &Env) -> Option<usize> {
+    if !env.file_exists(PiholeFile::Gravity) {
+        return None;
+    }
+
+    env.read_file_lines(PiholeFile::Gravity)
+        .ok()
+        .map(|lines| lines.iter().filter(|line| !line.trim().is_empty()).count())
+}
+
+/// Check if the domain is blocked by gravity. A missing gravity list blocks
+/// nothing.
+pub fn is_gravity_domain(env: &Env, domain: &str) -> Result<bool, Error> {
+    if !env.file_exists(PiholeFile::Gravity) {
+        return Ok(false);
+    }
+
+    // Older gravity lists have the format `IP domain`, so the domain is the
+    // last word of the line
+    Ok(env
+        .read_file_lines(PiholeFile::Gravity)?
+        .iter()
+        .filter_map(|line| line.split_whitespace().last())
+        .any(|gravity_domain| gravity_domain.eq_ignore_ascii_case(domain)))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{is_gravity_domain, GravityCountCache};
+    use crate::{
+        env::{Config, Env, PiholeFile},
+        testing::TestEnvBuilder
+    };
+    use std::collections::HashMap;
+
+    /// The domains in the gravity list are counted
+    #[test]
+    fn count() {
+        let env = Env::Test(
+            Config::default(),
+            TestEnvBuilder::new()
+                .file(
+                    PiholeFile::Gravity,
+                    "domain1.com\ndomain2.com\n\ndomain3.com\n"
+                )
+                .build()
+        );
+
+        assert_eq!(GravityCountCache::new().get(&env), Some(3));
+    }
+
+    /// A missing gravity list has no count
+    #[test]
+    fn missing() {
+        let env = Env::Test(Config::default(), HashMap::new());
+
+        assert_eq!(GravityCountCache::new().get(&env), None);
+    }
+
+    /// The count is cached, so changes to the gravity list are not seen until
+    /// the cached count expires
+    #[test]
+    fn cached() {
+        let cache = GravityCountCache::new();
+        let env = Env::Test(
+            Config::default(),
+            TestEnvBuilder::new()
+                .file(PiholeFile::Gravity, "domain1.com\n")
+                .build()
+        );
+
+        assert_eq!(cache.get(&env), Some(1));
+
+        let env = Env::Test(Config::default(), HashMap::new());
+
+        assert_eq!(cache.get(&env), Some(1));
+    }
+
+    /// A TTL of zero reads the gravity list on every call
+    #[test]
+    fn zero_ttl() {
+        let cache = GravityCountCache::new();
+        let env = Env::Test(
+            Config::default(),
+            TestEnvBuilder::new()
+                .file(PiholeFile::Gravity, "domain1.com\n")
+                .file(PiholeFile::SetupVars, "API_GRAVITY_COUNT_TTL=0")
+                .build()
+        );
+
+        assert_eq!(cache.get(&env), Some(1));
+
+        let env = Env::Test(
+            Config::default(),
+            TestEnvBuilder::new()
+                .file(PiholeFile::SetupVars, "API_GRAVITY_COUNT_TTL=0")
+                .build()
+        );
+
+        assert_eq!(cache.get(&env), None);
+    }
+
+    /// The info has the time the gravity list was modified, and is cached
+    /// like the count
+    #[test]
+    fn info() {
+        let cache = GravityCountCache::new();
+        let env = Env::Test(
+            Config::default(),
+            TestEnvBuilder::new()
+                .file(PiholeFile::Gravity, "domain1.com\n")
+                .build()
+        );
+        let info = cache.get_info(&env);
+
+        assert!(info.updated.is_some());
+        assert_eq!(info.domain_count, Some(1));
+
+        let env = Env::Test(Config::default(), HashMap::new());
+
+        assert_eq!(cache.get_info(&env), info);
+    }
+
+    /// Domains are found in both gravity list formats
+    #[test]
+    fn gravity_domain() {
+        let env = Env::Test(
+            Config::default(),
+            TestEnvBuilder::new()
+                .file(PiholeFile::Gravity, "domain1.com\n0.0.0.0 domain2.com\n")
+                .build()
+        );
+
+        assert!(is_gravity_domain(&env, "domain1.com").unwrap());
+        assert!(is_gravity_domain(&env, "domain2.com").unwrap());
+        assert!(!is_gravity_domain(&env, "domain3.com").unwrap());
+    }
+}
@@ -20,6 +20,7 @@ use std::collections::HashMap;
 use crate::databases::ftl::TEST_FTL_DATABASE_PATH;
 
 pub mod ftl;
+pub mod gravity;
 
 /// Load the database URLs from the API config into the Rocket config format
 pub fn load_databases(env: &Env) -> Result<HashMap<&str, HashMap<&str, Value>>, Error> {
@@ -83,7 +83,14 @@ impl Config {
             PiholeFile::Gravity => &self.file_locations.gravity,
             PiholeFile::GravityBackup => &self.file_locations.gravity_backup,
             PiholeFile::BlackList => &self.file_locations.black_list,
-            PiholeFile::BlackListBackup => &self.file_locations.black_list_backup
+            PiholeFile::BlackListBackup => &self.file_locations.black_list_backup,
+            PiholeFile::CustomList => &self.file_locations.custom_list,
+            PiholeFile::ListComments => &self.file_locations.list_comments,
+            PiholeFile::DhcpLeases => &self.file_locations.dhcp_leases,
+            PiholeFile::DhcpStaticLeases => &self.file_locations.dhcp_static_leases,
+            PiholeFile::CustomCnameList => &self.file_locations.custom_cname_list,
+            PiholeFile::Adlists => &self.file_locations.adlists,
+            PiholeFile::ListAuditLog => &self.file_locations.list_audit_log
         }
     }
 
@@ -130,7 +137,21 @@ pub struct Files {
     #[serde(default = "default_black_list")]
     black_list: String,
     #[serde(default = "default_black_list_backup")]
-    black_list_backup: String
+    black_list_backup: String,
+    #[serde(default = "default_custom_list")]
+    custom_list: String,
+    #[serde(default = "default_list_comments")]
+    list_comments: String,
+    #[serde(default = "default_dhcp_leases")]
+    dhcp_leases: String,
+    #[serde(default = "default_dhcp_static_leases")]
+    dhcp_static_leases: String,
+    #[serde(default = "default_custom_cname_list")]
+    custom_cname_list: String,
+    #[serde(default = "default_adlists")]
+    adlists: String,
+    #[serde(default = "default_list_audit_log")]
+    list_audit_log: String
 }
 
 impl Default for Files {
@@ -148,7 +169,14 @@ impl Default for Files {
             gravity: default_gravity(),
             gravity_backup: default_gravity_backup(),
             black_list: default_black_list(),
-            black_list_backup: default_black_list_backup()
+            black_list_backup: default_black_list_backup(),
+            custom_list: default_custom_list(),
+            list_comments: default_list_comments(),
+            dhcp_leases: default_dhcp_leases(),
+            dhcp_static_leases: default_dhcp_static_leases(),
+            custom_cname_list: default_custom_cname_list(),
+            adlists: default_adlists(),
+            list_audit_log: default_list_audit_log()
         }
     }
 }
@@ -168,7 +196,14 @@ impl Files {
             &self.gravity,
             &self.gravity_backup,
             &self.black_list,
-            &self.black_list_backup
+            &self.black_list_backup,
+            &self.custom_list,
+            &self.list_comments,
+            &self.dhcp_leases,
+            &self.dhcp_static_leases,
+            &self.custom_cname_list,
+            &self.adlists,
+            &self.list_audit_log
         ]
         .iter()
         .all(|file| Path::new(file).is_absolute())
@@ -197,6 +232,13 @@ default!(default_gravity, Gravity);
 default!(default_gravity_backup, GravityBackup);
 default!(default_black_list, BlackList);
 default!(default_black_list_backup, BlackListBackup);
+default!(default_custom_list, CustomList);
+default!(default_list_comments, ListComments);
+default!(default_dhcp_leases, DhcpLeases);
+default!(default_dhcp_static_leases, DhcpStaticLeases);
+default!(default_custom_cname_list, CustomCnameList);
+default!(default_adlists, Adlists);
+default!(default_list_audit_log, ListAuditLog);
 
 /// General config settings
 #[derive(Deserialize, Clone)]
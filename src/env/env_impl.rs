@@ -17,13 +17,14 @@ use std::{
     fs::{self, File, OpenOptions},
     io::{BufRead, BufReader},
     os::unix::fs::OpenOptionsExt,
-    path::Path
+    path::Path,
+    time::UNIX_EPOCH
 };
 
 #[cfg(test)]
 use std::{
     collections::HashMap,
-    io::{Read, Write}
+    io::{Read, Seek, SeekFrom, Write}
 };
 #[cfg(test)]
 use tempfile::{tempfile, NamedTempFile};
@@ -116,12 +117,16 @@ impl Env {
             }
             #[cfg(test)]
             Env::Test(_, map) => {
-                let file = match map.get(&file) {
+                let mut file = match map.get(&file) {
                     Some(file) => file.reopen().context(ErrorKind::Unknown)?,
                     None => return tempfile().context(ErrorKind::Unknown).map_err(Error::from)
                 };
 
-                if !append {
+                if append {
+                    // The reopened file starts at the beginning, so move to
+                    // the end to append
+                    file.seek(SeekFrom::End(0)).context(ErrorKind::Unknown)?;
+                } else {
                     file.set_len(0).context(ErrorKind::Unknown)?;
                 }
 
@@ -180,6 +185,23 @@ impl Env {
         }
     }
 
+    /// Get when a file was last modified, as a Unix timestamp. `None` is
+    /// returned if the file does not exist or the time can not be read.
+    pub fn file_modified(&self, file: PiholeFile) -> Option<u64> {
+        let metadata = match self {
+            Env::Production(_) => fs::metadata(self.file_location(file)).ok()?,
+            #[cfg(test)]
+            Env::Test(_, map) => map.get(&file)?.as_file().metadata().ok()?
+        };
+
+        metadata
+            .modified()
+            .ok()?
+            .duration_since(UNIX_EPOCH)
+            .ok()
+            .map(|duration| duration.as_secs())
+    }
+
     /// Check if we're in a testing environment
     pub fn is_test(&self) -> bool {
         match self {
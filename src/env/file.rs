@@ -23,7 +23,14 @@ pub enum PiholeFile {
     Gravity,
     GravityBackup,
     BlackList,
-    BlackListBackup
+    BlackListBackup,
+    CustomList,
+    ListComments,
+    DhcpLeases,
+    DhcpStaticLeases,
+    CustomCnameList,
+    Adlists,
+    ListAuditLog
 }
 
 impl PiholeFile {
@@ -42,7 +49,14 @@ impl PiholeFile {
             PiholeFile::Gravity => "/etc/pihole/gravity.list",
             PiholeFile::GravityBackup => "/etc/pihole/gravity.list.bck",
             PiholeFile::BlackList => "/etc/pihole/black.list",
-            PiholeFile::BlackListBackup => "/etc/pihole/black.list.bck"
+            PiholeFile::BlackListBackup => "/etc/pihole/black.list.bck",
+            PiholeFile::CustomList => "/etc/pihole/custom.list",
+            PiholeFile::ListComments => "/etc/pihole/list_comments.list",
+            PiholeFile::DhcpLeases => "/etc/pihole/dhcp.leases",
+            PiholeFile::DhcpStaticLeases => "/etc/dnsmasq.d/04-pihole-static-dhcp.conf",
+            PiholeFile::CustomCnameList => "/etc/dnsmasq.d/05-pihole-custom-cname.conf",
+            PiholeFile::Adlists => "/etc/pihole/adlists.list",
+            PiholeFile::ListAuditLog => "/etc/pihole/list_audit.log"
         }
     }
 }
@@ -0,0 +1,157 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// FTL Shared Memory Request Guard
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    ftl::{FtlClient, FtlCounters, FtlMemory, FtlQuery, FtlStrings, ShmLockGuard},
+    util::{Error, ErrorKind}
+};
+use rocket::{
+    request::{self, FromRequest, Request, State},
+    Outcome
+};
+use std::{
+    cell::{Ref, RefCell},
+    ops::Deref
+};
+
+/// A request guard which locks FTL's shared memory once for the whole
+/// request. Each section of shared memory is attached the first time it is
+/// used, and that attachment is reused for the rest of the request.
+///
+/// Use it as `Result<FtlMemoryGuard, Error>` in a route so that a failure to
+/// lock shared memory is returned as a normal error reply.
+pub struct FtlMemoryGuard<'r> {
+    memory: &'r FtlMemory,
+    counters: RefCell<Option<Box<dyn Deref<Target = FtlCounters> + 'r>>>,
+    clients: RefCell<Option<Box<dyn Deref<Target = [FtlClient]> + 'r>>>,
+    queries: RefCell<Option<Box<dyn Deref<Target = [FtlQuery]> + 'r>>>,
+    strings: RefCell<Option<FtlStrings<'r>>>,
+    // The lock is declared last so that it is released after the shared memory
+    // is detached
+    lock: ShmLockGuard<'r>
+}
+
+impl<'r> FtlMemoryGuard<'r> {
+    /// Lock the shared memory for the lifetime of the guard
+    pub fn new(memory: &'r FtlMemory) -> Result<FtlMemoryGuard<'r>, Error> {
+        Ok(FtlMemoryGuard {
+            memory,
+            counters: RefCell::new(None),
+            clients: RefCell::new(None),
+            queries: RefCell::new(None),
+            strings: RefCell::new(None),
+            lock: memory.lock()?
+        })
+    }
+
+    /// Get the FTL shared memory counters data
+    pub fn counters(&self) -> Result<Ref<FtlCounters>, Error> {
+        let counters = load_cached(&self.counters, || self.memory.counters(&self.lock))?;
+        Ok(Ref::map(counters, |counters| &***counters))
+    }
+
+    /// Get the FTL shared memory client data
+    pub fn clients(&self) -> Result<Ref<[FtlClient]>, Error> {
+        let clients = load_cached(&self.clients, || self.memory.clients(&self.lock))?;
+        Ok(Ref::map(clients, |clients| &***clients))
+    }
+
+    /// Get the FTL shared memory query data
+    pub fn queries(&self) -> Result<Ref<[FtlQuery]>, Error> {
+        let queries = load_cached(&self.queries, || self.memory.queries(&self.lock))?;
+        Ok(Ref::map(queries, |queries| &***queries))
+    }
+
+    /// Get the FTL shared memory string data
+    pub fn strings(&self) -> Result<Ref<FtlStrings<'r>>, Error> {
+        load_cached(&self.strings, || self.memory.strings(&self.lock))
+    }
+}
+
+/// Get the cached value, or load and cache it if this is the first use
+fn load_cached<T>(
+    cache: &RefCell<Option<T>>,
+    load: impl FnOnce() -> Result<T, Error>
+) -> Result<Ref<T>, Error> {
+    if cache.borrow().is_none() {
+        let value = load()?;
+        *cache.borrow_mut() = Some(value);
+    }
+
+    Ok(Ref::map(cache.borrow(), |value| value.as_ref().unwrap()))
+}
+
+impl<'a, 'r> FromRequest<'a, 'r> for FtlMemoryGuard<'r> {
+    type Error = Error;
+
+    fn from_request(request: &'a Request<'r>) -> request::Outcome<Self, Self::Error> {
+        let memory: State<'r, FtlMemory> = match request.guard().succeeded() {
+            Some(memory) => memory,
+            None => return Error::from(ErrorKind::Unknown).into_outcome()
+        };
+
+        match FtlMemoryGuard::new(memory.inner()) {
+            Ok(guard) => Outcome::Success(guard),
+            Err(e) => e.into_outcome()
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::FtlMemoryGuard;
+    use crate::ftl::{FtlClient, FtlCounters, FtlMemory, FtlSettings};
+    use std::collections::HashMap;
+
+    fn test_data() -> FtlMemory {
+        let mut strings = HashMap::new();
+        strings.insert(1, "10.1.1.1".to_owned());
+
+        FtlMemory::Test {
+            clients: vec![FtlClient::new(1, 0, 1, None)],
+            domains: Vec::new(),
+            over_time: Vec::new(),
+            upstreams: Vec::new(),
+            queries: Vec::new(),
+            strings,
+            counters: FtlCounters {
+                total_clients: 1,
+                ..FtlCounters::default()
+            },
+            settings: FtlSettings::default()
+        }
+    }
+
+    /// The same attachment is handed out each time a section is used
+    #[test]
+    fn attachment_reused() {
+        let memory = test_data();
+        let guard = FtlMemoryGuard::new(&memory).unwrap();
+
+        let first = guard.clients().unwrap().as_ptr();
+        let second = guard.clients().unwrap().as_ptr();
+
+        assert_eq!(first, second);
+    }
+
+    /// Different sections can be used at the same time
+    #[test]
+    fn sections_used_together() {
+        let memory = test_data();
+        let guard = FtlMemoryGuard::new(&memory).unwrap();
+
+        let counters = guard.counters().unwrap();
+        let clients = guard.clients().unwrap();
+        let strings = guard.strings().unwrap();
+
+        assert_eq!(counters.total_clients, 1);
+        assert_eq!(clients[0].get_ip(&strings), "10.1.1.1");
+    }
+}
@@ -24,7 +24,11 @@ use std::fmt::{
 #[cfg_attr(test, derive(Debug, PartialEq))]
 pub struct ClientReply {
     pub name: String,
-    pub ip: String
+    pub ip: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mac: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub interface: Option<String>
 }
 
 /// The client struct stored in shared memory.
@@ -99,6 +103,20 @@ impl FtlClient {
         }
     }
 
+    /// Get the MAC address of the client, or `None` if FTL does not provide
+    /// it. Version 4 of FTL's shared memory does not include the MAC address
+    /// of clients, so it is never available.
+    pub fn get_mac<'a>(&self, _strings: &'a FtlStrings) -> Option<&'a str> {
+        None
+    }
+
+    /// Get the name of the interface the client was seen on, or `None` if FTL
+    /// does not provide it. Like the MAC address, it is not included in
+    /// version 4 of FTL's shared memory.
+    pub fn get_interface<'a>(&self, _strings: &'a FtlStrings) -> Option<&'a str> {
+        None
+    }
+
     /// Convert this FTL client into the reply format
     pub fn as_reply(&self, strings: &FtlStrings) -> ClientReply {
         let name = self.get_name(&strings).unwrap_or_default();
@@ -106,7 +124,9 @@ impl FtlClient {
 
         ClientReply {
             name: name.to_owned(),
-            ip: ip.to_owned()
+            ip: ip.to_owned(),
+            mac: self.get_mac(&strings).map(str::to_owned),
+            interface: self.get_interface(&strings).map(str::to_owned)
         }
     }
 }
@@ -175,3 +195,47 @@ impl Debug for FtlClient {
             .finish()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{ClientReply, FtlClient, FtlStrings};
+    use std::collections::HashMap;
+
+    /// Version 4 of FTL's shared memory has no MAC address or interface, so
+    /// they are left out of the reply
+    #[test]
+    fn reply_without_mac_or_interface() {
+        let mut data = HashMap::new();
+        data.insert(1, "10.1.1.1".to_owned());
+        let strings = FtlStrings::Test(&data);
+        let client = FtlClient::new(0, 0, 1, None);
+
+        assert_eq!(client.get_mac(&strings), None);
+        assert_eq!(client.get_interface(&strings), None);
+        assert_eq!(
+            serde_json::to_value(client.as_reply(&strings)).unwrap(),
+            json!({ "name": "", "ip": "10.1.1.1" })
+        );
+    }
+
+    /// The MAC address and interface are in the reply when they are known
+    #[test]
+    fn reply_with_mac_and_interface() {
+        let reply = ClientReply {
+            name: "laptop".to_owned(),
+            ip: "10.1.1.1".to_owned(),
+            mac: Some("aa:bb:cc:dd:ee:ff".to_owned()),
+            interface: Some("eth0".to_owned())
+        };
+
+        assert_eq!(
+            serde_json::to_value(reply).unwrap(),
+            json!({
+                "name": "laptop",
+                "ip": "10.1.1.1",
+                "mac": "aa:bb:cc:dd:ee:ff",
+                "interface": "eth0"
+            })
+        );
+    }
+}
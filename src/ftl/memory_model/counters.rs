@@ -63,9 +63,13 @@ pub enum FtlQueryType {
 impl<'v> FromFormValue<'v> for FtlQueryType {
     type Error = &'v RawStr;
 
+    /// Query types can be given by their number or their name
     fn from_form_value(form_value: &'v RawStr) -> Result<Self, Self::Error> {
-        let num = form_value.parse::<u8>().map_err(|_| form_value)?;
-        Self::from_number(num as isize).ok_or(form_value)
+        match form_value.parse::<u8>() {
+            Ok(num) => Self::from_number(num as isize),
+            Err(_) => Self::from_name(form_value.as_str())
+        }
+        .ok_or(form_value)
     }
 }
 
@@ -98,6 +102,14 @@ impl FtlQueryType {
         }
     }
 
+    /// Get the query type from its name, ignoring case
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::variants()
+            .iter()
+            .find(|query_type| query_type.get_name().eq_ignore_ascii_case(name))
+            .cloned()
+    }
+
     /// Get the name of the query type
     pub fn get_name(self) -> String {
         format!("{:?}", self)
@@ -8,7 +8,7 @@
 // This file is copyright under the latest version of the EUPL.
 // Please see LICENSE file for your rights under this license.
 
-use crate::ftl::FtlStrings;
+use crate::ftl::{CachedStrings, FtlStrings};
 use libc;
 
 #[cfg(test)]
@@ -49,6 +49,13 @@ impl FtlDomain {
             .get_str(self.domain_str_id as usize)
             .unwrap_or_default()
     }
+
+    /// Get the domain name using cached strings
+    pub fn get_domain_cached<'a>(&self, strings: &CachedStrings<'a>) -> &'a str {
+        strings
+            .get_str(self.domain_str_id as usize)
+            .unwrap_or_default()
+    }
 }
 
 #[cfg(test)]
@@ -33,6 +33,6 @@ pub use self::{
     over_time::*,
     query::{FtlDnssecType, FtlQuery, FtlQueryReplyType, FtlQueryStatus, BLOCKED_STATUSES},
     settings::FtlSettings,
-    strings::FtlStrings,
+    strings::{CachedStrings, FtlStrings},
     upstream::FtlUpstream
 };
@@ -80,6 +80,25 @@ impl FtlQueryStatus {
             _ => None
         }
     }
+
+    /// Get the name of the query status
+    pub fn get_name(self) -> &'static str {
+        match self {
+            FtlQueryStatus::Unknown => "unknown",
+            FtlQueryStatus::Gravity => "gravity",
+            FtlQueryStatus::Forward => "forwarded",
+            FtlQueryStatus::Cache => "cached",
+            FtlQueryStatus::Wildcard => "wildcard",
+            FtlQueryStatus::Blacklist => "blacklist",
+            FtlQueryStatus::ExternalBlock => "external_block"
+        }
+    }
+
+    /// Get the name of a query status from its ordinal value. Values which are
+    /// not known (such as from a newer version of FTL) are named "unknown".
+    pub fn name_from_number(num: isize) -> &'static str {
+        Self::from_number(num).map_or("unknown", Self::get_name)
+    }
 }
 
 impl<'v> FromFormValue<'v> for FtlQueryStatus {
@@ -110,6 +129,24 @@ pub enum FtlQueryReplyType {
 }
 
 impl FtlQueryReplyType {
+    /// A list of all `FtlQueryReplyType` variants, in order of their ordinal
+    /// values
+    pub fn variants() -> &'static [FtlQueryReplyType] {
+        &[
+            FtlQueryReplyType::Unknown,
+            FtlQueryReplyType::NODATA,
+            FtlQueryReplyType::NXDOMAIN,
+            FtlQueryReplyType::CNAME,
+            FtlQueryReplyType::IP,
+            FtlQueryReplyType::DOMAIN,
+            FtlQueryReplyType::RRNAME,
+            FtlQueryReplyType::SERVFAIL,
+            FtlQueryReplyType::REFUSED,
+            FtlQueryReplyType::NOTIMP,
+            FtlQueryReplyType::OTHER
+        ]
+    }
+
     /// Get the query reply type from its ordinal value
     pub fn from_number(num: isize) -> Option<Self> {
         match num {
@@ -127,6 +164,23 @@ impl FtlQueryReplyType {
             _ => None
         }
     }
+
+    /// Get the name of the query reply type
+    pub fn get_name(self) -> &'static str {
+        match self {
+            FtlQueryReplyType::Unknown => "unknown",
+            FtlQueryReplyType::NODATA => "nodata",
+            FtlQueryReplyType::NXDOMAIN => "nxdomain",
+            FtlQueryReplyType::CNAME => "cname",
+            FtlQueryReplyType::IP => "ip",
+            FtlQueryReplyType::DOMAIN => "domain",
+            FtlQueryReplyType::RRNAME => "rrname",
+            FtlQueryReplyType::SERVFAIL => "servfail",
+            FtlQueryReplyType::REFUSED => "refused",
+            FtlQueryReplyType::NOTIMP => "notimp",
+            FtlQueryReplyType::OTHER => "other"
+        }
+    }
 }
 
 impl<'v> FromFormValue<'v> for FtlQueryReplyType {
@@ -8,6 +8,7 @@
 // This file is copyright under the latest version of the EUPL.
 // Please see LICENSE file for your rights under this license.
 
+use crate::ftl::FTL_SHM_VERSION;
 use libc;
 
 /// The settings structure used to share version information and other settings
@@ -22,7 +23,7 @@ pub struct FtlSettings {
 impl Default for FtlSettings {
     fn default() -> Self {
         FtlSettings {
-            version: 0,
+            version: FTL_SHM_VERSION as libc::c_int,
             global_shm_counter: 0,
             next_str_pos: 1 // 0 is used as the empty string
         }
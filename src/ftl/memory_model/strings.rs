@@ -10,10 +10,10 @@
 
 use libc;
 use shmem::Array;
-use std::{ffi::CStr, marker::PhantomData};
+use std::{cell::RefCell, collections::HashMap, ffi::CStr, marker::PhantomData};
 
 #[cfg(test)]
-use std::collections::HashMap;
+use std::cell::Cell;
 
 /// A safe wrapper around FTL's strings. It is used to access the strings
 /// referenced by other shared memory structs.
@@ -60,9 +60,49 @@ impl<'test> FtlStrings<'test> {
     }
 }
 
+/// Remembers the strings read from [`FtlStrings`], so that reading the same
+/// string many times in a request (such as the domain of many queries) only
+/// reads it from shared memory once. It should not outlive the request, as
+/// FTL may change the strings.
+///
+/// [`FtlStrings`]: enum.FtlStrings.html
+pub struct CachedStrings<'a> {
+    strings: &'a FtlStrings<'a>,
+    cache: RefCell<HashMap<usize, Option<&'a str>>>,
+    /// The number of strings read from `strings`
+    #[cfg(test)]
+    lookups: Cell<usize>
+}
+
+impl<'a> CachedStrings<'a> {
+    /// Create an empty cache of the strings
+    pub fn new(strings: &'a FtlStrings<'a>) -> CachedStrings<'a> {
+        CachedStrings {
+            strings,
+            cache: RefCell::new(HashMap::new()),
+            #[cfg(test)]
+            lookups: Cell::new(0)
+        }
+    }
+
+    /// Read a string, see [`FtlStrings::get_str`]
+    ///
+    /// [`FtlStrings::get_str`]: enum.FtlStrings.html#method.get_str
+    pub fn get_str(&self, id: usize) -> Option<&'a str> {
+        let strings = self.strings;
+
+        *self.cache.borrow_mut().entry(id).or_insert_with(|| {
+            #[cfg(test)]
+            self.lookups.set(self.lookups.get() + 1);
+
+            strings.get_str(id)
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::FtlStrings;
+    use super::{CachedStrings, FtlStrings};
     use libc;
     use std::collections::HashMap;
 
@@ -88,4 +128,24 @@ mod tests {
         assert_eq!(FtlStrings::get_str_prod(&strings, 1), Some("test"));
         assert_eq!(FtlStrings::get_str_prod(&strings, 6), None);
     }
+
+    /// Each string is only read from the underlying strings once, no matter
+    /// how many times it is read from the cache
+    #[test]
+    fn cached_lookups() {
+        let mut data = HashMap::new();
+        data.insert(1, "domain1.com".to_owned());
+        data.insert(2, "domain2.com".to_owned());
+        let strings = FtlStrings::Test(&data);
+        let cached = CachedStrings::new(&strings);
+
+        // The string IDs of 1000 queries, which only use 3 distinct strings
+        let ids = [1, 2, 2, 1, 3].iter().cycle().take(1000);
+
+        for &id in ids {
+            assert_eq!(cached.get_str(id), strings.get_str(id));
+        }
+
+        assert_eq!(cached.lookups.get(), 3);
+    }
 }
@@ -9,14 +9,16 @@
 // Please see LICENSE file for your rights under this license.
 
 mod lock_thread;
+mod memory_guard;
 mod memory_model;
 mod shared_lock;
 mod shared_memory;
 mod socket;
 
 pub use self::{
+    memory_guard::FtlMemoryGuard,
     memory_model::*,
     shared_lock::{ShmLock, ShmLockGuard},
-    shared_memory::FtlMemory,
+    shared_memory::{FtlMemory, FTL_SHM_VERSION},
     socket::{FtlConnection, FtlConnectionType}
 };
@@ -22,7 +22,8 @@ use crate::{ftl::memory_model::FtlSettings, util::ErrorKind};
 #[cfg(test)]
 use std::collections::HashMap;
 
-const FTL_SHM_VERSION: usize = 4;
+/// The version of FTL's shared memory which this API can read
+pub const FTL_SHM_VERSION: usize = 4;
 
 const FTL_SHM_CLIENTS: &str = "/FTL-clients";
 const FTL_SHM_DOMAINS: &str = "/FTL-domains";
@@ -68,26 +69,25 @@ impl FtlMemory {
     ///
     /// [`ShmLockGuard`]: ../shared_lock/enum.ShmLockGuard.html
     pub fn lock(&self) -> Result<ShmLockGuard, Error> {
-        match self {
-            FtlMemory::Production { lock } => {
-                let guard = lock.read()?;
-
-                // Check the version of shared memory, in case it is not the
-                // same version used by this API
-                let settings = self.settings(&guard)?;
-                let version = settings.version as usize;
-
-                if version == FTL_SHM_VERSION {
-                    Ok(guard)
-                } else {
-                    Err(Error::from(ErrorKind::SharedMemoryVersion(
-                        version,
-                        FTL_SHM_VERSION
-                    )))
-                }
-            }
+        let guard = match self {
+            FtlMemory::Production { lock } => lock.read()?,
             #[cfg(test)]
-            FtlMemory::Test { .. } => Ok(ShmLockGuard::Test)
+            FtlMemory::Test { .. } => ShmLockGuard::Test
+        };
+
+        // Check the version of shared memory, in case it is not the same
+        // version used by this API. This happens when FTL is updated or
+        // restarted while the API is running, and reading the other version's
+        // memory would return garbage data.
+        let version = self.settings(&guard)?.version as usize;
+
+        if version == FTL_SHM_VERSION {
+            Ok(guard)
+        } else {
+            Err(Error::from(ErrorKind::SharedMemoryVersion(
+                version,
+                FTL_SHM_VERSION
+            )))
         }
     }
 
@@ -211,3 +211,47 @@ impl FtlMemory {
         })
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{FtlMemory, FTL_SHM_VERSION};
+    use crate::{
+        ftl::{FtlCounters, FtlSettings},
+        util::ErrorKind
+    };
+    use std::collections::HashMap;
+
+    /// Create test memory with the shared memory version
+    fn test_memory(version: usize) -> FtlMemory {
+        FtlMemory::Test {
+            clients: Vec::new(),
+            domains: Vec::new(),
+            over_time: Vec::new(),
+            upstreams: Vec::new(),
+            queries: Vec::new(),
+            strings: HashMap::new(),
+            counters: FtlCounters::default(),
+            settings: FtlSettings {
+                version: version as libc::c_int,
+                ..FtlSettings::default()
+            }
+        }
+    }
+
+    /// Shared memory of the expected version can be locked
+    #[test]
+    fn expected_version() {
+        assert!(test_memory(FTL_SHM_VERSION).lock().is_ok());
+    }
+
+    /// Shared memory of another version is not read
+    #[test]
+    fn version_mismatch() {
+        let error = test_memory(FTL_SHM_VERSION + 1).lock().unwrap_err();
+
+        assert_eq!(
+            error.kind(),
+            ErrorKind::SharedMemoryVersion(FTL_SHM_VERSION + 1, FTL_SHM_VERSION)
+        );
+    }
+}
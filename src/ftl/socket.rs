@@ -8,7 +8,11 @@
 // This file is copyright under the latest version of the EUPL.
 // Please see LICENSE file for your rights under this license.
 
-use crate::util::{Error, ErrorKind};
+use crate::{
+    env::Env,
+    settings::{ConfigEntry, SetupVarsEntry, ValueType},
+    util::{Error, ErrorKind}
+};
 use failure::{Fail, ResultExt};
 use rmp::{
     decode::{self, DecodeStringError, ValueReadError},
@@ -24,32 +28,41 @@ use std::collections::HashMap;
 #[cfg(test)]
 use std::io::Cursor;
 
-/// The location of the FTL socket
-const SOCKET_LOCATION: &str = "/var/run/pihole/FTL.sock";
-
 /// A wrapper around the FTL socket to easily read in data. It takes a
 /// Box<Read> so that it can be tested with fake data from a Vec<u8>
 pub struct FtlConnection<'test>(Box<dyn Read + 'test>);
 
 /// A marker for the type of FTL connection to make.
 ///
-/// - Socket refers to the normal Unix socket connection.
+/// - Socket refers to the normal Unix socket connection, at the given path.
 /// - Test is for testing, so that a test can pass in arbitrary MessagePack
 /// data to be processed.   The map in Test maps FTL commands to data.
 pub enum FtlConnectionType {
-    Socket,
+    Socket(String),
     #[cfg(test)]
     Test(HashMap<String, Vec<u8>>)
 }
 
 impl FtlConnectionType {
+    /// Create a socket connection type using the socket path from
+    /// `SetupVarsEntry::FtlSocketPath`. The path must be absolute.
+    pub fn socket(env: &Env) -> Result<FtlConnectionType, Error> {
+        let path = SetupVarsEntry::FtlSocketPath.read(env)?;
+
+        if !ValueType::Path.is_valid(&path) {
+            return Err(Error::from(ErrorKind::InvalidFtlSocketPath(path)));
+        }
+
+        Ok(FtlConnectionType::Socket(path))
+    }
+
     /// Connect to FTL and run the specified command
     pub fn connect(&self, command: &str) -> Result<FtlConnection, Error> {
         // Determine the type of connection to create
         match *self {
-            FtlConnectionType::Socket => {
+            FtlConnectionType::Socket(ref path) => {
                 // Try to connect to FTL
-                let mut stream = match UnixStream::connect(SOCKET_LOCATION) {
+                let mut stream = match UnixStream::connect(path) {
                     Ok(s) => s,
                     Err(_) => return Err(Error::from(ErrorKind::FtlConnectionFail))
                 };
@@ -138,3 +151,54 @@ impl<'test> FtlConnection<'test> {
         FtlConnection::handle_eom_str(decode::read_str(&mut self.0, buffer))
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::FtlConnectionType;
+    use crate::{
+        env::{Config, Env, PiholeFile},
+        testing::TestEnvBuilder,
+        util::ErrorKind
+    };
+
+    /// Create a test environment with the setupVars.conf contents
+    fn test_env(setup_vars: &str) -> Env {
+        Env::Test(
+            Config::default(),
+            TestEnvBuilder::new()
+                .file(PiholeFile::SetupVars, setup_vars)
+                .build()
+        )
+    }
+
+    /// The default socket path is used if none is configured
+    #[test]
+    fn default_path() {
+        match FtlConnectionType::socket(&test_env("")).unwrap() {
+            FtlConnectionType::Socket(path) => assert_eq!(path, "/var/run/pihole/FTL.sock"),
+            _ => panic!("Expected a socket connection")
+        }
+    }
+
+    /// The configured socket path overrides the default
+    #[test]
+    fn configured_path() {
+        match FtlConnectionType::socket(&test_env("FTL_SOCKET_PATH=/run/ftl/FTL.sock")).unwrap() {
+            FtlConnectionType::Socket(path) => assert_eq!(path, "/run/ftl/FTL.sock"),
+            _ => panic!("Expected a socket connection")
+        }
+    }
+
+    /// Relative socket paths are rejected
+    #[test]
+    fn relative_path() {
+        let error = FtlConnectionType::socket(&test_env("FTL_SOCKET_PATH=run/FTL.sock"))
+            .err()
+            .unwrap();
+
+        assert_eq!(
+            error.kind(),
+            ErrorKind::InvalidFtlSocketPath("run/FTL.sock".to_owned())
+        );
+    }
+}
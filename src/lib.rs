@@ -24,9 +24,14 @@ extern crate rust_embed;
 
 pub use crate::setup::start;
 
+mod bind;
+mod compression;
+mod cors;
 mod databases;
 mod env;
 mod ftl;
+mod metrics;
+mod reverse_dns;
 mod routes;
 mod settings;
 mod setup;
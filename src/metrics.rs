@@ -0,0 +1,207 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// Request Metrics
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use rocket::{
+    fairing::{Fairing, Info, Kind},
+    Data, Request, Response
+};
+use std::{
+    collections::BTreeMap,
+    fmt::Write,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant}
+};
+
+/// The upper bounds of the request latency histogram buckets, in seconds
+const LATENCY_BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0
+];
+
+/// The request counts and latencies of a route
+#[derive(Default)]
+struct RouteMetrics {
+    count: u64,
+    /// The total latency, in seconds
+    latency_sum: f64,
+    /// The number of requests which took at most the matching
+    /// `LATENCY_BUCKETS` bound
+    latency_buckets: [u64; 11]
+}
+
+/// When the request started being handled, stored in the request's
+/// local cache by [`RequestMetrics`]
+///
+/// [`RequestMetrics`]: struct.RequestMetrics.html
+struct RequestStart(Instant);
+
+/// A fairing which records the number of requests and their latencies for
+/// each route, by method. Unmatched requests are not recorded. The same
+/// metrics are shared by clones, so one clone can be attached as the fairing
+/// and another managed as state for the metrics endpoint.
+#[derive(Clone, Default)]
+pub struct RequestMetrics(Arc<Mutex<BTreeMap<(String, String), RouteMetrics>>>);
+
+impl RequestMetrics {
+    /// Record a handled request
+    fn record(&self, method: &str, route: &str, latency: Duration) {
+        let latency = latency.as_secs() as f64 + latency.subsec_nanos() as f64 / 1_000_000_000.0;
+        let mut routes = self.0.lock().unwrap();
+        let metrics = routes
+            .entry((method.to_owned(), route.to_owned()))
+            .or_default();
+
+        metrics.count += 1;
+        metrics.latency_sum += latency;
+
+        for (bucket, &bound) in metrics.latency_buckets.iter_mut().zip(&LATENCY_BUCKETS) {
+            if latency <= bound {
+                *bucket += 1;
+            }
+        }
+    }
+
+    /// Write the request counts and latency histograms in the Prometheus text
+    /// format
+    pub fn render(&self, output: &mut String) {
+        let routes = self.0.lock().unwrap();
+
+        output.push_str(
+            "# HELP pihole_api_requests_total The number of requests handled by each route\n\
+             # TYPE pihole_api_requests_total counter\n"
+        );
+
+        for ((method, route), metrics) in routes.iter() {
+            writeln!(
+                output,
+                "pihole_api_requests_total{{{}}} {}",
+                route_labels(method, route),
+                metrics.count
+            )
+            .unwrap();
+        }
+
+        output.push_str(
+            "# HELP pihole_api_request_duration_seconds The time taken to handle requests to \
+             each route\n\
+             # TYPE pihole_api_request_duration_seconds histogram\n"
+        );
+
+        for ((method, route), metrics) in routes.iter() {
+            let labels = route_labels(method, route);
+
+            for (count, bound) in metrics.latency_buckets.iter().zip(&LATENCY_BUCKETS) {
+                writeln!(
+                    output,
+                    "pihole_api_request_duration_seconds_bucket{{{},le=\"{}\"}} {}",
+                    labels, bound, count
+                )
+                .unwrap();
+            }
+
+            writeln!(
+                output,
+                "pihole_api_request_duration_seconds_bucket{{{},le=\"+Inf\"}} {}\n\
+                 pihole_api_request_duration_seconds_sum{{{}}} {}\n\
+                 pihole_api_request_duration_seconds_count{{{}}} {}",
+                labels, metrics.count, labels, metrics.latency_sum, labels, metrics.count
+            )
+            .unwrap();
+        }
+    }
+}
+
+impl Fairing for RequestMetrics {
+    fn info(&self) -> Info {
+        Info {
+            name: "Request Metrics",
+            kind: Kind::Request | Kind::Response
+        }
+    }
+
+    fn on_request(&self, request: &mut Request, _data: &Data) {
+        request.local_cache(|| RequestStart(Instant::now()));
+    }
+
+    fn on_response(&self, request: &Request, _response: &mut Response) {
+        let route = match request.route() {
+            Some(route) => route,
+            None => return
+        };
+        let start = request.local_cache(|| RequestStart(Instant::now()));
+
+        self.record(
+            request.method().as_str(),
+            route.uri.path(),
+            start.0.elapsed()
+        );
+    }
+}
+
+/// Get the Prometheus labels of a route
+fn route_labels(method: &str, route: &str) -> String {
+    format!(
+        "method=\"{}\",route=\"{}\"",
+        escape_label(method),
+        escape_label(route)
+    )
+}
+
+/// Escape a Prometheus label value
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+#[cfg(test)]
+mod test {
+    use super::RequestMetrics;
+    use std::time::Duration;
+
+    /// Requests are counted by route, and their latencies fall into the
+    /// buckets above them
+    #[test]
+    fn render() {
+        let metrics = RequestMetrics::default();
+        metrics.record("GET", "/admin/api/version", Duration::from_millis(20));
+        metrics.record("GET", "/admin/api/version", Duration::from_millis(2000));
+
+        let mut output = String::new();
+        metrics.render(&mut output);
+
+        let labels = "method=\"GET\",route=\"/admin/api/version\"";
+        let expected = format!(
+            "# HELP pihole_api_requests_total The number of requests handled by each route\n\
+             # TYPE pihole_api_requests_total counter\n\
+             pihole_api_requests_total{{{labels}}} 2\n\
+             # HELP pihole_api_request_duration_seconds The time taken to handle requests to \
+             each route\n\
+             # TYPE pihole_api_request_duration_seconds histogram\n\
+             pihole_api_request_duration_seconds_bucket{{{labels},le=\"0.005\"}} 0\n\
+             pihole_api_request_duration_seconds_bucket{{{labels},le=\"0.01\"}} 0\n\
+             pihole_api_request_duration_seconds_bucket{{{labels},le=\"0.025\"}} 1\n\
+             pihole_api_request_duration_seconds_bucket{{{labels},le=\"0.05\"}} 1\n\
+             pihole_api_request_duration_seconds_bucket{{{labels},le=\"0.1\"}} 1\n\
+             pihole_api_request_duration_seconds_bucket{{{labels},le=\"0.25\"}} 1\n\
+             pihole_api_request_duration_seconds_bucket{{{labels},le=\"0.5\"}} 1\n\
+             pihole_api_request_duration_seconds_bucket{{{labels},le=\"1\"}} 1\n\
+             pihole_api_request_duration_seconds_bucket{{{labels},le=\"2.5\"}} 2\n\
+             pihole_api_request_duration_seconds_bucket{{{labels},le=\"5\"}} 2\n\
+             pihole_api_request_duration_seconds_bucket{{{labels},le=\"10\"}} 2\n\
+             pihole_api_request_duration_seconds_bucket{{{labels},le=\"+Inf\"}} 2\n\
+             pihole_api_request_duration_seconds_sum{{{labels}}} 2.02\n\
+             pihole_api_request_duration_seconds_count{{{labels}}} 2\n",
+            labels = labels
+        );
+
+        assert_eq!(output, expected);
+    }
+}
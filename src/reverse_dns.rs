@@ -0,0 +1,262 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// Reverse DNS Lookups
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::env::Env;
+use libc;
+use std::{
+    collections::HashMap,
+    ffi::CStr,
+    mem,
+    net::IpAddr,
+    ptr,
+    sync::{mpsc, Arc, Condvar, Mutex},
+    thread,
+    time::{Duration, Instant}
+};
+
+/// How long a request waits for reverse DNS lookups, in total
+const LOOKUP_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// The number of threads which make lookups. Addresses wait in a queue until
+/// a thread is free.
+const LOOKUP_THREADS: usize = 4;
+
+/// The size of the buffer for a looked up host name (`NI_MAXHOST`)
+const MAX_HOST_LEN: usize = 1025;
+
+/// The state of an address's lookup
+enum Lookup {
+    /// The address is queued or being looked up
+    Pending,
+    /// The address was looked up, and has the name if it has one
+    Done(Option<String>)
+}
+
+/// The lookups, shared with the lookup threads
+struct Lookups {
+    lookups: Mutex<HashMap<IpAddr, Lookup>>,
+    /// Notified when a lookup finishes
+    finished: Condvar
+}
+
+/// Caches the host names of IP addresses found by reverse DNS (PTR) lookups,
+/// for the lifetime of the process. Failed lookups are cached as well, so an
+/// address without a name is only looked up once.
+pub struct ReverseDnsCache {
+    shared: Arc<Lookups>,
+    /// Queues addresses for the lookup threads, which are started by the
+    /// first lookup
+    queue: Mutex<Option<mpsc::Sender<IpAddr>>>
+}
+
+impl ReverseDnsCache {
+    /// Create a new, empty cache
+    pub fn new() -> ReverseDnsCache {
+        ReverseDnsCache {
+            shared: Arc::new(Lookups {
+                lookups: Mutex::new(HashMap::new()),
+                finished: Condvar::new()
+            }),
+            queue: Mutex::new(None)
+        }
+    }
+
+    /// Add a looked up name to the cache
+    #[cfg(test)]
+    pub fn insert(&self, ip: IpAddr, name: Option<&str>) {
+        self.shared
+            .lookups
+            .lock()
+            .unwrap()
+            .insert(ip, Lookup::Done(name.map(str::to_owned)));
+    }
+
+    /// Get the host names of the IP addresses. Addresses which have not been
+    /// looked up are queued for the lookup threads, and the request waits at
+    /// most `LOOKUP_TIMEOUT` for its addresses, including ones queued by
+    /// other requests. An address is only queued once, so lookups which are
+    /// still running after the timeout are not started again, and their
+    /// result is cached when they finish. Addresses without a name are left
+    /// out of the result.
+    ///
+    /// Lookups are not made during testing, so only cached names are used.
+    pub fn resolve(&self, ips: &[IpAddr], env: &Env) -> HashMap<IpAddr, String> {
+        let mut lookups = self.shared.lookups.lock().unwrap();
+
+        if !env.is_test() {
+            for ip in ips {
+                if !lookups.contains_key(ip) {
+                    lookups.insert(*ip, Lookup::Pending);
+                    self.queue_lookup(*ip);
+                }
+            }
+
+            let deadline = Instant::now() + LOOKUP_TIMEOUT;
+
+            while ips
+                .iter()
+                .any(|ip| lookups.get(ip).map_or(false, Lookup::is_pending))
+            {
+                let now = Instant::now();
+
+                if now >= deadline {
+                    break;
+                }
+
+                lookups = self
+                    .shared
+                    .finished
+                    .wait_timeout(lookups, deadline - now)
+                    .unwrap()
+                    .0;
+            }
+        }
+
+        ips.iter()
+            .filter_map(|ip| match lookups.get(ip) {
+                Some(Lookup::Done(Some(name))) => Some((*ip, name.clone())),
+                _ => None
+            })
+            .collect()
+    }
+
+    /// Queue the address for the lookup threads, starting them if this is the
+    /// first lookup
+    fn queue_lookup(&self, ip: IpAddr) {
+        let mut queue = self.queue.lock().unwrap();
+        let sender = queue.get_or_insert_with(|| start_lookup_threads(&self.shared));
+
+        // The threads only stop once the cache is dropped
+        let _ = sender.send(ip);
+    }
+}
+
+impl Lookup {
+    /// Check if the lookup has not finished yet
+    fn is_pending(&self) -> bool {
+        match self {
+            Lookup::Pending => true,
+            Lookup::Done(_) => false
+        }
+    }
+}
+
+/// Start the lookup threads, which look up queued addresses one at a time
+/// and cache the results. The queue's sender is returned.
+fn start_lookup_threads(shared: &Arc<Lookups>) -> mpsc::Sender<IpAddr> {
+    let (sender, receiver) = mpsc::channel();
+    let receiver = Arc::new(Mutex::new(receiver));
+
+    for _ in 0..LOOKUP_THREADS {
+        let receiver = Arc::clone(&receiver);
+        let shared = Arc::clone(shared);
+
+        thread::spawn(move || loop {
+            // The queue is only locked while taking an address, and the
+            // thread stops once the sender is dropped
+            let ip = match receiver.lock().unwrap().recv() {
+                Ok(ip) => ip,
+                Err(_) => break
+            };
+            let name = lookup_name(ip);
+
+            shared
+                .lookups
+                .lock()
+                .unwrap()
+                .insert(ip, Lookup::Done(name));
+            shared.finished.notify_all();
+        });
+    }
+
+    sender
+}
+
+/// Look up the host name of the IP address with a PTR query. `None` is
+/// returned if the address has no name or the lookup fails.
+fn lookup_name(ip: IpAddr) -> Option<String> {
+    let mut host = [0 as libc::c_char; MAX_HOST_LEN];
+
+    // getnameinfo takes the address as a socket address of its family
+    let result = match ip {
+        IpAddr::V4(ip) => {
+            let mut address: libc::sockaddr_in = unsafe { mem::zeroed() };
+            address.sin_family = libc::AF_INET as libc::sa_family_t;
+            address.sin_addr.s_addr = u32::from(ip).to_be();
+
+            unsafe {
+                libc::getnameinfo(
+                    &address as *const libc::sockaddr_in as *const libc::sockaddr,
+                    mem::size_of::<libc::sockaddr_in>() as libc::socklen_t,
+                    host.as_mut_ptr(),
+                    MAX_HOST_LEN as libc::socklen_t,
+                    ptr::null_mut(),
+                    0,
+                    libc::NI_NAMEREQD
+                )
+            }
+        }
+        IpAddr::V6(ip) => {
+            let mut address: libc::sockaddr_in6 = unsafe { mem::zeroed() };
+            address.sin6_family = libc::AF_INET6 as libc::sa_family_t;
+            address.sin6_addr.s6_addr = ip.octets();
+
+            unsafe {
+                libc::getnameinfo(
+                    &address as *const libc::sockaddr_in6 as *const libc::sockaddr,
+                    mem::size_of::<libc::sockaddr_in6>() as libc::socklen_t,
+                    host.as_mut_ptr(),
+                    MAX_HOST_LEN as libc::socklen_t,
+                    ptr::null_mut(),
+                    0,
+                    libc::NI_NAMEREQD
+                )
+            }
+        }
+    };
+
+    if result != 0 {
+        return None;
+    }
+
+    unsafe { CStr::from_ptr(host.as_ptr()) }
+        .to_str()
+        .ok()
+        .filter(|name| !name.is_empty())
+        .map(str::to_owned)
+}
+
+#[cfg(test)]
+mod test {
+    use super::ReverseDnsCache;
+    use crate::{
+        env::{Config, Env},
+        testing::TestEnvBuilder
+    };
+    use std::net::IpAddr;
+
+    /// Only addresses with a cached name are resolved during testing
+    #[test]
+    fn cached_names() {
+        let env = Env::Test(Config::default(), TestEnvBuilder::new().build());
+        let cache = ReverseDnsCache::new();
+        let named: IpAddr = "10.1.1.1".parse().unwrap();
+        let unnamed: IpAddr = "10.1.1.2".parse().unwrap();
+        let uncached: IpAddr = "10.1.1.3".parse().unwrap();
+
+        cache.insert(named, Some("laptop.lan"));
+        cache.insert(unnamed, None);
+
+        let names = cache.resolve(&[named, unnamed, uncached], &env);
+
+        assert_eq!(names.len(), 1);
+        assert_eq!(names.get(&named).map(String::as_str), Some("laptop.lan"));
+    }
+}
@@ -8,17 +8,34 @@
 // This file is copyright under the latest version of the EUPL.
 // Please see LICENSE file for your rights under this license.
 
-use crate::util::{reply_success, Error, ErrorKind, Reply};
+use crate::{
+    env::Env,
+    settings::{ConfigEntry, SetupVarsEntry},
+    util::{reply_data, reply_success, Error, ErrorKind, Reply}
+};
+use failure::ResultExt;
 use rocket::{
-    http::{Cookie, Cookies},
+    http::{Cookie, Cookies, Status},
     outcome::IntoOutcome,
     request::{self, FromRequest, Request, State},
     Outcome
 };
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::Read,
+    net::IpAddr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex, RwLock
+    },
+    time::{Duration, Instant}
+};
 
 const USER_ATTR: &str = "user_id";
 const AUTH_HEADER: &str = "X-Pi-hole-Authenticate";
+const RANDOM_SOURCE: &str = "/dev/urandom";
+const KEY_BYTES: usize = 32;
 
 /// When used as a request guard, requests must be authenticated
 pub struct User {
@@ -27,8 +44,12 @@ pub struct User {
 
 /// Stores the API key in the server state
 pub struct AuthData {
-    key: String,
-    next_id: AtomicUsize
+    key: RwLock<String>,
+    next_id: AtomicUsize,
+    /// The time of the last request of each user with a valid session, by ID
+    sessions: Mutex<HashMap<usize, Instant>>,
+    /// The times of recent failed authentication attempts, by client IP
+    failed_attempts: Mutex<HashMap<IpAddr, Vec<Instant>>>
 }
 
 impl User {
@@ -40,8 +61,42 @@ impl User {
             None => return Error::from(ErrorKind::Unknown).into_outcome()
         };
 
+        let env: State<Env> = match request.guard().succeeded() {
+            Some(env) => env,
+            None => return Error::from(ErrorKind::Unknown).into_outcome()
+        };
+
+        // Limit the number of failed attempts per client
+        let client_ip = request.client_ip();
+        let now = Instant::now();
+
+        if let Some(ip) = client_ip {
+            let max_attempts = match SetupVarsEntry::ApiAuthMaxAttempts.read_as::<usize>(&env) {
+                Ok(max_attempts) => max_attempts,
+                Err(e) => return e.into_outcome()
+            };
+            let window = match SetupVarsEntry::ApiAuthWindow.read_as::<u64>(&env) {
+                Ok(window) => Duration::from_secs(window),
+                Err(e) => return e.into_outcome()
+            };
+
+            if auth_data.is_rate_limited(ip, max_attempts, window, now) {
+                return Error::from(ErrorKind::TooManyRequests).into_outcome();
+            }
+        }
+
         if auth_data.key_matches(input_key) {
-            let user = auth_data.create_user();
+            if let Some(ip) = client_ip {
+                auth_data.reset_failed_attempts(ip);
+            }
+
+            let session_timeout = match read_session_timeout(&env) {
+                Ok(session_timeout) => session_timeout,
+                Err(e) => return e.into_outcome()
+            };
+
+            auth_data.remove_expired_sessions(session_timeout, now);
+            let user = auth_data.create_user(now);
 
             // Set a new encrypted cookie with the user's ID
             request.cookies().add_private(
@@ -53,25 +108,54 @@ impl User {
 
             Outcome::Success(user)
         } else {
+            if let Some(ip) = client_ip {
+                auth_data.record_failed_attempt(ip, now);
+            }
+
             Error::from(ErrorKind::Unauthorized).into_outcome()
         }
     }
 
     /// Try to get the user ID from cookies. An error is returned if none are
-    /// found.
-    fn check_cookies(mut cookies: Cookies) -> request::Outcome<Self, Error> {
-        cookies
+    /// found, or if the session is no longer valid. A valid session's expiry
+    /// is extended.
+    fn check_cookies(request: &Request) -> request::Outcome<Self, Error> {
+        let auth_data: State<AuthData> = match request.guard().succeeded() {
+            Some(auth_data) => auth_data,
+            None => return Error::from(ErrorKind::Unknown).into_outcome()
+        };
+
+        let env: State<Env> = match request.guard().succeeded() {
+            Some(env) => env,
+            None => return Error::from(ErrorKind::Unknown).into_outcome()
+        };
+
+        let session_timeout = match read_session_timeout(&env) {
+            Ok(session_timeout) => session_timeout,
+            Err(e) => return e.into_outcome()
+        };
+
+        let user = request
+            .cookies()
             .get_private(USER_ATTR)
-            .and_then(|cookie| cookie.value().parse().ok())
-            .map(|id| User { id })
-            .into_outcome((
-                ErrorKind::Unauthorized.status(),
-                Error::from(ErrorKind::Unauthorized)
-            ))
+            .and_then(|cookie| cookie.value().parse::<usize>().ok())
+            .filter(|id| auth_data.refresh_session(*id, session_timeout, Instant::now()))
+            .map(|id| User { id });
+
+        // Drop the cookie of an expired or otherwise invalid session
+        if user.is_none() {
+            request.cookies().remove_private(Cookie::named(USER_ATTR));
+        }
+
+        user.into_outcome((
+            ErrorKind::Unauthorized.status(),
+            Error::from(ErrorKind::Unauthorized)
+        ))
     }
 
-    /// Log the user out by removing the cookie
-    fn logout(&self, mut cookies: Cookies) {
+    /// Log the user out by ending the session and removing the cookie
+    fn logout(&self, auth_data: &AuthData, mut cookies: Cookies) {
+        auth_data.end_session(self.id);
         cookies.remove_private(Cookie::named(USER_ATTR));
     }
 }
@@ -85,14 +169,20 @@ impl<'a, 'r> FromRequest<'a, 'r> for User {
             Some(key) => {
                 let auth_result = User::authenticate(request, key);
 
-                if auth_result.is_success() {
+                // Rate limited clients can not fall back to cookies
+                let is_rate_limited = match auth_result {
+                    Outcome::Failure((status, _)) => status == Status::TooManyRequests,
+                    _ => false
+                };
+
+                if auth_result.is_success() || is_rate_limited {
                     auth_result
                 } else {
-                    User::check_cookies(request.cookies())
+                    User::check_cookies(request)
                 }
             }
             // No attempt to authenticate, so check cookies
-            None => User::check_cookies(request.cookies())
+            None => User::check_cookies(request)
         }
     }
 }
@@ -101,22 +191,109 @@ impl AuthData {
     /// Create a new API key
     pub fn new(key: String) -> AuthData {
         AuthData {
-            key,
-            next_id: AtomicUsize::new(1)
+            key: RwLock::new(key),
+            next_id: AtomicUsize::new(1),
+            sessions: Mutex::new(HashMap::new()),
+            failed_attempts: Mutex::new(HashMap::new())
         }
     }
 
     /// Check if the key matches the server's key
     fn key_matches(&self, key: &str) -> bool {
-        self.key == key
+        *self.key.read().unwrap() == key
+    }
+
+    /// Replace the key and invalidate all existing sessions
+    fn rotate_key(&self, key: String) {
+        *self.key.write().unwrap() = key;
+        self.sessions.lock().unwrap().clear();
     }
 
-    /// Create a new user and increment `next_id`
-    fn create_user(&self) -> User {
-        User {
-            id: self.next_id.fetch_add(1, Ordering::Relaxed)
+    /// Check if the client has failed to authenticate at least `max_attempts`
+    /// times in the last `window` of time. Attempts older than the window are
+    /// forgotten for every client, so the client is unlocked once they expire
+    /// and clients which stopped trying do not stay in memory.
+    fn is_rate_limited(
+        &self,
+        ip: IpAddr,
+        max_attempts: usize,
+        window: Duration,
+        now: Instant
+    ) -> bool {
+        let mut failed_attempts = self.failed_attempts.lock().unwrap();
+
+        failed_attempts.retain(|_, attempts| {
+            attempts.retain(|attempt| now.duration_since(*attempt) < window);
+            !attempts.is_empty()
+        });
+
+        failed_attempts
+            .get(&ip)
+            .map_or(false, |attempts| attempts.len() >= max_attempts)
+    }
+
+    /// Record a failed authentication attempt by the client
+    fn record_failed_attempt(&self, ip: IpAddr, now: Instant) {
+        self.failed_attempts
+            .lock()
+            .unwrap()
+            .entry(ip)
+            .or_insert_with(Vec::new)
+            .push(now);
+    }
+
+    /// Forget the client's failed authentication attempts
+    fn reset_failed_attempts(&self, ip: IpAddr) {
+        self.failed_attempts.lock().unwrap().remove(&ip);
+    }
+
+    /// Create a new user, start its session, and increment `next_id`
+    fn create_user(&self, now: Instant) -> User {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.sessions.lock().unwrap().insert(id, now);
+
+        User { id }
+    }
+
+    /// Check if the user's session is still valid. A valid session is
+    /// extended to expire `timeout` from now, and an expired session is
+    /// removed.
+    fn refresh_session(&self, id: usize, timeout: Duration, now: Instant) -> bool {
+        let mut sessions = self.sessions.lock().unwrap();
+
+        match sessions.get_mut(&id) {
+            Some(last_seen) if now.duration_since(*last_seen) < timeout => {
+                *last_seen = now;
+                true
+            }
+            Some(_) => {
+                sessions.remove(&id);
+                false
+            }
+            None => false
         }
     }
+
+    /// Remove the sessions which have not been used in the last `timeout` of
+    /// time
+    fn remove_expired_sessions(&self, timeout: Duration, now: Instant) {
+        self.sessions
+            .lock()
+            .unwrap()
+            .retain(|_, last_seen| now.duration_since(*last_seen) < timeout);
+    }
+
+    /// End the user's session
+    fn end_session(&self, id: usize) {
+        self.sessions.lock().unwrap().remove(&id);
+    }
+}
+
+/// Read the session timeout setting
+fn read_session_timeout(env: &Env) -> Result<Duration, Error> {
+    Ok(Duration::from_secs(
+        SetupVarsEntry::ApiSessionTimeout.read_as::<u64>(env)?
+    ))
 }
 
 /// Provides an endpoint to authenticate or check if already authenticated
@@ -127,16 +304,64 @@ pub fn check(_user: User) -> Reply {
 
 /// Clears the user's authentication
 #[delete("/auth")]
-pub fn logout(user: User, cookies: Cookies) -> Reply {
-    user.logout(cookies);
+pub fn logout(user: User, auth_data: State<AuthData>, cookies: Cookies) -> Reply {
+    user.logout(&auth_data, cookies);
     reply_success()
 }
 
+/// Generate a new API key, replacing the current one. All existing sessions
+/// are invalidated. The new key is only returned this one time.
+#[post("/auth/rotate")]
+pub fn rotate(_user: User, auth_data: State<AuthData>, env: State<Env>) -> Reply {
+    let key = generate_key()?;
+    rotate_key(&auth_data, &env, key.clone())?;
+
+    reply_data(RotateReply { key })
+}
+
+/// Save the new key and swap it into the server state
+fn rotate_key(auth_data: &AuthData, env: &Env, key: String) -> Result<(), Error> {
+    // The key is saved first so it is not lost if the write fails
+    SetupVarsEntry::WebPassword.write_unvalidated(&key, env)?;
+    auth_data.rotate_key(key);
+
+    Ok(())
+}
+
+/// Generate a random key in the same format as the web password hash
+fn generate_key() -> Result<String, Error> {
+    let mut bytes = [0u8; KEY_BYTES];
+
+    File::open(RANDOM_SOURCE)
+        .and_then(|mut file| file.read_exact(&mut bytes))
+        .context(ErrorKind::FileRead(RANDOM_SOURCE.to_owned()))?;
+
+    Ok(bytes.iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+/// The reply structure for a rotated key
+#[derive(Serialize)]
+pub struct RotateReply {
+    key: String
+}
+
 #[cfg(test)]
 mod test {
-    use crate::testing::TestBuilder;
-    use rocket::http::{Header, Status};
+    use super::{generate_key, rotate_key, AuthData};
+    use crate::{
+        env::{Config, Env, PiholeFile},
+        testing::{TestBuilder, TestEnvBuilder}
+    };
+    use rocket::http::{Header, Method, Status};
     use serde_json::Value;
+    use std::{
+        net::{IpAddr, Ipv4Addr},
+        time::{Duration, Instant}
+    };
+
+    const CLIENT_IP: IpAddr = IpAddr::V4(Ipv4Addr::new(10, 1, 1, 1));
+    const WINDOW: Duration = Duration::from_secs(60);
+    const SESSION_TIMEOUT: Duration = Duration::from_secs(1800);
 
     /// Providing the correct authentication should authorize the request
     #[test]
@@ -187,4 +412,162 @@ mod test {
             }))
             .test();
     }
+
+    /// Clients are rate limited after too many failed attempts within the
+    /// window
+    #[test]
+    fn rate_limited() {
+        let auth_data = AuthData::new("test_key".to_owned());
+        let now = Instant::now();
+
+        for _ in 0..5 {
+            assert!(!auth_data.is_rate_limited(CLIENT_IP, 5, WINDOW, now));
+            auth_data.record_failed_attempt(CLIENT_IP, now);
+        }
+
+        assert!(auth_data.is_rate_limited(CLIENT_IP, 5, WINDOW, now));
+
+        // Other clients are not affected
+        let other_ip = IpAddr::V4(Ipv4Addr::new(10, 1, 1, 2));
+        assert!(!auth_data.is_rate_limited(other_ip, 5, WINDOW, now));
+    }
+
+    /// Clients are unlocked once their failed attempts leave the window
+    #[test]
+    fn rate_limit_expires() {
+        let auth_data = AuthData::new("test_key".to_owned());
+        let now = Instant::now();
+
+        for _ in 0..5 {
+            auth_data.record_failed_attempt(CLIENT_IP, now);
+        }
+
+        assert!(auth_data.is_rate_limited(CLIENT_IP, 5, WINDOW, now + WINDOW / 2));
+        assert!(!auth_data.is_rate_limited(CLIENT_IP, 5, WINDOW, now + WINDOW));
+    }
+
+    /// Expired attempts of other clients are removed when any client is
+    /// checked
+    #[test]
+    fn rate_limit_prunes_clients() {
+        let auth_data = AuthData::new("test_key".to_owned());
+        let now = Instant::now();
+        let other_ip = IpAddr::V4(Ipv4Addr::new(10, 1, 1, 2));
+
+        auth_data.record_failed_attempt(other_ip, now);
+        auth_data.record_failed_attempt(CLIENT_IP, now + WINDOW / 2);
+
+        assert!(!auth_data.is_rate_limited(CLIENT_IP, 5, WINDOW, now + WINDOW));
+
+        let failed_attempts = auth_data.failed_attempts.lock().unwrap();
+        assert!(!failed_attempts.contains_key(&other_ip));
+        assert_eq!(failed_attempts.get(&CLIENT_IP).map(Vec::len), Some(1));
+    }
+
+    /// Resetting the failed attempts unlocks the client
+    #[test]
+    fn rate_limit_reset() {
+        let auth_data = AuthData::new("test_key".to_owned());
+        let now = Instant::now();
+
+        for _ in 0..5 {
+            auth_data.record_failed_attempt(CLIENT_IP, now);
+        }
+
+        auth_data.reset_failed_attempts(CLIENT_IP);
+
+        assert!(!auth_data.is_rate_limited(CLIENT_IP, 5, WINDOW, now));
+    }
+
+    /// Rotating the key requires authentication
+    #[test]
+    fn rotate_unauthenticated() {
+        TestBuilder::new()
+            .endpoint("/admin/api/auth/rotate")
+            .method(Method::Post)
+            .should_auth(false)
+            .expect_status(Status::Unauthorized)
+            .expect_json(json!({
+                "error": {
+                    "key": "unauthorized",
+                    "message": "Unauthorized",
+                    "data": Value::Null
+                }
+            }))
+            .test();
+    }
+
+    /// Rotating the key saves the new key, replaces the old key, and
+    /// invalidates existing sessions
+    #[test]
+    fn rotate() {
+        let env_builder = TestEnvBuilder::new().file_expect(
+            PiholeFile::SetupVars,
+            "WEBPASSWORD=test_key\n",
+            "WEBPASSWORD=new_key\n"
+        );
+        let mut test_file = env_builder.get_test_files().into_iter().next().unwrap();
+        let env = Env::Test(Config::default(), env_builder.build());
+        let auth_data = AuthData::new("test_key".to_owned());
+        let user = auth_data.create_user(Instant::now());
+
+        rotate_key(&auth_data, &env, "new_key".to_owned()).unwrap();
+
+        assert!(!auth_data.key_matches("test_key"));
+        assert!(auth_data.key_matches("new_key"));
+        assert!(!auth_data.refresh_session(user.id, WINDOW, Instant::now()));
+
+        let mut buffer = String::new();
+        test_file.assert_expected(&mut buffer);
+    }
+
+    /// Generated keys have the same format as the web password hash
+    #[test]
+    fn generated_key_format() {
+        let key = generate_key().unwrap();
+
+        assert_eq!(key.len(), 64);
+        assert!(key.chars().all(|c| c.is_ascii_hexdigit()));
+        assert_ne!(key, generate_key().unwrap());
+    }
+
+    /// Sessions expire when they are not used within the timeout
+    #[test]
+    fn session_expires() {
+        let auth_data = AuthData::new("test_key".to_owned());
+        let now = Instant::now();
+        let user = auth_data.create_user(now);
+
+        assert!(!auth_data.refresh_session(user.id, SESSION_TIMEOUT, now + SESSION_TIMEOUT));
+
+        // The expired session was dropped
+        assert!(!auth_data.refresh_session(user.id, SESSION_TIMEOUT, now));
+    }
+
+    /// Using a session extends its expiry
+    #[test]
+    fn session_slides() {
+        let auth_data = AuthData::new("test_key".to_owned());
+        let now = Instant::now();
+        let user = auth_data.create_user(now);
+        let later = now + SESSION_TIMEOUT / 2;
+
+        assert!(auth_data.refresh_session(user.id, SESSION_TIMEOUT, later));
+        assert!(auth_data.refresh_session(user.id, SESSION_TIMEOUT, now + SESSION_TIMEOUT));
+        assert!(!auth_data.refresh_session(user.id, SESSION_TIMEOUT, later + SESSION_TIMEOUT));
+    }
+
+    /// Expired sessions are removed, but active sessions are kept
+    #[test]
+    fn remove_expired_sessions() {
+        let auth_data = AuthData::new("test_key".to_owned());
+        let now = Instant::now();
+        let old_user = auth_data.create_user(now);
+        let new_user = auth_data.create_user(now + SESSION_TIMEOUT / 2);
+
+        auth_data.remove_expired_sessions(SESSION_TIMEOUT, now + SESSION_TIMEOUT);
+
+        assert!(!auth_data.refresh_session(old_user.id, SESSION_TIMEOUT, now));
+        assert!(auth_data.refresh_session(new_user.id, SESSION_TIMEOUT, now + SESSION_TIMEOUT));
+    }
 }
@@ -13,27 +13,51 @@ use crate::{
     ftl::FtlConnectionType,
     routes::{
         auth::User,
-        dns::{common::reload_gravity, list::List}
+        dns::{
+            common::reload_gravity,
+            list::{List, ListChange}
+        }
     },
-    util::{reply_success, Reply}
+    util::{reply_data, reply_success, Reply}
 };
 use rocket::State;
 use rocket_contrib::json::Json;
 
-/// Represents an API input containing a domain
+/// Represents an API input containing a domain and an optional comment
 #[derive(Deserialize)]
 pub struct DomainInput {
-    domain: String
+    domain: String,
+    comment: Option<String>
+}
+
+/// Represents an API input containing multiple domains
+#[derive(Deserialize)]
+pub struct DomainsInput {
+    domains: Vec<String>
+}
+
+/// Represents the reply structure for adding multiple domains
+#[derive(Serialize)]
+pub struct BulkAddReply {
+    added: usize,
+    existing: usize
 }
 
 /// Add a domain to the whitelist
 #[post("/dns/whitelist", data = "<domain_input>")]
 pub fn add_whitelist(_auth: User, env: State<Env>, domain_input: Json<DomainInput>) -> Reply {
     let domain = &domain_input.0.domain;
+    let mut change = ListChange::new(&env);
 
     // We need to add it to the whitelist and remove it from the blacklist
-    List::White.add(domain, &env)?;
-    List::Black.try_remove(domain, &env)?;
+    List::White.add(domain, &mut change)?;
+    List::Black.try_remove(domain, &mut change)?;
+
+    if let Some(ref comment) = domain_input.0.comment {
+        List::White.set_comment(domain, Some(comment), &mut change)?;
+    }
+
+    change.commit()?;
 
     // At this point, since we haven't hit an error yet, reload gravity
     reload_gravity(List::White, &env)?;
@@ -44,10 +68,17 @@ pub fn add_whitelist(_auth: User, env: State<Env>, domain_input: Json<DomainInpu
 #[post("/dns/blacklist", data = "<domain_input>")]
 pub fn add_blacklist(_auth: User, env: State<Env>, domain_input: Json<DomainInput>) -> Reply {
     let domain = &domain_input.0.domain;
+    let mut change = ListChange::new(&env);
 
     // We need to add it to the blacklist and remove it from the whitelist
-    List::Black.add(domain, &env)?;
-    List::White.try_remove(domain, &env)?;
+    List::Black.add(domain, &mut change)?;
+    List::White.try_remove(domain, &mut change)?;
+
+    if let Some(ref comment) = domain_input.0.comment {
+        List::Black.set_comment(domain, Some(comment), &mut change)?;
+    }
+
+    change.commit()?;
 
     // At this point, since we haven't hit an error yet, reload gravity
     reload_gravity(List::Black, &env)?;
@@ -63,22 +94,96 @@ pub fn add_regexlist(
     domain_input: Json<DomainInput>
 ) -> Reply {
     let domain = &domain_input.0.domain;
+    let mut change = ListChange::new(&env);
 
     // We only need to add it to the regex list
-    List::Regex.add(domain, &env)?;
+    List::Regex.add(domain, &mut change)?;
+
+    if let Some(ref comment) = domain_input.0.comment {
+        List::Regex.set_comment(domain, Some(comment), &mut change)?;
+    }
+
+    change.commit()?;
 
     // At this point, since we haven't hit an error yet, tell FTL to recompile regex
     ftl.connect("recompile-regex")?.expect_eom()?;
     reply_success()
 }
 
+/// Add multiple domains to the whitelist. Either all of the domains are added
+/// or, if one is invalid, none of them are.
+#[post("/dns/whitelist/bulk", data = "<domains_input>")]
+pub fn add_whitelist_bulk(
+    _auth: User,
+    env: State<Env>,
+    domains_input: Json<DomainsInput>
+) -> Reply {
+    let domains = &domains_input.0.domains;
+    let mut change = ListChange::new(&env);
+
+    // We need to add them to the whitelist and remove them from the blacklist
+    let (added, existing) = List::White.add_all(domains, &mut change)?;
+
+    List::Black.remove_all(domains, &mut change)?;
+
+    change.commit()?;
+
+    // At this point, since we haven't hit an error yet, reload gravity
+    reload_gravity(List::White, &env)?;
+    reply_data(BulkAddReply { added, existing })
+}
+
+/// Add multiple domains to the blacklist. Either all of the domains are added
+/// or, if one is invalid, none of them are.
+#[post("/dns/blacklist/bulk", data = "<domains_input>")]
+pub fn add_blacklist_bulk(
+    _auth: User,
+    env: State<Env>,
+    domains_input: Json<DomainsInput>
+) -> Reply {
+    let domains = &domains_input.0.domains;
+    let mut change = ListChange::new(&env);
+
+    // We need to add them to the blacklist and remove them from the whitelist
+    let (added, existing) = List::Black.add_all(domains, &mut change)?;
+
+    List::White.remove_all(domains, &mut change)?;
+
+    change.commit()?;
+
+    // At this point, since we haven't hit an error yet, reload gravity
+    reload_gravity(List::Black, &env)?;
+    reply_data(BulkAddReply { added, existing })
+}
+
+/// Add multiple domains to the regex list. Either all of the domains are
+/// added or, if one is invalid, none of them are.
+#[post("/dns/regexlist/bulk", data = "<domains_input>")]
+pub fn add_regexlist_bulk(
+    _auth: User,
+    env: State<Env>,
+    ftl: State<FtlConnectionType>,
+    domains_input: Json<DomainsInput>
+) -> Reply {
+    let mut change = ListChange::new(&env);
+
+    // We only need to add them to the regex list
+    let (added, existing) = List::Regex.add_all(&domains_input.0.domains, &mut change)?;
+    change.commit()?;
+
+    // At this point, since we haven't hit an error yet, tell FTL to recompile regex
+    ftl.connect("recompile-regex")?.expect_eom()?;
+    reply_data(BulkAddReply { added, existing })
+}
+
 #[cfg(test)]
 mod test {
     use crate::{
         env::PiholeFile,
         testing::{write_eom, TestBuilder}
     };
-    use rocket::http::Method;
+    use regex::Regex;
+    use rocket::http::{Method, Status};
 
     #[test]
     fn test_add_whitelist() {
@@ -94,6 +199,72 @@ mod test {
             .test();
     }
 
+    /// The comment is saved along with the domain
+    #[test]
+    fn test_add_whitelist_comment() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/whitelist")
+            .method(Method::Post)
+            .file_expect(PiholeFile::Whitelist, "", "example.com\n")
+            .file(PiholeFile::Blacklist, "")
+            .file(PiholeFile::Regexlist, "")
+            .file(PiholeFile::SetupVars, "")
+            .file_expect(
+                PiholeFile::ListComments,
+                "black\texample.net\tAds\n",
+                "black\texample.net\tAds\nwhite\texample.com\tNeeded for login\n"
+            )
+            .body(json!({ "domain": "example.com", "comment": "Needed for login" }))
+            .expect_json(json!({ "status": "success" }))
+            .test();
+    }
+
+    /// Adding a domain which is already in the whitelist is a conflict, and
+    /// the lists are left unchanged
+    #[test]
+    fn test_add_whitelist_duplicate() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/whitelist")
+            .method(Method::Post)
+            .file_expect(PiholeFile::Whitelist, "example.com\n", "example.com\n")
+            .file_expect(PiholeFile::Blacklist, "", "")
+            .file(PiholeFile::Regexlist, "")
+            .file(PiholeFile::SetupVars, "")
+            .body(json!({ "domain": "example.com" }))
+            .expect_status(Status::Conflict)
+            .expect_json(json!({
+                "error": {
+                    "key": "already_exists",
+                    "message": "Item already exists",
+                    "data": None::<()>
+                }
+            }))
+            .test();
+    }
+
+    /// A disabled domain still counts as being in the blacklist, so adding it
+    /// again is a conflict which does not enable it
+    #[test]
+    fn test_add_blacklist_duplicate() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/blacklist")
+            .method(Method::Post)
+            .file_expect(PiholeFile::Blacklist, "#example.com\n", "#example.com\n")
+            .file_expect(PiholeFile::Whitelist, "", "")
+            .file(PiholeFile::Regexlist, "")
+            .file(PiholeFile::SetupVars, "")
+            .body(json!({ "domain": "example.com" }))
+            .expect_status(Status::Conflict)
+            .expect_json(json!({
+                "error": {
+                    "key": "already_exists",
+                    "message": "Item already exists",
+                    "data": None::<()>
+                }
+            }))
+            .test();
+    }
+
     #[test]
     fn test_add_blacklist() {
         TestBuilder::new()
@@ -125,4 +296,150 @@ mod test {
             .expect_json(json!({ "status": "success" }))
             .test();
     }
+
+    /// Adding a regex twice is a conflict, and FTL is not told to recompile
+    #[test]
+    fn test_add_regexlist_duplicate() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/regexlist")
+            .method(Method::Post)
+            .file_expect(
+                PiholeFile::Regexlist,
+                "^.*example.com$\n",
+                "^.*example.com$\n"
+            )
+            .file(PiholeFile::Whitelist, "")
+            .file(PiholeFile::Blacklist, "")
+            .file(PiholeFile::SetupVars, "IPV4_ADDRESS=10.1.1.1")
+            .body(json!({ "domain": "^.*example.com$" }))
+            .expect_status(Status::Conflict)
+            .expect_json(json!({
+                "error": {
+                    "key": "already_exists",
+                    "message": "Item already exists",
+                    "data": None::<()>
+                }
+            }))
+            .test();
+    }
+
+    /// Anchored patterns are valid regexes
+    #[test]
+    fn test_add_regexlist_anchored() {
+        let mut data = Vec::new();
+        write_eom(&mut data);
+
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/regexlist")
+            .method(Method::Post)
+            .ftl("recompile-regex", data)
+            .file_expect(PiholeFile::Regexlist, "", "(^|\\.)example\\.com$\n")
+            .file(PiholeFile::Whitelist, "")
+            .file(PiholeFile::Blacklist, "")
+            .file(PiholeFile::SetupVars, "IPV4_ADDRESS=10.1.1.1")
+            .body(json!({ "domain": "(^|\\.)example\\.com$" }))
+            .expect_json(json!({ "status": "success" }))
+            .test();
+    }
+
+    /// Regexes which do not compile are rejected with the compilation error,
+    /// and are not written to the regex list
+    #[test]
+    fn test_add_regexlist_invalid() {
+        let regex_error = Regex::new("example(com").unwrap_err().to_string();
+
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/regexlist")
+            .method(Method::Post)
+            .file(PiholeFile::Regexlist, "")
+            .file(PiholeFile::Whitelist, "")
+            .file(PiholeFile::Blacklist, "")
+            .file(PiholeFile::SetupVars, "IPV4_ADDRESS=10.1.1.1")
+            .body(json!({ "domain": "example(com" }))
+            .expect_status(Status::BadRequest)
+            .expect_json(json!({
+                "error": {
+                    "key": "invalid_regex",
+                    "message": format!("Invalid regex: {}", regex_error),
+                    "data": None::<()>
+                }
+            }))
+            .test();
+    }
+
+    /// New domains are added, and domains already in the list are counted
+    #[test]
+    fn test_add_whitelist_bulk() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/whitelist/bulk")
+            .method(Method::Post)
+            .file_expect(
+                PiholeFile::Whitelist,
+                "example.com\n",
+                "example.com\nexample.net\nexample.org\n"
+            )
+            .file_expect(PiholeFile::Blacklist, "example.org\n", "")
+            .file(PiholeFile::Regexlist, "")
+            .file(PiholeFile::SetupVars, "")
+            .body(json!({ "domains": ["example.com", "example.net", "example.org"] }))
+            .expect_json(json!({ "added": 2, "existing": 1 }))
+            .test();
+    }
+
+    /// Duplicate domains in the input are only added once
+    #[test]
+    fn test_add_blacklist_bulk_duplicates() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/blacklist/bulk")
+            .method(Method::Post)
+            .file_expect(PiholeFile::Blacklist, "", "example.com\n")
+            .file(PiholeFile::Whitelist, "")
+            .file(PiholeFile::Regexlist, "")
+            .file(PiholeFile::SetupVars, "")
+            .body(json!({ "domains": ["example.com", "example.com"] }))
+            .expect_json(json!({ "added": 1, "existing": 1 }))
+            .test();
+    }
+
+    /// If any domain is invalid, nothing is added and the invalid domain is
+    /// reported
+    #[test]
+    fn test_add_blacklist_bulk_invalid() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/blacklist/bulk")
+            .method(Method::Post)
+            .file(PiholeFile::Blacklist, "")
+            .file(PiholeFile::Whitelist, "example.com\n")
+            .file(PiholeFile::Regexlist, "")
+            .file(PiholeFile::SetupVars, "")
+            .body(json!({ "domains": ["example.com", "not a domain", "example.net"] }))
+            .expect_status(Status::BadRequest)
+            .expect_json(json!({
+                "error": {
+                    "key": "invalid_list_entry",
+                    "message": "Invalid list entry: not a domain",
+                    "data": { "entry": "not a domain" }
+                }
+            }))
+            .test();
+    }
+
+    /// Multiple regexes can be added at once
+    #[test]
+    fn test_add_regexlist_bulk() {
+        let mut data = Vec::new();
+        write_eom(&mut data);
+
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/regexlist/bulk")
+            .method(Method::Post)
+            .ftl("recompile-regex", data)
+            .file_expect(PiholeFile::Regexlist, "", "^ads\\.\n^tracker\\.\n")
+            .file(PiholeFile::Whitelist, "")
+            .file(PiholeFile::Blacklist, "")
+            .file(PiholeFile::SetupVars, "IPV4_ADDRESS=10.1.1.1")
+            .body(json!({ "domains": ["^ads\\.", "^tracker\\."] }))
+            .expect_json(json!({ "added": 2, "existing": 0 }))
+            .test();
+    }
 }
@@ -0,0 +1,253 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// List Audit Log
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    env::{Env, PiholeFile},
+    routes::auth::User,
+    util::{reply_data, Error, ErrorKind, Reply}
+};
+use failure::ResultExt;
+use rocket::State;
+use std::{
+    io::Write,
+    time::{SystemTime, UNIX_EPOCH}
+};
+
+/// The number of audit records returned if no limit is given
+const DEFAULT_AUDIT_LIMIT: usize = 100;
+
+/// A change made to a list
+#[derive(Copy, Clone)]
+pub enum AuditAction {
+    Add,
+    Remove,
+    Enable,
+    Disable
+}
+
+impl AuditAction {
+    /// Get the name of the action, as stored in the audit log
+    fn name(self) -> &'static str {
+        match self {
+            AuditAction::Add => "add",
+            AuditAction::Remove => "remove",
+            AuditAction::Enable => "enable",
+            AuditAction::Disable => "disable"
+        }
+    }
+}
+
+/// A record of a change made to a list
+#[derive(Serialize)]
+pub struct AuditRecord {
+    timestamp: u64,
+    action: String,
+    domain: String,
+    list: String
+}
+
+/// Get the most recent list changes, newest first. At most `limit` records
+/// are returned, which defaults to 100.
+#[get("/dns/audit?<limit>")]
+pub fn get_audit(env: State<Env>, _auth: User, limit: Option<usize>) -> Reply {
+    let mut records = read_audit_records(&env)?;
+
+    records.reverse();
+    records.truncate(limit.unwrap_or(DEFAULT_AUDIT_LIMIT));
+
+    reply_data(records)
+}
+
+/// Format a record of a list change as a line of the audit log, which is
+/// `timestamp<TAB>action<TAB>list<TAB>domain`
+pub fn format_audit_record(action: AuditAction, list_name: &str, domain: &str) -> String {
+    let timestamp = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs();
+
+    format!(
+        "{}\t{}\t{}\t{}",
+        timestamp,
+        action.name(),
+        list_name,
+        domain
+    )
+}
+
+/// Append formatted records to the audit log, one record per line. The log
+/// is only ever appended to.
+pub fn append_audit_records(records: &[String], env: &Env) -> Result<(), Error> {
+    if records.is_empty() {
+        return Ok(());
+    }
+
+    let mut contents = String::new();
+
+    for record in records {
+        contents.push_str(record);
+        contents.push('\n');
+    }
+
+    // Open the audit log in append mode (and create it if it doesn't exist)
+    env.write_file(PiholeFile::ListAuditLog, true)?
+        .write_all(contents.as_bytes())
+        .context(ErrorKind::FileWrite(
+            env.file_location(PiholeFile::ListAuditLog).to_owned()
+        ))?;
+
+    Ok(())
+}
+
+/// Read the records in the audit log, oldest first. If the log does not exist,
+/// there are no records. Malformed lines are skipped.
+fn read_audit_records(env: &Env) -> Result<Vec<AuditRecord>, Error> {
+    if !env.file_exists(PiholeFile::ListAuditLog) {
+        return Ok(Vec::new());
+    }
+
+    Ok(env
+        .read_file_lines(PiholeFile::ListAuditLog)?
+        .iter()
+        .filter_map(|line| parse_audit_record(line))
+        .collect())
+}
+
+/// Parse a line of the audit log into a record
+fn parse_audit_record(line: &str) -> Option<AuditRecord> {
+    let mut split = line.splitn(4, '\t');
+
+    match (split.next(), split.next(), split.next(), split.next()) {
+        (Some(timestamp), Some(action), Some(list), Some(domain)) => Some(AuditRecord {
+            timestamp: timestamp.parse().ok()?,
+            action: action.to_owned(),
+            domain: domain.to_owned(),
+            list: list.to_owned()
+        }),
+        _ => None
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::read_audit_records;
+    use crate::{
+        env::{Config, Env, PiholeFile},
+        routes::dns::list::{List, ListChange},
+        testing::{TestBuilder, TestEnvBuilder},
+        util::Error
+    };
+
+    /// The most recent records are returned first, up to the limit.
+    /// Malformed lines are skipped.
+    #[test]
+    fn get_audit() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/audit?limit=2")
+            .file(
+                PiholeFile::ListAuditLog,
+                "100\tadd\twhite\texample.com\n\
+                 not a record\n\
+                 200\tadd\tblack\texample.net\n\
+                 300\tdisable\tblack\texample.net\n"
+            )
+            .expect_json(json!([
+                {
+                    "timestamp": 300,
+                    "action": "disable",
+                    "domain": "example.net",
+                    "list": "black"
+                },
+                {
+                    "timestamp": 200,
+                    "action": "add",
+                    "domain": "example.net",
+                    "list": "black"
+                }
+            ]))
+            .test();
+    }
+
+    /// There are no records if the audit log does not exist
+    #[test]
+    fn get_audit_empty() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/audit")
+            .expect_json(json!([]))
+            .test();
+    }
+
+    /// Make a change to the lists and commit it
+    fn commit(env: &Env, make_change: impl FnOnce(&mut ListChange) -> Result<(), Error>) {
+        let mut change = ListChange::new(env);
+
+        make_change(&mut change).unwrap();
+        change.commit().unwrap();
+    }
+
+    /// Each kind of list change is recorded, in order. Failed changes are not
+    /// recorded.
+    #[test]
+    fn changes_recorded() {
+        let env = Env::Test(
+            Config::default(),
+            TestEnvBuilder::new()
+                .file(PiholeFile::Whitelist, "")
+                .file(PiholeFile::Regexlist, "")
+                .file(PiholeFile::ListAuditLog, "")
+                .build()
+        );
+
+        commit(&env, |change| List::White.add("example.com", change));
+        assert!(List::White
+            .add("example.com", &mut ListChange::new(&env))
+            .is_err());
+        commit(&env, |change| {
+            List::White.set_enabled("example.com", false, change)
+        });
+        commit(&env, |change| {
+            List::White.set_enabled("example.com", true, change)
+        });
+        commit(&env, |change| List::White.remove("example.com", change));
+        commit(&env, |change| {
+            List::Regex
+                .add_all(&["^ads\\.".to_owned()], change)
+                .map(|_| ())
+        });
+
+        // Changes which are not committed are not recorded
+        List::White
+            .add("example.net", &mut ListChange::new(&env))
+            .unwrap();
+
+        let records = read_audit_records(&env).unwrap();
+        let changes: Vec<(&str, &str, &str)> = records
+            .iter()
+            .map(|record| {
+                (
+                    record.action.as_str(),
+                    record.list.as_str(),
+                    record.domain.as_str()
+                )
+            })
+            .collect();
+
+        assert_eq!(
+            changes,
+            vec![
+                ("add", "white", "example.com"),
+                ("disable", "white", "example.com"),
+                ("enable", "white", "example.com"),
+                ("remove", "white", "example.com"),
+                ("add", "regex", "^ads\\."),
+            ]
+        );
+    }
+}
@@ -35,13 +35,32 @@ pub fn is_valid_domain(domain: &str) -> bool {
         && label_length_regex.is_match(domain)
 }
 
-/// Check if a regex is valid
-pub fn is_valid_regex(regex_str: &str) -> bool {
-    Regex::new(regex_str).is_ok()
+/// Check if a regex is valid. If it is not, the compilation error is returned.
+pub fn check_regex(regex_str: &str) -> Result<(), Error> {
+    Regex::new(regex_str)
+        .map(|_| ())
+        .map_err(|e| Error::from(ErrorKind::InvalidRegex(e.to_string())))
 }
 
 /// Reload Gravity to activate changes in lists
 pub fn reload_gravity(list: List, env: &Env) -> Result<(), Error> {
+    // Based on what list we modified, only reload what is necessary
+    let list_arg = match list {
+        List::White => "--whitelist-only",
+        List::Black => "--blacklist-only",
+        _ => return Err(Error::from(ErrorKind::Unknown))
+    };
+
+    run_gravity(&["--skip-download", list_arg], env)
+}
+
+/// Reload Gravity to activate changes in both the whitelist and blacklist
+pub fn reload_gravity_lists(env: &Env) -> Result<(), Error> {
+    run_gravity(&["--skip-download"], env)
+}
+
+/// Run Gravity with the arguments
+fn run_gravity(args: &[&str], env: &Env) -> Result<(), Error> {
     // Don't actually reload Gravity during testing
     if env.is_test() {
         return Ok(());
@@ -50,13 +69,7 @@ pub fn reload_gravity(list: List, env: &Env) -> Result<(), Error> {
     let status = Command::new("sudo")
         .arg("pihole")
         .arg("-g")
-        .arg("--skip-download")
-        // Based on what list we modified, only reload what is necessary
-        .arg(match list {
-            List::White => "--whitelist-only",
-            List::Black => "--blacklist-only",
-            _ => return Err(Error::from(ErrorKind::Unknown))
-        })
+        .args(args)
         // Ignore stdin, stdout, and stderr
         .stdin(Stdio::null())
         .stdout(Stdio::null())
@@ -13,7 +13,10 @@ use crate::{
     ftl::FtlConnectionType,
     routes::{
         auth::User,
-        dns::{common::reload_gravity, list::List}
+        dns::{
+            common::reload_gravity,
+            list::{List, ListChange}
+        }
     },
     util::{reply_success, Reply}
 };
@@ -22,7 +25,10 @@ use rocket::State;
 /// Delete a domain from the whitelist
 #[delete("/dns/whitelist/<domain>")]
 pub fn delete_whitelist(_auth: User, env: State<Env>, domain: String) -> Reply {
-    List::White.remove(&domain, &env)?;
+    let mut change = ListChange::new(&env);
+
+    List::White.remove(&domain, &mut change)?;
+    change.commit()?;
     reload_gravity(List::White, &env)?;
     reply_success()
 }
@@ -30,7 +36,10 @@ pub fn delete_whitelist(_auth: User, env: State<Env>, domain: String) -> Reply {
 /// Delete a domain from the blacklist
 #[delete("/dns/blacklist/<domain>")]
 pub fn delete_blacklist(_auth: User, env: State<Env>, domain: String) -> Reply {
-    List::Black.remove(&domain, &env)?;
+    let mut change = ListChange::new(&env);
+
+    List::Black.remove(&domain, &mut change)?;
+    change.commit()?;
     reload_gravity(List::Black, &env)?;
     reply_success()
 }
@@ -43,7 +52,10 @@ pub fn delete_regexlist(
     ftl: State<FtlConnectionType>,
     domain: String
 ) -> Reply {
-    List::Regex.remove(&domain, &env)?;
+    let mut change = ListChange::new(&env);
+
+    List::Regex.remove(&domain, &mut change)?;
+    change.commit()?;
     ftl.connect("recompile-regex")?.expect_eom()?;
     reply_success()
 }
@@ -54,7 +66,7 @@ mod test {
         env::PiholeFile,
         testing::{write_eom, TestBuilder}
     };
-    use rocket::http::Method;
+    use rocket::http::{Method, Status};
 
     #[test]
     fn test_delete_whitelist() {
@@ -66,6 +78,22 @@ mod test {
             .test();
     }
 
+    /// The domain's comment is deleted along with the domain
+    #[test]
+    fn test_delete_whitelist_comment() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/whitelist/example.com")
+            .method(Method::Delete)
+            .file_expect(PiholeFile::Whitelist, "example.com\n", "")
+            .file_expect(
+                PiholeFile::ListComments,
+                "white\texample.com\tNeeded for login\nblack\texample.com\tAds\n",
+                "black\texample.com\tAds\n"
+            )
+            .expect_json(json!({ "status": "success" }))
+            .test();
+    }
+
     #[test]
     fn test_delete_blacklist() {
         TestBuilder::new()
@@ -89,4 +117,65 @@ mod test {
             .expect_json(json!({ "status": "success" }))
             .test();
     }
+
+    /// Deleting a domain which is not in the whitelist is not found, and the
+    /// whitelist is left unchanged
+    #[test]
+    fn test_delete_whitelist_not_found() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/whitelist/example.com")
+            .method(Method::Delete)
+            .file_expect(PiholeFile::Whitelist, "example.net\n", "example.net\n")
+            .expect_status(Status::NotFound)
+            .expect_json(json!({
+                "error": {
+                    "key": "not_found",
+                    "message": "Not found",
+                    "data": None::<()>
+                }
+            }))
+            .test();
+    }
+
+    /// Deleting a domain which is only in the whitelist from the blacklist is
+    /// not found
+    #[test]
+    fn test_delete_blacklist_not_found() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/blacklist/example.com")
+            .method(Method::Delete)
+            .file_expect(PiholeFile::Blacklist, "", "")
+            .file_expect(PiholeFile::Whitelist, "example.com\n", "example.com\n")
+            .expect_status(Status::NotFound)
+            .expect_json(json!({
+                "error": {
+                    "key": "not_found",
+                    "message": "Not found",
+                    "data": None::<()>
+                }
+            }))
+            .test();
+    }
+
+    /// Deleting a regex which is not in the regex list is not found
+    #[test]
+    fn test_delete_regexlist_not_found() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/regexlist/%5E.%2Aexample.com%24")
+            .method(Method::Delete)
+            .file_expect(
+                PiholeFile::Regexlist,
+                "^.*example.net$\n",
+                "^.*example.net$\n"
+            )
+            .expect_status(Status::NotFound)
+            .expect_json(json!({
+                "error": {
+                    "key": "not_found",
+                    "message": "Not found",
+                    "data": None::<()>
+                }
+            }))
+            .test();
+    }
 }
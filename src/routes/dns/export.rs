@@ -0,0 +1,111 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// List Export Endpoint
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    env::Env,
+    routes::{
+        auth::User,
+        dns::list::{List, ListEntry},
+        settings::{read_adlist_entries, AdlistEntry}
+    },
+    util::{reply_data, Error, Reply}
+};
+use rocket::State;
+
+/// The version of the export format. It is increased when the format changes
+/// in a way which an older import could not read.
+const EXPORT_VERSION: usize = 1;
+
+/// A backup of every list. Each entry has the same fields as the entries in
+/// the list endpoints, minus the fields derived when the lists are read, so
+/// it can be imported again as it is.
+#[derive(Serialize, Deserialize)]
+pub struct ListExport {
+    version: usize,
+    whitelist: Vec<ListEntry>,
+    blacklist: Vec<ListEntry>,
+    regexlist: Vec<ListEntry>,
+    adlists: Vec<AdlistEntry>
+}
+
+/// Export the whitelist, blacklist, regex list, and adlists, with their
+/// enabled states and comments
+#[get("/dns/export")]
+pub fn export_lists(env: State<Env>, _auth: User) -> Reply {
+    reply_data(get_list_export(&env)?)
+}
+
+/// Read every list into the export format
+fn get_list_export(env: &Env) -> Result<ListExport, Error> {
+    Ok(ListExport {
+        version: EXPORT_VERSION,
+        whitelist: List::White.get_entries(env)?,
+        blacklist: List::Black.get_entries(env)?,
+        regexlist: List::Regex.get_entries(env)?,
+        adlists: read_adlist_entries(env)?
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{env::PiholeFile, testing::TestBuilder};
+
+    /// Every list is exported with its enabled states and comments
+    #[test]
+    fn export() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/export")
+            .file(PiholeFile::Whitelist, "example.com\n#example.net\n")
+            .file(PiholeFile::Blacklist, "ads.example.com\n")
+            .file(PiholeFile::Regexlist, "^tracker\\.\n")
+            .file(PiholeFile::Adlists, "https://example.com/hosts\n")
+            .file(
+                PiholeFile::ListComments,
+                "white\texample.net\tBroken\n\
+                 adlist\thttps://example.com/hosts\tDefault\n"
+            )
+            .expect_json(json!({
+                "version": 1,
+                "whitelist": [
+                    { "domain": "example.com", "enabled": true, "comment": None::<()> },
+                    { "domain": "example.net", "enabled": false, "comment": "Broken" }
+                ],
+                "blacklist": [
+                    { "domain": "ads.example.com", "enabled": true, "comment": None::<()> }
+                ],
+                "regexlist": [
+                    { "domain": "^tracker\\.", "enabled": true, "comment": None::<()> }
+                ],
+                "adlists": [
+                    {
+                        "address": "https://example.com/hosts",
+                        "enabled": true,
+                        "comment": "Default"
+                    }
+                ]
+            }))
+            .test();
+    }
+
+    /// Missing lists are exported as empty
+    #[test]
+    fn export_empty() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/export")
+            .expect_json(json!({
+                "version": 1,
+                "whitelist": [],
+                "blacklist": [],
+                "regexlist": [],
+                "adlists": []
+            }))
+            .test();
+    }
+}
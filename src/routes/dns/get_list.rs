@@ -11,26 +11,124 @@
 use crate::{
     env::Env,
     routes::dns::list::List,
-    util::{reply_result, Reply}
+    util::{reply_data, Reply}
+};
+use rocket::{
+    http::RawStr,
+    request::{Form, FromFormValue},
+    State
 };
-use rocket::State;
 
 /// Get the Whitelist domains
-#[get("/dns/whitelist")]
-pub fn get_whitelist(env: State<Env>) -> Reply {
-    reply_result(List::White.get(&env))
+#[get("/dns/whitelist?<params..>")]
+pub fn get_whitelist(env: State<Env>, params: Form<ListParams>) -> Reply {
+    get_list(List::White, &env, params.into_inner())
 }
 
 /// Get the Blacklist domains
-#[get("/dns/blacklist")]
-pub fn get_blacklist(env: State<Env>) -> Reply {
-    reply_result(List::Black.get(&env))
+#[get("/dns/blacklist?<params..>")]
+pub fn get_blacklist(env: State<Env>, params: Form<ListParams>) -> Reply {
+    get_list(List::Black, &env, params.into_inner())
 }
 
 /// Get the Regex list domains
-#[get("/dns/regexlist")]
-pub fn get_regexlist(env: State<Env>) -> Reply {
-    reply_result(List::Regex.get(&env))
+#[get("/dns/regexlist?<params..>")]
+pub fn get_regexlist(env: State<Env>, params: Form<ListParams>) -> Reply {
+    get_list(List::Regex, &env, params.into_inner())
+}
+
+/// Represents the possible GET parameters for list requests
+#[derive(FromForm)]
+pub struct ListParams {
+    pub limit: Option<usize>,
+    pub offset: Option<usize>,
+    pub sort: Option<ListSort>,
+    /// Reply with the entries, including disabled domains and comments,
+    /// instead of only the enabled domains
+    pub details: Option<bool>
+}
+
+impl ListParams {
+    /// Check if any of the page parameters were given
+    fn is_empty(&self) -> bool {
+        self.limit.is_none() && self.offset.is_none() && self.sort.is_none()
+    }
+}
+
+/// The orders which list entries can be sorted in
+#[cfg_attr(test, derive(Debug))]
+#[derive(Copy, Clone, PartialEq)]
+pub enum ListSort {
+    Domain,
+    /// The order the domains were added in, which is the order of the list
+    DateAdded
+}
+
+impl<'v> FromFormValue<'v> for ListSort {
+    type Error = &'v RawStr;
+
+    fn from_form_value(form_value: &'v RawStr) -> Result<Self, Self::Error> {
+        match form_value.as_str() {
+            "domain" => Ok(ListSort::Domain),
+            "date_added" => Ok(ListSort::DateAdded),
+            _ => Err(form_value)
+        }
+    }
+}
+
+/// Represents the reply structure for a page of the list. The page holds
+/// the enabled domains, or the entries if `details` is set.
+#[derive(Serialize)]
+pub struct ListPageReply<T> {
+    pub domains: Vec<T>,
+    /// The number of domains or entries in the whole list
+    pub total: usize
+}
+
+/// Get the list: the enabled domains, or every entry if `details` is set.
+/// Without any page parameters, the whole list is returned. Otherwise, the
+/// sorted page is returned with the size of the whole list.
+fn get_list(list: List, env: &Env, params: ListParams) -> Reply {
+    if params.details == Some(true) {
+        let entries = list.get_entries(env)?;
+
+        if params.is_empty() {
+            return reply_data(entries);
+        }
+
+        reply_data(get_list_page(entries, &params, |entry| &entry.domain))
+    } else {
+        let domains = list.get(env)?;
+
+        if params.is_empty() {
+            return reply_data(domains);
+        }
+
+        reply_data(get_list_page(domains, &params, |domain| domain))
+    }
+}
+
+/// Get a sorted page of the list. `domain` gets the domain of an item, which
+/// it is sorted by.
+fn get_list_page<T>(
+    mut items: Vec<T>,
+    params: &ListParams,
+    domain: impl Fn(&T) -> &String
+) -> ListPageReply<T> {
+    let total = items.len();
+
+    if params.sort == Some(ListSort::Domain) {
+        items.sort_by(|a, b| domain(a).cmp(domain(b)));
+    }
+
+    ListPageReply {
+        domains: items
+            .into_iter()
+            .skip(params.offset.unwrap_or(0))
+            .take(params.limit.unwrap_or(total))
+            .collect(),
+        total
+    }
 }
 
 #[cfg(test)]
@@ -63,4 +161,127 @@ mod test {
             .expect_json(json!(["^.*example.com$", "example.net"]))
             .test();
     }
+
+    /// Disabled domains are left out of the plain list
+    #[test]
+    fn disabled_hidden() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/whitelist")
+            .file(PiholeFile::Whitelist, "#example.com\nexample.net\n")
+            .expect_json(json!(["example.net"]))
+            .test();
+    }
+
+    /// With details, each entry has its comment. Comments of other lists are
+    /// not used.
+    #[test]
+    fn details_comments() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/whitelist?details=true")
+            .file(PiholeFile::Whitelist, "example.com\nexample.net\n")
+            .file(
+                PiholeFile::ListComments,
+                "white\texample.net\tNeeded for login\nblack\texample.com\tAds\n"
+            )
+            .expect_json(json!([
+                { "domain": "example.com", "enabled": true, "comment": None::<()> },
+                { "domain": "example.net", "enabled": true, "comment": "Needed for login" }
+            ]))
+            .test();
+    }
+
+    /// With details, disabled domains are listed as not enabled
+    #[test]
+    fn details_disabled() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/regexlist?details=true")
+            .file(PiholeFile::Regexlist, "#^.*example.com$\nexample.net\n")
+            .expect_json(json!([
+                { "domain": "^.*example.com$", "enabled": false, "comment": None::<()> },
+                { "domain": "example.net", "enabled": true, "comment": None::<()> }
+            ]))
+            .test();
+    }
+
+    /// A page of the enabled domains is returned with the number of enabled
+    /// domains
+    #[test]
+    fn page() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/whitelist?limit=2&offset=1")
+            .file(
+                PiholeFile::Whitelist,
+                "example.com\n#example.edu\nexample.net\nexample.org\nexample.info\n"
+            )
+            .expect_json(json!({
+                "domains": ["example.net", "example.org"],
+                "total": 4
+            }))
+            .test();
+    }
+
+    /// With details, the page holds entries and disabled domains are counted
+    #[test]
+    fn page_details() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/whitelist?limit=2&offset=1&details=true")
+            .file(
+                PiholeFile::Whitelist,
+                "example.com\n#example.edu\nexample.net\nexample.org\n"
+            )
+            .file(PiholeFile::ListComments, "white\texample.net\tLogin\n")
+            .expect_json(json!({
+                "domains": [
+                    { "domain": "example.edu", "enabled": false, "comment": None::<()> },
+                    { "domain": "example.net", "enabled": true, "comment": "Login" }
+                ],
+                "total": 4
+            }))
+            .test();
+    }
+
+    /// The domains can be sorted before the page is taken
+    #[test]
+    fn sort_by_domain() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/blacklist?sort=domain&limit=2")
+            .file(
+                PiholeFile::Blacklist,
+                "example.net\nexample.com\nads.example.org\n"
+            )
+            .expect_json(json!({
+                "domains": ["ads.example.org", "example.com"],
+                "total": 3
+            }))
+            .test();
+    }
+
+    /// With details, the entries are sorted by their domain
+    #[test]
+    fn sort_details_by_domain() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/blacklist?sort=domain&details=true")
+            .file(PiholeFile::Blacklist, "example.net\n#example.com\n")
+            .expect_json(json!({
+                "domains": [
+                    { "domain": "example.com", "enabled": false, "comment": None::<()> },
+                    { "domain": "example.net", "enabled": true, "comment": None::<()> }
+                ],
+                "total": 2
+            }))
+            .test();
+    }
+
+    /// Sorting by the date added keeps the order of the list
+    #[test]
+    fn sort_by_date_added() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/blacklist?sort=date_added")
+            .file(PiholeFile::Blacklist, "example.net\nexample.com\n")
+            .expect_json(json!({
+                "domains": ["example.net", "example.com"],
+                "total": 2
+            }))
+            .test();
+    }
 }
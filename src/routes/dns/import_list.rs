@@ -0,0 +1,241 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// Endpoints For Importing Domains Into Lists
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    env::Env,
+    routes::{
+        auth::User,
+        dns::{
+            common::reload_gravity,
+            list::{List, ListChange}
+        }
+    },
+    util::{reply_data, Error, ErrorKind, Reply}
+};
+use failure::ResultExt;
+use rocket::{Data, State};
+use std::io::Read;
+
+/// The largest import accepted, in bytes
+const MAX_IMPORT_SIZE: u64 = 4 * 1024 * 1024;
+
+/// Represents a line of an import which was not added because it is invalid
+#[derive(Serialize)]
+#[cfg_attr(test, derive(Debug, PartialEq))]
+pub struct SkippedLine {
+    /// The line number, starting at 1
+    line: usize,
+    entry: String
+}
+
+/// Represents the reply structure for importing domains
+#[derive(Serialize)]
+#[cfg_attr(test, derive(Debug, PartialEq))]
+pub struct ImportReply {
+    added: usize,
+    existing: usize,
+    skipped: Vec<SkippedLine>
+}
+
+/// Import a newline separated list of domains into the whitelist
+#[post("/dns/whitelist/import", data = "<data>")]
+pub fn import_whitelist(_auth: User, env: State<Env>, data: Data) -> Reply {
+    let text = read_import(data.open())?;
+
+    let mut change = ListChange::new(&env);
+
+    // We need to add them to the whitelist and remove them from the blacklist
+    let (reply, domains) = import_domains(&List::White, &text, &mut change)?;
+
+    List::Black.remove_all(&domains, &mut change)?;
+
+    change.commit()?;
+
+    // At this point, since we haven't hit an error yet, reload gravity
+    reload_gravity(List::White, &env)?;
+    reply_data(reply)
+}
+
+/// Import a newline separated list of domains into the blacklist
+#[post("/dns/blacklist/import", data = "<data>")]
+pub fn import_blacklist(_auth: User, env: State<Env>, data: Data) -> Reply {
+    let text = read_import(data.open())?;
+
+    let mut change = ListChange::new(&env);
+
+    // We need to add them to the blacklist and remove them from the whitelist
+    let (reply, domains) = import_domains(&List::Black, &text, &mut change)?;
+
+    List::White.remove_all(&domains, &mut change)?;
+
+    change.commit()?;
+
+    // At this point, since we haven't hit an error yet, reload gravity
+    reload_gravity(List::Black, &env)?;
+    reply_data(reply)
+}
+
+/// Read the uploaded text, which must be UTF-8. At most `MAX_IMPORT_SIZE`
+/// bytes are read, so large uploads are rejected before they are buffered.
+fn read_import(reader: impl Read) -> Result<String, Error> {
+    let mut bytes = Vec::new();
+
+    reader
+        .take(MAX_IMPORT_SIZE + 1)
+        .read_to_end(&mut bytes)
+        .context(ErrorKind::BadRequest)?;
+
+    if bytes.len() as u64 > MAX_IMPORT_SIZE {
+        return Err(Error::from(ErrorKind::PayloadTooLarge));
+    }
+
+    Ok(String::from_utf8(bytes).context(ErrorKind::BadRequest)?)
+}
+
+/// Add the valid domains in the text to the list, one per line. Blank lines
+/// and lines starting with `#` are ignored, and invalid lines are skipped. The
+/// valid domains are returned along with the reply.
+fn import_domains(
+    list: &List,
+    text: &str,
+    change: &mut ListChange
+) -> Result<(ImportReply, Vec<String>), Error> {
+    let mut domains = Vec::new();
+    let mut skipped = Vec::new();
+
+    for (i, line) in text.lines().enumerate() {
+        let entry = line.trim();
+
+        if entry.is_empty() || entry.starts_with('#') {
+            continue;
+        }
+
+        if list.check_valid(entry).is_ok() {
+            domains.push(entry.to_owned());
+        } else {
+            skipped.push(SkippedLine {
+                line: i + 1,
+                entry: entry.to_owned()
+            });
+        }
+    }
+
+    let (added, existing) = list.add_all(&domains, change)?;
+
+    Ok((
+        ImportReply {
+            added,
+            existing,
+            skipped
+        },
+        domains
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{import_domains, read_import, ImportReply, SkippedLine, MAX_IMPORT_SIZE};
+    use crate::{
+        env::{Config, Env, PiholeFile},
+        routes::dns::list::{List, ListChange},
+        testing::TestEnvBuilder,
+        util::ErrorKind
+    };
+    use std::io::{self, Read};
+
+    /// Valid domains are added, domains already in the list are counted, and
+    /// invalid lines are reported with their line numbers
+    #[test]
+    fn import() {
+        let env_builder = TestEnvBuilder::new().file_expect(
+            PiholeFile::Whitelist,
+            "example.com\n",
+            "example.com\nexample.net\nexample.org\n"
+        );
+        let mut test_file = env_builder.get_test_files().into_iter().next().unwrap();
+        let env = Env::Test(Config::default(), env_builder.build());
+
+        let mut change = ListChange::new(&env);
+        let (reply, domains) = import_domains(
+            &List::White,
+            "# Exported whitelist\nexample.com\nnot a domain\n\nexample.net\r\n  example.org\n",
+            &mut change
+        )
+        .unwrap();
+        change.commit().unwrap();
+
+        assert_eq!(
+            reply,
+            ImportReply {
+                added: 2,
+                existing: 1,
+                skipped: vec![SkippedLine {
+                    line: 3,
+                    entry: "not a domain".to_owned()
+                }]
+            }
+        );
+        assert_eq!(domains, vec!["example.com", "example.net", "example.org"]);
+
+        let mut buffer = String::new();
+        test_file.assert_expected(&mut buffer);
+    }
+
+    /// Imported domains are removed from the opposite list, along with their
+    /// comments there
+    #[test]
+    fn import_removes_from_opposite() {
+        let env_builder = TestEnvBuilder::new()
+            .file_expect(PiholeFile::Whitelist, "", "example.com\nexample.net\n")
+            .file_expect(
+                PiholeFile::Blacklist,
+                "example.com\nads.example.org\n#example.net\n",
+                "ads.example.org\n"
+            )
+            .file_expect(
+                PiholeFile::ListComments,
+                "black\texample.com\tAds\nblack\tads.example.org\tTracker\n",
+                "black\tads.example.org\tTracker\n"
+            );
+        let test_files = env_builder.get_test_files();
+        let env = Env::Test(Config::default(), env_builder.build());
+
+        let mut change = ListChange::new(&env);
+        let (_, domains) =
+            import_domains(&List::White, "example.com\nexample.net\n", &mut change).unwrap();
+        List::Black.remove_all(&domains, &mut change).unwrap();
+        change.commit().unwrap();
+
+        let mut buffer = String::new();
+        for mut test_file in test_files {
+            test_file.assert_expected(&mut buffer);
+        }
+    }
+
+    /// Uploads over the size limit are rejected
+    #[test]
+    fn too_large() {
+        let upload = io::repeat(b'a').take(MAX_IMPORT_SIZE + 1);
+
+        assert_eq!(
+            read_import(upload).unwrap_err().kind(),
+            ErrorKind::PayloadTooLarge
+        );
+    }
+
+    /// Uploads must be UTF-8
+    #[test]
+    fn not_utf8() {
+        assert_eq!(
+            read_import(&[0xff, 0xfe][..]).unwrap_err().kind(),
+            ErrorKind::BadRequest
+        );
+    }
+}
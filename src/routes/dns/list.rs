@@ -10,11 +10,17 @@
 
 use crate::{
     env::{Env, PiholeFile},
-    routes::dns::common::{is_valid_domain, is_valid_regex},
+    routes::dns::{
+        audit::{append_audit_records, format_audit_record, AuditAction},
+        common::{check_regex, is_valid_domain}
+    },
     util::{Error, ErrorKind}
 };
 use failure::ResultExt;
-use std::io::{prelude::*, BufWriter};
+use std::{
+    collections::{HashMap, HashSet},
+    io::Write
+};
 
 pub enum List {
     White,
@@ -22,6 +28,14 @@ pub enum List {
     Regex
 }
 
+/// Represents a domain in a list, whether it is enabled, and its comment
+#[derive(Serialize, Deserialize)]
+pub struct ListEntry {
+    pub domain: String,
+    pub enabled: bool,
+    pub comment: Option<String>
+}
+
 impl List {
     /// Get the associated `PiholeFile`
     fn file(&self) -> PiholeFile {
@@ -32,18 +46,36 @@ impl List {
         }
     }
 
-    /// Check if the list accepts the domain as valid
-    fn accepts(&self, domain: &str) -> bool {
+    /// Get the name of the list, which identifies its entries in the comments
+    /// file
+    fn name(&self) -> &'static str {
         match *self {
-            List::Regex => is_valid_regex(domain),
-            _ => is_valid_domain(domain)
+            List::White => "white",
+            List::Black => "black",
+            List::Regex => "regex"
         }
     }
 
-    /// Read in the domains from the list
-    pub fn get(&self, env: &Env) -> Result<Vec<String>, Error> {
-        let domains = match env.read_file_lines(self.file()) {
-            Ok(domains) => domains,
+    /// Check if the list accepts the domain as valid. Regexes must compile, and
+    /// the compilation error is returned if they do not.
+    pub fn check_valid(&self, domain: &str) -> Result<(), Error> {
+        match *self {
+            List::Regex => check_regex(domain),
+            _ => {
+                if is_valid_domain(domain) {
+                    Ok(())
+                } else {
+                    Err(Error::from(ErrorKind::InvalidDomain))
+                }
+            }
+        }
+    }
+
+    /// Read in the lines of the list, skipping blank lines. Disabled domains
+    /// are commented out.
+    fn read_lines(&self, env: &Env) -> Result<Vec<String>, Error> {
+        let lines = match env.read_file_lines(self.file()) {
+            Ok(lines) => lines,
             Err(e) => {
                 if e.kind() == ErrorKind::NotFound {
                     // If the file is not found, then the list is empty
@@ -54,39 +86,125 @@ impl List {
             }
         };
 
-        Ok(domains
+        Ok(lines.into_iter().filter(|line| !line.is_empty()).collect())
+    }
+
+    /// Read in the enabled domains from the list
+    pub fn get(&self, env: &Env) -> Result<Vec<String>, Error> {
+        Ok(self
+            .read_lines(env)?
             .into_iter()
-            .filter(|domain| !domain.is_empty())
+            .filter(|line| !line.starts_with('#'))
             .collect())
     }
 
+    /// Check if the domain is in the list, whether it is enabled or not
+    pub fn contains(&self, domain: &str, env: &Env) -> Result<bool, Error> {
+        Ok(self
+            .read_lines(env)?
+            .iter()
+            .any(|line| parse_line(line).0 == domain))
+    }
+
+    /// Read in the domains from the list, along with whether they are enabled
+    /// and their comments
+    pub fn get_entries(&self, env: &Env) -> Result<Vec<ListEntry>, Error> {
+        let mut comments = self.get_comments(env)?;
+
+        Ok(self
+            .read_lines(env)?
+            .iter()
+            .map(|line| {
+                let (domain, enabled) = parse_line(line);
+
+                ListEntry {
+                    domain: domain.to_owned(),
+                    enabled,
+                    comment: comments.remove(domain)
+                }
+            })
+            .collect())
+    }
+
+    /// Read in the comments of the list's domains
+    fn get_comments(&self, env: &Env) -> Result<HashMap<String, String>, Error> {
+        read_comments(self.name(), env)
+    }
+
+    /// Get the lines of the list in the change, which are read in the first
+    /// time the list is changed
+    fn change_lines<'c>(&self, change: &'c mut ListChange) -> Result<&'c mut Vec<String>, Error> {
+        change.lines(self.file(), |env| self.read_lines(env))
+    }
+
+    /// Set the comment of a domain in the list. If `comment` is `None`, the
+    /// domain's comment is removed.
+    pub fn set_comment(
+        &self,
+        domain: &str,
+        comment: Option<&str>,
+        change: &mut ListChange
+    ) -> Result<(), Error> {
+        change.set_comment(self.name(), domain, comment)
+    }
+
     /// Add a domain to the list
-    pub fn add(&self, domain: &str, env: &Env) -> Result<(), Error> {
+    pub fn add(&self, domain: &str, change: &mut ListChange) -> Result<(), Error> {
         // Check if it's a valid domain before doing anything
-        if !self.accepts(domain) {
-            return Err(Error::from(ErrorKind::InvalidDomain));
-        }
+        self.check_valid(domain)?;
+
+        let lines = self.change_lines(change)?;
 
-        // Check if the domain is already in the list
-        if self.get(env)?.contains(&domain.to_owned()) {
+        // Check if the domain is already in the list, even if it is disabled
+        if lines.iter().any(|line| parse_line(line).0 == domain) {
             return Err(Error::from(ErrorKind::AlreadyExists));
         }
 
-        // Open the list file in append mode (and create it if it doesn't exist)
-        let mut file = env.write_file(self.file(), true)?;
-
-        // Add the domain to the list
-        writeln!(file, "{}", domain).context(ErrorKind::FileWrite(
-            env.file_location(self.file()).to_owned()
-        ))?;
+        lines.push(domain.to_owned());
+        change.record(AuditAction::Add, self.name(), domain);
 
         Ok(())
     }
 
+    /// Add multiple domains to the list at once. If any of the domains are
+    /// invalid, nothing is changed and the first invalid domain is returned in
+    /// the error. Domains which are already in the list are skipped. The
+    /// number of added domains and the number of skipped domains is returned.
+    pub fn add_all(
+        &self,
+        domains: &[String],
+        change: &mut ListChange
+    ) -> Result<(usize, usize), Error> {
+        // Check that every domain is valid before changing anything
+        if let Some(invalid) = domains
+            .iter()
+            .find(|domain| self.check_valid(domain).is_err())
+        {
+            return Err(Error::from(ErrorKind::InvalidListEntry(invalid.to_owned())));
+        }
+
+        // Only add domains which are not yet in the list (or earlier in the
+        // input)
+        let lines = self.change_lines(change)?;
+        let mut existing: HashSet<&str> = lines.iter().map(|line| parse_line(line).0).collect();
+        let new_domains: Vec<&String> = domains
+            .iter()
+            .filter(|domain| existing.insert(domain.as_str()))
+            .collect();
+
+        lines.extend(new_domains.iter().map(|domain| domain.to_string()));
+
+        for domain in &new_domains {
+            change.record(AuditAction::Add, self.name(), domain);
+        }
+
+        Ok((new_domains.len(), domains.len() - new_domains.len()))
+    }
+
     /// Try to remove a domain from the list, but it is not an error if the
     /// domain does not exist
-    pub fn try_remove(&self, domain: &str, env: &Env) -> Result<(), Error> {
-        match self.remove(domain, env) {
+    pub fn try_remove(&self, domain: &str, change: &mut ListChange) -> Result<(), Error> {
+        match self.remove(domain, change) {
             // Pass through successful results
             Ok(_) => Ok(()),
             Err(e) => {
@@ -100,31 +218,287 @@ impl List {
         }
     }
 
+    /// Remove multiple domains from the list at once. Domains which are not
+    /// in the list are skipped, and the removed domains' comments are removed
+    /// along with them.
+    pub fn remove_all(&self, domains: &[String], change: &mut ListChange) -> Result<(), Error> {
+        let to_remove: HashSet<&str> = domains.iter().map(String::as_str).collect();
+        let mut removed: Vec<String> = Vec::new();
+
+        // Filter the list once, keeping track of the domains which were in it
+        self.change_lines(change)?.retain(|line| {
+            let domain = parse_line(line).0;
+
+            if to_remove.contains(domain) {
+                removed.push(domain.to_owned());
+                false
+            } else {
+                true
+            }
+        });
+
+        // A domain is only recorded once, even if it was in the list twice
+        let mut seen = HashSet::new();
+        removed.retain(|domain| seen.insert(domain.clone()));
+
+        for domain in &removed {
+            change.record(AuditAction::Remove, self.name(), domain);
+        }
+
+        change.remove_comments(self.name(), &removed)
+    }
+
     /// Remove a domain from the list
-    pub fn remove(&self, domain: &str, env: &Env) -> Result<(), Error> {
+    pub fn remove(&self, domain: &str, change: &mut ListChange) -> Result<(), Error> {
         // Check if it's a valid domain before doing anything
-        if !self.accepts(domain) {
-            return Err(Error::from(ErrorKind::InvalidDomain));
-        }
+        self.check_valid(domain)?;
 
         // Check if the domain is not in the list
-        let domains = self.get(env)?;
-        if !domains.contains(&domain.to_owned()) {
+        let lines = self.change_lines(change)?;
+        if !lines.iter().any(|line| parse_line(line).0 == domain) {
             return Err(Error::from(ErrorKind::NotFound));
         }
 
-        // Open the list file (and create it if it doesn't exist). This will truncate
-        // the list so we can add all the domains except the one we are deleting
-        let file = env.write_file(self.file(), false)?;
-        let mut writer = BufWriter::new(file);
+        // Keep all domains except the one we're deleting
+        lines.retain(|line| parse_line(line).0 != domain);
+        change.record(AuditAction::Remove, self.name(), domain);
+
+        // The domain's comment is removed along with it
+        self.set_comment(domain, None, change)
+    }
+
+    /// Enable or disable a domain in the list. Disabled domains are commented
+    /// out, so they keep their place and comment.
+    pub fn set_enabled(
+        &self,
+        domain: &str,
+        enabled: bool,
+        change: &mut ListChange
+    ) -> Result<(), Error> {
+        let lines = self.change_lines(change)?;
+        if !lines.iter().any(|line| parse_line(line).0 == domain) {
+            return Err(Error::from(ErrorKind::NotFound));
+        }
+
+        let new_line = if enabled {
+            domain.to_owned()
+        } else {
+            format!("#{}", domain)
+        };
+
+        for line in lines.iter_mut() {
+            if parse_line(line).0 == domain {
+                *line = new_line.clone();
+            }
+        }
+
+        let action = if enabled {
+            AuditAction::Enable
+        } else {
+            AuditAction::Disable
+        };
+
+        change.record(action, self.name(), domain);
+
+        Ok(())
+    }
+}
+
+/// A change to the list files, which is written all at once. The new lines
+/// of each file and the audit records of the change are built in memory
+/// first, and nothing is written until the change is committed. Dropping the
+/// change without committing it discards it.
+pub struct ListChange<'a> {
+    env: &'a Env,
+    /// The files in the change, with their original lines and their new lines
+    files: Vec<(PiholeFile, Vec<String>, Vec<String>)>,
+    /// The audit records of the change, ready to be appended to the log
+    records: Vec<String>
+}
 
-        // Write all domains except the one we're deleting
-        for domain in domains.into_iter().filter(|item| item != domain) {
-            writeln!(writer, "{}", domain).context(ErrorKind::FileWrite(
-                env.file_location(self.file()).to_owned()
-            ))?;
+impl<'a> ListChange<'a> {
+    /// Start an empty change
+    pub fn new(env: &'a Env) -> ListChange<'a> {
+        ListChange {
+            env,
+            files: Vec::new(),
+            records: Vec::new()
+        }
+    }
+
+    /// Get the new lines of a file. The first time a file is used in the
+    /// change, its current lines are read in with `read`.
+    fn lines(
+        &mut self,
+        file: PiholeFile,
+        read: impl FnOnce(&Env) -> Result<Vec<String>, Error>
+    ) -> Result<&mut Vec<String>, Error> {
+        let index = match self.files.iter().position(|(used, _, _)| *used == file) {
+            Some(index) => index,
+            None => {
+                let lines = read(self.env)?;
+
+                self.files.push((file, lines.clone(), lines));
+                self.files.len() - 1
+            }
+        };
+
+        Ok(&mut self.files[index].2)
+    }
+
+    /// Add an audit record of a list change
+    fn record(&mut self, action: AuditAction, list_name: &str, domain: &str) {
+        self.records
+            .push(format_audit_record(action, list_name, domain));
+    }
+
+    /// Set the comment of an entry in a list. If `comment` is `None`, the
+    /// entry's comment is removed.
+    pub fn set_comment(
+        &mut self,
+        list_name: &str,
+        entry: &str,
+        comment: Option<&str>
+    ) -> Result<(), Error> {
+        let entry_prefix = format!("{}\t{}\t", list_name, entry);
+        let lines = self.lines(PiholeFile::ListComments, read_comment_lines)?;
+
+        lines.retain(|line| !line.starts_with(&entry_prefix));
+
+        // Comments are stored on a single line
+        if let Some(comment) = comment {
+            lines.push(format!(
+                "{}{}",
+                entry_prefix,
+                comment.replace(|c| c == '\n' || c == '\r', " ")
+            ));
         }
 
         Ok(())
     }
+
+    /// Remove the comments of multiple entries in a list
+    fn remove_comments(&mut self, list_name: &str, entries: &[String]) -> Result<(), Error> {
+        if entries.is_empty() {
+            return Ok(());
+        }
+
+        let prefix = format!("{}\t", list_name);
+        let entries: HashSet<&str> = entries.iter().map(String::as_str).collect();
+        let lines = self.lines(PiholeFile::ListComments, read_comment_lines)?;
+
+        lines.retain(|line| {
+            !line.starts_with(&prefix)
+                || !entries.contains(line[prefix.len()..].split('\t').next().unwrap_or(""))
+        });
+
+        Ok(())
+    }
+
+    /// Write each changed file once, and then append the audit records. If
+    /// any of the writes fail, the files are restored to their original
+    /// lines, so the lists and the audit log do not disagree about what
+    /// changed.
+    pub fn commit(self) -> Result<(), Error> {
+        // Files which did not end up changing are not touched
+        let changed: Vec<&(PiholeFile, Vec<String>, Vec<String>)> = self
+            .files
+            .iter()
+            .filter(|(_, original, lines)| original != lines)
+            .collect();
+
+        for (i, (file, _, lines)) in changed.iter().enumerate() {
+            if let Err(e) = write_file_lines(*file, lines, self.env) {
+                // The failed file may have been truncated already, so it is
+                // restored as well
+                restore_files(&changed[..=i], self.env);
+                return Err(e);
+            }
+        }
+
+        if let Err(e) = append_audit_records(&self.records, self.env) {
+            restore_files(&changed, self.env);
+            return Err(e);
+        }
+
+        Ok(())
+    }
+}
+
+/// Restore the files of a failed change to their original lines. Errors are
+/// ignored, since the error which failed the change is the one reported.
+fn restore_files(files: &[&(PiholeFile, Vec<String>, Vec<String>)], env: &Env) {
+    for (file, original, _) in files {
+        let _ = write_file_lines(*file, original, env);
+    }
+}
+
+/// Write the lines of a file, replacing its contents
+fn write_file_lines(file: PiholeFile, lines: &[String], env: &Env) -> Result<(), Error> {
+    let mut contents = String::new();
+
+    for line in lines {
+        contents.push_str(line);
+        contents.push('\n');
+    }
+
+    // Open the file (and create it if it doesn't exist). This will truncate
+    // the file so all the lines can be written again.
+    env.write_file(file, false)?
+        .write_all(contents.as_bytes())
+        .context(ErrorKind::FileWrite(env.file_location(file).to_owned()))?;
+
+    Ok(())
+}
+
+/// Parse a line of a list into its domain and whether it is enabled
+fn parse_line(line: &str) -> (&str, bool) {
+    if line.starts_with('#') {
+        (&line[1..], false)
+    } else {
+        (line, true)
+    }
+}
+
+/// Read in the comments of a list's entries, by entry. Comments are stored in
+/// a separate file, one per line, as `list<TAB>entry<TAB>comment`.
+pub fn read_comments(list_name: &str, env: &Env) -> Result<HashMap<String, String>, Error> {
+    let prefix = format!("{}\t", list_name);
+
+    Ok(read_comment_lines(env)?
+        .into_iter()
+        .filter(|line| line.starts_with(&prefix))
+        .filter_map(|line| {
+            let mut split = line[prefix.len()..].splitn(2, '\t');
+
+            match (split.next(), split.next()) {
+                (Some(entry), Some(comment)) => Some((entry.to_owned(), comment.to_owned())),
+                _ => None
+            }
+        })
+        .collect())
+}
+
+/// Set the comment of an entry in a list. If `comment` is `None`, the entry's
+/// comment is removed.
+pub fn write_comment(
+    list_name: &str,
+    entry: &str,
+    comment: Option<&str>,
+    env: &Env
+) -> Result<(), Error> {
+    let mut change = ListChange::new(env);
+
+    change.set_comment(list_name, entry, comment)?;
+    change.commit()
+}
+
+/// Read the lines of the list comments file. If it does not exist, there are
+/// no comments.
+fn read_comment_lines(env: &Env) -> Result<Vec<String>, Error> {
+    if !env.file_exists(PiholeFile::ListComments) {
+        return Ok(Vec::new());
+    }
+
+    env.read_file_lines(PiholeFile::ListComments)
 }
@@ -9,10 +9,19 @@
 // Please see LICENSE file for your rights under this license.
 
 mod add_list;
+mod audit;
 mod common;
 mod delete_list;
+mod export;
 mod get_list;
-mod list;
+mod import_list;
+pub mod list;
+mod move_list;
+mod patch_list;
+mod search;
 mod status;
 
-pub use self::{add_list::*, delete_list::*, get_list::*, status::*};
+pub use self::{
+    add_list::*, audit::*, delete_list::*, export::*, get_list::*, import_list::*, move_list::*,
+    patch_list::*, search::*, status::*
+};
@@ -0,0 +1,177 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// Endpoint For Moving Domains Between Lists
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    env::Env,
+    routes::{
+        auth::User,
+        dns::{
+            common::reload_gravity_lists,
+            list::{List, ListChange}
+        }
+    },
+    util::{reply_success, Error, ErrorKind, Reply}
+};
+use rocket::State;
+use rocket_contrib::json::Json;
+
+/// Represents an API input for moving a domain to another list
+#[derive(Deserialize)]
+pub struct MoveInput {
+    domain: String,
+    to: String
+}
+
+/// Move a domain from the blacklist to the whitelist, or the other way around
+#[post("/dns/move", data = "<move_input>")]
+pub fn move_domain(_auth: User, env: State<Env>, move_input: Json<MoveInput>) -> Reply {
+    let MoveInput { domain, to } = move_input.into_inner();
+
+    let (from_list, to_list) = match to.as_str() {
+        "whitelist" => (List::Black, List::White),
+        "blacklist" => (List::White, List::Black),
+        _ => return Err(Error::from(ErrorKind::BadRequest))
+    };
+
+    // The domain must be in the source list, even if it is disabled. Check
+    // before changing anything.
+    let entry = match from_list
+        .get_entries(&env)?
+        .into_iter()
+        .find(|entry| entry.domain == domain)
+    {
+        Some(entry) => entry,
+        None => return Err(Error::from(ErrorKind::NotFound))
+    };
+
+    // Both lists and the comments are changed together, so the domain is
+    // never left in both lists or in neither
+    let mut change = ListChange::new(&env);
+
+    // If the domain is already in the destination list, the moved entry
+    // replaces it
+    from_list.remove(&domain, &mut change)?;
+    to_list.try_remove(&domain, &mut change)?;
+    to_list.add(&domain, &mut change)?;
+
+    // Keep the domain's enabled state and comment
+    if !entry.enabled {
+        to_list.set_enabled(&domain, false, &mut change)?;
+    }
+
+    to_list.set_comment(
+        &domain,
+        entry.comment.as_ref().map(String::as_str),
+        &mut change
+    )?;
+    change.commit()?;
+
+    // At this point, since we haven't hit an error yet, reload gravity
+    reload_gravity_lists(&env)?;
+    reply_success()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{env::PiholeFile, testing::TestBuilder};
+    use rocket::http::{Method, Status};
+
+    /// The domain is moved from the blacklist to the whitelist
+    #[test]
+    fn to_whitelist() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/move")
+            .method(Method::Post)
+            .file_expect(
+                PiholeFile::Whitelist,
+                "example.net\n",
+                "example.net\nexample.com\n"
+            )
+            .file_expect(PiholeFile::Blacklist, "example.com\n", "")
+            .body(json!({ "domain": "example.com", "to": "whitelist" }))
+            .expect_json(json!({ "status": "success" }))
+            .test();
+    }
+
+    /// The domain's comment is moved with it
+    #[test]
+    fn to_blacklist_with_comment() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/move")
+            .method(Method::Post)
+            .file_expect(PiholeFile::Whitelist, "example.com\n", "")
+            .file_expect(PiholeFile::Blacklist, "", "example.com\n")
+            .file_expect(
+                PiholeFile::ListComments,
+                "white\texample.com\tTracker\n",
+                "black\texample.com\tTracker\n"
+            )
+            .body(json!({ "domain": "example.com", "to": "blacklist" }))
+            .expect_json(json!({ "status": "success" }))
+            .test();
+    }
+
+    /// Disabled domains are moved, and stay disabled
+    #[test]
+    fn disabled() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/move")
+            .method(Method::Post)
+            .file_expect(PiholeFile::Whitelist, "", "#example.com\n")
+            .file_expect(
+                PiholeFile::Blacklist,
+                "example.net\n#example.com\n",
+                "example.net\n"
+            )
+            .body(json!({ "domain": "example.com", "to": "whitelist" }))
+            .expect_json(json!({ "status": "success" }))
+            .test();
+    }
+
+    /// Nothing is changed if the domain is not in the source list
+    #[test]
+    fn not_found() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/move")
+            .method(Method::Post)
+            .file_expect(PiholeFile::Whitelist, "example.com\n", "example.com\n")
+            .file_expect(PiholeFile::Blacklist, "", "")
+            .body(json!({ "domain": "example.com", "to": "whitelist" }))
+            .expect_status(Status::NotFound)
+            .expect_json(json!({
+                "error": {
+                    "key": "not_found",
+                    "message": "Not found",
+                    "data": None::<()>
+                }
+            }))
+            .test();
+    }
+
+    /// Domains can only be moved to the whitelist or blacklist
+    #[test]
+    fn invalid_list() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/move")
+            .method(Method::Post)
+            .file(PiholeFile::Whitelist, "example.com\n")
+            .file(PiholeFile::Blacklist, "")
+            .body(json!({ "domain": "example.com", "to": "regexlist" }))
+            .expect_status(Status::BadRequest)
+            .expect_json(json!({
+                "error": {
+                    "key": "bad_request",
+                    "message": "Bad request",
+                    "data": None::<()>
+                }
+            }))
+            .test();
+    }
+}
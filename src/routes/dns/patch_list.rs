@@ -0,0 +1,160 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// Endpoints For Enabling And Disabling List Domains
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    env::Env,
+    ftl::FtlConnectionType,
+    routes::{
+        auth::User,
+        dns::{
+            common::reload_gravity,
+            list::{List, ListChange}
+        }
+    },
+    util::{reply_success, Reply}
+};
+use rocket::State;
+use rocket_contrib::json::Json;
+
+/// Represents an API input containing whether a domain is enabled
+#[derive(Deserialize)]
+pub struct EnabledInput {
+    enabled: bool
+}
+
+/// Enable or disable a domain in the whitelist
+#[patch("/dns/whitelist/<domain>", data = "<input>")]
+pub fn patch_whitelist(
+    _auth: User,
+    env: State<Env>,
+    domain: String,
+    input: Json<EnabledInput>
+) -> Reply {
+    let mut change = ListChange::new(&env);
+
+    List::White.set_enabled(&domain, input.0.enabled, &mut change)?;
+    change.commit()?;
+    reload_gravity(List::White, &env)?;
+    reply_success()
+}
+
+/// Enable or disable a domain in the blacklist
+#[patch("/dns/blacklist/<domain>", data = "<input>")]
+pub fn patch_blacklist(
+    _auth: User,
+    env: State<Env>,
+    domain: String,
+    input: Json<EnabledInput>
+) -> Reply {
+    let mut change = ListChange::new(&env);
+
+    List::Black.set_enabled(&domain, input.0.enabled, &mut change)?;
+    change.commit()?;
+    reload_gravity(List::Black, &env)?;
+    reply_success()
+}
+
+/// Enable or disable a regex in the regex list
+#[patch("/dns/regexlist/<domain>", data = "<input>")]
+pub fn patch_regexlist(
+    _auth: User,
+    env: State<Env>,
+    ftl: State<FtlConnectionType>,
+    domain: String,
+    input: Json<EnabledInput>
+) -> Reply {
+    let mut change = ListChange::new(&env);
+
+    List::Regex.set_enabled(&domain, input.0.enabled, &mut change)?;
+    change.commit()?;
+    ftl.connect("recompile-regex")?.expect_eom()?;
+    reply_success()
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        env::PiholeFile,
+        testing::{write_eom, TestBuilder}
+    };
+    use rocket::http::{Method, Status};
+
+    /// Disabled domains are commented out in place, keeping their comment
+    #[test]
+    fn disable_whitelist() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/whitelist/example.com")
+            .method(Method::Patch)
+            .file_expect(
+                PiholeFile::Whitelist,
+                "example.com\nexample.net\n",
+                "#example.com\nexample.net\n"
+            )
+            .file_expect(
+                PiholeFile::ListComments,
+                "white\texample.com\tNeeded for login\n",
+                "white\texample.com\tNeeded for login\n"
+            )
+            .body(json!({ "enabled": false }))
+            .expect_json(json!({ "status": "success" }))
+            .test();
+    }
+
+    /// A disabled domain can be enabled again
+    #[test]
+    fn enable_blacklist() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/blacklist/example.com")
+            .method(Method::Patch)
+            .file_expect(PiholeFile::Blacklist, "#example.com\n", "example.com\n")
+            .body(json!({ "enabled": true }))
+            .expect_json(json!({ "status": "success" }))
+            .test();
+    }
+
+    /// Regexes are recompiled after being disabled
+    #[test]
+    fn disable_regexlist() {
+        let mut data = Vec::new();
+        write_eom(&mut data);
+
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/regexlist/%5E.%2Aexample.com%24")
+            .method(Method::Patch)
+            .ftl("recompile-regex", data)
+            .file_expect(
+                PiholeFile::Regexlist,
+                "^.*example.com$\n",
+                "#^.*example.com$\n"
+            )
+            .body(json!({ "enabled": false }))
+            .expect_json(json!({ "status": "success" }))
+            .test();
+    }
+
+    /// Changing a domain which is not in the list is an error
+    #[test]
+    fn not_found() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/whitelist/example.com")
+            .method(Method::Patch)
+            .file(PiholeFile::Whitelist, "example.net\n")
+            .body(json!({ "enabled": false }))
+            .expect_status(Status::NotFound)
+            .expect_json(json!({
+                "error": {
+                    "key": "not_found",
+                    "message": "Not found",
+                    "data": None::<()>
+                }
+            }))
+            .test();
+    }
+}
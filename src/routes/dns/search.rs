@@ -0,0 +1,147 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// Endpoint For Searching The Lists
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    databases::gravity::is_gravity_domain,
+    env::Env,
+    routes::dns::{common::is_valid_domain, list::List},
+    util::{reply_data, Error, ErrorKind, Reply}
+};
+use regex::Regex;
+use rocket::State;
+
+/// Represents the reply structure for searching the lists
+#[derive(Serialize)]
+pub struct SearchReply {
+    domain: String,
+    matches: Vec<SearchMatch>
+}
+
+/// Represents a list entry which matches the searched domain
+#[derive(Serialize)]
+pub struct SearchMatch {
+    /// The list with the entry: `gravity`, `whitelist`, `blacklist` or `regex`
+    list: &'static str,
+    /// The matching domain or regex
+    entry: String
+}
+
+/// Find every list entry which matches the domain, to explain why it is (or
+/// is not) blocked
+#[get("/dns/search?<domain>")]
+pub fn search(env: State<Env>, domain: String) -> Reply {
+    reply_data(search_lists(&env, &domain.to_lowercase())?)
+}
+
+/// Search gravity, the exact lists, and the enabled regexes for the domain
+fn search_lists(env: &Env, domain: &str) -> Result<SearchReply, Error> {
+    if !is_valid_domain(domain) {
+        return Err(Error::from(ErrorKind::InvalidDomain));
+    }
+
+    let mut matches = Vec::new();
+
+    if is_gravity_domain(env, domain)? {
+        matches.push(SearchMatch {
+            list: "gravity",
+            entry: domain.to_owned()
+        });
+    }
+
+    for (list, name) in [(List::White, "whitelist"), (List::Black, "blacklist")].iter() {
+        if list.get(env)?.iter().any(|entry| entry == domain) {
+            matches.push(SearchMatch {
+                list: *name,
+                entry: domain.to_owned()
+            });
+        }
+    }
+
+    // Regexes which do not compile can not match anything, so they are
+    // skipped
+    matches.extend(
+        List::Regex
+            .get(env)?
+            .into_iter()
+            .filter(|regex| Regex::new(regex).map_or(false, |regex| regex.is_match(domain)))
+            .map(|regex| SearchMatch {
+                list: "regex",
+                entry: regex
+            })
+    );
+
+    Ok(SearchReply {
+        domain: domain.to_owned(),
+        matches
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{env::PiholeFile, testing::TestBuilder};
+    use rocket::http::Status;
+
+    /// Every matching entry is reported, including overlapping ones.
+    /// Disabled regexes do not match.
+    #[test]
+    fn all_matches() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/search?domain=ads.Example.com")
+            .file(
+                PiholeFile::Gravity,
+                "ads.example.com\ntracker.example.com\n"
+            )
+            .file(PiholeFile::Whitelist, "ads.example.com\n")
+            .file(PiholeFile::Blacklist, "example.com\n")
+            .file(
+                PiholeFile::Regexlist,
+                "^ads\\.\n\
+                 example\\.com$\n\
+                 #^ads\n\
+                 ^tracker\\.\n"
+            )
+            .expect_json(json!({
+                "domain": "ads.example.com",
+                "matches": [
+                    { "list": "gravity", "entry": "ads.example.com" },
+                    { "list": "whitelist", "entry": "ads.example.com" },
+                    { "list": "regex", "entry": "^ads\\." },
+                    { "list": "regex", "entry": "example\\.com$" }
+                ]
+            }))
+            .test();
+    }
+
+    /// A domain in no list has no matches
+    #[test]
+    fn no_matches() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/search?domain=example.net")
+            .file(PiholeFile::Whitelist, "example.com\n")
+            .expect_json(json!({ "domain": "example.net", "matches": [] }))
+            .test();
+    }
+
+    /// The domain must be valid
+    #[test]
+    fn invalid_domain() {
+        TestBuilder::new()
+            .endpoint("/admin/api/dns/search?domain=example%20com")
+            .expect_status(Status::BadRequest)
+            .expect_json(json!({
+                "error": {
+                    "key": "invalid_domain",
+                    "message": "Invalid domain",
+                    "data": None::<()>
+                }
+            }))
+            .test();
+    }
+}
@@ -16,19 +16,88 @@ use crate::{
 };
 use rocket::State;
 use rocket_contrib::json::Json;
-use std::time::Duration;
+use std::{
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex
+    },
+    time::{Duration, Instant}
+};
 use task_scheduler::Scheduler;
 
-/// Get the DNS blocking status
+/// Tracks when blocking will be re-enabled after being temporarily disabled.
+/// Clones share the same timer, so it can be moved to the scheduler thread.
+#[derive(Clone, Default)]
+pub struct BlockingTimer {
+    /// The ID and end time of the pending re-enable, if there is one
+    pending: Arc<Mutex<Option<(usize, Instant)>>>,
+    next_id: Arc<AtomicUsize>
+}
+
+impl BlockingTimer {
+    /// Create a timer with no pending re-enable
+    pub fn new() -> BlockingTimer {
+        BlockingTimer::default()
+    }
+
+    /// Start a timer which ends `duration` from now, replacing any pending
+    /// timer. The ID of the new timer is returned.
+    fn start(&self, duration: Duration, now: Instant) -> usize {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        *self.pending.lock().unwrap() = Some((id, now + duration));
+
+        id
+    }
+
+    /// Cancel the pending timer, if there is one
+    fn cancel(&self) {
+        *self.pending.lock().unwrap() = None;
+    }
+
+    /// Finish the timer with the ID. If it is no longer pending (it was
+    /// cancelled or replaced), false is returned.
+    fn finish(&self, id: usize) -> bool {
+        let mut pending = self.pending.lock().unwrap();
+
+        match *pending {
+            Some((pending_id, _)) if pending_id == id => {
+                *pending = None;
+                true
+            }
+            _ => false
+        }
+    }
+
+    /// Get the time left until the pending timer ends
+    fn remaining(&self, now: Instant) -> Option<Duration> {
+        self.pending.lock().unwrap().map(|(_, end)| {
+            if end > now {
+                end - now
+            } else {
+                Duration::from_secs(0)
+            }
+        })
+    }
+}
+
+/// Get the DNS blocking status. If blocking is temporarily disabled, the
+/// number of seconds until it is re-enabled is included.
 #[get("/dns/status")]
-pub fn status(env: State<Env>) -> Reply {
-    let status = if SetupVarsEntry::BlockingEnabled.is_true(&env)? {
-        "enabled"
+pub fn status(env: State<Env>, timer: State<BlockingTimer>) -> Reply {
+    let enabled = SetupVarsEntry::BlockingEnabled.is_true(&env)?;
+    let status = if enabled { "enabled" } else { "disabled" };
+    let remaining_seconds = if enabled {
+        None
     } else {
-        "disabled"
+        timer
+            .remaining(Instant::now())
+            .map(|remaining| remaining.as_secs())
     };
 
-    reply_data(json!({ "status": status }))
+    reply_data(json!({
+        "status": status,
+        "remaining_seconds": remaining_seconds
+    }))
 }
 
 /// Enable/Disable blocking
@@ -36,11 +105,18 @@ pub fn status(env: State<Env>) -> Reply {
 pub fn change_status(
     env: State<Env>,
     scheduler: State<Scheduler>,
+    timer: State<BlockingTimer>,
     data: Json<ChangeStatus>
 ) -> Reply {
     match (data.action.as_str(), data.time) {
-        ("enable", None) => enable(&env)?,
-        ("disable", time) => disable(&env, time, Some(&scheduler))?,
+        ("enable", None) => {
+            enable(&env)?;
+
+            // Blocking is now enabled, so the pending re-enable is not
+            // needed anymore
+            timer.cancel();
+        }
+        ("disable", time) => disable(&env, time, &timer, Some(&scheduler))?,
         _ => return reply_error(ErrorKind::BadRequest)
     }
 
@@ -71,7 +147,12 @@ fn enable(env: &Env) -> Result<(), Error> {
 
 /// Disable blocking. If the time is `None`, then disable permanently.
 /// Otherwise, re-enable after the specified number of seconds.
-fn disable(env: &Env, time: Option<usize>, scheduler: Option<&Scheduler>) -> Result<(), Error> {
+fn disable(
+    env: &Env,
+    time: Option<usize>,
+    timer: &BlockingTimer,
+    scheduler: Option<&Scheduler>
+) -> Result<(), Error> {
     // Can't disable blocking when it's already disabled
     if !SetupVarsEntry::BlockingEnabled.is_true(&env)? {
         return Err(Error::from(ErrorKind::BadRequest));
@@ -97,6 +178,9 @@ fn disable(env: &Env, time: Option<usize>, scheduler: Option<&Scheduler>) -> Res
 
     reload_dns(env)?;
 
+    // Keep track of when blocking will be re-enabled
+    let timer_id = time.map(|time| timer.start(Duration::from_secs(time as u64), Instant::now()));
+
     // Don't schedule the re-enable when testing. The Clone implementation for
     // Env::Test is not available (crashes due to unimplemented!()), and we
     // don't want to be scheduling work which runs after the tests.
@@ -108,14 +192,21 @@ fn disable(env: &Env, time: Option<usize>, scheduler: Option<&Scheduler>) -> Res
         );
 
         // Check if we should re-enable after a specified timeout
-        if let Some(time) = time {
-            // Make a copy of the Env to move to the scheduler thread
+        if let (Some(time), Some(timer_id)) = (time, timer_id) {
+            // Make a copy of the Env and timer to move to the scheduler thread
             let env_copy = env.clone();
+            let timer = timer.clone();
 
             // Re-enable blocking after the timeout
             scheduler
                 .unwrap()
                 .after_duration(Duration::from_secs(time as u64), move || {
+                    // Don't re-enable if the timer was cancelled, such as by
+                    // enabling blocking manually
+                    if !timer.finish(timer_id) {
+                        return;
+                    }
+
                     // Handle the result of enabling, so that if it's an error
                     // the thread does not panic
                     if let Err(e) = enable(&env_copy) {
@@ -143,18 +234,20 @@ pub struct ChangeStatus {
 
     /// The number of seconds to wait before re-enabling. Should be None when
     /// the action is "enable".
+    #[serde(alias = "duration_seconds")]
     time: Option<usize>
 }
 
 #[cfg(test)]
 mod test {
-    use super::{disable, enable};
+    use super::{disable, enable, BlockingTimer};
     use crate::{
         env::{Config, Env, PiholeFile},
         testing::{TestBuilder, TestEnvBuilder},
         util::ErrorKind
     };
     use rocket::http::Method;
+    use std::time::{Duration, Instant};
 
     /// Return enabled status if blocking is enabled
     #[test]
@@ -162,7 +255,7 @@ mod test {
         TestBuilder::new()
             .endpoint("/admin/api/dns/status")
             .file(PiholeFile::SetupVars, "BLOCKING_ENABLED=true")
-            .expect_json(json!({ "status": "enabled" }))
+            .expect_json(json!({ "status": "enabled", "remaining_seconds": None::<u64> }))
             .test();
     }
 
@@ -172,7 +265,7 @@ mod test {
         TestBuilder::new()
             .endpoint("/admin/api/dns/status")
             .file(PiholeFile::SetupVars, "BLOCKING_ENABLED=false")
-            .expect_json(json!({ "status": "disabled" }))
+            .expect_json(json!({ "status": "disabled", "remaining_seconds": None::<u64> }))
             .test();
     }
 
@@ -182,7 +275,7 @@ mod test {
         TestBuilder::new()
             .endpoint("/admin/api/dns/status")
             .file(PiholeFile::SetupVars, "")
-            .expect_json(json!({ "status": "enabled" }))
+            .expect_json(json!({ "status": "enabled", "remaining_seconds": None::<u64> }))
             .test();
     }
 
@@ -253,8 +346,70 @@ mod test {
         );
 
         assert_eq!(
-            disable(&env, None, None).map_err(|e| e.kind()),
+            disable(&env, None, &BlockingTimer::new(), None).map_err(|e| e.kind()),
             Err(ErrorKind::BadRequest)
         );
     }
+
+    /// Disabling for a duration starts the re-enable timer
+    #[test]
+    fn disable_for_duration() {
+        let env = Env::Test(
+            Config::default(),
+            TestEnvBuilder::new()
+                .file(PiholeFile::SetupVars, "BLOCKING_ENABLED=true")
+                .build()
+        );
+        let timer = BlockingTimer::new();
+
+        disable(&env, Some(60), &timer, None).unwrap();
+
+        let remaining = timer.remaining(Instant::now()).unwrap();
+        assert!(remaining <= Duration::from_secs(60));
+        assert!(remaining > Duration::from_secs(50));
+    }
+
+    /// The remaining time counts down, and does not go below zero
+    #[test]
+    fn timer_remaining() {
+        let timer = BlockingTimer::new();
+        let now = Instant::now();
+
+        assert_eq!(timer.remaining(now), None);
+
+        timer.start(Duration::from_secs(60), now);
+
+        assert_eq!(
+            timer.remaining(now + Duration::from_secs(20)),
+            Some(Duration::from_secs(40))
+        );
+        assert_eq!(
+            timer.remaining(now + Duration::from_secs(90)),
+            Some(Duration::from_secs(0))
+        );
+    }
+
+    /// A cancelled timer does not finish
+    #[test]
+    fn timer_cancelled() {
+        let timer = BlockingTimer::new();
+        let id = timer.start(Duration::from_secs(60), Instant::now());
+
+        timer.cancel();
+
+        assert_eq!(timer.remaining(Instant::now()), None);
+        assert!(!timer.finish(id));
+    }
+
+    /// A replaced timer does not finish, but the new timer does
+    #[test]
+    fn timer_replaced() {
+        let timer = BlockingTimer::new();
+        let old_id = timer.start(Duration::from_secs(60), Instant::now());
+        let new_id = timer.start(Duration::from_secs(120), Instant::now());
+
+        assert!(!timer.finish(old_id));
+        assert!(timer.finish(new_id));
+        assert_eq!(timer.remaining(Instant::now()), None);
+    }
 }
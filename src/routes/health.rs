@@ -0,0 +1,81 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// Health Endpoint
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    ftl::{FtlConnectionType, FtlMemory},
+    util::{reply, Reply}
+};
+use rocket::{http::Status, State};
+
+/// Check if FTL is reachable and its shared memory can be read. If the shared
+/// memory can not be read, the status is 503 Service Unavailable.
+#[get("/health")]
+pub fn health(ftl: State<FtlConnectionType>, ftl_memory: State<FtlMemory>) -> Reply {
+    let ftl_connected = ftl.connect("version").is_ok();
+    let shm_attached = ftl_memory
+        .lock()
+        .and_then(|lock| ftl_memory.counters(&lock).map(|_| ()))
+        .is_ok();
+
+    let status = if shm_attached {
+        Status::Ok
+    } else {
+        Status::ServiceUnavailable
+    };
+
+    reply(
+        Ok(HealthReply {
+            ftl: if ftl_connected {
+                "connected"
+            } else {
+                "disconnected"
+            },
+            shm_attached
+        }),
+        status
+    )
+}
+
+/// Represents the reply structure for the health check
+#[derive(Serialize)]
+pub struct HealthReply {
+    pub ftl: &'static str,
+    pub shm_attached: bool
+}
+
+#[cfg(test)]
+mod test {
+    use crate::testing::{write_eom, TestBuilder};
+
+    /// FTL is disconnected if the socket can not be used, but shared memory can
+    /// still be attached
+    #[test]
+    fn ftl_disconnected() {
+        TestBuilder::new()
+            .endpoint("/admin/api/health")
+            .should_auth(false)
+            .expect_json(json!({ "ftl": "disconnected", "shm_attached": true }))
+            .test();
+    }
+
+    /// FTL is connected if the socket can be used
+    #[test]
+    fn ftl_connected() {
+        let mut data = Vec::new();
+        write_eom(&mut data);
+
+        TestBuilder::new()
+            .endpoint("/admin/api/health")
+            .should_auth(false)
+            .ftl("version", data)
+            .expect_json(json!({ "ftl": "connected", "shm_attached": true }))
+            .test();
+    }
+}
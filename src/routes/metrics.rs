@@ -0,0 +1,174 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// Metrics Endpoint
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    env::Env,
+    ftl::FtlMemory,
+    metrics::RequestMetrics,
+    settings::{ConfigEntry, SetupVarsEntry},
+    util::{Error, ErrorKind}
+};
+use rocket::{http::ContentType, response::content::Content, State};
+use std::{fmt::Write, net::SocketAddr};
+
+/// Get the request metrics and FTL's counters in the Prometheus text format.
+/// Authentication is not required, so unless `API_METRICS_LOCALHOST_ONLY` is
+/// disabled, only clients on the same host can see the metrics.
+#[get("/metrics")]
+pub fn metrics(
+    remote: Option<SocketAddr>,
+    request_metrics: State<RequestMetrics>,
+    ftl_memory: State<FtlMemory>,
+    env: State<Env>
+) -> Result<Content<String>, Error> {
+    let is_local = remote.map_or(false, |remote| remote.ip().is_loopback());
+
+    if !is_local && SetupVarsEntry::ApiMetricsLocalhostOnly.is_true(&env)? {
+        return Err(Error::from(ErrorKind::NotFound));
+    }
+
+    let mut output = String::new();
+    request_metrics.render(&mut output);
+
+    // The FTL gauges are left out if the shared memory can not be read, so
+    // that the request metrics are still available
+    let _ = render_ftl_gauges(&ftl_memory, &mut output);
+
+    Ok(Content(ContentType::Plain, output))
+}
+
+/// Write gauges of FTL's counters in the Prometheus text format
+fn render_ftl_gauges(ftl_memory: &FtlMemory, output: &mut String) -> Result<(), Error> {
+    let lock = ftl_memory.lock()?;
+    let counters = ftl_memory.counters(&lock)?;
+
+    let gauges = [
+        (
+            "pihole_ftl_queries",
+            "The number of queries",
+            counters.total_queries
+        ),
+        (
+            "pihole_ftl_blocked_queries",
+            "The number of blocked queries",
+            counters.blocked_queries
+        ),
+        (
+            "pihole_ftl_cached_queries",
+            "The number of queries answered from the cache",
+            counters.cached_queries
+        ),
+        (
+            "pihole_ftl_forwarded_queries",
+            "The number of queries forwarded to an upstream",
+            counters.forwarded_queries
+        ),
+        (
+            "pihole_ftl_clients",
+            "The number of clients",
+            counters.total_clients
+        ),
+        (
+            "pihole_ftl_domains",
+            "The number of domains",
+            counters.total_domains
+        )
+    ];
+
+    for (name, help, value) in gauges.iter() {
+        writeln!(
+            output,
+            "# HELP {name} {help}\n# TYPE {name} gauge\n{name} {value}",
+            name = name,
+            help = help,
+            value = value
+        )
+        .unwrap();
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::render_ftl_gauges;
+    use crate::{
+        ftl::{FtlCounters, FtlMemory, FtlSettings},
+        testing::TestBuilder
+    };
+    use rocket::http::Status;
+    use std::collections::HashMap;
+
+    /// The FTL gauges hold the counters
+    #[test]
+    fn ftl_gauges() {
+        let ftl_memory = FtlMemory::Test {
+            clients: Vec::new(),
+            domains: Vec::new(),
+            over_time: Vec::new(),
+            strings: HashMap::new(),
+            upstreams: Vec::new(),
+            queries: Vec::new(),
+            counters: FtlCounters {
+                total_queries: 10,
+                blocked_queries: 4,
+                cached_queries: 2,
+                forwarded_queries: 3,
+                total_clients: 5,
+                total_domains: 6,
+                ..FtlCounters::default()
+            },
+            settings: FtlSettings::default()
+        };
+
+        let mut output = String::new();
+        render_ftl_gauges(&ftl_memory, &mut output).unwrap();
+
+        assert_eq!(
+            output,
+            "# HELP pihole_ftl_queries The number of queries\n\
+             # TYPE pihole_ftl_queries gauge\n\
+             pihole_ftl_queries 10\n\
+             # HELP pihole_ftl_blocked_queries The number of blocked queries\n\
+             # TYPE pihole_ftl_blocked_queries gauge\n\
+             pihole_ftl_blocked_queries 4\n\
+             # HELP pihole_ftl_cached_queries The number of queries answered from the cache\n\
+             # TYPE pihole_ftl_cached_queries gauge\n\
+             pihole_ftl_cached_queries 2\n\
+             # HELP pihole_ftl_forwarded_queries The number of queries forwarded to an \
+             upstream\n\
+             # TYPE pihole_ftl_forwarded_queries gauge\n\
+             pihole_ftl_forwarded_queries 3\n\
+             # HELP pihole_ftl_clients The number of clients\n\
+             # TYPE pihole_ftl_clients gauge\n\
+             pihole_ftl_clients 5\n\
+             # HELP pihole_ftl_domains The number of domains\n\
+             # TYPE pihole_ftl_domains gauge\n\
+             pihole_ftl_domains 6\n"
+        );
+    }
+
+    /// The metrics are hidden from clients which are not on the same host
+    #[test]
+    fn localhost_only() {
+        TestBuilder::new()
+            .endpoint("/metrics")
+            .should_auth(false)
+            .expect_status(Status::NotFound)
+            .expect_json(json!({
+                "error": {
+                    "key": "not_found",
+                    "message": "Not found",
+                    "data": None::<()>
+                }
+            }))
+            .test();
+    }
+}
@@ -10,6 +10,8 @@
 
 pub mod auth;
 pub mod dns;
+pub mod health;
+pub mod metrics;
 pub mod settings;
 pub mod stats;
 pub mod version;
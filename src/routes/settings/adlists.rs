@@ -0,0 +1,440 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// Adlist Endpoints
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    env::{Env, PiholeFile},
+    routes::{
+        auth::User,
+        dns::list::{read_comments, write_comment},
+        settings::common::SettingsWriter
+    },
+    settings::ValueType,
+    util::{reply_data, Error, ErrorKind, Reply}
+};
+use failure::ResultExt;
+use rocket::State;
+use rocket_contrib::json::Json;
+use std::io::{BufWriter, Write};
+
+/// The name which identifies adlists in the list comments
+const ADLIST_COMMENTS: &str = "adlist";
+
+/// A configured adlist
+#[derive(Serialize)]
+pub struct Adlist {
+    /// The position of the adlist in the adlists file, starting at 1
+    id: usize,
+    address: String,
+    enabled: bool,
+    comment: Option<String>,
+    /// When gravity was last updated, which downloads every adlist
+    date_updated: Option<u64>
+}
+
+/// An adlist in a backup. The ID and update date are left out, since they
+/// are derived when the adlists are read.
+#[derive(Serialize, Deserialize)]
+pub struct AdlistEntry {
+    pub address: String,
+    pub enabled: bool,
+    pub comment: Option<String>
+}
+
+/// An adlist to add
+#[derive(Deserialize)]
+pub struct NewAdlist {
+    address: String,
+    comment: Option<String>,
+    enabled: Option<bool>
+}
+
+/// Get the configured adlists
+#[get("/settings/adlists")]
+pub fn get_adlists(env: State<Env>, _auth: User) -> Reply {
+    reply_data(read_adlists(&env)?)
+}
+
+/// Add an adlist. Adlists are enabled unless `enabled` is false. With
+/// `dry_run`, the change is only reported.
+#[post("/settings/adlists?<dry_run>", data = "<adlist>")]
+pub fn add_adlist(
+    env: State<Env>,
+    _auth: User,
+    adlist: Json<NewAdlist>,
+    dry_run: Option<bool>
+) -> Reply {
+    let mut writer = SettingsWriter::new(&env, dry_run);
+
+    add_adlist_impl(&env, &mut writer, &adlist.into_inner())?;
+    writer.finish(|_| Ok(()))
+}
+
+/// Remove an adlist by its ID. With `dry_run`, the change is only reported.
+#[delete("/settings/adlists/<id>?<dry_run>")]
+pub fn delete_adlist(env: State<Env>, _auth: User, id: usize, dry_run: Option<bool>) -> Reply {
+    let mut writer = SettingsWriter::new(&env, dry_run);
+
+    delete_adlist_impl(&env, &mut writer, id)?;
+    writer.finish(|_| Ok(()))
+}
+
+/// Read the adlists and their comments
+fn read_adlists(env: &Env) -> Result<Vec<Adlist>, Error> {
+    let mut comments = read_comments(ADLIST_COMMENTS, env)?;
+    let date_updated = env.file_modified(PiholeFile::Gravity);
+
+    Ok(read_adlist_lines(env)?
+        .iter()
+        .filter_map(|line| parse_adlist(line))
+        .enumerate()
+        .map(|(index, (address, enabled))| Adlist {
+            id: index + 1,
+            comment: comments.remove(address),
+            address: address.to_owned(),
+            enabled,
+            date_updated
+        })
+        .collect())
+}
+
+/// Read the adlists and their comments for a backup
+pub fn read_adlist_entries(env: &Env) -> Result<Vec<AdlistEntry>, Error> {
+    Ok(read_adlists(env)?
+        .into_iter()
+        .map(|adlist| AdlistEntry {
+            address: adlist.address,
+            enabled: adlist.enabled,
+            comment: adlist.comment
+        })
+        .collect())
+}
+
+/// Add the adlist to the end of the adlists file. An address can only be
+/// added once, even if the existing adlist is disabled.
+fn add_adlist_impl(
+    env: &Env,
+    writer: &mut SettingsWriter,
+    adlist: &NewAdlist
+) -> Result<(), Error> {
+    if !ValueType::Url.is_valid(&adlist.address) {
+        return Err(Error::from(ErrorKind::InvalidSettingValue));
+    }
+
+    let lines = read_adlist_lines(env)?;
+
+    if lines
+        .iter()
+        .filter_map(|line| parse_adlist(line))
+        .any(|(address, _)| address == adlist.address)
+    {
+        return Err(Error::from(ErrorKind::AlreadyExists));
+    }
+
+    let line = if adlist.enabled.unwrap_or(true) {
+        adlist.address.clone()
+    } else {
+        format!("#{}", adlist.address)
+    };
+
+    writer.append_line(PiholeFile::Adlists, &line)?;
+
+    if let Some(ref comment) = adlist.comment {
+        writer.write_with(PiholeFile::ListComments, &adlist.address, comment, |env| {
+            write_comment(ADLIST_COMMENTS, &adlist.address, Some(comment), env)
+        })?;
+    }
+
+    Ok(())
+}
+
+/// Remove the adlist with the ID from the adlists file, along with its
+/// comment
+fn delete_adlist_impl(env: &Env, writer: &mut SettingsWriter, id: usize) -> Result<(), Error> {
+    let lines = read_adlist_lines(env)?;
+    let address = match lines
+        .iter()
+        .filter_map(|line| parse_adlist(line))
+        .nth(id.wrapping_sub(1))
+    {
+        Some((address, _)) => address.to_owned(),
+        None => return Err(Error::from(ErrorKind::NotFound))
+    };
+
+    writer.write_with(PiholeFile::Adlists, &address, "", |env| {
+        write_adlist_lines(
+            env,
+            lines.iter().filter(|line| {
+                parse_adlist(line).map_or(true, |(line_address, _)| line_address != address)
+            })
+        )
+    })?;
+
+    if read_comments(ADLIST_COMMENTS, env)?.contains_key(&address) {
+        writer.write_with(PiholeFile::ListComments, &address, "", |env| {
+            write_comment(ADLIST_COMMENTS, &address, None, env)
+        })?;
+    }
+
+    Ok(())
+}
+
+/// Read the lines of the adlists file, if it exists
+fn read_adlist_lines(env: &Env) -> Result<Vec<String>, Error> {
+    if env.file_exists(PiholeFile::Adlists) {
+        env.read_file_lines(PiholeFile::Adlists)
+    } else {
+        Ok(Vec::new())
+    }
+}
+
+/// Overwrite the adlists file with the lines
+fn write_adlist_lines<'a>(env: &Env, lines: impl Iterator<Item = &'a String>) -> Result<(), Error> {
+    let mut writer = BufWriter::new(env.write_file(PiholeFile::Adlists, false)?);
+
+    for line in lines {
+        writeln!(writer, "{}", line).context(ErrorKind::FileWrite(
+            env.file_location(PiholeFile::Adlists).to_owned()
+        ))?;
+    }
+
+    Ok(())
+}
+
+/// Parse a line of the adlists file into the adlist's address and whether it
+/// is enabled. Disabled adlists are commented out, which is told apart from
+/// other comments by the address directly following the `#`.
+fn parse_adlist(line: &str) -> Option<(&str, bool)> {
+    let line = line.trim();
+
+    if line.starts_with('#') {
+        let address = &line[1..];
+
+        if ValueType::Url.is_valid(address) {
+            Some((address, false))
+        } else {
+            None
+        }
+    } else if line.is_empty() {
+        None
+    } else {
+        Some((line, true))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{env::PiholeFile, testing::TestBuilder};
+    use rocket::http::{Method, Status};
+
+    /// The adlists are listed with their IDs, states and comments. Other
+    /// comments are skipped.
+    #[test]
+    fn get_adlists() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/adlists")
+            .file(
+                PiholeFile::Adlists,
+                "# Adlists\n\
+                 https://example.com/hosts\n\
+                 #https://example.net/hosts\n"
+            )
+            .file(
+                PiholeFile::ListComments,
+                "adlist\thttps://example.net/hosts\tToo strict\n"
+            )
+            .expect_json(json!([
+                {
+                    "id": 1,
+                    "address": "https://example.com/hosts",
+                    "enabled": true,
+                    "comment": None::<()>,
+                    "date_updated": None::<()>
+                },
+                {
+                    "id": 2,
+                    "address": "https://example.net/hosts",
+                    "enabled": false,
+                    "comment": "Too strict",
+                    "date_updated": None::<()>
+                }
+            ]))
+            .test();
+    }
+
+    /// An adlist is added to the end of the file with its comment
+    #[test]
+    fn add_adlist() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/adlists")
+            .method(Method::Post)
+            .file_expect(
+                PiholeFile::Adlists,
+                "https://example.com/hosts\n",
+                "https://example.com/hosts\n\
+                 #https://example.net/hosts\n"
+            )
+            .file_expect(
+                PiholeFile::ListComments,
+                "",
+                "adlist\thttps://example.net/hosts\tToo strict\n"
+            )
+            .body(json!({
+                "address": "https://example.net/hosts",
+                "comment": "Too strict",
+                "enabled": false
+            }))
+            .expect_json(json!({ "status": "success" }))
+            .test();
+    }
+
+    /// With `dry_run`, the adlist and its comment are reported but not
+    /// written
+    #[test]
+    fn add_dry_run() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/adlists?dry_run=true")
+            .method(Method::Post)
+            .file_expect(PiholeFile::Adlists, "", "")
+            .file_expect(PiholeFile::ListComments, "", "")
+            .body(json!({
+                "address": "https://example.net/hosts",
+                "comment": "Too strict"
+            }))
+            .expect_json(json!({
+                "dry_run": true,
+                "changes": [
+                    {
+                        "file": "/etc/pihole/adlists.list",
+                        "value": "https://example.net/hosts"
+                    },
+                    {
+                        "file": "/etc/pihole/list_comments.list",
+                        "key": "https://example.net/hosts",
+                        "value": "Too strict"
+                    }
+                ]
+            }))
+            .test();
+    }
+
+    /// An address can not be added twice, even if it is disabled
+    #[test]
+    fn add_existing() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/adlists")
+            .method(Method::Post)
+            .file(PiholeFile::Adlists, "#https://example.com/hosts\n")
+            .body(json!({ "address": "https://example.com/hosts" }))
+            .expect_status(Status::Conflict)
+            .expect_json(json!({
+                "error": {
+                    "key": "already_exists",
+                    "message": "Item already exists",
+                    "data": None::<()>
+                }
+            }))
+            .test();
+    }
+
+    /// Addresses must be URLs
+    #[test]
+    fn add_invalid() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/adlists")
+            .method(Method::Post)
+            .file(PiholeFile::Adlists, "")
+            .body(json!({ "address": "example.com/hosts" }))
+            .expect_status(Status::BadRequest)
+            .expect_json(json!({
+                "error": {
+                    "key": "invalid_setting_value",
+                    "message": "Invalid setting value",
+                    "data": None::<()>
+                }
+            }))
+            .test();
+    }
+
+    /// An adlist is removed by its ID, along with its comment
+    #[test]
+    fn delete_adlist() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/adlists/2")
+            .method(Method::Delete)
+            .file_expect(
+                PiholeFile::Adlists,
+                "# Adlists\n\
+                 https://example.com/hosts\n\
+                 #https://example.net/hosts\n",
+                "# Adlists\n\
+                 https://example.com/hosts\n"
+            )
+            .file_expect(
+                PiholeFile::ListComments,
+                "adlist\thttps://example.net/hosts\tToo strict\n",
+                ""
+            )
+            .expect_json(json!({ "status": "success" }))
+            .test();
+    }
+
+    /// With `dry_run`, the removal of the adlist and its comment is reported
+    /// but not written
+    #[test]
+    fn delete_dry_run() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/adlists/1?dry_run=true")
+            .method(Method::Delete)
+            .file_expect(
+                PiholeFile::Adlists,
+                "https://example.com/hosts\n",
+                "https://example.com/hosts\n"
+            )
+            .file_expect(
+                PiholeFile::ListComments,
+                "adlist\thttps://example.com/hosts\tDefault\n",
+                "adlist\thttps://example.com/hosts\tDefault\n"
+            )
+            .expect_json(json!({
+                "dry_run": true,
+                "changes": [
+                    {
+                        "file": "/etc/pihole/adlists.list",
+                        "key": "https://example.com/hosts",
+                        "value": ""
+                    },
+                    {
+                        "file": "/etc/pihole/list_comments.list",
+                        "key": "https://example.com/hosts",
+                        "value": ""
+                    }
+                ]
+            }))
+            .test();
+    }
+
+    /// Deleting an ID without an adlist is an error
+    #[test]
+    fn delete_missing() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/adlists/3")
+            .method(Method::Delete)
+            .file(PiholeFile::Adlists, "https://example.com/hosts\n")
+            .expect_status(Status::NotFound)
+            .expect_json(json!({
+                "error": {
+                    "key": "not_found",
+                    "message": "Not found",
+                    "data": None::<()>
+                }
+            }))
+            .test();
+    }
+}
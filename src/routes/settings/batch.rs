@@ -0,0 +1,294 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// Batch Settings Endpoint
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    env::{Env, PiholeFile},
+    routes::{
+        auth::User,
+        settings::common::{restart_dns, SettingsWriter}
+    },
+    settings::{generate_dnsmasq_config, is_dnsmasq_entry, ConfigEntry, SetupVarsEntry},
+    util::{Error, ErrorKind, Reply}
+};
+use failure::Fail;
+use rocket::State;
+use rocket_contrib::json::Json;
+use std::{
+    collections::BTreeMap,
+    io::{self, prelude::*, BufWriter}
+};
+
+/// Update several SetupVars entries at once. The body maps entry keys to
+/// their new values. Nothing is written unless every value is valid, and if
+/// a write fails, the entries are restored to their previous values. The
+/// changed entries are reported, and with `dry_run` they are only reported.
+#[put("/settings/batch?<dry_run>", data = "<data>")]
+pub fn put_batch(
+    env: State<Env>,
+    _auth: User,
+    data: Json<BTreeMap<String, String>>,
+    dry_run: Option<bool>
+) -> Reply {
+    let settings = data.into_inner();
+    let changes = validate_batch(&settings)?;
+    let mut writer = SettingsWriter::new(&env, dry_run);
+    let changed = write_batch(&env, &mut writer, &changes, |entry, value, env| {
+        entry.write(value, env)
+    })?;
+
+    writer.finish_with_changes(|env| {
+        // The DNS server only needs to restart if its config changed
+        if changed.iter().any(|entry| is_dnsmasq_entry(entry)) {
+            generate_dnsmasq_config(env)?;
+            restart_dns(env)?;
+        }
+
+        Ok(())
+    })
+}
+
+/// Find the entry of each key and check its value. The error contains the
+/// first unknown key or key with an invalid value.
+fn validate_batch(
+    settings: &BTreeMap<String, String>
+) -> Result<Vec<(SetupVarsEntry, &str)>, Error> {
+    settings
+        .iter()
+        .map(|(key, value)| match SetupVarsEntry::from_key(key) {
+            Some(entry) if entry.is_valid(value) => Ok((entry, value.as_str())),
+            _ => Err(Error::from(ErrorKind::InvalidSetting(key.to_owned())))
+        })
+        .collect()
+}
+
+/// Write the entries whose values changed using `write`, and return them. If
+/// a write fails, the original SetupVars lines are written back.
+fn write_batch(
+    env: &Env,
+    writer: &mut SettingsWriter,
+    changes: &[(SetupVarsEntry, &str)],
+    write: impl Fn(&SetupVarsEntry, &str, &Env) -> Result<(), Error>
+) -> Result<Vec<SetupVarsEntry>, Error> {
+    let original_lines = env.read_file_lines(PiholeFile::SetupVars)?;
+    let mut changed = Vec::new();
+
+    for (entry, value) in changes {
+        if entry.read(env)? == *value {
+            continue;
+        }
+
+        if let Err(error) = writer.write_with(entry.file(), &entry.key(), value, |env| {
+            write(entry, value, env)
+        }) {
+            restore_setup_vars(env, &original_lines)?;
+            return Err(error);
+        }
+
+        changed.push(*entry);
+    }
+
+    Ok(changed)
+}
+
+/// Overwrite SetupVars with the lines
+fn restore_setup_vars(env: &Env, lines: &[String]) -> Result<(), Error> {
+    let mut file_writer = BufWriter::new(env.write_file(PiholeFile::SetupVars, false)?);
+
+    // Create the context for the error lazily.
+    // This way it is not allocating for errors at all, unless an error is thrown.
+    let apply_context = |error: io::Error| {
+        error.context(ErrorKind::FileWrite(
+            env.file_location(PiholeFile::SetupVars).to_owned()
+        ))
+    };
+
+    for line in lines {
+        writeln!(file_writer, "{}", line).map_err(apply_context)?;
+    }
+
+    file_writer.flush().map_err(apply_context)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::write_batch;
+    use crate::{
+        env::{Config, Env, PiholeFile},
+        routes::settings::common::SettingsWriter,
+        settings::{ConfigEntry, SetupVarsEntry},
+        testing::{TestBuilder, TestEnvBuilder},
+        util::{Error, ErrorKind}
+    };
+    use rocket::http::{Method, Status};
+
+    /// The changed entries are written and reported. Entries which already
+    /// have the value are not reported.
+    #[test]
+    fn changed_keys() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/batch")
+            .method(Method::Put)
+            .file_expect(
+                PiholeFile::SetupVars,
+                "DNSSEC=false\n\
+                 DNS_FQDN_REQUIRED=true\n",
+                "DNS_FQDN_REQUIRED=true\n\
+                 DNSSEC=true\n\
+                 PIHOLE_DNS_1=1.1.1.1\n"
+            )
+            .file(PiholeFile::DnsmasqConfig, "")
+            .body(json!({
+                "DNSSEC": "true",
+                "DNS_FQDN_REQUIRED": "true",
+                "PIHOLE_DNS_1": "1.1.1.1"
+            }))
+            .expect_json(json!({
+                "dry_run": false,
+                "changes": [
+                    {
+                        "file": "/etc/pihole/setupVars.conf",
+                        "key": "DNSSEC",
+                        "value": "true"
+                    },
+                    {
+                        "file": "/etc/pihole/setupVars.conf",
+                        "key": "PIHOLE_DNS_1",
+                        "value": "1.1.1.1"
+                    }
+                ]
+            }))
+            .test();
+    }
+
+    /// With `dry_run`, the changes are reported but not written
+    #[test]
+    fn dry_run() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/batch?dry_run=true")
+            .method(Method::Put)
+            .file_expect(PiholeFile::SetupVars, "DNSSEC=false\n", "DNSSEC=false\n")
+            .file_expect(PiholeFile::DnsmasqConfig, "", "")
+            .body(json!({ "DNSSEC": "true" }))
+            .expect_json(json!({
+                "dry_run": true,
+                "changes": [
+                    {
+                        "file": "/etc/pihole/setupVars.conf",
+                        "key": "DNSSEC",
+                        "value": "true"
+                    }
+                ]
+            }))
+            .test();
+    }
+
+    /// The dnsmasq config is not generated again when only API settings
+    /// change
+    #[test]
+    fn api_settings_only() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/batch")
+            .method(Method::Put)
+            .file_expect(PiholeFile::SetupVars, "", "API_MAX_RESULTS=50\n")
+            .file_expect(PiholeFile::DnsmasqConfig, "", "")
+            .body(json!({ "API_MAX_RESULTS": "50" }))
+            .expect_json(json!({
+                "dry_run": false,
+                "changes": [
+                    {
+                        "file": "/etc/pihole/setupVars.conf",
+                        "key": "API_MAX_RESULTS",
+                        "value": "50"
+                    }
+                ]
+            }))
+            .test();
+    }
+
+    /// Nothing is written if any value is invalid
+    #[test]
+    fn invalid_value() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/batch")
+            .method(Method::Put)
+            .file_expect(PiholeFile::SetupVars, "DNSSEC=false\n", "DNSSEC=false\n")
+            .body(json!({
+                "DNSSEC": "true",
+                "PIHOLE_DNS_1": "not a server"
+            }))
+            .expect_status(Status::BadRequest)
+            .expect_json(json!({
+                "error": {
+                    "key": "invalid_setting",
+                    "message": "Invalid setting: PIHOLE_DNS_1",
+                    "data": { "key": "PIHOLE_DNS_1" }
+                }
+            }))
+            .test();
+    }
+
+    /// Unknown keys are rejected
+    #[test]
+    fn unknown_key() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/batch")
+            .method(Method::Put)
+            .file(PiholeFile::SetupVars, "")
+            .body(json!({ "NOT_A_SETTING": "true" }))
+            .expect_status(Status::BadRequest)
+            .expect_json(json!({
+                "error": {
+                    "key": "invalid_setting",
+                    "message": "Invalid setting: NOT_A_SETTING",
+                    "data": { "key": "NOT_A_SETTING" }
+                }
+            }))
+            .test();
+    }
+
+    /// If a write fails, the entries which were already written are restored
+    #[test]
+    fn rollback() {
+        let env_builder = TestEnvBuilder::new().file_expect(
+            PiholeFile::SetupVars,
+            "DNSSEC=false\n\
+             QUERY_LOGGING=true\n",
+            "DNSSEC=false\n\
+             QUERY_LOGGING=true\n"
+        );
+        let mut test_file = env_builder.get_test_files().into_iter().next().unwrap();
+        let env = Env::Test(Config::default(), env_builder.build());
+
+        let result = write_batch(
+            &env,
+            &mut SettingsWriter::new(&env, None),
+            &[
+                (SetupVarsEntry::Dnssec, "true"),
+                (SetupVarsEntry::QueryLogging, "false")
+            ],
+            |entry, value, env| {
+                if *entry == SetupVarsEntry::QueryLogging {
+                    Err(Error::from(ErrorKind::FileWrite(
+                        env.file_location(PiholeFile::SetupVars).to_owned()
+                    )))
+                } else {
+                    entry.write(value, env)
+                }
+            }
+        );
+
+        assert!(result.is_err());
+
+        let mut buffer = String::new();
+        test_file.assert_expected(&mut buffer);
+    }
+}
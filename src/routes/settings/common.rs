@@ -9,11 +9,15 @@
 // Please see LICENSE file for your rights under this license.
 
 use crate::{
-    env::Env,
-    util::{Error, ErrorKind}
+    env::{Env, PiholeFile},
+    settings::ConfigEntry,
+    util::{reply_data, reply_success, Error, ErrorKind, Reply}
 };
 use failure::ResultExt;
-use std::process::{Command, Stdio};
+use std::{
+    io::Write,
+    process::{Command, Stdio}
+};
 
 /// Restart the DNS server (via `pihole restartdns`)
 pub fn restart_dns(env: &Env) -> Result<(), Error> {
@@ -37,3 +41,131 @@ pub fn restart_dns(env: &Env) -> Result<(), Error> {
         Err(Error::from(ErrorKind::RestartDnsError))
     }
 }
+
+/// A change to a config file. Entries have a key, while lines appended to a
+/// file do not.
+#[derive(Serialize)]
+pub struct SettingChange {
+    pub file: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key: Option<String>,
+    pub value: String
+}
+
+/// Writes settings to their config files and records the changes. During a
+/// dry run the changes are validated and recorded, but nothing is written.
+pub struct SettingsWriter<'a> {
+    env: &'a Env,
+    dry_run: bool,
+    changes: Vec<SettingChange>
+}
+
+impl<'a> SettingsWriter<'a> {
+    /// Create a writer. `dry_run` is the optional `dry_run` query parameter.
+    pub fn new(env: &'a Env, dry_run: Option<bool>) -> Self {
+        SettingsWriter {
+            env,
+            dry_run: dry_run.unwrap_or(false),
+            changes: Vec::new()
+        }
+    }
+
+    /// Check if this is a dry run
+    pub fn is_dry_run(&self) -> bool {
+        self.dry_run
+    }
+
+    /// Validate and write the entry. See `ConfigEntry::write`.
+    pub fn write(&mut self, entry: &impl ConfigEntry, value: &str) -> Result<(), Error> {
+        if !entry.is_valid(value) {
+            return Err(Error::from(ErrorKind::InvalidSettingValue));
+        }
+
+        self.changes.push(SettingChange {
+            file: self.env.file_location(entry.file()).to_owned(),
+            key: Some(entry.key().into_owned()),
+            value: value.to_owned()
+        });
+
+        if self.dry_run {
+            Ok(())
+        } else {
+            entry.write(value, self.env)
+        }
+    }
+
+    /// Delete the entry. This is the same as writing an empty string.
+    pub fn delete(&mut self, entry: &impl ConfigEntry) -> Result<(), Error> {
+        self.write(entry, "")
+    }
+
+    /// Record a change to an entry of a file which is not a `ConfigEntry`, and
+    /// make the change with `write` unless this is a dry run. The value must
+    /// already be validated. An empty value means the entry is removed.
+    pub fn write_with(
+        &mut self,
+        file: PiholeFile,
+        key: &str,
+        value: &str,
+        write: impl FnOnce(&Env) -> Result<(), Error>
+    ) -> Result<(), Error> {
+        if !self.dry_run {
+            write(self.env)?;
+        }
+
+        self.changes.push(SettingChange {
+            file: self.env.file_location(file).to_owned(),
+            key: Some(key.to_owned()),
+            value: value.to_owned()
+        });
+
+        Ok(())
+    }
+
+    /// Append a line to the file, creating the file if it doesn't exist
+    pub fn append_line(&mut self, file: PiholeFile, line: &str) -> Result<(), Error> {
+        let location = self.env.file_location(file).to_owned();
+
+        if !self.dry_run {
+            let mut writer = self.env.write_file(file, true)?;
+
+            writeln!(writer, "{}", line).context(ErrorKind::FileWrite(location.clone()))?;
+        }
+
+        self.changes.push(SettingChange {
+            file: location,
+            key: None,
+            value: line.to_owned()
+        });
+
+        Ok(())
+    }
+
+    /// Finish the update. During a dry run the changes which would have been
+    /// made are returned. Otherwise, `apply` is run to make the changes take
+    /// effect (such as restarting DNS) and success is returned.
+    pub fn finish(self, apply: impl FnOnce(&Env) -> Result<(), Error>) -> Reply {
+        if self.dry_run {
+            return reply_data(json!({
+                "dry_run": true,
+                "changes": self.changes
+            }));
+        }
+
+        apply(self.env)?;
+        reply_success()
+    }
+
+    /// Finish the update like `finish`, but report the changes whether or
+    /// not this is a dry run
+    pub fn finish_with_changes(self, apply: impl FnOnce(&Env) -> Result<(), Error>) -> Reply {
+        if !self.dry_run {
+            apply(self.env)?;
+        }
+
+        reply_data(json!({
+            "dry_run": self.dry_run,
+            "changes": self.changes
+        }))
+    }
+}
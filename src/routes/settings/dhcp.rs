@@ -10,9 +10,12 @@
 
 use crate::{
     env::Env,
-    routes::{auth::User, settings::common::restart_dns},
+    routes::{
+        auth::User,
+        settings::common::{restart_dns, SettingsWriter}
+    },
     settings::{generate_dnsmasq_config, ConfigEntry, SetupVarsEntry},
-    util::{reply_data, reply_success, Error, ErrorKind, Reply}
+    util::{reply_data, Error, ErrorKind, Reply}
 };
 use rocket::State;
 use rocket_contrib::json::Json;
@@ -64,26 +67,40 @@ pub fn get_dhcp(env: State<Env>, _auth: User) -> Reply {
     reply_data(dhcp_settings)
 }
 
-/// Update DHCP Configuration
-#[put("/settings/dhcp", data = "<data>")]
-pub fn put_dhcp(env: State<Env>, _auth: User, data: Json<DhcpSettings>) -> Reply {
+/// Update DHCP Configuration. With `dry_run`, the changes are only reported.
+#[put("/settings/dhcp?<dry_run>", data = "<data>")]
+pub fn put_dhcp(
+    env: State<Env>,
+    _auth: User,
+    data: Json<DhcpSettings>,
+    dry_run: Option<bool>
+) -> Reply {
     let settings: DhcpSettings = data.into_inner();
 
     if !settings.is_valid() {
         return Err(Error::from(ErrorKind::InvalidSettingValue));
     }
 
-    SetupVarsEntry::DhcpActive.write(&settings.active.to_string(), &env)?;
-    SetupVarsEntry::DhcpStart.write(&settings.ip_start, &env)?;
-    SetupVarsEntry::DhcpEnd.write(&settings.ip_end, &env)?;
-    SetupVarsEntry::DhcpRouter.write(&settings.router_ip, &env)?;
-    SetupVarsEntry::DhcpLeasetime.write(&settings.lease_time.to_string(), &env)?;
-    SetupVarsEntry::PiholeDomain.write(&settings.domain, &env)?;
-    SetupVarsEntry::DhcpIpv6.write(&settings.ipv6_support.to_string(), &env)?;
+    let mut writer = SettingsWriter::new(&env, dry_run);
 
-    generate_dnsmasq_config(&env)?;
-    restart_dns(&env)?;
-    reply_success()
+    writer.write(&SetupVarsEntry::DhcpActive, &settings.active.to_string())?;
+    writer.write(&SetupVarsEntry::DhcpStart, &settings.ip_start)?;
+    writer.write(&SetupVarsEntry::DhcpEnd, &settings.ip_end)?;
+    writer.write(&SetupVarsEntry::DhcpRouter, &settings.router_ip)?;
+    writer.write(
+        &SetupVarsEntry::DhcpLeasetime,
+        &settings.lease_time.to_string()
+    )?;
+    writer.write(&SetupVarsEntry::PiholeDomain, &settings.domain)?;
+    writer.write(
+        &SetupVarsEntry::DhcpIpv6,
+        &settings.ipv6_support.to_string()
+    )?;
+
+    writer.finish(|env| {
+        generate_dnsmasq_config(env)?;
+        restart_dns(env)
+    })
 }
 
 #[cfg(test)]
@@ -0,0 +1,152 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// DHCP Lease Endpoints
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    env::{Env, PiholeFile},
+    routes::auth::User,
+    settings::{ConfigEntry, SetupVarsEntry},
+    util::{reply_result, Error, Reply}
+};
+use rocket::State;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A DHCP lease given out by dnsmasq
+#[derive(Serialize)]
+#[cfg_attr(test, derive(PartialEq, Debug))]
+pub struct DhcpLease {
+    ip: String,
+    mac: String,
+    hostname: Option<String>,
+    /// The expiry timestamp of the lease. Zero means the lease never expires.
+    expires: u64
+}
+
+/// Get the active DHCP leases
+#[get("/settings/dhcp/leases")]
+pub fn get_dhcp_leases(env: State<Env>, _auth: User) -> Reply {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Current time is older than epoch")
+        .as_secs();
+
+    reply_result(get_dhcp_leases_impl(&env, now))
+}
+
+/// Get the DHCP leases which have not expired by `now`. If DHCP is disabled,
+/// there are no leases.
+fn get_dhcp_leases_impl(env: &Env, now: u64) -> Result<Vec<DhcpLease>, Error> {
+    if !SetupVarsEntry::DhcpActive.is_true(env)? {
+        return Ok(Vec::new());
+    }
+
+    Ok(env
+        .read_file_lines(PiholeFile::DhcpLeases)?
+        .iter()
+        .filter_map(|line| parse_lease(line))
+        .filter(|lease| lease.expires == 0 || lease.expires > now)
+        .collect())
+}
+
+/// Parse a line of the dnsmasq leases file, which has the format
+/// `expires mac ip hostname client_id`. Unknown hostnames are written as `*`.
+fn parse_lease(line: &str) -> Option<DhcpLease> {
+    let mut split = line.split_whitespace();
+
+    let expires = split.next()?.parse().ok()?;
+    let mac = split.next()?.to_owned();
+    let ip = split.next()?.to_owned();
+    let hostname = match split.next()? {
+        "*" => None,
+        hostname => Some(hostname.to_owned())
+    };
+
+    Some(DhcpLease {
+        ip,
+        mac,
+        hostname,
+        expires
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::{get_dhcp_leases_impl, DhcpLease};
+    use crate::{
+        env::{Config, Env, PiholeFile},
+        testing::{TestBuilder, TestEnvBuilder}
+    };
+
+    const NOW: u64 = 1_550_000_000;
+    const LEASES: &str = "1550003600 aa:bb:cc:dd:ee:ff 192.168.1.50 laptop 01:aa:bb:cc:dd:ee:ff\n\
+                          0 11:22:33:44:55:66 192.168.1.51 * *\n\
+                          1549996400 66:55:44:33:22:11 192.168.1.52 phone *\n";
+
+    /// Active leases are listed, but expired leases are not
+    #[test]
+    fn active_leases() {
+        let env = Env::Test(
+            Config::default(),
+            TestEnvBuilder::new()
+                .file(PiholeFile::SetupVars, "DHCP_ACTIVE=true")
+                .file(PiholeFile::DhcpLeases, LEASES)
+                .build()
+        );
+
+        assert_eq!(
+            get_dhcp_leases_impl(&env, NOW).unwrap(),
+            vec![
+                DhcpLease {
+                    ip: "192.168.1.50".to_owned(),
+                    mac: "aa:bb:cc:dd:ee:ff".to_owned(),
+                    hostname: Some("laptop".to_owned()),
+                    expires: 1_550_003_600
+                },
+                DhcpLease {
+                    ip: "192.168.1.51".to_owned(),
+                    mac: "11:22:33:44:55:66".to_owned(),
+                    hostname: None,
+                    expires: 0
+                },
+            ]
+        );
+    }
+
+    /// There are no leases when DHCP is disabled
+    #[test]
+    fn dhcp_disabled() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/dhcp/leases")
+            .file(PiholeFile::SetupVars, "DHCP_ACTIVE=false")
+            .file(PiholeFile::DhcpLeases, LEASES)
+            .expect_json(json!([]))
+            .test();
+    }
+
+    /// Leases which never expire are listed by the endpoint
+    #[test]
+    fn endpoint() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/dhcp/leases")
+            .file(PiholeFile::SetupVars, "DHCP_ACTIVE=true")
+            .file(
+                PiholeFile::DhcpLeases,
+                "0 11:22:33:44:55:66 192.168.1.51 * *\n"
+            )
+            .expect_json(json!([
+                {
+                    "ip": "192.168.1.51",
+                    "mac": "11:22:33:44:55:66",
+                    "hostname": None::<()>,
+                    "expires": 0
+                }
+            ]))
+            .test();
+    }
+}
@@ -0,0 +1,246 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// Static DHCP Lease Endpoints
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    env::{Env, PiholeFile},
+    routes::{
+        auth::User,
+        settings::common::{restart_dns, SettingsWriter}
+    },
+    settings::ValueType,
+    util::{reply_success, Error, ErrorKind, Reply}
+};
+use failure::ResultExt;
+use rocket::State;
+use rocket_contrib::json::Json;
+use std::io::{BufWriter, Write};
+
+const DHCP_HOST: &str = "dhcp-host=";
+
+/// A static DHCP lease, which reserves an IP address for a MAC address
+#[derive(Deserialize)]
+pub struct StaticLease {
+    mac: String,
+    ip: String,
+    hostname: Option<String>
+}
+
+impl StaticLease {
+    /// Check if the MAC address, IP address, and hostname are valid
+    fn is_valid(&self) -> bool {
+        ValueType::MacAddress.is_valid(&self.mac)
+            && ValueType::Ipv4.is_valid(&self.ip)
+            && self
+                .hostname
+                .as_ref()
+                .map_or(true, |hostname| ValueType::Hostname.is_valid(hostname))
+    }
+
+    /// Get the dnsmasq config line for the lease
+    fn to_config_line(&self) -> String {
+        match self.hostname {
+            Some(ref hostname) => format!("{}{},{},{}", DHCP_HOST, self.mac, self.ip, hostname),
+            None => format!("{}{},{}", DHCP_HOST, self.mac, self.ip)
+        }
+    }
+}
+
+/// Add a static DHCP lease. With `dry_run`, the change is only reported.
+#[post("/settings/dhcp/static?<dry_run>", data = "<lease>")]
+pub fn add_static_lease(
+    env: State<Env>,
+    _auth: User,
+    lease: Json<StaticLease>,
+    dry_run: Option<bool>
+) -> Reply {
+    let mut writer = SettingsWriter::new(&env, dry_run);
+
+    add_static_lease_impl(&env, &mut writer, &lease.into_inner())?;
+    writer.finish(restart_dns)
+}
+
+/// Remove the static DHCP lease of a MAC address
+#[delete("/settings/dhcp/static/<mac>")]
+pub fn delete_static_lease(env: State<Env>, _auth: User, mac: String) -> Reply {
+    delete_static_lease_impl(&env, &mac)?;
+    restart_dns(&env)?;
+    reply_success()
+}
+
+/// Add the static lease to the config. A MAC address can only have one
+/// static lease.
+fn add_static_lease_impl(
+    env: &Env,
+    writer: &mut SettingsWriter,
+    lease: &StaticLease
+) -> Result<(), Error> {
+    if !lease.is_valid() {
+        return Err(Error::from(ErrorKind::InvalidSettingValue));
+    }
+
+    if read_lease_lines(env)?
+        .iter()
+        .any(|line| is_lease_for_mac(line, &lease.mac))
+    {
+        return Err(Error::from(ErrorKind::AlreadyExists));
+    }
+
+    writer.append_line(PiholeFile::DhcpStaticLeases, &lease.to_config_line())
+}
+
+/// Remove the static lease of the MAC address from the config
+fn delete_static_lease_impl(env: &Env, mac: &str) -> Result<(), Error> {
+    let lines = read_lease_lines(env)?;
+
+    if !lines.iter().any(|line| is_lease_for_mac(line, mac)) {
+        return Err(Error::from(ErrorKind::NotFound));
+    }
+
+    // Rewrite the config without the lease
+    let mut writer = BufWriter::new(env.write_file(PiholeFile::DhcpStaticLeases, false)?);
+
+    for line in lines.iter().filter(|line| !is_lease_for_mac(line, mac)) {
+        writeln!(writer, "{}", line).context(ErrorKind::FileWrite(
+            env.file_location(PiholeFile::DhcpStaticLeases).to_owned()
+        ))?;
+    }
+
+    Ok(())
+}
+
+/// Read the lines of the static lease config, if it exists
+fn read_lease_lines(env: &Env) -> Result<Vec<String>, Error> {
+    if env.file_exists(PiholeFile::DhcpStaticLeases) {
+        env.read_file_lines(PiholeFile::DhcpStaticLeases)
+    } else {
+        Ok(Vec::new())
+    }
+}
+
+/// Check if the config line is a static lease for the MAC address. MAC
+/// addresses are compared case insensitively.
+fn is_lease_for_mac(line: &str, mac: &str) -> bool {
+    line.starts_with(DHCP_HOST)
+        && line[DHCP_HOST.len()..]
+            .split(',')
+            .next()
+            .map_or(false, |lease_mac| lease_mac.eq_ignore_ascii_case(mac))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{env::PiholeFile, testing::TestBuilder};
+    use rocket::http::{Method, Status};
+
+    /// A static lease is added to the config
+    #[test]
+    fn add_lease() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/dhcp/static")
+            .method(Method::Post)
+            .file_expect(
+                PiholeFile::DhcpStaticLeases,
+                "dhcp-host=11:22:33:44:55:66,192.168.1.20\n",
+                "dhcp-host=11:22:33:44:55:66,192.168.1.20\n\
+                 dhcp-host=aa:bb:cc:dd:ee:ff,192.168.1.21,laptop\n"
+            )
+            .body(json!({
+                "mac": "aa:bb:cc:dd:ee:ff",
+                "ip": "192.168.1.21",
+                "hostname": "laptop"
+            }))
+            .expect_json(json!({ "status": "success" }))
+            .test();
+    }
+
+    /// A MAC address can not have two static leases
+    #[test]
+    fn add_duplicate_mac() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/dhcp/static")
+            .method(Method::Post)
+            .file_expect(
+                PiholeFile::DhcpStaticLeases,
+                "dhcp-host=AA:BB:CC:DD:EE:FF,192.168.1.20\n",
+                "dhcp-host=AA:BB:CC:DD:EE:FF,192.168.1.20\n"
+            )
+            .body(json!({
+                "mac": "aa:bb:cc:dd:ee:ff",
+                "ip": "192.168.1.21"
+            }))
+            .expect_status(Status::Conflict)
+            .expect_json(json!({
+                "error": {
+                    "key": "already_exists",
+                    "message": "Item already exists",
+                    "data": None::<()>
+                }
+            }))
+            .test();
+    }
+
+    /// Invalid MAC addresses are rejected
+    #[test]
+    fn add_invalid_mac() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/dhcp/static")
+            .method(Method::Post)
+            .body(json!({
+                "mac": "not a MAC",
+                "ip": "192.168.1.21"
+            }))
+            .expect_status(Status::BadRequest)
+            .expect_json(json!({
+                "error": {
+                    "key": "invalid_setting_value",
+                    "message": "Invalid setting value",
+                    "data": None::<()>
+                }
+            }))
+            .test();
+    }
+
+    /// A static lease is removed by its MAC address
+    #[test]
+    fn delete_lease() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/dhcp/static/aa:bb:cc:dd:ee:ff")
+            .method(Method::Delete)
+            .file_expect(
+                PiholeFile::DhcpStaticLeases,
+                "dhcp-host=11:22:33:44:55:66,192.168.1.20\n\
+                 dhcp-host=aa:bb:cc:dd:ee:ff,192.168.1.21,laptop\n",
+                "dhcp-host=11:22:33:44:55:66,192.168.1.20\n"
+            )
+            .expect_json(json!({ "status": "success" }))
+            .test();
+    }
+
+    /// Removing a static lease which does not exist is not found
+    #[test]
+    fn delete_missing_lease() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/dhcp/static/aa:bb:cc:dd:ee:ff")
+            .method(Method::Delete)
+            .file(
+                PiholeFile::DhcpStaticLeases,
+                "dhcp-host=11:22:33:44:55:66,192.168.1.20\n"
+            )
+            .expect_status(Status::NotFound)
+            .expect_json(json!({
+                "error": {
+                    "key": "not_found",
+                    "message": "Not found",
+                    "data": None::<()>
+                }
+            }))
+            .test();
+    }
+}
@@ -10,9 +10,12 @@
 
 use crate::{
     env::Env,
-    routes::{auth::User, settings::common::restart_dns},
-    settings::{generate_dnsmasq_config, ConfigEntry, SetupVarsEntry},
-    util::{reply_data, reply_success, Error, ErrorKind, Reply}
+    routes::{
+        auth::User,
+        settings::common::{restart_dns, SettingsWriter}
+    },
+    settings::{generate_dnsmasq_config, ConfigEntry, SetupVarsEntry, ValueType},
+    util::{reply_data, Error, ErrorKind, Reply}
 };
 use rocket::State;
 use rocket_contrib::json::Json;
@@ -72,6 +75,23 @@ impl DnsConditionalForwarding {
         SetupVarsEntry::DhcpRouter.is_valid(&self.router_ip)
             && SetupVarsEntry::ConditionalForwardingDomain.is_valid(&self.domain)
     }
+
+    /// Check each domain of the comma separated domain list. The error
+    /// contains the index of the first empty or invalid domain.
+    fn check_domains(&self) -> Result<(), Error> {
+        if self.domain.is_empty() {
+            return Ok(());
+        }
+
+        match self
+            .domain
+            .split(',')
+            .position(|domain| domain.is_empty() || !ValueType::Hostname.is_valid(domain))
+        {
+            Some(index) => Err(Error::from(ErrorKind::InvalidSettingListValue(index))),
+            None => Ok(())
+        }
+    }
 }
 
 /// Get upstream DNS servers
@@ -112,28 +132,51 @@ pub fn get_dns(env: State<Env>, _auth: User) -> Reply {
     reply_data(dns_settings)
 }
 
-/// Update DNS Configuration
-#[put("/settings/dns", data = "<data>")]
-pub fn put_dns(env: State<Env>, _auth: User, data: Json<DnsSettings>) -> Reply {
+/// Update DNS Configuration. With `dry_run`, the changes are only reported.
+#[put("/settings/dns?<dry_run>", data = "<data>")]
+pub fn put_dns(
+    env: State<Env>,
+    _auth: User,
+    data: Json<DnsSettings>,
+    dry_run: Option<bool>
+) -> Reply {
     let settings: DnsSettings = data.into_inner();
 
+    settings.conditional_forwarding.check_domains()?;
+
     if !settings.is_valid() {
         return Err(Error::from(ErrorKind::InvalidSettingValue));
     }
 
+    let mut writer = SettingsWriter::new(&env, dry_run);
+
     // Delete previous upstream DNS entries
-    SetupVarsEntry::delete_upstream_dns(&env)?;
+    if !writer.is_dry_run() {
+        SetupVarsEntry::delete_upstream_dns(&env)?;
+    }
 
     // Add new upstream DNS
     for (i, dns) in settings.upstream_dns.into_iter().enumerate() {
-        SetupVarsEntry::PiholeDns(i + 1).write(&dns, &env)?;
+        writer.write(&SetupVarsEntry::PiholeDns(i + 1), &dns)?;
     }
 
     // Write DNS settings to SetupVars
-    SetupVarsEntry::DnsFqdnRequired.write(&settings.options.fqdn_required.to_string(), &env)?;
-    SetupVarsEntry::DnsBogusPriv.write(&settings.options.bogus_priv.to_string(), &env)?;
-    SetupVarsEntry::Dnssec.write(&settings.options.dnssec.to_string(), &env)?;
-    SetupVarsEntry::DnsmasqListening.write(&settings.options.listening_type, &env)?;
+    writer.write(
+        &SetupVarsEntry::DnsFqdnRequired,
+        &settings.options.fqdn_required.to_string()
+    )?;
+    writer.write(
+        &SetupVarsEntry::DnsBogusPriv,
+        &settings.options.bogus_priv.to_string()
+    )?;
+    writer.write(
+        &SetupVarsEntry::Dnssec,
+        &settings.options.dnssec.to_string()
+    )?;
+    writer.write(
+        &SetupVarsEntry::DnsmasqListening,
+        &settings.options.listening_type
+    )?;
 
     if settings.conditional_forwarding.enabled {
         let address_segments: Vec<&str> = settings
@@ -147,28 +190,36 @@ pub fn put_dns(env: State<Env>, _auth: User, data: Json<DnsSettings>) -> Reply {
             address_segments[2], address_segments[1], address_segments[0]
         );
 
-        SetupVarsEntry::ConditionalForwarding.write("true", &env)?;
-        SetupVarsEntry::ConditionalForwardingReverse.write(&reverse_address, &env)?;
-        SetupVarsEntry::ConditionalForwardingIp
-            .write(&settings.conditional_forwarding.router_ip, &env)?;
-        SetupVarsEntry::ConditionalForwardingDomain
-            .write(&settings.conditional_forwarding.domain, &env)?;
+        writer.write(&SetupVarsEntry::ConditionalForwarding, "true")?;
+        writer.write(
+            &SetupVarsEntry::ConditionalForwardingReverse,
+            &reverse_address
+        )?;
+        writer.write(
+            &SetupVarsEntry::ConditionalForwardingIp,
+            &settings.conditional_forwarding.router_ip
+        )?;
+        writer.write(
+            &SetupVarsEntry::ConditionalForwardingDomain,
+            &settings.conditional_forwarding.domain
+        )?;
     } else {
-        SetupVarsEntry::ConditionalForwarding.write("false", &env)?;
-        SetupVarsEntry::ConditionalForwardingReverse.delete(&env)?;
-        SetupVarsEntry::ConditionalForwardingIp.delete(&env)?;
-        SetupVarsEntry::ConditionalForwardingDomain.delete(&env)?;
+        writer.write(&SetupVarsEntry::ConditionalForwarding, "false")?;
+        writer.delete(&SetupVarsEntry::ConditionalForwardingReverse)?;
+        writer.delete(&SetupVarsEntry::ConditionalForwardingIp)?;
+        writer.delete(&SetupVarsEntry::ConditionalForwardingDomain)?;
     }
 
-    generate_dnsmasq_config(&env)?;
-    restart_dns(&env)?;
-    reply_success()
+    writer.finish(|env| {
+        generate_dnsmasq_config(env)?;
+        restart_dns(env)
+    })
 }
 
 #[cfg(test)]
 mod test {
     use crate::{env::PiholeFile, testing::TestBuilder};
-    use rocket::http::Method;
+    use rocket::http::{Method, Status};
 
     /// Basic test for reported settings
     #[test]
@@ -312,4 +363,111 @@ mod test {
             }))
             .test();
     }
+
+    /// Multiple conditional forwarding domains are written in order
+    #[test]
+    fn test_put_dns_multiple_domains() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/dns")
+            .method(Method::Put)
+            .file_expect(
+                PiholeFile::SetupVars,
+                "",
+                "PIHOLE_DNS_1=8.8.8.8\n\
+                PIHOLE_DNS_2=8.8.4.4\n\
+                DNS_FQDN_REQUIRED=true\n\
+                DNS_BOGUS_PRIV=true\n\
+                DNSSEC=true\n\
+                DNSMASQ_LISTENING=local\n\
+                CONDITIONAL_FORWARDING=true\n\
+                CONDITIONAL_FORWARDING_REVERSE=1.168.192.in-addr.arpa\n\
+                CONDITIONAL_FORWARDING_IP=192.168.1.1\n\
+                CONDITIONAL_FORWARDING_DOMAIN=local,lan\n"
+            )
+            .file_expect(
+                PiholeFile::DnsmasqConfig,
+                "",
+                "################################################################\n\
+                    #       THIS FILE IS AUTOMATICALLY GENERATED BY PI-HOLE.       #\n\
+                    #          ANY CHANGES MADE TO THIS FILE WILL BE LOST.         #\n\
+                    #                                                              #\n\
+                    #  NEW CONFIG SETTINGS MUST BE MADE IN A SEPARATE CONFIG FILE  #\n\
+                    #                OR IN /etc/dnsmasq.conf                       #\n\
+                    ################################################################\n\
+                    \n\
+                    localise-queries\n\
+                    local-ttl=2\n\
+                    cache-size=10000\n\
+                    server=8.8.8.8\n\
+                    server=8.8.4.4\n\
+                    addn-hosts=/etc/pihole/gravity.list\n\
+                    addn-hosts=/etc/pihole/black.list\n\
+                    addn-hosts=/etc/pihole/local.list\n\
+                    domain-needed\n\
+                    bogus-priv\n\
+                    dnssec\n\
+                    trust-anchor=.,19036,8,2,49AAC11D7B6F6446702E54A1607371607A1A41855200FD2CE1CDDE32F24E8FB5\n\
+                    trust-anchor=.,20326,8,2,E06D44B80B8F1D39A95C0B0D7C65D08458E880409BBC683457104237C7F8EC8D\n\
+                    local-service\n\
+                    server=/local/192.168.1.1\n\
+                    server=/lan/192.168.1.1\n\
+                    server=/1.168.192.in-addr.arpa/192.168.1.1\n"
+            )
+            .body(json!({
+                "upstream_dns": [
+                    "8.8.8.8", "8.8.4.4"
+                ],
+                "conditional_forwarding": {
+                    "domain": "local,lan",
+                    "enabled": true,
+                    "router_ip": "192.168.1.1"
+                },
+                "options": {
+                    "bogus_priv": true,
+                    "dnssec": true,
+                    "fqdn_required": true,
+                    "listening_type": "local"
+                }
+            }))
+            .expect_json(json!({
+                "status": "success"
+            }))
+            .test();
+    }
+
+    /// An empty conditional forwarding domain is rejected with its index
+    #[test]
+    fn test_put_dns_empty_domain() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/dns")
+            .method(Method::Put)
+            .file_expect(PiholeFile::SetupVars, "", "")
+            .body(json!({
+                "upstream_dns": [
+                    "8.8.8.8"
+                ],
+                "conditional_forwarding": {
+                    "domain": "local,,lan",
+                    "enabled": true,
+                    "router_ip": "192.168.1.1"
+                },
+                "options": {
+                    "bogus_priv": true,
+                    "dnssec": true,
+                    "fqdn_required": true,
+                    "listening_type": "local"
+                }
+            }))
+            .expect_status(Status::BadRequest)
+            .expect_json(json!({
+                "error": {
+                    "key": "invalid_setting_list_value",
+                    "message": "Invalid setting value at index 1",
+                    "data": {
+                        "index": 1
+                    }
+                }
+            }))
+            .test();
+    }
 }
@@ -0,0 +1,268 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// Local CNAME Record Endpoints
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    env::{Env, PiholeFile},
+    routes::{
+        auth::User,
+        settings::{
+            common::{restart_dns, SettingsWriter},
+            dns_records::read_dns_records
+        }
+    },
+    settings::ValueType,
+    util::{reply_data, reply_success, Error, ErrorKind, Reply}
+};
+use failure::ResultExt;
+use rocket::State;
+use rocket_contrib::json::Json;
+use std::io::{BufWriter, Write};
+
+const CNAME: &str = "cname=";
+
+/// A local CNAME record, which makes a domain an alias of the target domain
+#[derive(Serialize, Deserialize)]
+#[cfg_attr(test, derive(PartialEq, Debug))]
+pub struct CnameRecord {
+    domain: String,
+    target: String
+}
+
+impl CnameRecord {
+    /// Check if the domain and target are valid, and that the record does not
+    /// point to itself
+    fn is_valid(&self) -> bool {
+        ValueType::Hostname.is_valid(&self.domain)
+            && ValueType::Hostname.is_valid(&self.target)
+            && !self.domain.eq_ignore_ascii_case(&self.target)
+    }
+
+    /// Get the dnsmasq config line for the record
+    fn to_config_line(&self) -> String {
+        format!("{}{},{}", CNAME, self.domain, self.target)
+    }
+}
+
+/// Get the local CNAME records
+#[get("/settings/dns/cname")]
+pub fn get_cname_records(env: State<Env>, _auth: User) -> Reply {
+    reply_data(read_cname_records(&env)?)
+}
+
+/// Add a local CNAME record. With `dry_run`, the change is only reported.
+#[post("/settings/dns/cname?<dry_run>", data = "<record>")]
+pub fn add_cname_record(
+    env: State<Env>,
+    _auth: User,
+    record: Json<CnameRecord>,
+    dry_run: Option<bool>
+) -> Reply {
+    let mut writer = SettingsWriter::new(&env, dry_run);
+
+    add_cname_record_impl(&env, &mut writer, &record.into_inner())?;
+    writer.finish(restart_dns)
+}
+
+/// Remove the local CNAME record of a domain
+#[delete("/settings/dns/cname/<domain>")]
+pub fn delete_cname_record(env: State<Env>, _auth: User, domain: String) -> Reply {
+    delete_cname_record_impl(&env, &domain)?;
+    restart_dns(&env)?;
+    reply_success()
+}
+
+/// Read the CNAME records from the config
+fn read_cname_records(env: &Env) -> Result<Vec<CnameRecord>, Error> {
+    Ok(read_cname_lines(env)?
+        .iter()
+        .filter_map(|line| parse_cname(line))
+        .collect())
+}
+
+/// Add the record to the config. Adding a record which already exists does
+/// nothing, but a domain can only have one CNAME record, and it can not have
+/// both a CNAME record and a local DNS record.
+fn add_cname_record_impl(
+    env: &Env,
+    writer: &mut SettingsWriter,
+    record: &CnameRecord
+) -> Result<(), Error> {
+    if !record.is_valid() {
+        return Err(Error::from(ErrorKind::InvalidSettingValue));
+    }
+
+    // dnsmasq would ignore one of the records, so the conflict is rejected
+    if let Some(dns_record) = read_dns_records(env)?
+        .into_iter()
+        .find(|dns_record| dns_record.domain.eq_ignore_ascii_case(&record.domain))
+    {
+        return Err(Error::from(ErrorKind::ConflictingDnsRecord(
+            dns_record.domain,
+            dns_record.ip
+        )));
+    }
+
+    let existing = read_cname_records(env)?
+        .into_iter()
+        .find(|existing| existing.domain.eq_ignore_ascii_case(&record.domain));
+
+    match existing {
+        Some(ref existing) if existing.target.eq_ignore_ascii_case(&record.target) => return Ok(()),
+        Some(_) => return Err(Error::from(ErrorKind::AlreadyExists)),
+        None => ()
+    }
+
+    writer.append_line(PiholeFile::CustomCnameList, &record.to_config_line())
+}
+
+/// Remove the CNAME record of the domain from the config
+fn delete_cname_record_impl(env: &Env, domain: &str) -> Result<(), Error> {
+    let lines = read_cname_lines(env)?;
+    let is_cname_for_domain = |line: &String| {
+        parse_cname(line).map_or(false, |record| record.domain.eq_ignore_ascii_case(domain))
+    };
+
+    if !lines.iter().any(|line| is_cname_for_domain(line)) {
+        return Err(Error::from(ErrorKind::NotFound));
+    }
+
+    // Rewrite the config without the record
+    let mut writer = BufWriter::new(env.write_file(PiholeFile::CustomCnameList, false)?);
+
+    for line in lines.iter().filter(|line| !is_cname_for_domain(line)) {
+        writeln!(writer, "{}", line).context(ErrorKind::FileWrite(
+            env.file_location(PiholeFile::CustomCnameList).to_owned()
+        ))?;
+    }
+
+    Ok(())
+}
+
+/// Read the lines of the CNAME config, if it exists
+fn read_cname_lines(env: &Env) -> Result<Vec<String>, Error> {
+    if env.file_exists(PiholeFile::CustomCnameList) {
+        env.read_file_lines(PiholeFile::CustomCnameList)
+    } else {
+        Ok(Vec::new())
+    }
+}
+
+/// Parse a `cname=domain,target` config line into a record
+fn parse_cname(line: &str) -> Option<CnameRecord> {
+    if !line.starts_with(CNAME) {
+        return None;
+    }
+
+    let mut split = line[CNAME.len()..].split(',');
+
+    Some(CnameRecord {
+        domain: split.next()?.trim().to_owned(),
+        target: split.next()?.trim().to_owned()
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{env::PiholeFile, testing::TestBuilder};
+    use rocket::http::{Method, Status};
+
+    /// The CNAME records are listed
+    #[test]
+    fn get_records() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/dns/cname")
+            .file(
+                PiholeFile::CustomCnameList,
+                "cname=files.lan,nas.lan\n\
+                 cname=media.lan,nas.lan\n"
+            )
+            .expect_json(json!([
+                { "domain": "files.lan", "target": "nas.lan" },
+                { "domain": "media.lan", "target": "nas.lan" }
+            ]))
+            .test();
+    }
+
+    /// A CNAME record is added to the config
+    #[test]
+    fn add_record() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/dns/cname")
+            .method(Method::Post)
+            .file(PiholeFile::CustomList, "192.168.1.10 nas.lan\n")
+            .file_expect(
+                PiholeFile::CustomCnameList,
+                "cname=files.lan,nas.lan\n",
+                "cname=files.lan,nas.lan\n\
+                 cname=media.lan,nas.lan\n"
+            )
+            .body(json!({ "domain": "media.lan", "target": "nas.lan" }))
+            .expect_json(json!({ "status": "success" }))
+            .test();
+    }
+
+    /// A CNAME record can not point to itself
+    #[test]
+    fn add_loop() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/dns/cname")
+            .method(Method::Post)
+            .file(PiholeFile::CustomCnameList, "")
+            .body(json!({ "domain": "nas.lan", "target": "NAS.lan" }))
+            .expect_status(Status::BadRequest)
+            .expect_json(json!({
+                "error": {
+                    "key": "invalid_setting_value",
+                    "message": "Invalid setting value",
+                    "data": None::<()>
+                }
+            }))
+            .test();
+    }
+
+    /// A CNAME record can not be added for a domain with a local DNS record,
+    /// and the conflicting record is returned
+    #[test]
+    fn add_conflicting_dns_record() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/dns/cname")
+            .method(Method::Post)
+            .file(PiholeFile::CustomList, "192.168.1.10 nas.lan\n")
+            .file(PiholeFile::CustomCnameList, "")
+            .body(json!({ "domain": "nas.lan", "target": "storage.lan" }))
+            .expect_status(Status::Conflict)
+            .expect_json(json!({
+                "error": {
+                    "key": "conflicting_dns_record",
+                    "message": "A local DNS record already exists for the domain",
+                    "data": {
+                        "record": { "domain": "nas.lan", "ip": "192.168.1.10" }
+                    }
+                }
+            }))
+            .test();
+    }
+
+    /// A CNAME record is removed by its domain
+    #[test]
+    fn delete_record() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/dns/cname/files.lan")
+            .method(Method::Delete)
+            .file_expect(
+                PiholeFile::CustomCnameList,
+                "cname=files.lan,nas.lan\n\
+                 cname=media.lan,nas.lan\n",
+                "cname=media.lan,nas.lan\n"
+            )
+            .expect_json(json!({ "status": "success" }))
+            .test();
+    }
+}
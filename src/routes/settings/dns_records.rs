@@ -0,0 +1,296 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// Local DNS Record Endpoints
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    env::{Env, PiholeFile},
+    routes::{
+        auth::User,
+        settings::common::{restart_dns, SettingsWriter}
+    },
+    settings::ValueType,
+    util::{reply_data, reply_success, Error, ErrorKind, Reply}
+};
+use failure::ResultExt;
+use rocket::State;
+use rocket_contrib::json::Json;
+use std::io::{BufWriter, Write};
+
+/// A local DNS record, which resolves a domain to an IP address
+#[derive(Serialize, Deserialize)]
+#[cfg_attr(test, derive(PartialEq, Debug))]
+pub struct DnsRecord {
+    pub domain: String,
+    pub ip: String
+}
+
+impl DnsRecord {
+    /// Check if the domain and IP address are valid
+    fn is_valid(&self) -> bool {
+        ValueType::Hostname.is_valid(&self.domain)
+            && (ValueType::Ipv4.is_valid(&self.ip) || ValueType::Ipv6.is_valid(&self.ip))
+    }
+}
+
+/// Get the local DNS records
+#[get("/settings/dns/records")]
+pub fn get_dns_records(env: State<Env>, _auth: User) -> Reply {
+    reply_data(read_dns_records(&env)?)
+}
+
+/// Add a local DNS record. With `dry_run`, the change is only reported.
+#[post("/settings/dns/records?<dry_run>", data = "<record>")]
+pub fn add_dns_record(
+    env: State<Env>,
+    _auth: User,
+    record: Json<DnsRecord>,
+    dry_run: Option<bool>
+) -> Reply {
+    let mut writer = SettingsWriter::new(&env, dry_run);
+
+    add_dns_record_impl(&env, &mut writer, &record.into_inner())?;
+    writer.finish(restart_dns)
+}
+
+/// Remove the local DNS record of a domain
+#[delete("/settings/dns/records/<domain>")]
+pub fn delete_dns_record(env: State<Env>, _auth: User, domain: String) -> Reply {
+    delete_dns_record_impl(&env, &domain)?;
+    restart_dns(&env)?;
+    reply_success()
+}
+
+/// Read the local DNS records, which use the hosts file format
+/// (`IP domain`). Comments and invalid lines are skipped.
+pub fn read_dns_records(env: &Env) -> Result<Vec<DnsRecord>, Error> {
+    Ok(read_record_lines(env)?
+        .iter()
+        .filter_map(|line| parse_record(line))
+        .collect())
+}
+
+/// Add the record to the custom list. Adding a record which already exists
+/// does nothing, but a domain can not be mapped to a different IP address.
+fn add_dns_record_impl(
+    env: &Env,
+    writer: &mut SettingsWriter,
+    record: &DnsRecord
+) -> Result<(), Error> {
+    if !record.is_valid() {
+        return Err(Error::from(ErrorKind::InvalidSettingValue));
+    }
+
+    let existing = read_dns_records(env)?
+        .into_iter()
+        .find(|existing| existing.domain.eq_ignore_ascii_case(&record.domain));
+
+    match existing {
+        Some(ref existing) if existing.ip == record.ip => return Ok(()),
+        Some(_) => return Err(Error::from(ErrorKind::AlreadyExists)),
+        None => ()
+    }
+
+    writer.append_line(
+        PiholeFile::CustomList,
+        &format!("{} {}", record.ip, record.domain)
+    )
+}
+
+/// Remove the records of the domain from the custom list
+fn delete_dns_record_impl(env: &Env, domain: &str) -> Result<(), Error> {
+    let lines = read_record_lines(env)?;
+    let is_record_for_domain = |line: &String| {
+        parse_record(line).map_or(false, |record| record.domain.eq_ignore_ascii_case(domain))
+    };
+
+    if !lines.iter().any(|line| is_record_for_domain(line)) {
+        return Err(Error::from(ErrorKind::NotFound));
+    }
+
+    // Rewrite the list without the records
+    let mut writer = BufWriter::new(env.write_file(PiholeFile::CustomList, false)?);
+
+    for line in lines.iter().filter(|line| !is_record_for_domain(line)) {
+        writeln!(writer, "{}", line).context(ErrorKind::FileWrite(
+            env.file_location(PiholeFile::CustomList).to_owned()
+        ))?;
+    }
+
+    Ok(())
+}
+
+/// Read the lines of the custom list, if it exists
+fn read_record_lines(env: &Env) -> Result<Vec<String>, Error> {
+    if env.file_exists(PiholeFile::CustomList) {
+        env.read_file_lines(PiholeFile::CustomList)
+    } else {
+        Ok(Vec::new())
+    }
+}
+
+/// Parse a line of the custom list into a record
+fn parse_record(line: &str) -> Option<DnsRecord> {
+    // Ignore comments
+    let line = line.split('#').next().unwrap_or_default();
+    let mut split = line.split_whitespace();
+
+    Some(DnsRecord {
+        ip: split.next()?.to_owned(),
+        domain: split.next()?.to_owned()
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{env::PiholeFile, testing::TestBuilder};
+    use rocket::http::{Method, Status};
+
+    /// The records are listed, and comments are skipped
+    #[test]
+    fn get_records() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/dns/records")
+            .file(
+                PiholeFile::CustomList,
+                "# Local records\n\
+                 192.168.1.10 nas.lan\n\
+                 fd00::10 printer.lan\n"
+            )
+            .expect_json(json!([
+                { "domain": "nas.lan", "ip": "192.168.1.10" },
+                { "domain": "printer.lan", "ip": "fd00::10" }
+            ]))
+            .test();
+    }
+
+    /// A record is added to the list
+    #[test]
+    fn add_record() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/dns/records")
+            .method(Method::Post)
+            .file_expect(
+                PiholeFile::CustomList,
+                "192.168.1.10 nas.lan\n",
+                "192.168.1.10 nas.lan\n\
+                 192.168.1.11 printer.lan\n"
+            )
+            .body(json!({ "domain": "printer.lan", "ip": "192.168.1.11" }))
+            .expect_json(json!({ "status": "success" }))
+            .test();
+    }
+
+    /// A dry run reports the line which would be added, without adding it
+    #[test]
+    fn add_record_dry_run() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/dns/records?dry_run=true")
+            .method(Method::Post)
+            .file_expect(
+                PiholeFile::CustomList,
+                "192.168.1.10 nas.lan\n",
+                "192.168.1.10 nas.lan\n"
+            )
+            .body(json!({ "domain": "printer.lan", "ip": "192.168.1.11" }))
+            .expect_json(json!({
+                "dry_run": true,
+                "changes": [
+                    {
+                        "file": "/etc/pihole/custom.list",
+                        "value": "192.168.1.11 printer.lan"
+                    }
+                ]
+            }))
+            .test();
+    }
+
+    /// Adding a record which already exists does not add it again
+    #[test]
+    fn add_existing_record() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/dns/records")
+            .method(Method::Post)
+            .file(PiholeFile::CustomList, "192.168.1.10 nas.lan\n")
+            .body(json!({ "domain": "nas.lan", "ip": "192.168.1.10" }))
+            .expect_json(json!({ "status": "success" }))
+            .test();
+    }
+
+    /// A domain can not be mapped to a different IP address
+    #[test]
+    fn add_conflicting_record() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/dns/records")
+            .method(Method::Post)
+            .file(PiholeFile::CustomList, "192.168.1.10 nas.lan\n")
+            .body(json!({ "domain": "NAS.lan", "ip": "192.168.1.20" }))
+            .expect_status(Status::Conflict)
+            .expect_json(json!({
+                "error": {
+                    "key": "already_exists",
+                    "message": "Item already exists",
+                    "data": None::<()>
+                }
+            }))
+            .test();
+    }
+
+    /// Invalid IP addresses are rejected
+    #[test]
+    fn add_invalid_ip() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/dns/records")
+            .method(Method::Post)
+            .file(PiholeFile::CustomList, "")
+            .body(json!({ "domain": "nas.lan", "ip": "not an IP" }))
+            .expect_status(Status::BadRequest)
+            .expect_json(json!({
+                "error": {
+                    "key": "invalid_setting_value",
+                    "message": "Invalid setting value",
+                    "data": None::<()>
+                }
+            }))
+            .test();
+    }
+
+    /// A record is removed by its domain
+    #[test]
+    fn delete_record() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/dns/records/nas.lan")
+            .method(Method::Delete)
+            .file_expect(
+                PiholeFile::CustomList,
+                "192.168.1.10 nas.lan\n\
+                 192.168.1.11 printer.lan\n",
+                "192.168.1.11 printer.lan\n"
+            )
+            .expect_json(json!({ "status": "success" }))
+            .test();
+    }
+
+    /// Removing a record which does not exist is not found
+    #[test]
+    fn delete_missing_record() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/dns/records/nas.lan")
+            .method(Method::Delete)
+            .file(PiholeFile::CustomList, "192.168.1.11 printer.lan\n")
+            .expect_status(Status::NotFound)
+            .expect_json(json!({
+                "error": {
+                    "key": "not_found",
+                    "message": "Not found",
+                    "data": None::<()>
+                }
+            }))
+            .test();
+    }
+}
@@ -0,0 +1,126 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// FTL Blocking Mode Settings
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    env::Env,
+    routes::{
+        auth::User,
+        settings::common::{restart_dns, SettingsWriter}
+    },
+    settings::{ConfigEntry, FtlConfEntry, BLOCKING_MODES},
+    util::{reply_data, Error, ErrorKind, Reply}
+};
+use rocket::State;
+use rocket_contrib::json::Json;
+
+/// The blocking mode used by FTL
+#[derive(Serialize, Deserialize)]
+pub struct BlockingMode {
+    mode: String
+}
+
+/// Get the blocking mode
+#[get("/settings/ftl/blocking_mode")]
+pub fn get_blocking_mode(env: State<Env>, _auth: User) -> Reply {
+    reply_data(BlockingMode {
+        mode: FtlConfEntry::BlockingMode.read(&env)?
+    })
+}
+
+/// Set the blocking mode. FTL is restarted to apply the new mode. With
+/// `dry_run`, the change is only reported.
+#[put("/settings/ftl/blocking_mode?<dry_run>", data = "<data>")]
+pub fn put_blocking_mode(
+    env: State<Env>,
+    _auth: User,
+    data: Json<BlockingMode>,
+    dry_run: Option<bool>
+) -> Reply {
+    let mode = data.into_inner().mode;
+
+    // An empty mode would delete the entry, so it is not accepted either
+    if mode.is_empty() || !FtlConfEntry::BlockingMode.is_valid(&mode) {
+        return Err(Error::from(ErrorKind::InvalidSettingOption(
+            &BLOCKING_MODES
+        )));
+    }
+
+    let mut writer = SettingsWriter::new(&env, dry_run);
+
+    writer.write(&FtlConfEntry::BlockingMode, &mode)?;
+    writer.finish(restart_dns)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{env::PiholeFile, testing::TestBuilder};
+    use rocket::http::{Method, Status};
+
+    /// The current blocking mode is reported
+    #[test]
+    fn get_mode() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/ftl/blocking_mode")
+            .file(PiholeFile::FtlConfig, "BLOCKINGMODE=NXDOMAIN\n")
+            .expect_json(json!({ "mode": "NXDOMAIN" }))
+            .test();
+    }
+
+    /// The default blocking mode is reported if it is not set
+    #[test]
+    fn get_default_mode() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/ftl/blocking_mode")
+            .file(PiholeFile::FtlConfig, "")
+            .expect_json(json!({ "mode": "NULL" }))
+            .test();
+    }
+
+    /// Setting the blocking mode writes it to the FTL config
+    #[test]
+    fn put_mode() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/ftl/blocking_mode")
+            .method(Method::Put)
+            .file_expect(
+                PiholeFile::FtlConfig,
+                "BLOCKINGMODE=NULL\n",
+                "BLOCKINGMODE=IP\n"
+            )
+            .body(json!({ "mode": "IP" }))
+            .expect_json(json!({ "status": "success" }))
+            .test();
+    }
+
+    /// Unknown blocking modes are rejected, and the valid modes are listed
+    #[test]
+    fn put_unknown_mode() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/ftl/blocking_mode")
+            .method(Method::Put)
+            .file_expect(
+                PiholeFile::FtlConfig,
+                "BLOCKINGMODE=NULL\n",
+                "BLOCKINGMODE=NULL\n"
+            )
+            .body(json!({ "mode": "REFUSED" }))
+            .expect_status(Status::BadRequest)
+            .expect_json(json!({
+                "error": {
+                    "key": "invalid_setting_option",
+                    "message": "Invalid setting value",
+                    "data": {
+                        "options": ["NULL", "IP-AAAA-NODATA", "IP", "NXDOMAIN"]
+                    }
+                }
+            }))
+            .test();
+    }
+}
@@ -0,0 +1,151 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// FTL Database Interval Settings
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    env::Env,
+    routes::{
+        auth::User,
+        settings::common::{restart_dns, SettingsWriter}
+    },
+    settings::{ConfigEntry, FtlConfEntry},
+    util::{reply_data, Error, ErrorKind, Reply}
+};
+use rocket::State;
+use rocket_contrib::json::Json;
+
+/// The shortest interval, in minutes, which FTL can store queries at
+const MIN_DB_INTERVAL: f64 = 0.1;
+
+/// How often, in minutes, FTL stores queries in its database
+#[derive(Serialize, Deserialize)]
+pub struct DbInterval {
+    minutes: f64
+}
+
+/// Get how often queries are stored in the database
+#[get("/settings/ftl/db_interval")]
+pub fn get_db_interval(env: State<Env>, _auth: User) -> Reply {
+    reply_data(DbInterval {
+        minutes: FtlConfEntry::DbInterval.read_as(&env)?
+    })
+}
+
+/// Set how often queries are stored in the database. FTL is restarted to
+/// apply the new interval. With `dry_run`, the change is only reported.
+#[put("/settings/ftl/db_interval?<dry_run>", data = "<data>")]
+pub fn put_db_interval(
+    env: State<Env>,
+    _auth: User,
+    data: Json<DbInterval>,
+    dry_run: Option<bool>
+) -> Reply {
+    let mut writer = SettingsWriter::new(&env, dry_run);
+
+    write_db_interval(&mut writer, data.minutes)?;
+    writer.finish(restart_dns)
+}
+
+/// Write the database interval to the FTL config. Intervals shorter than
+/// `MIN_DB_INTERVAL` are rejected, as they would wear out SD cards.
+fn write_db_interval(writer: &mut SettingsWriter, minutes: f64) -> Result<(), Error> {
+    let minutes_str = minutes.to_string();
+
+    if minutes < MIN_DB_INTERVAL || !FtlConfEntry::DbInterval.is_valid(&minutes_str) {
+        return Err(Error::from(ErrorKind::InvalidSettingValue));
+    }
+
+    writer.write(&FtlConfEntry::DbInterval, &minutes_str)
+}
+
+#[cfg(test)]
+mod test {
+    use super::write_db_interval;
+    use crate::{
+        env::{Config, Env, PiholeFile},
+        routes::settings::common::SettingsWriter,
+        settings::{ConfigEntry, FtlConfEntry},
+        testing::{TestBuilder, TestEnvBuilder}
+    };
+    use rocket::http::{Method, Status};
+
+    /// The set interval is reported
+    #[test]
+    fn get_interval() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/ftl/db_interval")
+            .file(PiholeFile::FtlConfig, "DBINTERVAL=0.5\n")
+            .expect_json(json!({ "minutes": 0.5 }))
+            .test();
+    }
+
+    /// The default interval is reported if it is not set
+    #[test]
+    fn get_default_interval() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/ftl/db_interval")
+            .file(PiholeFile::FtlConfig, "")
+            .expect_json(json!({ "minutes": 1.0 }))
+            .test();
+    }
+
+    /// The written interval is in the FTL config, and is read back
+    #[test]
+    fn round_trip() {
+        let env_builder = TestEnvBuilder::new().file_expect(
+            PiholeFile::FtlConfig,
+            "DBINTERVAL=1.0\n",
+            "DBINTERVAL=2.5\n"
+        );
+        let mut test_file = env_builder.get_test_files().into_iter().next().unwrap();
+        let env = Env::Test(Config::default(), env_builder.build());
+
+        write_db_interval(&mut SettingsWriter::new(&env, None), 2.5).unwrap();
+
+        assert_eq!(FtlConfEntry::DbInterval.read_as::<f64>(&env).unwrap(), 2.5);
+
+        let mut buffer = String::new();
+        test_file.assert_expected(&mut buffer);
+    }
+
+    /// Setting the interval writes it to the FTL config
+    #[test]
+    fn put_interval() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/ftl/db_interval")
+            .method(Method::Put)
+            .file_expect(PiholeFile::FtlConfig, "DBINTERVAL=1.0\n", "DBINTERVAL=10\n")
+            .body(json!({ "minutes": 10.0 }))
+            .expect_json(json!({ "status": "success" }))
+            .test();
+    }
+
+    /// Intervals below the minimum are rejected
+    #[test]
+    fn put_too_short() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/ftl/db_interval")
+            .method(Method::Put)
+            .file_expect(
+                PiholeFile::FtlConfig,
+                "DBINTERVAL=1.0\n",
+                "DBINTERVAL=1.0\n"
+            )
+            .body(json!({ "minutes": 0.05 }))
+            .expect_status(Status::BadRequest)
+            .expect_json(json!({
+                "error": {
+                    "key": "invalid_setting_value",
+                    "message": "Invalid setting value",
+                    "data": None::<()>
+                }
+            }))
+            .test();
+    }
+}
@@ -0,0 +1,177 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// FTL Database Maintenance Endpoint
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    databases::ftl::FtlDatabase,
+    env::Env,
+    routes::auth::User,
+    settings::{ConfigEntry, FtlConfEntry},
+    util::{reply_data, Error, ErrorKind, Reply}
+};
+use diesel::{sqlite::SqliteConnection, Connection};
+use failure::ResultExt;
+use rocket::{
+    request::{self, FromRequest},
+    Outcome, Request, State
+};
+use std::fs;
+
+/// Represents the reply structure for optimizing the database. The sizes are
+/// in bytes.
+#[derive(Serialize)]
+pub struct OptimizeReply {
+    size_before: u64,
+    size_after: u64,
+    reclaimed: u64
+}
+
+/// A connection to the long term database, which is only made if the
+/// database file exists. SQLite creates a missing database file when it
+/// connects, so the file is checked for before the database guard runs.
+pub struct ExistingFtlDatabase(FtlDatabase);
+
+impl<'a, 'r> FromRequest<'a, 'r> for ExistingFtlDatabase {
+    type Error = Error;
+
+    fn from_request(request: &'a Request<'r>) -> request::Outcome<Self, Self::Error> {
+        let env: State<Env> = match request.guard().succeeded() {
+            Some(env) => env,
+            None => return Error::from(ErrorKind::Unknown).into_outcome()
+        };
+
+        let db_path = match FtlConfEntry::DbFile.read(&env) {
+            Ok(db_path) => db_path,
+            Err(e) => return e.into_outcome()
+        };
+
+        if let Err(e) = database_size(&db_path) {
+            return e.into_outcome();
+        }
+
+        match request.guard::<FtlDatabase>() {
+            Outcome::Success(db) => Outcome::Success(ExistingFtlDatabase(db)),
+            _ => Error::from(ErrorKind::FtlDatabase).into_outcome()
+        }
+    }
+}
+
+/// Vacuum the long term database to defragment it, and optionally let SQLite
+/// optimize it with `PRAGMA optimize`. Only the database connection is held
+/// while this runs, so it does not block shared memory or the settings files.
+/// If the database does not exist, it is not created.
+#[post("/settings/ftl/database/optimize?<optimize>")]
+pub fn optimize_database(
+    env: State<Env>,
+    _auth: User,
+    db: Result<ExistingFtlDatabase, Error>,
+    optimize: Option<bool>
+) -> Reply {
+    let db = db?;
+
+    reply_data(optimize_database_impl(
+        &FtlConfEntry::DbFile.read(&env)?,
+        &db.0,
+        optimize.unwrap_or(false)
+    )?)
+}
+
+/// Vacuum the database at `db_path` through the connection, and report how
+/// much its file shrank
+fn optimize_database_impl(
+    db_path: &str,
+    db: &SqliteConnection,
+    optimize: bool
+) -> Result<OptimizeReply, Error> {
+    let size_before = database_size(db_path)?;
+
+    db.execute("VACUUM").context(ErrorKind::FtlDatabase)?;
+
+    if optimize {
+        db.execute("PRAGMA optimize")
+            .context(ErrorKind::FtlDatabase)?;
+    }
+
+    let size_after = database_size(db_path)?;
+
+    Ok(OptimizeReply {
+        size_before,
+        size_after,
+        reclaimed: size_before.saturating_sub(size_after)
+    })
+}
+
+/// Get the size of the database file. It is an error if it does not exist.
+fn database_size(db_path: &str) -> Result<u64, Error> {
+    Ok(fs::metadata(db_path)
+        .context(ErrorKind::FtlDatabaseMissing)?
+        .len())
+}
+
+#[cfg(test)]
+mod test {
+    use super::optimize_database_impl;
+    use crate::{
+        databases::ftl::TEST_FTL_DATABASE_PATH, env::PiholeFile, testing::TestBuilder,
+        util::ErrorKind
+    };
+    use diesel::{sqlite::SqliteConnection, Connection};
+    use rocket::http::{Method, Status};
+    use std::fs;
+    use tempfile::NamedTempFile;
+
+    /// Vacuuming a copy of the test database reports its sizes
+    #[test]
+    fn vacuum() {
+        let db_file = NamedTempFile::new().unwrap();
+        fs::copy(TEST_FTL_DATABASE_PATH, db_file.path()).unwrap();
+
+        let db_path = db_file.path().to_str().unwrap();
+        let db = SqliteConnection::establish(db_path).unwrap();
+        let reply = optimize_database_impl(db_path, &db, true).unwrap();
+
+        assert_eq!(
+            reply.size_before,
+            fs::metadata(TEST_FTL_DATABASE_PATH).unwrap().len()
+        );
+        assert_eq!(reply.size_after, fs::metadata(db_path).unwrap().len());
+        assert_eq!(
+            reply.reclaimed,
+            reply.size_before.saturating_sub(reply.size_after)
+        );
+    }
+
+    /// A missing database can not be optimized
+    #[test]
+    fn missing_database() {
+        let db = SqliteConnection::establish(":memory:").unwrap();
+        let error = optimize_database_impl("/tmp/missing/FTL.db", &db, false).unwrap_err();
+
+        assert_eq!(error.kind(), ErrorKind::FtlDatabaseMissing);
+    }
+
+    /// The endpoint reports a missing database without connecting to it, so
+    /// the database is not created
+    #[test]
+    fn missing_database_route() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/ftl/database/optimize")
+            .method(Method::Post)
+            .file(PiholeFile::FtlConfig, "DBFILE=/tmp/missing/FTL.db\n")
+            .expect_status(Status::ServiceUnavailable)
+            .expect_json(json!({
+                "error": {
+                    "key": "ftl_database_missing",
+                    "message": "The FTL database does not exist",
+                    "data": None::<()>
+                }
+            }))
+            .test();
+    }
+}
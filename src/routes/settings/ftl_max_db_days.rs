@@ -0,0 +1,145 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// FTL Database Age Settings
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    env::Env,
+    routes::{
+        auth::User,
+        settings::common::{restart_dns, SettingsWriter}
+    },
+    settings::{ConfigEntry, FtlConfEntry},
+    util::{reply_data, Error, ErrorKind, Reply}
+};
+use rocket::State;
+use rocket_contrib::json::Json;
+
+/// The number of days of queries which FTL keeps in its database
+#[derive(Serialize, Deserialize)]
+pub struct MaxDbDays {
+    days: usize
+}
+
+/// Get the maximum age of queries in the database
+#[get("/settings/ftl/max_db_days")]
+pub fn get_max_db_days(env: State<Env>, _auth: User) -> Reply {
+    reply_data(MaxDbDays {
+        days: FtlConfEntry::MaxDbDays.read_as(&env)?
+    })
+}
+
+/// Set the maximum age of queries in the database. FTL is restarted to apply
+/// the new age. With `dry_run`, the change is only reported.
+#[put("/settings/ftl/max_db_days?<dry_run>", data = "<data>")]
+pub fn put_max_db_days(
+    env: State<Env>,
+    _auth: User,
+    data: Json<MaxDbDays>,
+    dry_run: Option<bool>
+) -> Reply {
+    let mut writer = SettingsWriter::new(&env, dry_run);
+
+    write_max_db_days(&mut writer, data.days)?;
+    writer.finish(restart_dns)
+}
+
+/// Write the maximum age of queries to the FTL config
+fn write_max_db_days(writer: &mut SettingsWriter, days: usize) -> Result<(), Error> {
+    let days = days.to_string();
+
+    if !FtlConfEntry::MaxDbDays.is_valid(&days) {
+        return Err(Error::from(ErrorKind::InvalidSettingValue));
+    }
+
+    writer.write(&FtlConfEntry::MaxDbDays, &days)
+}
+
+#[cfg(test)]
+mod test {
+    use super::write_max_db_days;
+    use crate::{
+        env::{Config, Env, PiholeFile},
+        routes::settings::common::SettingsWriter,
+        settings::{ConfigEntry, FtlConfEntry},
+        testing::{TestBuilder, TestEnvBuilder}
+    };
+    use rocket::http::Method;
+
+    /// The set age is reported
+    #[test]
+    fn get_days() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/ftl/max_db_days")
+            .file(PiholeFile::FtlConfig, "MAXDBDAYS=30\n")
+            .expect_json(json!({ "days": 30 }))
+            .test();
+    }
+
+    /// The default age is reported if it is not set
+    #[test]
+    fn get_default_days() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/ftl/max_db_days")
+            .file(PiholeFile::FtlConfig, "")
+            .expect_json(json!({ "days": 365 }))
+            .test();
+    }
+
+    /// The written age is in the FTL config, and is read back
+    #[test]
+    fn round_trip() {
+        let env_builder = TestEnvBuilder::new().file_expect(
+            PiholeFile::FtlConfig,
+            "MAXDBDAYS=365\n",
+            "MAXDBDAYS=7\n"
+        );
+        let mut test_file = env_builder.get_test_files().into_iter().next().unwrap();
+        let env = Env::Test(Config::default(), env_builder.build());
+
+        write_max_db_days(&mut SettingsWriter::new(&env, None), 7).unwrap();
+
+        assert_eq!(FtlConfEntry::MaxDbDays.read_as::<usize>(&env).unwrap(), 7);
+
+        let mut buffer = String::new();
+        test_file.assert_expected(&mut buffer);
+    }
+
+    /// Setting the age writes it to the FTL config
+    #[test]
+    fn put_days() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/ftl/max_db_days")
+            .method(Method::Put)
+            .file_expect(PiholeFile::FtlConfig, "MAXDBDAYS=365\n", "MAXDBDAYS=14\n")
+            .body(json!({ "days": 14 }))
+            .expect_json(json!({ "status": "success" }))
+            .test();
+    }
+
+    /// A dry run reports the change without writing it
+    #[test]
+    fn put_days_dry_run() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/ftl/max_db_days?dry_run=true")
+            .method(Method::Put)
+            .file_expect(PiholeFile::FtlConfig, "MAXDBDAYS=365\n", "MAXDBDAYS=365\n")
+            .body(json!({ "days": 14 }))
+            .expect_json(json!({
+                "dry_run": true,
+                "changes": [
+                    {
+                        "file": "/etc/pihole/pihole-FTL.conf",
+                        "key": "MAXDBDAYS",
+                        "value": "14"
+                    }
+                ]
+            }))
+            .test();
+    }
+}
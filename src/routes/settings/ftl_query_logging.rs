@@ -0,0 +1,187 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// Query Logging Settings
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    env::Env,
+    routes::{
+        auth::User,
+        settings::common::{restart_dns, SettingsWriter}
+    },
+    settings::{
+        generate_dnsmasq_config, ConfigEntry, FtlConfEntry, FtlPrivacyLevel, SetupVarsEntry
+    },
+    util::{reply_data, Error, Reply}
+};
+use rocket::State;
+use rocket_contrib::json::Json;
+
+/// Whether queries are logged, and the privacy level FTL uses for them
+#[derive(Serialize, Deserialize)]
+pub struct QueryLogging {
+    enabled: bool,
+    privacy_level: usize
+}
+
+/// Get the query logging settings
+#[get("/settings/ftl/query_logging")]
+pub fn get_query_logging(env: State<Env>, _auth: User) -> Reply {
+    reply_data(QueryLogging {
+        enabled: SetupVarsEntry::QueryLogging.is_true(&env)?,
+        privacy_level: FtlConfEntry::PrivacyLevel
+            .read_as::<FtlPrivacyLevel>(&env)?
+            .get_level()
+    })
+}
+
+/// Set the query logging settings. The dnsmasq config is regenerated and DNS
+/// is restarted to apply them. With `dry_run`, the changes are only reported.
+#[put("/settings/ftl/query_logging?<dry_run>", data = "<data>")]
+pub fn put_query_logging(
+    env: State<Env>,
+    _auth: User,
+    data: Json<QueryLogging>,
+    dry_run: Option<bool>
+) -> Reply {
+    let mut writer = SettingsWriter::new(&env, dry_run);
+
+    write_query_logging(&mut writer, &data)?;
+    writer.finish(|env| {
+        generate_dnsmasq_config(env)?;
+        restart_dns(env)
+    })
+}
+
+/// Write the query logging settings. The privacy level must be one of FTL's
+/// levels (0 to 4), and is checked before anything is written. The stats
+/// endpoints read the privacy level on each request, so it applies at once.
+fn write_query_logging(writer: &mut SettingsWriter, settings: &QueryLogging) -> Result<(), Error> {
+    let privacy_level = settings.privacy_level.to_string();
+
+    // Validate the privacy level before writing the other setting
+    privacy_level.parse::<FtlPrivacyLevel>()?;
+
+    writer.write(&SetupVarsEntry::QueryLogging, &settings.enabled.to_string())?;
+    writer.write(&FtlConfEntry::PrivacyLevel, &privacy_level)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{env::PiholeFile, testing::TestBuilder};
+    use rocket::http::{Method, Status};
+
+    /// The query logging settings are reported
+    #[test]
+    fn get_query_logging() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/ftl/query_logging")
+            .file(PiholeFile::SetupVars, "QUERY_LOGGING=true\n")
+            .file(PiholeFile::FtlConfig, "PRIVACYLEVEL=1\n")
+            .expect_json(json!({ "enabled": true, "privacy_level": 1 }))
+            .test();
+    }
+
+    /// The defaults are reported if the settings are not set
+    #[test]
+    fn get_defaults() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/ftl/query_logging")
+            .file(PiholeFile::SetupVars, "")
+            .file(PiholeFile::FtlConfig, "")
+            .expect_json(json!({ "enabled": false, "privacy_level": 0 }))
+            .test();
+    }
+
+    /// Every privacy level can be set, along with query logging
+    #[test]
+    fn put_each_level() {
+        for level in 0..=4 {
+            TestBuilder::new()
+                .endpoint("/admin/api/settings/ftl/query_logging")
+                .method(Method::Put)
+                .file_expect(
+                    PiholeFile::SetupVars,
+                    "QUERY_LOGGING=true\n",
+                    "QUERY_LOGGING=false\n"
+                )
+                .file_expect(
+                    PiholeFile::FtlConfig,
+                    "PRIVACYLEVEL=0\n",
+                    &format!("PRIVACYLEVEL={}\n", level)
+                )
+                .file(PiholeFile::DnsmasqConfig, "")
+                .body(json!({ "enabled": false, "privacy_level": level }))
+                .expect_json(json!({ "status": "success" }))
+                .test();
+        }
+    }
+
+    /// Privacy levels past 4 are rejected, and nothing is written
+    #[test]
+    fn put_invalid_level() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/ftl/query_logging")
+            .method(Method::Put)
+            .file_expect(
+                PiholeFile::SetupVars,
+                "QUERY_LOGGING=true\n",
+                "QUERY_LOGGING=true\n"
+            )
+            .file_expect(
+                PiholeFile::FtlConfig,
+                "PRIVACYLEVEL=0\n",
+                "PRIVACYLEVEL=0\n"
+            )
+            .body(json!({ "enabled": false, "privacy_level": 5 }))
+            .expect_status(Status::BadRequest)
+            .expect_json(json!({
+                "error": {
+                    "key": "invalid_setting_value",
+                    "message": "Invalid setting value",
+                    "data": None::<()>
+                }
+            }))
+            .test();
+    }
+
+    /// A dry run reports the changes without writing them
+    #[test]
+    fn put_dry_run() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/ftl/query_logging?dry_run=true")
+            .method(Method::Put)
+            .file_expect(
+                PiholeFile::SetupVars,
+                "QUERY_LOGGING=true\n",
+                "QUERY_LOGGING=true\n"
+            )
+            .file_expect(
+                PiholeFile::FtlConfig,
+                "PRIVACYLEVEL=0\n",
+                "PRIVACYLEVEL=0\n"
+            )
+            .body(json!({ "enabled": false, "privacy_level": 3 }))
+            .expect_json(json!({
+                "dry_run": true,
+                "changes": [
+                    {
+                        "file": "/etc/pihole/setupVars.conf",
+                        "key": "QUERY_LOGGING",
+                        "value": "false"
+                    },
+                    {
+                        "file": "/etc/pihole/pihole-FTL.conf",
+                        "key": "PRIVACYLEVEL",
+                        "value": "3"
+                    }
+                ]
+            }))
+            .test();
+    }
+}
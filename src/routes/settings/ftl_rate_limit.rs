@@ -0,0 +1,139 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// FTL Rate Limit Settings
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    env::Env,
+    routes::{
+        auth::User,
+        settings::common::{restart_dns, SettingsWriter}
+    },
+    settings::{ConfigEntry, FtlConfEntry, ValueType},
+    util::{reply_data, Error, ErrorKind, Reply}
+};
+use rocket::State;
+use rocket_contrib::json::Json;
+use std::str::FromStr;
+
+/// The number of queries a client may make in an interval of seconds before
+/// FTL rate limits it
+#[derive(Serialize, Deserialize)]
+pub struct RateLimit {
+    pub count: usize,
+    pub interval: usize
+}
+
+impl FromStr for RateLimit {
+    type Err = Error;
+
+    /// Parse the rate limit from FTL's `count/interval` format
+    fn from_str(s: &str) -> Result<Self, Error> {
+        if !ValueType::RateLimit.is_valid(s) {
+            return Err(Error::from(ErrorKind::InvalidSettingValue));
+        }
+
+        let mut parts = s.splitn(2, '/').map(|part| part.parse::<usize>());
+
+        match (parts.next(), parts.next()) {
+            (Some(Ok(count)), Some(Ok(interval))) => Ok(RateLimit { count, interval }),
+            _ => Err(Error::from(ErrorKind::InvalidSettingValue))
+        }
+    }
+}
+
+/// Get the rate limit
+#[get("/settings/ftl/rate_limit")]
+pub fn get_rate_limit(env: State<Env>, _auth: User) -> Reply {
+    reply_data(FtlConfEntry::RateLimit.read_as::<RateLimit>(&env)?)
+}
+
+/// Set the rate limit. FTL is restarted to apply the new limit. With
+/// `dry_run`, the change is only reported.
+#[put("/settings/ftl/rate_limit?<dry_run>", data = "<data>")]
+pub fn put_rate_limit(
+    env: State<Env>,
+    _auth: User,
+    data: Json<RateLimit>,
+    dry_run: Option<bool>
+) -> Reply {
+    let mut writer = SettingsWriter::new(&env, dry_run);
+
+    write_rate_limit(&mut writer, &data)?;
+    writer.finish(restart_dns)
+}
+
+/// Write the rate limit to the FTL config in the `count/interval` format.
+/// Both must be positive.
+fn write_rate_limit(writer: &mut SettingsWriter, rate_limit: &RateLimit) -> Result<(), Error> {
+    writer.write(
+        &FtlConfEntry::RateLimit,
+        &format!("{}/{}", rate_limit.count, rate_limit.interval)
+    )
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{env::PiholeFile, testing::TestBuilder};
+    use rocket::http::{Method, Status};
+
+    /// The rate limit is reported as separate fields
+    #[test]
+    fn get_rate_limit() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/ftl/rate_limit")
+            .file(PiholeFile::FtlConfig, "RATE_LIMIT=500/30\n")
+            .expect_json(json!({ "count": 500, "interval": 30 }))
+            .test();
+    }
+
+    /// FTL's default rate limit is reported if it is not set
+    #[test]
+    fn get_default() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/ftl/rate_limit")
+            .file(PiholeFile::FtlConfig, "")
+            .expect_json(json!({ "count": 1000, "interval": 60 }))
+            .test();
+    }
+
+    /// Setting the rate limit writes it to the FTL config
+    #[test]
+    fn put_rate_limit() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/ftl/rate_limit")
+            .method(Method::Put)
+            .file_expect(PiholeFile::FtlConfig, "", "RATE_LIMIT=100/10\n")
+            .body(json!({ "count": 100, "interval": 10 }))
+            .expect_json(json!({ "status": "success" }))
+            .test();
+    }
+
+    /// The count and interval must be positive
+    #[test]
+    fn put_invalid() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/ftl/rate_limit")
+            .method(Method::Put)
+            .file_expect(
+                PiholeFile::FtlConfig,
+                "RATE_LIMIT=1000/60\n",
+                "RATE_LIMIT=1000/60\n"
+            )
+            .body(json!({ "count": 100, "interval": 0 }))
+            .expect_status(Status::BadRequest)
+            .expect_json(json!({
+                "error": {
+                    "key": "invalid_setting_value",
+                    "message": "Invalid setting value",
+                    "data": None::<()>
+                }
+            }))
+            .test();
+    }
+}
@@ -122,4 +122,29 @@ mod test {
             }))
             .test();
     }
+
+    /// RESOLVE_IPV4 and RESOLVE_IPV6 are separate settings
+    #[test]
+    fn test_get_ftl_resolve_ipv4() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/ftl")
+            .file(PiholeFile::FtlConfig, "RESOLVE_IPV6=no\n")
+            .expect_json(json!({
+                "socket_listening": "localonly",
+                "query_display": "yes",
+                "aaaa_query_analysis": "yes",
+                "resolve_ipv6": "no",
+                "resolve_ipv4": "yes",
+                "max_db_days": 365,
+                "db_interval": 1.0,
+                "db_file": "/etc/pihole/pihole-FTL.db",
+                "max_log_age": 24.0,
+                "ftl_port": 4711,
+                "privacy_level": 0,
+                "ignore_local_host": "no",
+                "blocking_mode": "NULL",
+                "regex_debug_mode": false
+            }))
+            .test();
+    }
 }
@@ -8,12 +8,30 @@
 // This file is copyright under the latest version of the EUPL.
 // Please see LICENSE file for your rights under this license.
 
+mod adlists;
+mod batch;
 mod common;
 mod dhcp;
+mod dhcp_leases;
+mod dhcp_static;
 mod dns;
+mod dns_cname;
+mod dns_records;
+mod ftl_blocking_mode;
+mod ftl_db_interval;
+mod ftl_db_optimize;
+mod ftl_max_db_days;
+mod ftl_query_logging;
+mod ftl_rate_limit;
 mod get_ftl;
 mod get_ftldb;
 mod get_network;
+mod validate;
 mod web;
 
-pub use self::{common::*, dhcp::*, dns::*, get_ftl::*, get_ftldb::*, get_network::*, web::*};
+pub use self::{
+    adlists::*, batch::*, common::*, dhcp::*, dhcp_leases::*, dhcp_static::*, dns::*, dns_cname::*,
+    dns_records::*, ftl_blocking_mode::*, ftl_db_interval::*, ftl_db_optimize::*,
+    ftl_max_db_days::*, ftl_query_logging::*, ftl_rate_limit::*, get_ftl::*, get_ftldb::*,
+    get_network::*, validate::*, web::*
+};
@@ -0,0 +1,232 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// Settings Validation Endpoints
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    env::{Env, PiholeFile},
+    routes::auth::User,
+    settings::{ConfigEntry, FtlConfEntry, SetupVarsEntry},
+    util::{reply_data, Error, ErrorKind, Reply}
+};
+use rocket::State;
+
+/// The result of validating a config file
+#[derive(Serialize)]
+pub struct ValidationReply {
+    pub valid: bool,
+    pub errors: Vec<ValidationError>,
+    pub warnings: Vec<ValidationWarning>
+}
+
+/// An entry in a config file which has an invalid value
+#[derive(Serialize)]
+pub struct ValidationError {
+    pub key: String,
+    pub value: String,
+    pub error: String
+}
+
+/// A line in a config file which does not belong to a known entry
+#[derive(Serialize)]
+pub struct ValidationWarning {
+    pub key: String,
+    pub value: String,
+    pub warning: String
+}
+
+/// Validate the entries of SetupVars
+#[get("/settings/validate")]
+pub fn validate_setup_vars(env: State<Env>, _auth: User) -> Reply {
+    reply_data(validate_config(
+        &env,
+        PiholeFile::SetupVars,
+        SetupVarsEntry::from_key,
+        false
+    )?)
+}
+
+/// Validate the entries of FTL's config. Unknown keys are likely typos, so
+/// they are reported as warnings.
+#[get("/settings/ftl/validate")]
+pub fn validate_ftl_config(env: State<Env>, _auth: User) -> Reply {
+    reply_data(validate_config(
+        &env,
+        PiholeFile::FtlConfig,
+        FtlConfEntry::from_key,
+        true
+    )?)
+}
+
+/// Validate every entry found in the config file. Entries which are missing
+/// from the file use their default value, so they are valid. Lines which do
+/// not belong to a known entry are skipped, or reported as warnings if
+/// `warn_unknown` is set. Comments and blank lines are always skipped.
+fn validate_config<E: ConfigEntry>(
+    env: &Env,
+    file: PiholeFile,
+    from_key: impl Fn(&str) -> Option<E>,
+    warn_unknown: bool
+) -> Result<ValidationReply, Error> {
+    let mut errors = Vec::new();
+    let mut warnings = Vec::new();
+
+    for line in env.read_file_lines(file)? {
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        // Entries have the format `KEY=value`
+        let mut split = line.splitn(2, '=');
+        let key = split.next().unwrap_or_default();
+        let value = split.next().unwrap_or_default();
+
+        match from_key(key) {
+            Some(entry) => {
+                if !entry.is_valid(value) {
+                    errors.push(ValidationError {
+                        key: key.to_owned(),
+                        value: value.to_owned(),
+                        error: ErrorKind::InvalidSettingValue.to_string()
+                    });
+                }
+            }
+            None => {
+                if warn_unknown {
+                    warnings.push(ValidationWarning {
+                        key: key.to_owned(),
+                        value: value.to_owned(),
+                        warning: "Unknown setting".to_owned()
+                    });
+                }
+            }
+        }
+    }
+
+    Ok(ValidationReply {
+        valid: errors.is_empty(),
+        errors,
+        warnings
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{env::PiholeFile, testing::TestBuilder};
+
+    /// A file with valid entries is valid. Unknown keys are skipped.
+    #[test]
+    fn setup_vars_valid() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/validate")
+            .file(
+                PiholeFile::SetupVars,
+                "BLOCKING_ENABLED=true\n\
+                 PIHOLE_DNS_1=8.8.8.8\n\
+                 DHCP_START=\n\
+                 INSTALL_WEB_SERVER=true\n"
+            )
+            .expect_json(json!({ "valid": true, "errors": [], "warnings": [] }))
+            .test();
+    }
+
+    /// Entries which are not in the file are valid, because they use their
+    /// defaults
+    #[test]
+    fn setup_vars_missing_entries() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/validate")
+            .file(PiholeFile::SetupVars, "")
+            .expect_json(json!({ "valid": true, "errors": [], "warnings": [] }))
+            .test();
+    }
+
+    /// Each invalid entry is reported with its value
+    #[test]
+    fn setup_vars_invalid() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/validate")
+            .file(
+                PiholeFile::SetupVars,
+                "BLOCKING_ENABLED=yes\n\
+                 PIHOLE_DNS_1=8.8.8.8\n\
+                 PIHOLE_DNS_2=not a server\n\
+                 DHCP_START=192.168.1\n"
+            )
+            .expect_json(json!({
+                "valid": false,
+                "errors": [
+                    {
+                        "key": "BLOCKING_ENABLED",
+                        "value": "yes",
+                        "error": "Invalid setting value"
+                    },
+                    {
+                        "key": "PIHOLE_DNS_2",
+                        "value": "not a server",
+                        "error": "Invalid setting value"
+                    },
+                    {
+                        "key": "DHCP_START",
+                        "value": "192.168.1",
+                        "error": "Invalid setting value"
+                    }
+                ],
+                "warnings": []
+            }))
+            .test();
+    }
+
+    /// A valid FTL config has no errors or warnings
+    #[test]
+    fn ftl_valid() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/ftl/validate")
+            .file(
+                PiholeFile::FtlConfig,
+                "# FTL settings\n\
+                 BLOCKINGMODE=NXDOMAIN\n\
+                 RESOLVE_IPV4=no\n\
+                 MAXDBDAYS=30\n"
+            )
+            .expect_json(json!({ "valid": true, "errors": [], "warnings": [] }))
+            .test();
+    }
+
+    /// Invalid FTL entries are errors, and unknown keys are warnings. Unknown
+    /// keys alone do not make the config invalid.
+    #[test]
+    fn ftl_invalid_and_unknown() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/ftl/validate")
+            .file(
+                PiholeFile::FtlConfig,
+                "BLOCKINGMODE=NXDOMAN\n\
+                 MAXDBDAY=30\n\
+                 FTLPORT=4711\n"
+            )
+            .expect_json(json!({
+                "valid": false,
+                "errors": [
+                    {
+                        "key": "BLOCKINGMODE",
+                        "value": "NXDOMAN",
+                        "error": "Invalid setting value"
+                    }
+                ],
+                "warnings": [
+                    {
+                        "key": "MAXDBDAY",
+                        "value": "30",
+                        "warning": "Unknown setting"
+                    }
+                ]
+            }))
+            .test();
+    }
+}
@@ -10,9 +10,9 @@
 
 use crate::{
     env::Env,
-    routes::auth::User,
+    routes::{auth::User, settings::common::SettingsWriter},
     settings::{ConfigEntry, SetupVarsEntry},
-    util::{reply_data, reply_success, Error, ErrorKind, Reply}
+    util::{reply_data, Error, ErrorKind, Reply}
 };
 use rocket::State;
 use rocket_contrib::json::Json;
@@ -28,19 +28,27 @@ pub fn get_web(env: State<Env>) -> Reply {
     reply_data(settings)
 }
 
-/// Update web interface settings
-#[put("/settings/web", data = "<settings>")]
-pub fn put_web(_auth: User, env: State<Env>, settings: Json<WebSettings>) -> Reply {
+/// Update web interface settings. With `dry_run`, the changes are only
+/// reported.
+#[put("/settings/web?<dry_run>", data = "<settings>")]
+pub fn put_web(
+    _auth: User,
+    env: State<Env>,
+    settings: Json<WebSettings>,
+    dry_run: Option<bool>
+) -> Reply {
     let settings = settings.into_inner();
 
     if !settings.is_valid() {
         return Err(Error::from(ErrorKind::InvalidSettingValue));
     }
 
-    SetupVarsEntry::WebLayout.write(&settings.layout, &env)?;
-    SetupVarsEntry::WebLanguage.write(&settings.language, &env)?;
+    let mut writer = SettingsWriter::new(&env, dry_run);
 
-    reply_success()
+    writer.write(&SetupVarsEntry::WebLayout, &settings.layout)?;
+    writer.write(&SetupVarsEntry::WebLanguage, &settings.language)?;
+
+    writer.finish(|_| Ok(()))
 }
 
 #[derive(Serialize, Deserialize)]
@@ -0,0 +1,293 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// Active Clients Endpoint
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    env::Env,
+    ftl::{FtlClient, FtlMemory, FtlQuery, FtlStrings},
+    routes::{
+        auth::User,
+        stats::{
+            clients::{filter_ftl_clients, ClientParams},
+            common::anonymize_clients
+        }
+    },
+    util::{reply_timed, Error, Reply, RequestTimer}
+};
+use rocket::State;
+use std::{
+    collections::HashMap,
+    time::{SystemTime, UNIX_EPOCH}
+};
+
+/// The default window, in seconds, which a client must have queried in to be
+/// active. It matches the resolution of the over time data.
+const DEFAULT_WITHIN_SECONDS: u64 = 600;
+
+/// Represents a client which has recently made a query
+#[derive(Serialize)]
+#[cfg_attr(test, derive(Debug, PartialEq))]
+pub struct ActiveClientReply {
+    name: String,
+    ip: String,
+    /// The timestamp of the client's most recent query
+    last_seen: u64
+}
+
+/// Get the clients which have made a query in the last `within_seconds`
+/// seconds
+#[get("/stats/clients/active?<within_seconds>")]
+pub fn active_clients(
+    timer: RequestTimer,
+    _auth: User,
+    ftl_memory: State<FtlMemory>,
+    env: State<Env>,
+    within_seconds: Option<u64>
+) -> Reply {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Current time is older than epoch")
+        .as_secs();
+
+    reply_timed(
+        get_active_clients(
+            &ftl_memory,
+            &env,
+            within_seconds.unwrap_or(DEFAULT_WITHIN_SECONDS),
+            now
+        ),
+        timer
+    )
+}
+
+/// Get the clients whose most recent query is within `within_seconds` of
+/// `now`, most recently seen first
+fn get_active_clients(
+    ftl_memory: &FtlMemory,
+    env: &Env,
+    within_seconds: u64,
+    now: u64
+) -> Result<Vec<ActiveClientReply>, Error> {
+    let lock = ftl_memory.lock()?;
+    let counters = ftl_memory.counters(&lock)?;
+    let strings = ftl_memory.strings(&lock)?;
+    let clients = ftl_memory.clients(&lock)?;
+    let queries = ftl_memory.queries(&lock)?;
+    let since = now.saturating_sub(within_seconds);
+
+    let last_seen = get_last_seen(
+        &queries,
+        counters.total_queries as usize,
+        &clients,
+        &strings
+    );
+
+    let mut active_clients: Vec<ActiveClientReply> =
+        filter_ftl_clients(ftl_memory, &lock, &clients, env, ClientParams::default())?
+            .into_iter()
+            .filter_map(|client| {
+                let client_last_seen = *last_seen.get(client.get_ip(&strings))?;
+
+                if client_last_seen < since {
+                    return None;
+                }
+
+                let reply = client.as_reply(&strings);
+
+                Some(ActiveClientReply {
+                    name: reply.name,
+                    ip: reply.ip,
+                    last_seen: client_last_seen
+                })
+            })
+            .collect();
+
+    active_clients.sort_by(|a, b| b.last_seen.cmp(&a.last_seen));
+    anonymize_clients(
+        active_clients
+            .iter_mut()
+            .map(|client| (&mut client.name, &mut client.ip)),
+        env
+    )?;
+
+    Ok(active_clients)
+}
+
+/// Get the time of each client's most recent query, by client IP. The queries
+/// are scanned once, so this is linear in the number of queries.
+pub fn get_last_seen<'a>(
+    queries: &[FtlQuery],
+    total_queries: usize,
+    clients: &[FtlClient],
+    strings: &'a FtlStrings
+) -> HashMap<&'a str, u64> {
+    // Find the time of each client's most recent query, by client ID
+    let mut last_seen: HashMap<usize, u64> = HashMap::new();
+
+    for query in queries.iter().take(total_queries) {
+        let timestamp = query.timestamp as u64;
+        let client_last_seen = last_seen.entry(query.client_id as usize).or_insert(0);
+
+        if timestamp > *client_last_seen {
+            *client_last_seen = timestamp;
+        }
+    }
+
+    // Map the times to the client IPs, so they can be matched with the
+    // filtered clients
+    last_seen
+        .into_iter()
+        .filter_map(|(client_id, timestamp)| {
+            clients
+                .get(client_id)
+                .map(|client| (client.get_ip(strings), timestamp))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{get_active_clients, ActiveClientReply};
+    use crate::{
+        env::{Config, Env, PiholeFile},
+        ftl::{FtlClient, FtlCounters, FtlMemory, FtlQuery, FtlSettings},
+        testing::{test_query, TestBuilder, TestEnvBuilder}
+    };
+    use std::collections::HashMap;
+
+    const NOW: u64 = 1_550_000_000;
+
+    /// Shorthand for making `FtlQuery` structs
+    fn query(client_id: i32, timestamp: u64) -> FtlQuery {
+        FtlQuery {
+            timestamp: timestamp as i64,
+            client_id,
+            ..test_query()
+        }
+    }
+
+    /// There are 4 clients, one of them hidden. The first client was seen a
+    /// minute ago, the second 20 minutes ago, and the third and the hidden
+    /// client were seen 5 minutes ago.
+    fn test_data() -> FtlMemory {
+        let mut strings = HashMap::new();
+        strings.insert(1, "10.1.1.1".to_owned());
+        strings.insert(2, "client1".to_owned());
+        strings.insert(3, "10.1.1.2".to_owned());
+        strings.insert(4, "10.1.1.3".to_owned());
+        strings.insert(5, "0.0.0.0".to_owned());
+
+        FtlMemory::Test {
+            clients: vec![
+                FtlClient::new(2, 0, 1, Some(2)),
+                FtlClient::new(1, 0, 3, None),
+                FtlClient::new(1, 0, 4, None),
+                FtlClient::new(1, 0, 5, None),
+            ],
+            domains: Vec::new(),
+            over_time: Vec::new(),
+            strings,
+            upstreams: Vec::new(),
+            queries: vec![
+                query(1, NOW - 1200),
+                query(0, NOW - 3600),
+                query(2, NOW - 300),
+                query(3, NOW - 300),
+                query(0, NOW - 60),
+            ],
+            counters: FtlCounters {
+                total_clients: 4,
+                total_queries: 5,
+                ..FtlCounters::default()
+            },
+            settings: FtlSettings::default()
+        }
+    }
+
+    /// Get the active clients using the environment files
+    fn active_clients(env_builder: TestEnvBuilder, within_seconds: u64) -> Vec<ActiveClientReply> {
+        let env = Env::Test(Config::default(), env_builder.build());
+
+        get_active_clients(&test_data(), &env, within_seconds, NOW).unwrap()
+    }
+
+    /// Only clients seen in the window are active, and they are sorted by their
+    /// last query. Hidden clients are not shown.
+    #[test]
+    fn default_window() {
+        assert_eq!(
+            active_clients(TestEnvBuilder::new(), 600),
+            vec![
+                ActiveClientReply {
+                    name: "client1".to_owned(),
+                    ip: "10.1.1.1".to_owned(),
+                    last_seen: NOW - 60
+                },
+                ActiveClientReply {
+                    name: "".to_owned(),
+                    ip: "10.1.1.3".to_owned(),
+                    last_seen: NOW - 300
+                },
+            ]
+        );
+    }
+
+    /// A larger window includes clients seen longer ago
+    #[test]
+    fn larger_window() {
+        assert_eq!(
+            active_clients(TestEnvBuilder::new(), 1800)
+                .into_iter()
+                .map(|client| client.ip)
+                .collect::<Vec<String>>(),
+            vec!["10.1.1.1", "10.1.1.3", "10.1.1.2"]
+        );
+    }
+
+    /// Excluded clients are not shown
+    #[test]
+    fn excluded_clients() {
+        assert_eq!(
+            active_clients(
+                TestEnvBuilder::new().file(PiholeFile::SetupVars, "API_EXCLUDE_CLIENTS=client1"),
+                600
+            ),
+            vec![ActiveClientReply {
+                name: "".to_owned(),
+                ip: "10.1.1.3".to_owned(),
+                last_seen: NOW - 300
+            }]
+        );
+    }
+
+    /// Privacy level 2 does not show any clients
+    #[test]
+    fn privacy_hides_clients() {
+        assert_eq!(
+            active_clients(
+                TestEnvBuilder::new().file(PiholeFile::FtlConfig, "PRIVACYLEVEL=2"),
+                600
+            ),
+            Vec::new()
+        );
+    }
+
+    /// The window is relative to the current time, so the old test queries are
+    /// not active
+    #[test]
+    fn endpoint() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/clients/active?within_seconds=60")
+            .ftl_memory(test_data())
+            .expect_json(json!({
+                "data": []
+            }))
+            .test();
+    }
+}
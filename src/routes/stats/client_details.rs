@@ -0,0 +1,191 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// Client Details Endpoint
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    env::Env,
+    ftl::FtlMemory,
+    routes::{
+        auth::User,
+        stats::common::{anonymize_clients, check_client_visible}
+    },
+    util::{reply_timed, Error, ErrorKind, Reply, RequestTimer}
+};
+use rocket::State;
+use std::iter;
+
+/// Get the details of a client by its ID (its index in the client array)
+#[get("/stats/client/<id>")]
+pub fn client_details(
+    timer: RequestTimer,
+    _auth: User,
+    ftl_memory: State<FtlMemory>,
+    env: State<Env>,
+    id: usize
+) -> Reply {
+    reply_timed(client_details_impl(&ftl_memory, &env, id), timer)
+}
+
+/// Get the details of a client
+fn client_details_impl(
+    ftl_memory: &FtlMemory,
+    env: &Env,
+    id: usize
+) -> Result<ClientDetailsReply, Error> {
+    let lock = ftl_memory.lock()?;
+    let counters = ftl_memory.counters(&lock)?;
+    let strings = ftl_memory.strings(&lock)?;
+    let clients = ftl_memory.clients(&lock)?;
+
+    // FTL allocates more clients than it uses
+    let client = clients
+        .iter()
+        .take(counters.total_clients as usize)
+        .nth(id)
+        .ok_or_else(|| Error::from(ErrorKind::NotFound))?;
+
+    check_client_visible(client, &strings, env)?;
+
+    let mut name = client.get_name(&strings).unwrap_or_default().to_owned();
+    let mut ip = client.get_ip(&strings).to_owned();
+    anonymize_clients(iter::once((&mut name, &mut ip)), env)?;
+
+    Ok(ClientDetailsReply {
+        id,
+        name,
+        ip,
+        mac: client.get_mac(&strings).map(str::to_owned),
+        total_queries: client.query_count as usize,
+        blocked_queries: client.blocked_count as usize
+    })
+}
+
+/// Represents the reply structure for a client's details
+#[derive(Serialize)]
+#[cfg_attr(test, derive(Debug, PartialEq))]
+pub struct ClientDetailsReply {
+    pub id: usize,
+    pub name: String,
+    pub ip: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mac: Option<String>,
+    pub total_queries: usize,
+    pub blocked_queries: usize
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        env::PiholeFile,
+        ftl::{FtlClient, FtlCounters, FtlMemory, FtlSettings},
+        testing::TestBuilder
+    };
+    use rocket::http::Status;
+    use std::collections::HashMap;
+
+    /// There are 3 clients, one of which is hidden. FTL allocated space for a
+    /// fourth client.
+    fn test_data() -> FtlMemory {
+        let mut strings = HashMap::new();
+        strings.insert(1, "10.1.1.1".to_owned());
+        strings.insert(2, "client1".to_owned());
+        strings.insert(3, "10.1.1.2".to_owned());
+        strings.insert(4, "0.0.0.0".to_owned());
+
+        FtlMemory::Test {
+            clients: vec![
+                FtlClient::new(4, 1, 1, Some(2)),
+                FtlClient::new(2, 0, 3, None),
+                FtlClient::new(1, 0, 4, None),
+                FtlClient::default(),
+            ],
+            domains: Vec::new(),
+            over_time: Vec::new(),
+            strings,
+            upstreams: Vec::new(),
+            queries: Vec::new(),
+            counters: FtlCounters {
+                total_clients: 3,
+                ..FtlCounters::default()
+            },
+            settings: FtlSettings::default()
+        }
+    }
+
+    /// The client's details are returned. The MAC address is left out, since
+    /// FTL does not provide it.
+    #[test]
+    fn details() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/client/0")
+            .ftl_memory(test_data())
+            .expect_json(json!({
+                "data": {
+                    "id": 0,
+                    "name": "client1",
+                    "ip": "10.1.1.1",
+                    "total_queries": 4,
+                    "blocked_queries": 1
+                }
+            }))
+            .test();
+    }
+
+    /// IDs past the clients in use are not found
+    #[test]
+    fn unused_id() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/client/3")
+            .ftl_memory(test_data())
+            .expect_status(Status::NotFound)
+            .expect_json(json!({
+                "error": {
+                    "key": "not_found",
+                    "message": "Not found",
+                    "data": None::<()>
+                }
+            }))
+            .test();
+    }
+
+    /// Hidden clients are not found
+    #[test]
+    fn hidden_client() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/client/2")
+            .ftl_memory(test_data())
+            .expect_status(Status::NotFound)
+            .expect_json(json!({
+                "error": {
+                    "key": "not_found",
+                    "message": "Not found",
+                    "data": None::<()>
+                }
+            }))
+            .test();
+    }
+
+    /// Excluded clients are not found
+    #[test]
+    fn excluded_client() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/client/1")
+            .ftl_memory(test_data())
+            .file(PiholeFile::SetupVars, "API_EXCLUDE_CLIENTS=10.1.1.2")
+            .expect_status(Status::NotFound)
+            .expect_json(json!({
+                "error": {
+                    "key": "not_found",
+                    "message": "Not found",
+                    "data": None::<()>
+                }
+            }))
+            .test();
+    }
+}
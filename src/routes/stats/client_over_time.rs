@@ -0,0 +1,222 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// Client Query History Over Time Endpoint
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    env::Env,
+    ftl::{FtlMemory, OVERTIME_INTERVAL},
+    routes::{
+        auth::User,
+        stats::{
+            common::{check_interval, find_client_id},
+            over_time_history::{group_queries_over_time, OverTimeItem}
+        }
+    },
+    util::{reply_timed, Error, Reply, RequestTimer}
+};
+use rocket::State;
+
+/// Get the query history of a single client over time (separated into
+/// blocked and not blocked). The client is identified by its IP address,
+/// hostname, or ID. The queries are grouped into slots of `interval` seconds,
+/// which defaults to the size of FTL's overTime slots.
+#[get("/stats/client/<client>/over_time?<interval>")]
+pub fn client_over_time(
+    timer: RequestTimer,
+    _auth: User,
+    ftl_memory: State<FtlMemory>,
+    env: State<Env>,
+    client: String,
+    interval: Option<usize>
+) -> Reply {
+    reply_timed(
+        client_over_time_impl(
+            &ftl_memory,
+            &env,
+            &client,
+            interval.unwrap_or(OVERTIME_INTERVAL)
+        ),
+        timer
+    )
+}
+
+/// Get the query history of a single client in slots of `interval` seconds.
+/// The slots are aligned the same way as the global query history slots.
+fn client_over_time_impl(
+    ftl_memory: &FtlMemory,
+    env: &Env,
+    client_identifier: &str,
+    interval: usize
+) -> Result<Vec<OverTimeItem>, Error> {
+    check_interval(interval)?;
+
+    let lock = ftl_memory.lock()?;
+    let counters = ftl_memory.counters(&lock)?;
+    let strings = ftl_memory.strings(&lock)?;
+    let clients = ftl_memory.clients(&lock)?;
+    let queries = ftl_memory.queries(&lock)?;
+
+    let client_id = find_client_id(
+        &clients,
+        counters.total_clients as usize,
+        &strings,
+        env,
+        client_identifier
+    )?;
+
+    Ok(group_queries_over_time(
+        queries
+            .iter()
+            .take(counters.total_queries as usize)
+            .filter(|query| query.client_id as usize == client_id),
+        interval as u64
+    ))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        env::PiholeFile,
+        ftl::{FtlClient, FtlCounters, FtlMemory, FtlQuery, FtlQueryStatus, FtlSettings},
+        testing::{test_query, TestBuilder}
+    };
+    use rocket::http::Status;
+    use std::collections::HashMap;
+
+    /// Shorthand for making `FtlQuery` structs
+    fn query(timestamp: i64, status: FtlQueryStatus, client_id: i32) -> FtlQuery {
+        FtlQuery {
+            timestamp,
+            client_id,
+            status,
+            ..test_query()
+        }
+    }
+
+    /// There are 3 clients, one of which is hidden, and 6 queries
+    fn test_data() -> FtlMemory {
+        let mut strings = HashMap::new();
+        strings.insert(1, "10.1.1.1".to_owned());
+        strings.insert(2, "client1".to_owned());
+        strings.insert(3, "10.1.1.2".to_owned());
+        strings.insert(4, "0.0.0.0".to_owned());
+
+        FtlMemory::Test {
+            clients: vec![
+                FtlClient::new(4, 0, 1, Some(2)),
+                FtlClient::new(1, 0, 3, None),
+                FtlClient::new(1, 0, 4, None),
+            ],
+            domains: Vec::new(),
+            over_time: Vec::new(),
+            strings,
+            upstreams: Vec::new(),
+            queries: vec![
+                query(1000, FtlQueryStatus::Forward, 0),
+                query(1100, FtlQueryStatus::Gravity, 0),
+                query(1150, FtlQueryStatus::Forward, 1),
+                query(2500, FtlQueryStatus::Cache, 0),
+                query(4000, FtlQueryStatus::Forward, 2),
+                query(4100, FtlQueryStatus::Unknown, 0),
+            ],
+            counters: FtlCounters {
+                total_clients: 3,
+                total_queries: 6,
+                ..FtlCounters::default()
+            },
+            settings: FtlSettings::default()
+        }
+    }
+
+    /// Only the client's queries are counted, in FTL's overTime slots by
+    /// default
+    #[test]
+    fn default_interval() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/client/client1/over_time")
+            .ftl_memory(test_data())
+            .expect_json(json!({
+                "data": [
+                    { "timestamp": 900,  "total_queries": 2, "blocked_queries": 1 },
+                    { "timestamp": 1500, "total_queries": 0, "blocked_queries": 0 },
+                    { "timestamp": 2100, "total_queries": 0, "blocked_queries": 0 },
+                    { "timestamp": 2700, "total_queries": 1, "blocked_queries": 0 }
+                ]
+            }))
+            .test();
+    }
+
+    /// The queries are grouped into slots of the given interval
+    #[test]
+    fn interval() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/client/10.1.1.1/over_time?interval=1200")
+            .ftl_memory(test_data())
+            .expect_json(json!({
+                "data": [
+                    { "timestamp": 600,  "total_queries": 2, "blocked_queries": 1 },
+                    { "timestamp": 1800, "total_queries": 0, "blocked_queries": 0 },
+                    { "timestamp": 3000, "total_queries": 1, "blocked_queries": 0 }
+                ]
+            }))
+            .test();
+    }
+
+    /// An interval which does not evenly divide a day is a bad request
+    #[test]
+    fn invalid_interval() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/client/10.1.1.1/over_time?interval=7")
+            .ftl_memory(test_data())
+            .expect_status(Status::BadRequest)
+            .expect_json(json!({
+                "error": {
+                    "key": "bad_request",
+                    "message": "Bad request",
+                    "data": None::<()>
+                }
+            }))
+            .test();
+    }
+
+    /// Hidden clients are not found
+    #[test]
+    fn hidden_client() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/client/0.0.0.0/over_time")
+            .ftl_memory(test_data())
+            .expect_status(Status::NotFound)
+            .expect_json(json!({
+                "error": {
+                    "key": "not_found",
+                    "message": "Not found",
+                    "data": None::<()>
+                }
+            }))
+            .test();
+    }
+
+    /// Excluded clients are not found
+    #[test]
+    fn excluded_client() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/client/10.1.1.1/over_time")
+            .ftl_memory(test_data())
+            .file(PiholeFile::SetupVars, "API_EXCLUDE_CLIENTS=client1")
+            .expect_status(Status::NotFound)
+            .expect_json(json!({
+                "error": {
+                    "key": "not_found",
+                    "message": "Not found",
+                    "data": None::<()>
+                }
+            }))
+            .test();
+    }
+}
@@ -0,0 +1,282 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// Client Query Types Endpoint
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    env::Env,
+    ftl::{FtlMemory, FtlQueryType},
+    routes::{
+        auth::User,
+        stats::{common::find_client_id, query_types::QueryTypeReply}
+    },
+    util::{reply_timed, Error, Reply, RequestTimer}
+};
+use rocket::State;
+use std::collections::HashMap;
+
+/// Get the query types of a single client. The client is identified by its IP
+/// address, hostname, or ID.
+#[get("/stats/client/<client>/query_types")]
+pub fn client_query_types(
+    timer: RequestTimer,
+    _auth: User,
+    ftl_memory: State<FtlMemory>,
+    env: State<Env>,
+    client: String
+) -> Reply {
+    reply_timed(client_query_types_impl(&ftl_memory, &env, &client), timer)
+}
+
+/// Get the query types of a single client
+fn client_query_types_impl(
+    ftl_memory: &FtlMemory,
+    env: &Env,
+    client_identifier: &str
+) -> Result<ClientQueryTypesReply, Error> {
+    let lock = ftl_memory.lock()?;
+    let counters = ftl_memory.counters(&lock)?;
+    let strings = ftl_memory.strings(&lock)?;
+    let clients = ftl_memory.clients(&lock)?;
+    let queries = ftl_memory.queries(&lock)?;
+
+    let client_id = find_client_id(
+        &clients,
+        counters.total_clients as usize,
+        &strings,
+        env,
+        client_identifier
+    )?;
+
+    // Count the client's queries by query type
+    let mut counts: HashMap<FtlQueryType, usize> = HashMap::new();
+    let mut total_queries = 0;
+
+    for query in queries
+        .iter()
+        .take(counters.total_queries as usize)
+        .filter(|query| query.client_id as usize == client_id)
+    {
+        *counts.entry(query.query_type).or_insert(0) += 1;
+        total_queries += 1;
+    }
+
+    let query_types = FtlQueryType::variants()
+        .iter()
+        .map(|variant| QueryTypeReply {
+            name: variant.get_name(),
+            count: counts.get(variant).cloned().unwrap_or(0)
+        })
+        .collect();
+
+    Ok(ClientQueryTypesReply {
+        query_types,
+        total_queries
+    })
+}
+
+/// Represents the reply structure for returning a client's query type data
+#[derive(Serialize)]
+#[cfg_attr(test, derive(Debug, PartialEq))]
+pub struct ClientQueryTypesReply {
+    pub query_types: Vec<QueryTypeReply>,
+    pub total_queries: usize
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        env::PiholeFile,
+        ftl::{FtlClient, FtlCounters, FtlMemory, FtlQuery, FtlQueryType, FtlSettings},
+        testing::{test_query, TestBuilder}
+    };
+    use rocket::http::Status;
+    use std::collections::HashMap;
+
+    /// Shorthand for making `FtlQuery` structs
+    fn query(query_type: FtlQueryType, client_id: i32) -> FtlQuery {
+        FtlQuery {
+            client_id,
+            query_type,
+            ..test_query()
+        }
+    }
+
+    /// There are 3 clients, one of which is hidden, and 6 queries
+    fn test_data() -> FtlMemory {
+        let mut strings = HashMap::new();
+        strings.insert(1, "10.1.1.1".to_owned());
+        strings.insert(2, "client1".to_owned());
+        strings.insert(3, "10.1.1.2".to_owned());
+        strings.insert(4, "0.0.0.0".to_owned());
+
+        FtlMemory::Test {
+            clients: vec![
+                FtlClient::new(4, 0, 1, Some(2)),
+                FtlClient::new(1, 0, 3, None),
+                FtlClient::new(1, 0, 4, None),
+            ],
+            domains: Vec::new(),
+            over_time: Vec::new(),
+            strings,
+            upstreams: Vec::new(),
+            queries: vec![
+                query(FtlQueryType::A, 0),
+                query(FtlQueryType::A, 0),
+                query(FtlQueryType::AAAA, 0),
+                query(FtlQueryType::PTR, 0),
+                query(FtlQueryType::A, 1),
+                query(FtlQueryType::TXT, 2),
+            ],
+            counters: FtlCounters {
+                total_clients: 3,
+                total_queries: 6,
+                ..FtlCounters::default()
+            },
+            settings: FtlSettings::default()
+        }
+    }
+
+    /// The client's queries are counted by query type
+    #[test]
+    fn by_ip() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/client/10.1.1.1/query_types")
+            .ftl_memory(test_data())
+            .expect_json(json!({
+                "data": {
+                    "query_types": [
+                        { "name": "A",    "count": 2 },
+                        { "name": "AAAA", "count": 1 },
+                        { "name": "ANY",  "count": 0 },
+                        { "name": "SRV",  "count": 0 },
+                        { "name": "SOA",  "count": 0 },
+                        { "name": "PTR",  "count": 1 },
+                        { "name": "TXT",  "count": 0 }
+                    ],
+                    "total_queries": 4
+                }
+            }))
+            .test();
+    }
+
+    /// The client can be identified by its hostname
+    #[test]
+    fn by_name() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/client/client1/query_types")
+            .ftl_memory(test_data())
+            .expect_json(json!({
+                "data": {
+                    "query_types": [
+                        { "name": "A",    "count": 2 },
+                        { "name": "AAAA", "count": 1 },
+                        { "name": "ANY",  "count": 0 },
+                        { "name": "SRV",  "count": 0 },
+                        { "name": "SOA",  "count": 0 },
+                        { "name": "PTR",  "count": 1 },
+                        { "name": "TXT",  "count": 0 }
+                    ],
+                    "total_queries": 4
+                }
+            }))
+            .test();
+    }
+
+    /// The client can be identified by its ID
+    #[test]
+    fn by_id() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/client/1/query_types")
+            .ftl_memory(test_data())
+            .expect_json(json!({
+                "data": {
+                    "query_types": [
+                        { "name": "A",    "count": 1 },
+                        { "name": "AAAA", "count": 0 },
+                        { "name": "ANY",  "count": 0 },
+                        { "name": "SRV",  "count": 0 },
+                        { "name": "SOA",  "count": 0 },
+                        { "name": "PTR",  "count": 0 },
+                        { "name": "TXT",  "count": 0 }
+                    ],
+                    "total_queries": 1
+                }
+            }))
+            .test();
+    }
+
+    /// IDs past the clients in use are not found
+    #[test]
+    fn unused_id() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/client/3/query_types")
+            .ftl_memory(test_data())
+            .expect_status(Status::NotFound)
+            .expect_json(json!({
+                "error": {
+                    "key": "not_found",
+                    "message": "Not found",
+                    "data": None::<()>
+                }
+            }))
+            .test();
+    }
+
+    /// Unknown clients are not found
+    #[test]
+    fn unknown_client() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/client/10.1.1.9/query_types")
+            .ftl_memory(test_data())
+            .expect_status(Status::NotFound)
+            .expect_json(json!({
+                "error": {
+                    "key": "not_found",
+                    "message": "Not found",
+                    "data": None::<()>
+                }
+            }))
+            .test();
+    }
+
+    /// Hidden clients are not found
+    #[test]
+    fn hidden_client() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/client/0.0.0.0/query_types")
+            .ftl_memory(test_data())
+            .expect_status(Status::NotFound)
+            .expect_json(json!({
+                "error": {
+                    "key": "not_found",
+                    "message": "Not found",
+                    "data": None::<()>
+                }
+            }))
+            .test();
+    }
+
+    /// Excluded clients are not found
+    #[test]
+    fn excluded_client() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/client/10.1.1.1/query_types")
+            .ftl_memory(test_data())
+            .file(PiholeFile::SetupVars, "API_EXCLUDE_CLIENTS=client1")
+            .expect_status(Status::NotFound)
+            .expect_json(json!({
+                "error": {
+                    "key": "not_found",
+                    "message": "Not found",
+                    "data": None::<()>
+                }
+            }))
+            .test();
+    }
+}
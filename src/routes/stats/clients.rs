@@ -11,34 +11,87 @@
 use crate::{
     env::Env,
     ftl::{ClientReply, FtlClient, FtlMemory, ShmLockGuard},
+    reverse_dns::ReverseDnsCache,
     routes::{
         auth::User,
-        stats::common::{remove_excluded_clients, remove_hidden_clients}
+        stats::common::{anonymize_clients, remove_excluded_clients, remove_hidden_clients}
     },
     settings::{ConfigEntry, FtlConfEntry, FtlPrivacyLevel},
-    util::{reply_result, Error, Reply}
+    util::{reply_timed, Error, Reply, RequestTimer}
 };
 use rocket::{request::Form, State};
+use std::net::IpAddr;
 
 /// Get client information
 #[get("/stats/clients?<params..>")]
 pub fn clients(
+    timer: RequestTimer,
     _auth: User,
     ftl_memory: State<FtlMemory>,
     env: State<Env>,
+    reverse_dns: State<ReverseDnsCache>,
     params: Form<ClientParams>
 ) -> Reply {
-    reply_result(get_clients(&ftl_memory, &env, params.into_inner()))
+    reply_timed(
+        get_clients(&ftl_memory, &env, &reverse_dns, params.into_inner()),
+        timer
+    )
 }
 
 /// The possible GET parameters for `/stats/clients`
 #[derive(FromForm, Default)]
 pub struct ClientParams {
-    inactive: Option<bool>
+    pub inactive: Option<bool>,
+    /// Look up the names of clients which do not have one with reverse DNS
+    pub resolve: Option<bool>
 }
 
 /// Get client data for API output according to the parameters
 fn get_clients(
+    ftl_memory: &FtlMemory,
+    env: &Env,
+    reverse_dns: &ReverseDnsCache,
+    params: ClientParams
+) -> Result<Vec<ClientReply>, Error> {
+    let resolve = params.resolve.unwrap_or(false);
+
+    // The shared memory lock is released before any reverse DNS lookups
+    let mut clients = read_clients(ftl_memory, env, params)?;
+
+    // Fill in the names of unnamed clients from reverse DNS. Clients whose
+    // lookup fails or times out keep only their IP.
+    if resolve {
+        let unnamed: Vec<IpAddr> = clients
+            .iter()
+            .filter(|client| client.name.is_empty())
+            .filter_map(|client| client.ip.parse().ok())
+            .collect();
+        let names = reverse_dns.resolve(&unnamed, env);
+
+        for client in clients.iter_mut().filter(|client| client.name.is_empty()) {
+            if let Some(name) = client
+                .ip
+                .parse::<IpAddr>()
+                .ok()
+                .and_then(|ip| names.get(&ip))
+            {
+                client.name = name.clone();
+            }
+        }
+    }
+
+    anonymize_clients(
+        clients
+            .iter_mut()
+            .map(|client| (&mut client.name, &mut client.ip)),
+        env
+    )?;
+
+    Ok(clients)
+}
+
+/// Read the clients from shared memory in the reply format
+fn read_clients(
     ftl_memory: &FtlMemory,
     env: &Env,
     params: ClientParams
@@ -47,12 +100,12 @@ fn get_clients(
     let strings = ftl_memory.strings(&lock)?;
     let clients = ftl_memory.clients(&lock)?;
 
-    Ok(
-        filter_ftl_clients(ftl_memory, &lock, &clients, env, params)?
-            .iter()
-            .map(|client| client.as_reply(&strings))
-            .collect::<Vec<ClientReply>>()
-    )
+    let clients = filter_ftl_clients(ftl_memory, &lock, &clients, env, params)?
+        .iter()
+        .map(|client| client.as_reply(&strings))
+        .collect();
+
+    Ok(clients)
 }
 
 /// Get FTL clients which are allowed to be used according to settings and
@@ -81,7 +134,7 @@ pub fn filter_ftl_clients<'a>(
         .collect();
 
     // Ignore hidden and excluded clients
-    remove_hidden_clients(&mut clients, &strings);
+    remove_hidden_clients(&mut clients, &strings, &env)?;
     remove_excluded_clients(&mut clients, &env, &strings)?;
 
     // Ignore inactive clients by default (retain active clients)
@@ -94,10 +147,12 @@ pub fn filter_ftl_clients<'a>(
 
 #[cfg(test)]
 mod test {
+    use super::{get_clients, ClientParams};
     use crate::{
-        env::PiholeFile,
+        env::{Config, Env, PiholeFile},
         ftl::{FtlClient, FtlCounters, FtlMemory, FtlSettings},
-        testing::TestBuilder
+        reverse_dns::ReverseDnsCache,
+        testing::{TestBuilder, TestEnvBuilder}
     };
     use std::collections::HashMap;
 
@@ -141,12 +196,33 @@ mod test {
         TestBuilder::new()
             .endpoint("/admin/api/stats/clients")
             .ftl_memory(test_data())
-            .expect_json(json!([
-                { "name": "client1", "ip": "10.1.1.1" },
-                { "name": "",        "ip": "10.1.1.2" },
-                { "name": "client3", "ip": "10.1.1.3" },
-                { "name": "",        "ip": "10.1.1.4" }
-            ]))
+            .expect_json(json!({
+                "data": [
+                    { "name": "client1", "ip": "10.1.1.1" },
+                    { "name": "",        "ip": "10.1.1.2" },
+                    { "name": "client3", "ip": "10.1.1.3" },
+                    { "name": "",        "ip": "10.1.1.4" }
+                ]
+            }))
+            .test();
+    }
+
+    /// Client IPs are masked and names are hashed when anonymization is
+    /// enabled
+    #[test]
+    fn anonymized_clients() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/clients")
+            .ftl_memory(test_data())
+            .file(PiholeFile::SetupVars, "API_ANONYMIZE_CLIENTS=true")
+            .expect_json(json!({
+                "data": [
+                    { "name": "6a2a1e2880507f1d", "ip": "10.1.1.0" },
+                    { "name": "",                 "ip": "10.1.1.0" },
+                    { "name": "6a2a1c2880507bb7", "ip": "10.1.1.0" },
+                    { "name": "",                 "ip": "10.1.1.0" }
+                ]
+            }))
             .test();
     }
 
@@ -157,7 +233,9 @@ mod test {
             .endpoint("/admin/api/stats/clients")
             .ftl_memory(test_data())
             .file(PiholeFile::FtlConfig, "PRIVACYLEVEL=2")
-            .expect_json(json!([]))
+            .expect_json(json!({
+                "data": []
+            }))
             .test();
     }
 
@@ -167,13 +245,15 @@ mod test {
         TestBuilder::new()
             .endpoint("/admin/api/stats/clients?inactive=true")
             .ftl_memory(test_data())
-            .expect_json(json!([
-                { "name": "client1", "ip": "10.1.1.1" },
-                { "name": "",        "ip": "10.1.1.2" },
-                { "name": "client3", "ip": "10.1.1.3" },
-                { "name": "",        "ip": "10.1.1.4" },
-                { "name": "",        "ip": "10.1.1.5" }
-            ]))
+            .expect_json(json!({
+                "data": [
+                    { "name": "client1", "ip": "10.1.1.1" },
+                    { "name": "",        "ip": "10.1.1.2" },
+                    { "name": "client3", "ip": "10.1.1.3" },
+                    { "name": "",        "ip": "10.1.1.4" },
+                    { "name": "",        "ip": "10.1.1.5" }
+                ]
+            }))
             .test();
     }
 
@@ -187,10 +267,34 @@ mod test {
                 PiholeFile::SetupVars,
                 "API_EXCLUDE_CLIENTS=client3,10.1.1.2"
             )
-            .expect_json(json!([
-                { "name": "client1", "ip": "10.1.1.1" },
-                { "name": "",        "ip": "10.1.1.4" }
-            ]))
+            .expect_json(json!({
+                "data": [
+                    { "name": "client1", "ip": "10.1.1.1" },
+                    { "name": "",        "ip": "10.1.1.4" }
+                ]
+            }))
             .test();
     }
+
+    /// Unnamed clients are named by reverse DNS when resolving. Clients whose
+    /// lookup failed keep only their IP.
+    #[test]
+    fn resolve() {
+        let env = Env::Test(Config::default(), TestEnvBuilder::new().build());
+        let reverse_dns = ReverseDnsCache::new();
+        reverse_dns.insert("10.1.1.2".parse().unwrap(), Some("laptop.lan"));
+        reverse_dns.insert("10.1.1.4".parse().unwrap(), None);
+
+        let params = ClientParams {
+            resolve: Some(true),
+            ..ClientParams::default()
+        };
+        let names: Vec<String> = get_clients(&test_data(), &env, &reverse_dns, params)
+            .unwrap()
+            .into_iter()
+            .map(|client| client.name)
+            .collect();
+
+        assert_eq!(names, vec!["client1", "laptop.lan", "client3", ""]);
+    }
 }
@@ -0,0 +1,197 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// Clients Last Seen Endpoint
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    env::Env,
+    ftl::FtlMemory,
+    routes::{
+        auth::User,
+        stats::{
+            active_clients::get_last_seen,
+            clients::{filter_ftl_clients, ClientParams},
+            common::anonymize_clients
+        }
+    },
+    util::{reply_timed, Error, Reply, RequestTimer}
+};
+use rocket::State;
+
+/// Represents a client and the time of its most recent query
+#[derive(Serialize)]
+pub struct LastSeenClientReply {
+    name: String,
+    ip: String,
+    /// The timestamp of the client's most recent query, or `None` if none of
+    /// its queries are still in memory
+    last_seen: Option<u64>
+}
+
+/// Get every client with the time of its most recent query
+#[get("/stats/clients/last_seen")]
+pub fn clients_last_seen(
+    timer: RequestTimer,
+    _auth: User,
+    ftl_memory: State<FtlMemory>,
+    env: State<Env>
+) -> Reply {
+    reply_timed(get_clients_last_seen(&ftl_memory, &env), timer)
+}
+
+/// Get the visible clients, including inactive ones, with the time of their
+/// most recent query. The clients seen longest ago are first.
+fn get_clients_last_seen(
+    ftl_memory: &FtlMemory,
+    env: &Env
+) -> Result<Vec<LastSeenClientReply>, Error> {
+    let lock = ftl_memory.lock()?;
+    let counters = ftl_memory.counters(&lock)?;
+    let strings = ftl_memory.strings(&lock)?;
+    let clients = ftl_memory.clients(&lock)?;
+    let queries = ftl_memory.queries(&lock)?;
+
+    let last_seen = get_last_seen(
+        &queries,
+        counters.total_queries as usize,
+        &clients,
+        &strings
+    );
+    let params = ClientParams {
+        inactive: Some(true),
+        ..ClientParams::default()
+    };
+
+    let mut clients: Vec<LastSeenClientReply> =
+        filter_ftl_clients(ftl_memory, &lock, &clients, env, params)?
+            .into_iter()
+            .map(|client| {
+                let reply = client.as_reply(&strings);
+
+                LastSeenClientReply {
+                    last_seen: last_seen.get(client.get_ip(&strings)).cloned(),
+                    name: reply.name,
+                    ip: reply.ip
+                }
+            })
+            .collect();
+
+    clients.sort_by(|a, b| a.last_seen.cmp(&b.last_seen));
+    anonymize_clients(
+        clients
+            .iter_mut()
+            .map(|client| (&mut client.name, &mut client.ip)),
+        env
+    )?;
+
+    Ok(clients)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        env::PiholeFile,
+        ftl::{FtlClient, FtlCounters, FtlMemory, FtlQuery, FtlSettings},
+        testing::{test_query, TestBuilder}
+    };
+    use std::collections::HashMap;
+
+    /// Shorthand for making `FtlQuery` structs
+    fn query(client_id: i32, timestamp: i64) -> FtlQuery {
+        FtlQuery {
+            timestamp,
+            client_id,
+            ..test_query()
+        }
+    }
+
+    /// There are 4 clients, one of them hidden. The third client has no
+    /// queries left in memory.
+    fn test_data() -> FtlMemory {
+        let mut strings = HashMap::new();
+        strings.insert(1, "10.1.1.1".to_owned());
+        strings.insert(2, "client1".to_owned());
+        strings.insert(3, "10.1.1.2".to_owned());
+        strings.insert(4, "10.1.1.3".to_owned());
+        strings.insert(5, "0.0.0.0".to_owned());
+
+        FtlMemory::Test {
+            clients: vec![
+                FtlClient::new(2, 0, 1, Some(2)),
+                FtlClient::new(1, 0, 3, None),
+                FtlClient::new(0, 0, 4, None),
+                FtlClient::new(1, 0, 5, None),
+            ],
+            domains: Vec::new(),
+            over_time: Vec::new(),
+            strings,
+            upstreams: Vec::new(),
+            queries: vec![
+                query(0, 1000),
+                query(1, 1200),
+                query(3, 1300),
+                query(0, 1100),
+            ],
+            counters: FtlCounters {
+                total_clients: 4,
+                total_queries: 4,
+                ..FtlCounters::default()
+            },
+            settings: FtlSettings::default()
+        }
+    }
+
+    /// Every visible client is listed with its most recent query time, seen
+    /// longest ago first
+    #[test]
+    fn last_seen() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/clients/last_seen")
+            .ftl_memory(test_data())
+            .expect_json(json!({
+                "data": [
+                    { "name": "", "ip": "10.1.1.3", "last_seen": None::<()> },
+                    { "name": "client1", "ip": "10.1.1.1", "last_seen": 1100 },
+                    { "name": "", "ip": "10.1.1.2", "last_seen": 1200 }
+                ]
+            }))
+            .test();
+    }
+
+    /// Excluded clients are not shown
+    #[test]
+    fn excluded_clients() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/clients/last_seen")
+            .ftl_memory(test_data())
+            .file(
+                PiholeFile::SetupVars,
+                "API_EXCLUDE_CLIENTS=client1,10.1.1.3"
+            )
+            .expect_json(json!({
+                "data": [{ "name": "", "ip": "10.1.1.2", "last_seen": 1200 }]
+            }))
+            .test();
+    }
+
+    /// The timestamps can be formatted as RFC 3339
+    #[test]
+    fn rfc3339_time_format() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/clients/last_seen?time_format=rfc3339")
+            .ftl_memory(test_data())
+            .file(
+                PiholeFile::SetupVars,
+                "API_EXCLUDE_CLIENTS=client1,10.1.1.3"
+            )
+            .expect_json(json!({
+                "data": [{ "name": "", "ip": "10.1.1.2", "last_seen": "1970-01-01T00:20:00Z" }]
+            }))
+            .test();
+    }
+}
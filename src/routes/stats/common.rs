@@ -9,13 +9,14 @@
 // Please see LICENSE file for your rights under this license.
 
 use crate::{
-    env::Env,
+    env::{Env, PiholeFile},
     ftl::{FtlClient, FtlDomain, FtlOverTime, FtlStrings, OVERTIME_SLOTS},
-    settings::{ConfigEntry, SetupVarsEntry},
-    util::Error
+    settings::{ConfigEntry, FtlConfEntry, FtlPrivacyLevel, SetupVarsEntry},
+    util::{Error, ErrorKind}
 };
 use std::{
-    collections::HashSet,
+    collections::{HashMap, HashSet},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
     time::{SystemTime, UNIX_EPOCH}
 };
 
@@ -72,7 +73,11 @@ pub fn remove_excluded_domains(
 
     if !excluded_domains.is_empty() {
         // Only retain domains which do not appear in the exclusion list
-        domains.retain(|domain| !excluded_domains.contains(&domain.get_domain(strings)));
+        domains.retain(|domain| {
+            let domain = domain.get_domain(strings).to_lowercase();
+
+            !excluded_domains.contains(&domain.as_str())
+        });
     }
 
     Ok(())
@@ -90,11 +95,80 @@ pub fn get_excluded_domains(env: &Env) -> Result<Vec<String>, Error> {
         .collect())
 }
 
+/// Get the hostnames of IP addresses from the custom DNS list, which uses the
+/// hosts file format (`IP hostname [aliases...]`). If an IP address appears
+/// more than once, the first hostname is used. If the list does not exist,
+/// there are no names.
+pub fn get_custom_dns_names(env: &Env) -> Result<HashMap<String, String>, Error> {
+    let mut names = HashMap::new();
+
+    if !env.file_exists(PiholeFile::CustomList) {
+        return Ok(names);
+    }
+
+    for line in env.read_file_lines(PiholeFile::CustomList)? {
+        // Ignore comments
+        let line = line.split('#').next().unwrap_or_default();
+        let mut split = line.split_whitespace();
+
+        if let (Some(ip), Some(hostname)) = (split.next(), split.next()) {
+            names
+                .entry(ip.to_owned())
+                .or_insert_with(|| hostname.to_owned());
+        }
+    }
+
+    Ok(names)
+}
+
 /// Remove clients from the `clients` vector if they are marked as hidden due
-/// to the privacy level.
-pub fn remove_hidden_clients(clients: &mut Vec<&FtlClient>, strings: &FtlStrings) {
+/// to the privacy level. If [`SetupVarsEntry::ApiHideLoopback`] is enabled,
+/// loopback clients and the Pi-hole host itself are removed as well.
+///
+/// [`SetupVarsEntry::ApiHideLoopback`]:
+/// ../../../settings/entries/enum.SetupVarsEntry.html#variant.ApiHideLoopback
+pub fn remove_hidden_clients(
+    clients: &mut Vec<&FtlClient>,
+    strings: &FtlStrings,
+    env: &Env
+) -> Result<(), Error> {
     let hidden_client_ip = get_hidden_client_ip();
     clients.retain(|client| client.get_ip(strings) != hidden_client_ip);
+
+    if SetupVarsEntry::ApiHideLoopback.is_true(env)? {
+        let host_ips = get_host_ips(env)?;
+
+        clients.retain(|client| {
+            client
+                .get_ip(strings)
+                .parse::<IpAddr>()
+                .map_or(true, |ip| !ip.is_loopback() && !host_ips.contains(&ip))
+        });
+    }
+
+    Ok(())
+}
+
+/// Get the IP addresses of the Pi-hole host, from
+/// [`SetupVarsEntry::Ipv4Address`] and [`SetupVarsEntry::Ipv6Address`]. The
+/// addresses may have a subnet mask, which is removed.
+///
+/// [`SetupVarsEntry::Ipv4Address`]:
+/// ../../../settings/entries/enum.SetupVarsEntry.html#variant.Ipv4Address
+/// [`SetupVarsEntry::Ipv6Address`]:
+/// ../../../settings/entries/enum.SetupVarsEntry.html#variant.Ipv6Address
+fn get_host_ips(env: &Env) -> Result<Vec<IpAddr>, Error> {
+    let mut host_ips = Vec::new();
+
+    for entry in &[SetupVarsEntry::Ipv4Address, SetupVarsEntry::Ipv6Address] {
+        let address = entry.read(env)?;
+
+        if let Some(Ok(ip)) = address.split('/').next().map(str::parse) {
+            host_ips.push(ip);
+        }
+    }
+
+    Ok(host_ips)
 }
 
 /// Get the hidden client IP address
@@ -114,6 +188,193 @@ pub fn get_hidden_domain() -> &'static str {
     "hidden"
 }
 
+/// Anonymize the clients if [`SetupVarsEntry::ApiAnonymizeClients`] is
+/// enabled. Each item is a `(name, ip)` pair from a reply. IP addresses are
+/// masked and names are replaced with a stable hash, so the same client still
+/// shows up the same way across replies.
+///
+/// [`SetupVarsEntry::ApiAnonymizeClients`]:
+/// ../../../settings/entries/enum.SetupVarsEntry.html#variant.
+/// ApiAnonymizeClients
+pub fn anonymize_clients<'a>(
+    clients: impl IntoIterator<Item = (&'a mut String, &'a mut String)>,
+    env: &Env
+) -> Result<(), Error> {
+    if !SetupVarsEntry::ApiAnonymizeClients.is_true(env)? {
+        return Ok(());
+    }
+
+    for (name, ip) in clients {
+        *ip = anonymize_ip(ip);
+
+        // Clients without a name are left without a name
+        if !name.is_empty() {
+            *name = anonymize_name(name);
+        }
+    }
+
+    Ok(())
+}
+
+/// Mask the IP address by zeroing the last octet of IPv4 addresses and the
+/// last 80 bits of IPv6 addresses. Values which are not IP addresses are
+/// returned unchanged.
+fn anonymize_ip(ip: &str) -> String {
+    match ip.parse() {
+        Ok(IpAddr::V4(ip)) => {
+            let octets = ip.octets();
+            Ipv4Addr::new(octets[0], octets[1], octets[2], 0).to_string()
+        }
+        Ok(IpAddr::V6(ip)) => {
+            let segments = ip.segments();
+            Ipv6Addr::new(segments[0], segments[1], segments[2], 0, 0, 0, 0, 0).to_string()
+        }
+        Err(_) => ip.to_owned()
+    }
+}
+
+/// Replace the name with its 64-bit FNV-1a hash. The hash is written out here
+/// instead of using the standard library's hasher, which may change between
+/// Rust releases, so a name is always replaced by the same hash.
+fn anonymize_name(name: &str) -> String {
+    const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+    let hash = name.bytes().fold(FNV_OFFSET_BASIS, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(FNV_PRIME)
+    });
+
+    format!("{:016x}", hash)
+}
+
+/// Find the ID (the index in the client array) of the client with the IP
+/// address or hostname, among the first `total_clients` clients. If no client
+/// has the IP address or hostname, a number is used as the client ID, the same
+/// as in `/stats/client/<id>`. When clients are private, or the client is
+/// hidden or excluded, it is treated as if it does not exist.
+pub fn find_client_id(
+    clients: &[FtlClient],
+    total_clients: usize,
+    strings: &FtlStrings,
+    env: &Env,
+    client_identifier: &str
+) -> Result<usize, Error> {
+    let client_id = clients
+        .iter()
+        .take(total_clients)
+        .position(|client| {
+            client.get_ip(strings) == client_identifier
+                || client.get_name(strings) == Some(client_identifier)
+        })
+        .or_else(|| {
+            client_identifier
+                .parse::<usize>()
+                .ok()
+                .filter(|&id| id < total_clients.min(clients.len()))
+        })
+        .ok_or_else(|| Error::from(ErrorKind::NotFound))?;
+
+    check_client_visible(&clients[client_id], strings, env)?;
+
+    Ok(client_id)
+}
+
+/// Check that the client's details can be shown. When clients are private,
+/// or the client is hidden or excluded, a not found error is returned as if
+/// the client does not exist.
+pub fn check_client_visible(
+    client: &FtlClient,
+    strings: &FtlStrings,
+    env: &Env
+) -> Result<(), Error> {
+    // Client details are not available when clients are private
+    if FtlConfEntry::PrivacyLevel.read_as::<FtlPrivacyLevel>(env)?
+        >= FtlPrivacyLevel::HideDomainsAndClients
+    {
+        return Err(Error::from(ErrorKind::NotFound));
+    }
+
+    // Hidden and excluded clients are treated as if they do not exist
+    let mut allowed_clients = vec![client];
+    remove_hidden_clients(&mut allowed_clients, strings, env)?;
+    remove_excluded_clients(&mut allowed_clients, env, strings)?;
+
+    if allowed_clients.is_empty() {
+        return Err(Error::from(ErrorKind::NotFound));
+    }
+
+    Ok(())
+}
+
+/// Limit the number of results to [`SetupVarsEntry::ApiMaxResults`].
+///
+/// [`SetupVarsEntry::ApiMaxResults`]:
+/// ../../../settings/entries/enum.SetupVarsEntry.html#variant.ApiMaxResults
+pub fn limit_results(limit: usize, env: &Env) -> Result<usize, Error> {
+    Ok(limit.min(SetupVarsEntry::ApiMaxResults.read_as::<usize>(env)?))
+}
+
+/// Check that an overTime interval (in seconds) is usable. The interval must
+/// be a positive multiple of FTL's 10 minute overTime slots, and evenly divide
+/// a day so that slots line up across days.
+pub fn check_interval(interval: usize) -> Result<(), Error> {
+    if interval == 0 || interval % 600 != 0 || 86400 % interval != 0 {
+        return Err(Error::from(ErrorKind::BadRequest));
+    }
+
+    Ok(())
+}
+
+/// Group timestamped items into slots of `interval` seconds. The slots are
+/// aligned to the interval, so slots made from different sets of items line
+/// up. Each slot's data starts as `empty()`, and `add` adds an item to it.
+///
+/// The slots are returned from the first to the last slot with items,
+/// including empty slots in between, along with their timestamps. The
+/// timestamps are centered in the slot interval.
+pub fn group_over_time<T, D>(
+    items: impl IntoIterator<Item = (u64, T)>,
+    interval: u64,
+    empty: impl Fn() -> D,
+    mut add: impl FnMut(&mut D, T)
+) -> Vec<(u64, D)> {
+    // Add each item to its slot, by slot start time
+    let mut slots: HashMap<u64, D> = HashMap::new();
+
+    for (timestamp, item) in items {
+        add(
+            slots
+                .entry(timestamp - timestamp % interval)
+                .or_insert_with(&empty),
+            item
+        );
+    }
+
+    let (first, last) = match (slots.keys().min(), slots.keys().max()) {
+        (Some(&first), Some(&last)) => (first, last),
+        _ => return Vec::new()
+    };
+
+    (first..=last)
+        .step_by(interval as usize)
+        .map(|start| {
+            let data = slots.remove(&start).unwrap_or_else(&empty);
+
+            (start + interval / 2, data)
+        })
+        .collect()
+}
+
+/// Check that a time range is not inverted. Both ends are inclusive, so a
+/// range can start and end at the same time.
+pub fn check_time_range(from: u64, until: u64) -> Result<(), Error> {
+    if from > until {
+        return Err(Error::from(ErrorKind::BadRequest));
+    }
+
+    Ok(())
+}
+
 /// Get the current overTime slot index, based on the current time. If all of
 /// the slots are in the past, then the last slot index will be returned.
 pub fn get_current_over_time_slot(over_time: &[FtlOverTime]) -> usize {
@@ -140,8 +401,9 @@ pub fn get_current_over_time_slot(over_time: &[FtlOverTime]) -> usize {
 #[cfg(test)]
 mod tests {
     use super::{
-        remove_excluded_clients, remove_excluded_domains, remove_hidden_clients,
-        remove_hidden_domains
+        anonymize_clients, anonymize_ip, anonymize_name, check_interval, get_custom_dns_names,
+        group_over_time, limit_results, remove_excluded_clients, remove_excluded_domains,
+        remove_hidden_clients, remove_hidden_domains
     };
     use crate::{
         env::{Config, Env, PiholeFile},
@@ -150,7 +412,7 @@ mod tests {
         },
         testing::TestEnvBuilder
     };
-    use std::collections::HashMap;
+    use std::{collections::HashMap, fs};
 
     /// There are 4 clients, one hidden
     fn test_data() -> FtlMemory {
@@ -247,6 +509,38 @@ mod tests {
         );
     }
 
+    /// Domains are excluded regardless of case
+    #[test]
+    fn excluded_domains_case_insensitive() {
+        let ftl_memory = test_data();
+
+        let env = Env::Test(
+            Config::default(),
+            TestEnvBuilder::new()
+                .file(PiholeFile::SetupVars, "API_EXCLUDE_DOMAINS=Example.COM")
+                .build()
+        );
+
+        let lock_guard = ShmLockGuard::Test;
+        let domains = ftl_memory.domains(&lock_guard).unwrap();
+        let mut domains = domains.iter().collect();
+
+        remove_excluded_domains(
+            &mut domains,
+            &env,
+            &ftl_memory.strings(&lock_guard).unwrap()
+        )
+        .unwrap();
+
+        assert_eq!(
+            domains,
+            vec![
+                &FtlDomain::new(0, 0, 6, FtlRegexMatch::Unknown),
+                &FtlDomain::new(0, 0, 7, FtlRegexMatch::Unknown),
+            ]
+        );
+    }
+
     /// Clients marked as hidden are removed
     #[test]
     fn hidden_clients() {
@@ -258,11 +552,66 @@ mod tests {
         let mut clients_clone = clients.clone();
         clients_clone.remove(2);
 
-        remove_hidden_clients(&mut clients, &ftl_memory.strings(&lock_guard).unwrap());
+        remove_hidden_clients(
+            &mut clients,
+            &ftl_memory.strings(&lock_guard).unwrap(),
+            &Env::Test(Config::default(), HashMap::new())
+        )
+        .unwrap();
 
         assert_eq!(clients, clients_clone);
     }
 
+    /// Loopback clients and the Pi-hole host are removed when
+    /// `API_HIDE_LOOPBACK` is enabled
+    #[test]
+    fn hidden_loopback_clients() {
+        let mut strings = HashMap::new();
+        strings.insert(1, "127.0.0.1".to_owned());
+        strings.insert(2, "::1".to_owned());
+        strings.insert(3, "10.1.1.5".to_owned());
+        strings.insert(4, "10.1.1.1".to_owned());
+
+        let ftl_memory = FtlMemory::Test {
+            clients: vec![
+                FtlClient::new(1, 0, 1, None),
+                FtlClient::new(1, 0, 2, None),
+                FtlClient::new(1, 0, 3, None),
+                FtlClient::new(1, 0, 4, None),
+            ],
+            domains: Vec::new(),
+            over_time: Vec::new(),
+            strings,
+            upstreams: Vec::new(),
+            queries: Vec::new(),
+            counters: FtlCounters::default(),
+            settings: FtlSettings::default()
+        };
+        let env = Env::Test(
+            Config::default(),
+            TestEnvBuilder::new()
+                .file(
+                    PiholeFile::SetupVars,
+                    "API_HIDE_LOOPBACK=true\nIPV4_ADDRESS=10.1.1.5/24"
+                )
+                .build()
+        );
+        let lock_guard = ShmLockGuard::Test;
+
+        let clients = ftl_memory.clients(&lock_guard).unwrap();
+        let mut clients: Vec<&FtlClient> = clients.iter().collect();
+        let expected_clients = vec![clients[3]];
+
+        remove_hidden_clients(
+            &mut clients,
+            &ftl_memory.strings(&lock_guard).unwrap(),
+            &env
+        )
+        .unwrap();
+
+        assert_eq!(clients, expected_clients);
+    }
+
     /// Domains marked as hidden are removed
     #[test]
     fn hidden_domains() {
@@ -278,4 +627,165 @@ mod tests {
 
         assert_eq!(domains, domains_clone);
     }
+
+    /// Limits below the maximum are not changed
+    #[test]
+    fn limit_below_max() {
+        let env = Env::Test(
+            Config::default(),
+            TestEnvBuilder::new()
+                .file(PiholeFile::SetupVars, "API_MAX_RESULTS=50")
+                .build()
+        );
+
+        assert_eq!(limit_results(20, &env).unwrap(), 20);
+    }
+
+    /// Limits above the maximum are clamped to the maximum
+    #[test]
+    fn limit_above_max() {
+        let env = Env::Test(
+            Config::default(),
+            TestEnvBuilder::new()
+                .file(PiholeFile::SetupVars, "API_MAX_RESULTS=50")
+                .build()
+        );
+
+        assert_eq!(limit_results(200, &env).unwrap(), 50);
+    }
+
+    /// The default maximum is 10000
+    #[test]
+    fn limit_default_max() {
+        let env = Env::Test(
+            Config::default(),
+            TestEnvBuilder::new()
+                .file(PiholeFile::SetupVars, "")
+                .build()
+        );
+
+        assert_eq!(limit_results(20000, &env).unwrap(), 10000);
+    }
+
+    /// Multiples of 10 minutes which evenly divide a day are valid
+    #[test]
+    fn interval_valid() {
+        assert!(check_interval(600).is_ok());
+        assert!(check_interval(86400).is_ok());
+    }
+
+    /// Zero and intervals which do not evenly divide a day are invalid
+    #[test]
+    fn interval_invalid() {
+        assert!(check_interval(0).is_err());
+        assert!(check_interval(7).is_err());
+    }
+
+    /// Intervals shorter than or not a multiple of 10 minutes are invalid,
+    /// even if they evenly divide a day
+    #[test]
+    fn interval_not_multiple() {
+        assert!(check_interval(300).is_err());
+        assert!(check_interval(900).is_err());
+    }
+
+    /// IPv4 addresses have their last octet zeroed, and IPv6 addresses have
+    /// their last 80 bits zeroed
+    #[test]
+    fn anonymize_ips() {
+        assert_eq!(anonymize_ip("192.168.1.42"), "192.168.1.0");
+        assert_eq!(
+            anonymize_ip("2001:db8:85a3:1234:5678:8a2e:370:7334"),
+            "2001:db8:85a3::"
+        );
+        assert_eq!(anonymize_ip("not an ip"), "not an ip");
+    }
+
+    /// Items are counted in slots aligned to the interval, and the empty slots
+    /// between them are included
+    #[test]
+    fn group_over_time_slots() {
+        let slots = group_over_time(
+            vec![(610, ()), (1190, ()), (1830, ())],
+            600,
+            || 0,
+            |count, _| *count += 1
+        );
+
+        assert_eq!(slots, vec![(900, 2), (1500, 0), (2100, 1)]);
+    }
+
+    /// Names are hashed with 64-bit FNV-1a
+    #[test]
+    fn anonymize_names() {
+        assert_eq!(anonymize_name(""), "cbf29ce484222325");
+        assert_eq!(anonymize_name("a"), "af63dc4c8601ec8c");
+        assert_eq!(anonymize_name("client1"), "6a2a1e2880507f1d");
+    }
+
+    /// Client IPs are masked and names are hashed when anonymization is
+    /// enabled. Unnamed clients stay unnamed, and hashes are stable.
+    #[test]
+    fn anonymize_enabled() {
+        let env = Env::Test(
+            Config::default(),
+            TestEnvBuilder::new()
+                .file(PiholeFile::SetupVars, "API_ANONYMIZE_CLIENTS=true")
+                .build()
+        );
+
+        let mut clients = vec![
+            ("client1".to_owned(), "10.1.1.1".to_owned()),
+            (String::new(), "10.1.1.2".to_owned()),
+            ("client1".to_owned(), "10.1.2.1".to_owned()),
+        ];
+
+        anonymize_clients(clients.iter_mut().map(|(name, ip)| (name, ip)), &env).unwrap();
+
+        assert_ne!(clients[0].0, "client1");
+        assert_eq!(clients[0].0, clients[2].0);
+        assert_eq!(clients[0].1, "10.1.1.0");
+        assert_eq!(clients[1], (String::new(), "10.1.1.0".to_owned()));
+        assert_eq!(clients[2].1, "10.1.2.0");
+    }
+
+    /// Clients are not changed when anonymization is disabled
+    #[test]
+    fn anonymize_disabled() {
+        let env = Env::Test(
+            Config::default(),
+            TestEnvBuilder::new()
+                .file(PiholeFile::SetupVars, "")
+                .build()
+        );
+
+        let mut clients = vec![("client1".to_owned(), "10.1.1.1".to_owned())];
+
+        anonymize_clients(clients.iter_mut().map(|(name, ip)| (name, ip)), &env).unwrap();
+
+        assert_eq!(clients, vec![("client1".to_owned(), "10.1.1.1".to_owned())]);
+    }
+
+    /// A missing custom DNS list has no names. Unlike in testing, reading a
+    /// missing file in production is an error, so it must be checked first.
+    #[test]
+    fn custom_dns_names_missing_list() {
+        let dir = tempfile::tempdir().unwrap();
+        let config_path = dir.path().join("config.toml");
+        let custom_list = dir.path().join("custom.list");
+
+        fs::write(
+            &config_path,
+            format!(
+                "[file_locations]\ncustom_list = \"{}\"\n",
+                custom_list.display()
+            )
+        )
+        .unwrap();
+
+        let config = Config::parse(config_path.to_str().unwrap()).unwrap();
+        let env = Env::Production(config);
+
+        assert!(get_custom_dns_names(&env).unwrap().is_empty());
+    }
 }
@@ -0,0 +1,195 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// Clients Endpoint - DB Version
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    databases::ftl::FtlDatabase,
+    env::Env,
+    routes::{
+        auth::User,
+        stats::{common::anonymize_clients, database::get_ignored_clients}
+    },
+    settings::{ConfigEntry, FtlConfEntry, FtlPrivacyLevel, ValueType},
+    util::{reply_timed, Error, ErrorKind, Reply, RequestTimer}
+};
+use diesel::{dsl::sql, prelude::*, sql_types::BigInt};
+use failure::ResultExt;
+use rocket::State;
+
+/// Represents a client found in the database
+#[derive(Serialize)]
+#[cfg_attr(test, derive(Debug, PartialEq))]
+pub struct ClientDbReply {
+    name: String,
+    ip: String,
+    total_queries: usize
+}
+
+/// Get the clients which made queries in the time interval
+#[get("/stats/database/clients?<from>&<until>")]
+pub fn clients_db(
+    timer: RequestTimer,
+    _auth: User,
+    env: State<Env>,
+    db: FtlDatabase,
+    from: u64,
+    until: u64
+) -> Reply {
+    reply_timed(
+        clients_db_impl(&env, &db as &SqliteConnection, from, until),
+        timer
+    )
+}
+
+/// Get the clients which made queries in the time interval, with their query
+/// counts. The clients with the most queries are first.
+fn clients_db_impl(
+    env: &Env,
+    db: &SqliteConnection,
+    from: u64,
+    until: u64
+) -> Result<Vec<ClientDbReply>, Error> {
+    // Check if the client details are private
+    if FtlConfEntry::PrivacyLevel.read_as::<FtlPrivacyLevel>(&env)?
+        >= FtlPrivacyLevel::HideDomainsAndClients
+    {
+        return Ok(Vec::new());
+    }
+
+    let ignored_clients = get_ignored_clients(env)?;
+
+    let mut clients: Vec<ClientDbReply> = execute_clients_query(db, from, until, ignored_clients)?
+        .into_iter()
+        .map(|(client_identifier, count)| {
+            if ValueType::Ipv4.is_valid(&client_identifier)
+                || ValueType::Ipv6.is_valid(&client_identifier)
+            {
+                // If the identifier is an IP address, use it as the client IP
+                ClientDbReply {
+                    name: "".to_owned(),
+                    ip: client_identifier,
+                    total_queries: count as usize
+                }
+            } else {
+                // If the identifier is not an IP address, use it as the name
+                ClientDbReply {
+                    name: client_identifier,
+                    ip: "".to_owned(),
+                    total_queries: count as usize
+                }
+            }
+        })
+        .collect();
+
+    anonymize_clients(
+        clients
+            .iter_mut()
+            .map(|client| (&mut client.name, &mut client.ip)),
+        env
+    )?;
+
+    Ok(clients)
+}
+
+/// Create and execute the database query to retrieve the distinct clients.
+/// The returned Vec contains each client's identifier and query count.
+fn execute_clients_query(
+    db: &SqliteConnection,
+    from: u64,
+    until: u64,
+    ignored_clients: Vec<String>
+) -> Result<Vec<(String, i64)>, Error> {
+    use crate::databases::ftl::queries::dsl::*;
+
+    Ok(queries
+        .select((client, sql::<BigInt>("COUNT(*)")))
+        // Only consider queries in the time interval
+        .filter(timestamp.ge(from as i32))
+        .filter(timestamp.le(until as i32))
+        // Filter out ignored clients
+        .filter(client.ne_all(ignored_clients))
+        // Group queries by client
+        .group_by(client)
+        .order((sql::<BigInt>("COUNT(*)").desc(), client))
+        .load::<(String, i64)>(db)
+        .context(ErrorKind::FtlDatabase)?)
+}
+
+#[cfg(test)]
+mod test {
+    use super::{clients_db_impl, ClientDbReply};
+    use crate::{
+        databases::ftl::connect_to_test_db,
+        env::{Config, Env, PiholeFile},
+        testing::TestEnvBuilder
+    };
+    use std::collections::HashMap;
+
+    const FROM_TIMESTAMP: u64 = 0;
+    const UNTIL_TIMESTAMP: u64 = 177_180;
+
+    /// Every client in the interval is listed with its query count
+    #[test]
+    fn all_clients() {
+        let expected = vec![
+            ClientDbReply {
+                name: "".to_owned(),
+                ip: "127.0.0.1".to_owned(),
+                total_queries: 93
+            },
+            ClientDbReply {
+                name: "".to_owned(),
+                ip: "10.1.1.1".to_owned(),
+                total_queries: 1
+            },
+        ];
+
+        let db = connect_to_test_db();
+        let env = Env::Test(Config::default(), HashMap::new());
+        let actual = clients_db_impl(&env, &db, FROM_TIMESTAMP, UNTIL_TIMESTAMP).unwrap();
+
+        assert_eq!(actual, expected);
+    }
+
+    /// Excluded clients are not shown
+    #[test]
+    fn excluded_clients() {
+        let expected = vec![ClientDbReply {
+            name: "".to_owned(),
+            ip: "10.1.1.1".to_owned(),
+            total_queries: 1
+        }];
+
+        let db = connect_to_test_db();
+        let env = Env::Test(
+            Config::default(),
+            TestEnvBuilder::new()
+                .file(PiholeFile::SetupVars, "API_EXCLUDE_CLIENTS=127.0.0.1")
+                .build()
+        );
+        let actual = clients_db_impl(&env, &db, FROM_TIMESTAMP, UNTIL_TIMESTAMP).unwrap();
+
+        assert_eq!(actual, expected);
+    }
+
+    /// Privacy level 2 does not show any clients
+    #[test]
+    fn privacy() {
+        let db = connect_to_test_db();
+        let env = Env::Test(
+            Config::default(),
+            TestEnvBuilder::new()
+                .file(PiholeFile::FtlConfig, "PRIVACYLEVEL=2")
+                .build()
+        );
+        let actual = clients_db_impl(&env, &db, FROM_TIMESTAMP, UNTIL_TIMESTAMP).unwrap();
+
+        assert_eq!(actual, Vec::new());
+    }
+}
@@ -8,6 +8,7 @@
 // This file is copyright under the latest version of the EUPL.
 // Please see LICENSE file for your rights under this license.
 
+mod clients_db;
 mod over_time_clients_db;
 mod over_time_history_db;
 mod query_types_db;
@@ -17,6 +18,6 @@ mod top_domains_db;
 mod upstreams_db;
 
 pub use self::{
-    over_time_clients_db::*, over_time_history_db::*, query_types_db::*, summary_db::*,
-    top_clients_db::*, top_domains_db::*, upstreams_db::*
+    clients_db::*, over_time_clients_db::*, over_time_history_db::*, query_types_db::*,
+    summary_db::*, top_clients_db::*, top_domains_db::*, upstreams_db::*
 };
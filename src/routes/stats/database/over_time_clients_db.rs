@@ -15,13 +15,13 @@ use crate::{
     routes::{
         auth::User,
         stats::{
-            common::{get_excluded_clients, get_hidden_client_ip},
+            common::{check_interval, get_excluded_clients, get_hidden_client_ip},
             database::over_time_history_db::align_from_until,
             over_time_clients::{OverTimeClientItem, OverTimeClients}
         }
     },
     settings::ValueType,
-    util::{reply_result, Error, ErrorKind, Reply}
+    util::{reply_timed, Error, ErrorKind, Reply, RequestTimer}
 };
 use diesel::{dsl::sql, prelude::*, sql_types::BigInt, SqliteConnection};
 use failure::ResultExt;
@@ -31,6 +31,7 @@ use std::collections::HashMap;
 /// Get the clients queries over time data from the database
 #[get("/stats/database/overTime/clients?<from>&<until>&<interval>")]
 pub fn over_time_clients_db(
+    timer: RequestTimer,
     from: u64,
     until: u64,
     interval: Option<usize>,
@@ -38,13 +39,16 @@ pub fn over_time_clients_db(
     db: FtlDatabase,
     env: State<Env>
 ) -> Reply {
-    reply_result(over_time_clients_db_impl(
-        from,
-        until,
-        interval.unwrap_or(600),
-        &db as &SqliteConnection,
-        &env
-    ))
+    reply_timed(
+        over_time_clients_db_impl(
+            from,
+            until,
+            interval.unwrap_or(600),
+            &db as &SqliteConnection,
+            &env
+        ),
+        timer
+    )
 }
 
 /// Get the clients queries over time data from the database
@@ -55,6 +59,7 @@ fn over_time_clients_db_impl(
     db: &SqliteConnection,
     env: &Env
 ) -> Result<OverTimeClients, Error> {
+    check_interval(interval)?;
     let (from, until) = align_from_until(from, until, interval as u64)?;
 
     // Load the clients (names or IP addresses)
@@ -100,13 +105,17 @@ fn over_time_clients_db_impl(
                 // If the identifier is an IP address, use it as the client IP
                 ClientReply {
                     name: "".to_owned(),
-                    ip: client_identifier
+                    ip: client_identifier,
+                    mac: None,
+                    interface: None
                 }
             } else {
                 // If the identifier is not an IP address, use it as the name
                 ClientReply {
                     name: client_identifier,
-                    ip: "".to_owned()
+                    ip: "".to_owned(),
+                    mac: None,
+                    interface: None
                 }
             }
         })
@@ -182,7 +191,8 @@ mod test {
         env::{Config, Env, PiholeFile},
         ftl::ClientReply,
         routes::stats::over_time_clients::{OverTimeClientItem, OverTimeClients},
-        testing::TestEnvBuilder
+        testing::TestEnvBuilder,
+        util::ErrorKind
     };
     use std::collections::HashMap;
 
@@ -197,11 +207,15 @@ mod test {
             clients: vec![
                 ClientReply {
                     name: "".to_owned(),
-                    ip: "127.0.0.1".to_owned()
+                    ip: "127.0.0.1".to_owned(),
+                    mac: None,
+                    interface: None
                 },
                 ClientReply {
                     name: "".to_owned(),
-                    ip: "10.1.1.1".to_owned()
+                    ip: "10.1.1.1".to_owned(),
+                    mac: None,
+                    interface: None
                 },
             ],
             over_time: vec![
@@ -274,4 +288,15 @@ mod test {
 
         assert_eq!(actual, expected);
     }
+
+    /// Intervals which do not evenly divide a day are a bad request
+    #[test]
+    fn invalid_interval() {
+        let db = connect_to_test_db();
+        let env = Env::Test(Config::default(), HashMap::new());
+        let error =
+            over_time_clients_db_impl(FROM_TIMESTAMP, UNTIL_TIMESTAMP, 7, &db, &env).unwrap_err();
+
+        assert_eq!(error.kind(), ErrorKind::BadRequest);
+    }
 }
@@ -11,8 +11,11 @@
 use crate::{
     databases::ftl::FtlDatabase,
     ftl::BLOCKED_STATUSES,
-    routes::{auth::User, stats::over_time_history::OverTimeItem},
-    util::{reply_result, Error, ErrorKind, Reply}
+    routes::{
+        auth::User,
+        stats::{common::check_interval, over_time_history::OverTimeItem}
+    },
+    util::{reply_timed, Error, ErrorKind, Reply, RequestTimer}
 };
 use diesel::{dsl::sql, prelude::*, sql_types::BigInt};
 use failure::ResultExt;
@@ -22,18 +25,22 @@ use std::collections::HashMap;
 /// (separated into blocked and not blocked)
 #[get("/stats/database/overTime/history?<from>&<until>&<interval>")]
 pub fn over_time_history_db(
+    timer: RequestTimer,
     from: u64,
     until: u64,
     interval: Option<usize>,
     _auth: User,
     db: FtlDatabase
 ) -> Reply {
-    reply_result(over_time_history_db_impl(
-        from,
-        until,
-        interval.unwrap_or(600),
-        &db as &SqliteConnection
-    ))
+    reply_timed(
+        over_time_history_db_impl(
+            from,
+            until,
+            interval.unwrap_or(600),
+            &db as &SqliteConnection
+        ),
+        timer
+    )
 }
 
 /// Get the over time data from the database
@@ -43,6 +50,7 @@ fn over_time_history_db_impl(
     interval: usize,
     db: &SqliteConnection
 ) -> Result<Vec<OverTimeItem>, Error> {
+    check_interval(interval)?;
     let (from, until) = align_from_until(from, until, interval as u64)?;
 
     // Get the overTime data
@@ -12,7 +12,7 @@ use crate::{
     databases::ftl::FtlDatabase,
     ftl::FtlQueryType,
     routes::{auth::User, stats::query_types::QueryTypeReply},
-    util::{reply_result, Error, ErrorKind, Reply}
+    util::{reply_timed, Error, ErrorKind, Reply, RequestTimer}
 };
 use diesel::{dsl::sql, prelude::*, sql_types::BigInt, sqlite::SqliteConnection};
 use failure::ResultExt;
@@ -20,8 +20,17 @@ use std::collections::HashMap;
 
 /// Get query type counts from the database
 #[get("/stats/database/query_types?<from>&<until>")]
-pub fn query_types_db(from: u64, until: u64, _auth: User, db: FtlDatabase) -> Reply {
-    reply_result(query_types_db_impl(from, until, &db as &SqliteConnection))
+pub fn query_types_db(
+    timer: RequestTimer,
+    from: u64,
+    until: u64,
+    _auth: User,
+    db: FtlDatabase
+) -> Reply {
+    reply_timed(
+        query_types_db_impl(from, until, &db as &SqliteConnection),
+        timer
+    )
 }
 
 /// Get query type counts from the database
@@ -15,12 +15,13 @@ use crate::{
     routes::{
         auth::User,
         stats::{
+            common::check_time_range,
             database::get_query_type_counts,
-            summary::{ReplyTypes, Summary, TotalQueries}
+            summary::{DnssecTypes, QueryStatuses, ReplyTypes, Summary, TotalQueries}
         }
     },
     settings::{ConfigEntry, SetupVarsEntry},
-    util::{reply_result, Error, ErrorKind, Reply}
+    util::{reply_timed, Error, ErrorKind, Reply, RequestTimer}
 };
 use diesel::prelude::*;
 use failure::ResultExt;
@@ -29,18 +30,17 @@ use rocket::State;
 /// Get summary data from database
 #[get("/stats/database/summary?<from>&<until>")]
 pub fn get_summary_db(
+    timer: RequestTimer,
     from: u64,
     until: u64,
     _auth: User,
     db: FtlDatabase,
     env: State<Env>
 ) -> Reply {
-    reply_result(get_summary_impl(
-        from,
-        until,
-        &db as &SqliteConnection,
-        &env
-    ))
+    reply_timed(
+        get_summary_impl(from, until, &db as &SqliteConnection, &env),
+        timer
+    )
 }
 
 /// Implementation of [`get_summary_db`]
@@ -52,6 +52,8 @@ fn get_summary_impl(
     db: &SqliteConnection,
     env: &Env
 ) -> Result<Summary, Error> {
+    check_time_range(from, until)?;
+
     let query_type_counts = get_query_type_counts(db, from, until)?;
 
     let total_queries_a = *query_type_counts.get(&FtlQueryType::A).unwrap_or(&0);
@@ -75,6 +77,7 @@ fn get_summary_impl(
         // Gravity size is set to zero because it is not relevant when looking
         // at long term data
         gravity_size: 0,
+        gravity_domains_blocked: None,
         total_queries: TotalQueries {
             A: total_queries_a,
             AAAA: total_queries_aaaa,
@@ -101,14 +104,18 @@ fn get_summary_impl(
             NODATA: 0,
             NXDOMAIN: 0
         },
-        // TODO: use real client values when we can accurately determine the number of clients
-        total_clients: 0,
-        active_clients: 0,
+        // TODO: use real values when the database supports DNSSEC statuses
+        dnssec: Some(DnssecTypes::default()),
+        query_status: Some(get_query_statuses(db, from, until)?),
+        total_clients: Some(get_unique_client_count(db, from, until)?),
+        // TODO: use a real value when we can accurately determine the number of active clients
+        active_clients: Some(0),
         status: if SetupVarsEntry::BlockingEnabled.is_true(&env)? {
             "enabled"
         } else {
             "disabled"
-        }
+        },
+        warnings: Vec::new()
     })
 }
 
@@ -146,6 +153,21 @@ fn get_unique_domain_count(db: &SqliteConnection, from: u64, until: u64) -> Resu
     Ok(count as usize)
 }
 
+/// Get the number of unique clients in the specified time range
+fn get_unique_client_count(db: &SqliteConnection, from: u64, until: u64) -> Result<usize, Error> {
+    use crate::databases::ftl::queries::dsl::*;
+    use diesel::{dsl::sql, sql_types::BigInt};
+
+    let count = queries
+        // Raw SQL is used for the same reason as in `get_unique_domain_count`
+        .select(sql::<BigInt>("COUNT(DISTINCT client)"))
+        .filter(timestamp.le(until as i32).and(timestamp.ge(from as i32)))
+        .first::<i64>(db)
+        .context(ErrorKind::FtlDatabase)?;
+
+    Ok(count as usize)
+}
+
 /// Get the number of queries with the specified query status in the specified
 /// time range
 pub fn get_query_status_count(
@@ -166,16 +188,49 @@ pub fn get_query_status_count(
     Ok(count as usize)
 }
 
+/// Get the number of queries in each query status group in the specified
+/// time range
+fn get_query_statuses(
+    db: &SqliteConnection,
+    from: u64,
+    until: u64
+) -> Result<QueryStatuses, Error> {
+    use crate::databases::ftl::queries::dsl::*;
+    use diesel::{dsl::sql, sql_types::BigInt};
+
+    let counts = queries
+        // Raw SQL is used for the same reason as in `get_query_type_counts`
+        .select((status, sql::<BigInt>("COUNT(*)")))
+        .filter(timestamp.le(until as i32).and(timestamp.ge(from as i32)))
+        .group_by(status)
+        .get_results::<(i32, i64)>(db)
+        .context(ErrorKind::FtlDatabase)?;
+
+    let mut statuses = QueryStatuses::default();
+
+    for (query_status, count) in counts {
+        // Statuses which are not known are counted as other
+        statuses.add(
+            FtlQueryStatus::from_number(query_status as isize).unwrap_or(FtlQueryStatus::Unknown),
+            count as usize
+        );
+    }
+
+    Ok(statuses)
+}
+
 #[cfg(test)]
 mod test {
     use super::{
-        get_blocked_query_count, get_query_status_count, get_summary_impl, get_unique_domain_count
+        get_blocked_query_count, get_query_status_count, get_query_statuses, get_summary_impl,
+        get_unique_client_count, get_unique_domain_count
     };
     use crate::{
         databases::ftl::connect_to_test_db,
         env::{Config, Env},
         ftl::FtlQueryStatus,
-        routes::stats::summary::{ReplyTypes, Summary, TotalQueries}
+        routes::stats::summary::{DnssecTypes, QueryStatuses, ReplyTypes, Summary, TotalQueries},
+        util::ErrorKind
     };
     use std::collections::HashMap;
 
@@ -187,6 +242,7 @@ mod test {
     fn summary_impl() {
         let expected_summary = Summary {
             gravity_size: 0,
+            gravity_domains_blocked: None,
             total_queries: TotalQueries {
                 A: 36,
                 AAAA: 35,
@@ -208,9 +264,19 @@ mod test {
                 NODATA: 0,
                 NXDOMAIN: 0
             },
-            total_clients: 0,
-            active_clients: 0,
-            status: "enabled"
+            dnssec: Some(DnssecTypes::default()),
+            query_status: Some(QueryStatuses {
+                gravity: 0,
+                regex: 0,
+                blacklist: 0,
+                cached: 28,
+                forwarded: 26,
+                other: 40
+            }),
+            total_clients: Some(2),
+            active_clients: Some(0),
+            status: "enabled",
+            warnings: Vec::new()
         };
 
         let db = connect_to_test_db();
@@ -220,6 +286,31 @@ mod test {
         assert_eq!(actual_summary, expected_summary);
     }
 
+    /// An inverted time range is a bad request
+    #[test]
+    fn inverted_range() {
+        let db = connect_to_test_db();
+        let env = Env::Test(Config::default(), HashMap::new());
+        let error = get_summary_impl(UNTIL_TIMESTAMP, FROM_TIMESTAMP, &db, &env).unwrap_err();
+
+        assert_eq!(error.kind(), ErrorKind::BadRequest);
+    }
+
+    /// A time range without any stored data has a summary of zeros
+    #[test]
+    fn range_without_data() {
+        let db = connect_to_test_db();
+        let env = Env::Test(Config::default(), HashMap::new());
+        let summary =
+            get_summary_impl(UNTIL_TIMESTAMP + 1, UNTIL_TIMESTAMP + 100, &db, &env).unwrap();
+
+        assert_eq!(summary.blocked_queries, 0);
+        assert_eq!(summary.unique_domains, 0);
+        assert_eq!(summary.forwarded_queries, 0);
+        assert_eq!(summary.cached_queries, 0);
+        assert_eq!(summary.total_clients, Some(0));
+    }
+
     /// Verify the blocked query count is accurate
     #[test]
     fn blocked_query_count() {
@@ -242,6 +333,17 @@ mod test {
         assert_eq!(actual, expected);
     }
 
+    /// Verify the unique client count is accurate
+    #[test]
+    fn unique_client_count() {
+        let expected = 2;
+
+        let db = connect_to_test_db();
+        let actual = get_unique_client_count(&db, FROM_TIMESTAMP, UNTIL_TIMESTAMP).unwrap();
+
+        assert_eq!(actual, expected);
+    }
+
     /// Verify the query status count is accurate
     #[test]
     fn query_status_count() {
@@ -258,4 +360,22 @@ mod test {
 
         assert_eq!(actual, expected);
     }
+
+    /// Verify the query status groups are accurate
+    #[test]
+    fn query_statuses() {
+        let expected = QueryStatuses {
+            gravity: 0,
+            regex: 0,
+            blacklist: 0,
+            cached: 28,
+            forwarded: 26,
+            other: 40
+        };
+
+        let db = connect_to_test_db();
+        let actual = get_query_statuses(&db, FROM_TIMESTAMP, UNTIL_TIMESTAMP).unwrap();
+
+        assert_eq!(actual, expected);
+    }
 }
@@ -16,13 +16,13 @@ use crate::{
         auth::User,
         stats::{
             check_privacy_level_top_clients,
-            common::{get_excluded_clients, get_hidden_client_ip},
+            common::{get_excluded_clients, get_hidden_client_ip, limit_results},
             database::{get_blocked_query_count, get_query_type_counts},
             top_clients::{TopClientItemReply, TopClientParams, TopClientsReply}
         }
     },
     settings::ValueType,
-    util::{reply_result, Error, ErrorKind, Reply}
+    util::{reply_timed, Error, ErrorKind, Reply, RequestTimer}
 };
 use diesel::{dsl::sql, prelude::*, sql_types::BigInt};
 use failure::ResultExt;
@@ -31,6 +31,7 @@ use rocket::{request::Form, State};
 /// Get the top clients
 #[get("/stats/database/top_clients?<from>&<until>&<params..>")]
 pub fn top_clients_db(
+    timer: RequestTimer,
     _auth: User,
     env: State<Env>,
     db: FtlDatabase,
@@ -38,13 +39,16 @@ pub fn top_clients_db(
     until: u64,
     params: Form<TopClientParams>
 ) -> Reply {
-    reply_result(top_clients_db_impl(
-        &env,
-        &db as &SqliteConnection,
-        from,
-        until,
-        params.into_inner()
-    ))
+    reply_timed(
+        top_clients_db_impl(
+            &env,
+            &db as &SqliteConnection,
+            from,
+            until,
+            params.into_inner()
+        ),
+        timer
+    )
 }
 
 /// Get the top clients
@@ -55,8 +59,9 @@ fn top_clients_db_impl(
     until: u64,
     params: TopClientParams
 ) -> Result<TopClientsReply, Error> {
-    // Resolve the parameters (the inactive param is ignored)
-    let limit = params.limit.unwrap_or(10);
+    // Resolve the parameters (the inactive and as_percentage params are
+    // ignored)
+    let limit = limit_results(params.limit.unwrap_or(10), env)?;
     let ascending = params.ascending.unwrap_or(false);
     let blocked = params.blocked.unwrap_or(false);
 
@@ -88,14 +93,16 @@ fn top_clients_db_impl(
                     TopClientItemReply {
                         name: "".to_owned(),
                         ip: client_identifier,
-                        count: count as usize
+                        count: count as usize,
+                        percentage: None
                     }
                 } else {
                     // If the identifier is not an IP address, use it as the name
                     TopClientItemReply {
                         name: client_identifier,
                         ip: "".to_owned(),
-                        count: count as usize
+                        count: count as usize,
+                        percentage: None
                     }
                 }
             })
@@ -118,7 +125,7 @@ fn top_clients_db_impl(
 }
 
 /// Get the list of clients to ignore
-fn get_ignored_clients(env: &Env) -> Result<Vec<String>, Error> {
+pub fn get_ignored_clients(env: &Env) -> Result<Vec<String>, Error> {
     // Ignore clients excluded via SetupVars
     let mut ignored_clients = get_excluded_clients(env)?;
 
@@ -200,12 +207,14 @@ mod test {
                 TopClientItemReply {
                     name: "".to_owned(),
                     ip: "127.0.0.1".to_owned(),
-                    count: 93
+                    count: 93,
+                    percentage: None
                 },
                 TopClientItemReply {
                     name: "".to_owned(),
                     ip: "10.1.1.1".to_owned(),
-                    count: 1
+                    count: 1,
+                    percentage: None
                 },
             ],
             total_queries: Some(94),
@@ -250,7 +259,8 @@ mod test {
             top_clients: vec![TopClientItemReply {
                 name: "".to_owned(),
                 ip: "127.0.0.1".to_owned(),
-                count: 93
+                count: 93,
+                percentage: None
             }],
             total_queries: Some(94),
             blocked_queries: None
@@ -276,12 +286,14 @@ mod test {
                 TopClientItemReply {
                     name: "".to_owned(),
                     ip: "10.1.1.1".to_owned(),
-                    count: 1
+                    count: 1,
+                    percentage: None
                 },
                 TopClientItemReply {
                     name: "".to_owned(),
                     ip: "127.0.0.1".to_owned(),
-                    count: 93
+                    count: 93,
+                    percentage: None
                 },
             ],
             total_queries: Some(94),
@@ -300,6 +312,37 @@ mod test {
         assert_eq!(actual, expected);
     }
 
+    /// The limit is clamped to `API_MAX_RESULTS`
+    #[test]
+    fn limit_clamped() {
+        let expected = TopClientsReply {
+            top_clients: vec![TopClientItemReply {
+                name: "".to_owned(),
+                ip: "127.0.0.1".to_owned(),
+                count: 93,
+                percentage: None
+            }],
+            total_queries: Some(94),
+            blocked_queries: None
+        };
+
+        let db = connect_to_test_db();
+        let env = Env::Test(
+            Config::default(),
+            TestEnvBuilder::new()
+                .file(PiholeFile::SetupVars, "API_MAX_RESULTS=1")
+                .build()
+        );
+        let params = TopClientParams {
+            limit: Some(10),
+            ..TopClientParams::default()
+        };
+        let actual =
+            top_clients_db_impl(&env, &db, FROM_TIMESTAMP, UNTIL_TIMESTAMP, params).unwrap();
+
+        assert_eq!(actual, expected);
+    }
+
     /// Privacy level 2 does not show any clients
     #[test]
     fn privacy() {
@@ -357,7 +400,8 @@ mod test {
             top_clients: vec![TopClientItemReply {
                 name: "".to_owned(),
                 ip: "10.1.1.1".to_owned(),
-                count: 1
+                count: 1,
+                percentage: None
             }],
             total_queries: Some(94),
             blocked_queries: None
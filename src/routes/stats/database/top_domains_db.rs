@@ -16,22 +16,28 @@ use crate::{
         auth::User,
         stats::{
             check_privacy_level_top_domains, check_query_log_show_top_domains,
-            common::{get_excluded_domains, get_hidden_domain},
+            common::{check_time_range, get_excluded_domains, get_hidden_domain, limit_results},
             database::{
                 query_types_db::get_query_type_counts, summary_db::get_blocked_query_count
             },
-            top_domains::{TopDomainItemReply, TopDomainParams, TopDomainsReply}
+            top_domains::{get_min_count, TopDomainItemReply, TopDomainParams, TopDomainsReply}
         }
     },
-    util::{reply_result, Error, ErrorKind, Reply}
+    util::{reply_timed, Error, ErrorKind, Reply, RequestTimer}
+};
+use diesel::{
+    dsl::sql,
+    prelude::*,
+    sql_types::{BigInt, Text},
+    sqlite::SqliteConnection
 };
-use diesel::{dsl::sql, prelude::*, sql_types::BigInt, sqlite::SqliteConnection};
 use failure::ResultExt;
 use rocket::{request::Form, State};
 
 /// Return the top domains
 #[get("/stats/database/top_domains?<from>&<until>&<params..>")]
 pub fn top_domains_db(
+    timer: RequestTimer,
     _auth: User,
     env: State<Env>,
     db: FtlDatabase,
@@ -39,13 +45,16 @@ pub fn top_domains_db(
     until: u64,
     params: Form<TopDomainParams>
 ) -> Reply {
-    reply_result(top_domains_db_impl(
-        &env,
-        &db as &SqliteConnection,
-        from,
-        until,
-        params.into_inner()
-    ))
+    reply_timed(
+        top_domains_db_impl(
+            &env,
+            &db as &SqliteConnection,
+            from,
+            until,
+            params.into_inner()
+        ),
+        timer
+    )
 }
 
 /// Return the top domains
@@ -56,11 +65,14 @@ fn top_domains_db_impl(
     until: u64,
     params: TopDomainParams
 ) -> Result<TopDomainsReply, Error> {
+    check_time_range(from, until)?;
+
     // Resolve the parameters
-    let limit = params.limit.unwrap_or(10);
+    let limit = limit_results(params.limit.unwrap_or(10), env)?;
     let audit = params.audit.unwrap_or(false);
     let ascending = params.ascending.unwrap_or(false);
     let blocked = params.blocked.unwrap_or(false);
+    let min_count = get_min_count(&params);
 
     // Check if we are allowed to share the top domains
     if let Some(reply) = check_query_log_show_top_domains(env, blocked)? {
@@ -87,14 +99,22 @@ fn top_domains_db_impl(
     let ignored_domains = get_ignored_domains(env, audit)?;
 
     // Fetch the top domains and map into the reply structure
-    let top_domains: Vec<TopDomainItemReply> =
-        execute_top_domains_query(db, from, until, ignored_domains, blocked, ascending, limit)?
-            .into_iter()
-            .map(|(domain, count)| TopDomainItemReply {
-                domain,
-                count: count as usize
-            })
-            .collect();
+    let top_domains: Vec<TopDomainItemReply> = execute_top_domains_query(
+        db,
+        from,
+        until,
+        ignored_domains,
+        blocked,
+        ascending,
+        limit,
+        min_count
+    )?
+    .into_iter()
+    .map(|(domain, count)| TopDomainItemReply {
+        domain,
+        count: count as usize
+    })
+    .collect();
 
     // Output format changes when getting top blocked domains
     if blocked {
@@ -112,8 +132,8 @@ fn top_domains_db_impl(
     }
 }
 
-/// Get the list of domains to ignore. If the audit flag is true, audited
-/// domains are ignored (only show unaudited domains).
+/// Get the list of domains to ignore, in lowercase. If the audit flag is true,
+/// audited domains are ignored (only show unaudited domains).
 fn get_ignored_domains(env: &Env, audit: bool) -> Result<Vec<String>, Error> {
     // Ignore domains excluded via SetupVars
     let mut ignored_domains = get_excluded_domains(env)?;
@@ -123,7 +143,11 @@ fn get_ignored_domains(env: &Env, audit: bool) -> Result<Vec<String>, Error> {
 
     // If audit flag is true, only include unaudited domains
     if audit {
-        ignored_domains.extend(env.read_file_lines(PiholeFile::AuditLog)?);
+        ignored_domains.extend(
+            env.read_file_lines(PiholeFile::AuditLog)?
+                .into_iter()
+                .map(|domain| domain.to_lowercase())
+        );
     }
 
     Ok(ignored_domains)
@@ -132,6 +156,7 @@ fn get_ignored_domains(env: &Env, audit: bool) -> Result<Vec<String>, Error> {
 /// Create and execute the database query to retrieve the top domain details.
 /// The returned Vec contains each domain and its count, sorted and ordered
 /// according to the parameters.
+#[allow(clippy::too_many_arguments)]
 fn execute_top_domains_query(
     db: &SqliteConnection,
     from: u64,
@@ -139,7 +164,8 @@ fn execute_top_domains_query(
     ignored_domains: Vec<String>,
     blocked: bool,
     ascending: bool,
-    limit: usize
+    limit: usize,
+    min_count: usize
 ) -> Result<Vec<(String, i64)>, Error> {
     use crate::databases::ftl::queries::dsl::*;
 
@@ -149,15 +175,23 @@ fn execute_top_domains_query(
         // Only consider queries in the time interval
         .filter(timestamp.ge(from as i32))
         .filter(timestamp.le(until as i32))
-        // Filter out ignored domains
-        .filter(domain.ne_all(ignored_domains))
+        // Filter out ignored domains. Domains are compared in lowercase, the
+        // same as when excluding domains from shared memory.
+        .filter(sql::<Text>("LOWER(domain)").ne_all(ignored_domains))
         // Group queries by domain
         .group_by(domain)
-        // Take into account the limit
-        .limit(limit as i64)
         // Box the query so we can conditionally modify it
         .into_boxed();
 
+    // Take into account the limit. Diesel does not support HAVING, so the
+    // minimum count is applied to the results, and the limit can only be
+    // applied here if every domain has the minimum count.
+    let db_query = if min_count <= 1 {
+        db_query.limit(limit as i64)
+    } else {
+        db_query
+    };
+
     // Set the sort order
     let db_query = if ascending {
         db_query.order((sql::<BigInt>("COUNT(*)").asc(), domain))
@@ -173,9 +207,17 @@ fn execute_top_domains_query(
     };
 
     // Execute query
-    Ok(db_query
+    let results = db_query
         .load::<(String, i64)>(db)
-        .context(ErrorKind::FtlDatabase)?)
+        .context(ErrorKind::FtlDatabase)?;
+
+    // Only keep domains with at least the minimum count, before the limit is
+    // taken
+    Ok(results
+        .into_iter()
+        .filter(|(_, count)| *count >= min_count as i64)
+        .take(limit)
+        .collect())
 }
 
 #[cfg(test)]
@@ -185,7 +227,8 @@ mod test {
         databases::ftl::connect_to_test_db,
         env::{Config, Env, PiholeFile},
         routes::stats::top_domains::{TopDomainItemReply, TopDomainParams, TopDomainsReply},
-        testing::TestEnvBuilder
+        testing::TestEnvBuilder,
+        util::ErrorKind
     };
     use std::collections::HashMap;
 
@@ -337,6 +380,39 @@ mod test {
         assert_eq!(actual, expected);
     }
 
+    /// Domains with fewer queries than the minimum count are not shown, and
+    /// the limit is applied after them
+    #[test]
+    fn min_count() {
+        let expected = TopDomainsReply {
+            top_domains: vec![
+                TopDomainItemReply {
+                    domain: "4.4.8.8.in-addr.arpa".to_owned(),
+                    count: 9
+                },
+                TopDomainItemReply {
+                    domain: "3.ubuntu.pool.ntp.org".to_owned(),
+                    count: 10
+                },
+            ],
+            total_queries: Some(94),
+            blocked_queries: None
+        };
+
+        let db = connect_to_test_db();
+        let env = Env::Test(Config::default(), HashMap::new());
+        let params = TopDomainParams {
+            ascending: Some(true),
+            limit: Some(2),
+            min_count: Some(9),
+            ..TopDomainParams::default()
+        };
+        let actual =
+            top_domains_db_impl(&env, &db, FROM_TIMESTAMP, UNTIL_TIMESTAMP, params).unwrap();
+
+        assert_eq!(actual, expected);
+    }
+
     /// Show unaudited domains in ascending order, but no hidden, inactive, or
     /// audited domains
     #[test]
@@ -412,4 +488,59 @@ mod test {
 
         assert_eq!(actual, expected);
     }
+
+    /// Excluded domains are matched case insensitively
+    #[test]
+    fn excluded_case_insensitive() {
+        let expected = TopDomainsReply {
+            top_domains: vec![
+                TopDomainItemReply {
+                    domain: "0.ubuntu.pool.ntp.org".to_owned(),
+                    count: 14
+                },
+                TopDomainItemReply {
+                    domain: "github.com".to_owned(),
+                    count: 12
+                },
+            ],
+            total_queries: Some(94),
+            blocked_queries: None
+        };
+
+        let db = connect_to_test_db();
+        let env = Env::Test(
+            Config::default(),
+            TestEnvBuilder::new()
+                .file(
+                    PiholeFile::SetupVars,
+                    "API_EXCLUDE_DOMAINS=1.Ubuntu.Pool.NTP.org"
+                )
+                .build()
+        );
+        let params = TopDomainParams {
+            limit: Some(2),
+            ..TopDomainParams::default()
+        };
+        let actual =
+            top_domains_db_impl(&env, &db, FROM_TIMESTAMP, UNTIL_TIMESTAMP, params).unwrap();
+
+        assert_eq!(actual, expected);
+    }
+
+    /// An inverted time range is a bad request
+    #[test]
+    fn inverted_range() {
+        let db = connect_to_test_db();
+        let env = Env::Test(Config::default(), HashMap::new());
+        let error = top_domains_db_impl(
+            &env,
+            &db,
+            UNTIL_TIMESTAMP,
+            FROM_TIMESTAMP,
+            TopDomainParams::default()
+        )
+        .unwrap_err();
+
+        assert_eq!(error.kind(), ErrorKind::BadRequest);
+    }
 }
@@ -14,11 +14,12 @@ use crate::{
     routes::{
         auth::User,
         stats::{
+            common::check_time_range,
             database::{get_blocked_query_count, get_query_status_count},
             upstreams::{UpstreamItemReply, UpstreamsReply}
         }
     },
-    util::{reply_result, Error, ErrorKind, Reply}
+    util::{reply_timed, Error, ErrorKind, Reply, RequestTimer}
 };
 use diesel::{dsl::sql, prelude::*, sql_types::BigInt, sqlite::SqliteConnection};
 use failure::ResultExt;
@@ -26,8 +27,17 @@ use std::collections::HashMap;
 
 /// Get upstream data from the database
 #[get("/stats/database/upstreams?<from>&<until>")]
-pub fn upstreams_db(from: u64, until: u64, _auth: User, db: FtlDatabase) -> Reply {
-    reply_result(upstreams_db_impl(from, until, &db as &SqliteConnection))
+pub fn upstreams_db(
+    timer: RequestTimer,
+    from: u64,
+    until: u64,
+    _auth: User,
+    db: FtlDatabase
+) -> Reply {
+    reply_timed(
+        upstreams_db_impl(from, until, &db as &SqliteConnection),
+        timer
+    )
 }
 
 /// Get upstream data from the database
@@ -36,6 +46,8 @@ fn upstreams_db_impl(
     until: u64,
     db: &SqliteConnection
 ) -> Result<UpstreamsReply, Error> {
+    check_time_range(from, until)?;
+
     let upstream_counts = get_upstream_counts(from, until, db)?;
     let blocked_count = get_blocked_query_count(db, from, until)?;
     let cached_count = get_query_status_count(db, from, until, FtlQueryStatus::Cache)?;
@@ -43,8 +55,10 @@ fn upstreams_db_impl(
     // Total queries is the sum of the upstream counts
     let total_queries = upstream_counts.values().sum::<i64>() as usize;
     // Forwarded queries are the sum of all upstream counts where the upstream is
-    // not null
-    let forwarded_queries = total_queries - upstream_counts[&None] as usize;
+    // not null. There are no queries without an upstream if the range has no
+    // cached or blocked queries.
+    let forwarded_queries =
+        total_queries - upstream_counts.get(&None).cloned().unwrap_or_default() as usize;
 
     // Capacity is the number of upstreams plus 1 for blocklists and 1 for
     // cache. upstream_counts.len() equals the number of upstreams plus 1
@@ -55,12 +69,14 @@ fn upstreams_db_impl(
     upstreams.push(UpstreamItemReply {
         name: "blocklist".to_owned(),
         ip: "blocklist".to_owned(),
-        count: blocked_count
+        count: blocked_count,
+        response_times: None
     });
     upstreams.push(UpstreamItemReply {
         name: "cache".to_owned(),
         ip: "cache".to_owned(),
-        count: cached_count
+        count: cached_count,
+        response_times: None
     });
 
     // Convert the upstreams into the reply structs
@@ -71,7 +87,9 @@ fn upstreams_db_impl(
                 Some(UpstreamItemReply {
                     name: "".to_owned(),
                     ip,
-                    count: count as usize
+                    count: count as usize,
+                    // Response times are not stored in the database
+                    response_times: None
                 })
             } else {
                 // Ignore the blocked and cached queries. These have already
@@ -125,7 +143,8 @@ mod test {
     use super::{get_upstream_counts, upstreams_db_impl};
     use crate::{
         databases::ftl::connect_to_test_db,
-        routes::stats::upstreams::{UpstreamItemReply, UpstreamsReply}
+        routes::stats::upstreams::{UpstreamItemReply, UpstreamsReply},
+        util::ErrorKind
     };
     use std::collections::HashMap;
 
@@ -140,22 +159,26 @@ mod test {
                 UpstreamItemReply {
                     name: "blocklist".to_owned(),
                     ip: "blocklist".to_owned(),
-                    count: 0
+                    count: 0,
+                    response_times: None
                 },
                 UpstreamItemReply {
                     name: "cache".to_owned(),
                     ip: "cache".to_owned(),
-                    count: 28
+                    count: 28,
+                    response_times: None
                 },
                 UpstreamItemReply {
                     name: "".to_owned(),
                     ip: "8.8.4.4".to_owned(),
-                    count: 22
+                    count: 22,
+                    response_times: None
                 },
                 UpstreamItemReply {
                     name: "".to_owned(),
                     ip: "8.8.8.8".to_owned(),
-                    count: 4
+                    count: 4,
+                    response_times: None
                 },
             ],
             total_queries: 94,
@@ -181,4 +204,42 @@ mod test {
 
         assert_eq!(actual, expected);
     }
+
+    /// A range without any queries has only the blocklist and cache, with no
+    /// queries
+    #[test]
+    fn empty_range() {
+        let expected = UpstreamsReply {
+            upstreams: vec![
+                UpstreamItemReply {
+                    name: "blocklist".to_owned(),
+                    ip: "blocklist".to_owned(),
+                    count: 0,
+                    response_times: None
+                },
+                UpstreamItemReply {
+                    name: "cache".to_owned(),
+                    ip: "cache".to_owned(),
+                    count: 0,
+                    response_times: None
+                },
+            ],
+            total_queries: 0,
+            forwarded_queries: 0
+        };
+
+        let db = connect_to_test_db();
+        let actual = upstreams_db_impl(UNTIL_TIMESTAMP + 1, UNTIL_TIMESTAMP + 2, &db).unwrap();
+
+        assert_eq!(actual, expected);
+    }
+
+    /// An inverted time range is a bad request
+    #[test]
+    fn inverted_range() {
+        let db = connect_to_test_db();
+        let error = upstreams_db_impl(UNTIL_TIMESTAMP, FROM_TIMESTAMP, &db).unwrap_err();
+
+        assert_eq!(error.kind(), ErrorKind::BadRequest);
+    }
 }
@@ -0,0 +1,231 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// Domain Clients Endpoint
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    env::Env,
+    ftl::FtlMemory,
+    routes::{
+        auth::User,
+        stats::{
+            clients::{filter_ftl_clients, ClientParams},
+            common::anonymize_clients,
+            top_clients::TopClientItemReply
+        }
+    },
+    util::{reply_timed, Error, Reply, RequestTimer}
+};
+use rocket::State;
+use std::collections::{HashMap, HashSet};
+
+/// Get the clients which queried a domain, and how many times they did
+#[get("/stats/domain/<domain>/clients")]
+pub fn domain_clients(
+    timer: RequestTimer,
+    _auth: User,
+    ftl_memory: State<FtlMemory>,
+    env: State<Env>,
+    domain: String
+) -> Reply {
+    reply_timed(get_domain_clients(&ftl_memory, &env, &domain), timer)
+}
+
+/// Count the queries for the domain by client, with the most frequent clients
+/// first. The domain is matched case insensitively.
+fn get_domain_clients(
+    ftl_memory: &FtlMemory,
+    env: &Env,
+    domain: &str
+) -> Result<Vec<TopClientItemReply>, Error> {
+    let lock = ftl_memory.lock()?;
+    let counters = ftl_memory.counters(&lock)?;
+    let strings = ftl_memory.strings(&lock)?;
+    let clients = ftl_memory.clients(&lock)?;
+    let domains = ftl_memory.domains(&lock)?;
+    let queries = ftl_memory.queries(&lock)?;
+
+    // Find the IDs of the domain. The domain should only appear once, but a
+    // set is used in case it was stored with different cases.
+    let domain_ids: HashSet<usize> = domains
+        .iter()
+        .take(counters.total_domains as usize)
+        .enumerate()
+        .filter(|(_, ftl_domain)| ftl_domain.get_domain(&strings).eq_ignore_ascii_case(domain))
+        .map(|(id, _)| id)
+        .collect();
+
+    if domain_ids.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    // Count the domain's queries by client ID
+    let mut client_counts: HashMap<usize, usize> = HashMap::new();
+
+    for query in queries
+        .iter()
+        .take(counters.total_queries as usize)
+        .filter(|query| domain_ids.contains(&(query.domain_id as usize)))
+    {
+        *client_counts.entry(query.client_id as usize).or_insert(0) += 1;
+    }
+
+    // Map the counts to the client IPs, so they can be matched with the
+    // filtered clients
+    let client_counts: HashMap<&str, usize> = client_counts
+        .into_iter()
+        .filter_map(|(client_id, count)| {
+            clients
+                .get(client_id)
+                .map(|client| (client.get_ip(&strings), count))
+        })
+        .collect();
+
+    let mut domain_clients: Vec<TopClientItemReply> =
+        filter_ftl_clients(ftl_memory, &lock, &clients, env, ClientParams::default())?
+            .into_iter()
+            .filter_map(|client| {
+                let count = *client_counts.get(client.get_ip(&strings))?;
+                let reply = client.as_reply(&strings);
+
+                Some(TopClientItemReply {
+                    name: reply.name,
+                    ip: reply.ip,
+                    count,
+                    percentage: None
+                })
+            })
+            .collect();
+
+    domain_clients.sort_by(|a, b| b.count.cmp(&a.count));
+    anonymize_clients(
+        domain_clients
+            .iter_mut()
+            .map(|client| (&mut client.name, &mut client.ip)),
+        env
+    )?;
+
+    Ok(domain_clients)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        env::PiholeFile,
+        ftl::{FtlClient, FtlCounters, FtlDomain, FtlMemory, FtlQuery, FtlRegexMatch, FtlSettings},
+        testing::{test_query, TestBuilder}
+    };
+    use std::collections::HashMap;
+
+    /// Shorthand for making `FtlQuery` structs
+    fn query(domain_id: i32, client_id: i32) -> FtlQuery {
+        FtlQuery {
+            domain_id,
+            client_id,
+            ..test_query()
+        }
+    }
+
+    /// There are 3 clients, one of them hidden, and 2 domains. The first
+    /// domain was queried twice by the second client, and once by the others.
+    fn test_data() -> FtlMemory {
+        let mut strings = HashMap::new();
+        strings.insert(1, "10.1.1.1".to_owned());
+        strings.insert(2, "client1".to_owned());
+        strings.insert(3, "10.1.1.2".to_owned());
+        strings.insert(4, "0.0.0.0".to_owned());
+        strings.insert(5, "example.com".to_owned());
+        strings.insert(6, "example.net".to_owned());
+
+        FtlMemory::Test {
+            clients: vec![
+                FtlClient::new(2, 0, 1, Some(2)),
+                FtlClient::new(2, 0, 3, None),
+                FtlClient::new(1, 0, 4, None),
+            ],
+            domains: vec![
+                FtlDomain::new(4, 0, 5, FtlRegexMatch::NotBlocked),
+                FtlDomain::new(1, 0, 6, FtlRegexMatch::NotBlocked),
+            ],
+            over_time: Vec::new(),
+            strings,
+            upstreams: Vec::new(),
+            queries: vec![
+                query(0, 0),
+                query(0, 1),
+                query(1, 0),
+                query(0, 1),
+                query(0, 2),
+            ],
+            counters: FtlCounters {
+                total_clients: 3,
+                total_domains: 2,
+                total_queries: 5,
+                ..FtlCounters::default()
+            },
+            settings: FtlSettings::default()
+        }
+    }
+
+    /// The clients which queried the domain are counted, and hidden clients
+    /// are not shown
+    #[test]
+    fn domain_clients() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/domain/example.com/clients")
+            .ftl_memory(test_data())
+            .expect_json(json!({
+                "data": [
+                    { "name": "",        "ip": "10.1.1.2", "count": 2 },
+                    { "name": "client1", "ip": "10.1.1.1", "count": 1 }
+                ]
+            }))
+            .test();
+    }
+
+    /// The domain is matched case insensitively
+    #[test]
+    fn case_insensitive() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/domain/EXAMPLE.net/clients")
+            .ftl_memory(test_data())
+            .expect_json(json!({
+                "data": [
+                    { "name": "client1", "ip": "10.1.1.1", "count": 1 }
+                ]
+            }))
+            .test();
+    }
+
+    /// Excluded clients are not shown
+    #[test]
+    fn excluded_clients() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/domain/example.com/clients")
+            .ftl_memory(test_data())
+            .file(PiholeFile::SetupVars, "API_EXCLUDE_CLIENTS=10.1.1.2")
+            .expect_json(json!({
+                "data": [
+                    { "name": "client1", "ip": "10.1.1.1", "count": 1 }
+                ]
+            }))
+            .test();
+    }
+
+    /// A domain which was never queried has no clients
+    #[test]
+    fn unknown_domain() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/domain/example.org/clients")
+            .ftl_memory(test_data())
+            .expect_json(json!({
+                "data": []
+            }))
+            .test();
+    }
+}
@@ -0,0 +1,74 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// Flush Query Data Endpoint
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    ftl::FtlConnectionType,
+    routes::auth::User,
+    util::{reply_success, Error, ErrorKind, Reply}
+};
+use failure::ResultExt;
+use rocket::State;
+
+/// Flush FTL's in-memory query data, which resets the statistics without
+/// restarting FTL.
+///
+/// This is destructive: the queries in memory are discarded, and only the
+/// queries which were already stored in the long term database can be seen
+/// afterwards.
+#[post("/stats/flush")]
+pub fn flush(ftl: State<FtlConnectionType>, _auth: User) -> Reply {
+    flush_ftl(&ftl)?;
+    reply_success()
+}
+
+/// Tell FTL to flush its query data, and wait for it to acknowledge the flush
+fn flush_ftl(ftl: &FtlConnectionType) -> Result<(), Error> {
+    let mut con = ftl.connect("flush").context(ErrorKind::FtlFlushError)?;
+    con.expect_eom().context(ErrorKind::FtlFlushError)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use crate::testing::{write_eom, TestBuilder};
+    use rocket::http::{Method, Status};
+
+    /// FTL acknowledges the flush
+    #[test]
+    fn flush() {
+        let mut data = Vec::new();
+        write_eom(&mut data);
+
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/flush")
+            .method(Method::Post)
+            .ftl("flush", data)
+            .expect_json(json!({ "status": "success" }))
+            .test();
+    }
+
+    /// If FTL does not acknowledge the flush, the service is unavailable
+    #[test]
+    fn no_acknowledgement() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/flush")
+            .method(Method::Post)
+            .expect_status(Status::ServiceUnavailable)
+            .expect_json(json!({
+                "error": {
+                    "key": "ftl_flush_error",
+                    "message": "FTL did not acknowledge the flush",
+                    "data": None::<()>
+                }
+            }))
+            .test();
+    }
+}
@@ -0,0 +1,46 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// Gravity Info Endpoint
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    databases::gravity::GravityCountCache,
+    env::Env,
+    util::{reply_timed, Reply, RequestTimer}
+};
+use rocket::State;
+
+/// Get when gravity was last updated and how many domains it blocks. The
+/// values are null if the gravity list is missing.
+#[get("/stats/gravity/info")]
+pub fn gravity_info(
+    timer: RequestTimer,
+    env: State<Env>,
+    gravity_count: State<GravityCountCache>
+) -> Reply {
+    reply_timed(Ok(gravity_count.get_info(&env)), timer)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::testing::TestBuilder;
+
+    /// A missing gravity list has no info
+    #[test]
+    fn missing_gravity() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/gravity/info")
+            .expect_json(json!({
+                "data": {
+                    "updated": None::<()>,
+                    "domain_count": None::<()>
+                }
+            }))
+            .test();
+    }
+}
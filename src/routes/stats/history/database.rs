@@ -56,6 +56,7 @@ pub fn load_queries_from_database(
     let db_query = filter_time_from_db(db_query, params);
     let db_query = filter_time_until_db(db_query, params);
     let db_query = filter_domain_db(db_query, params);
+    let db_query = filter_domain_search_db(db_query, params);
     let db_query = filter_client_db(db_query, params);
     let db_query = filter_upstream_db(db_query, params);
     let db_query = filter_query_type_db(db_query, params);
@@ -86,6 +87,49 @@ pub fn load_queries_from_database(
     Ok((results, cursor))
 }
 
+/// Load a page of queries from the database using keyset pagination. Queries
+/// are ordered by ID, oldest first, so the ID of the last query can be used to
+/// load the next page. Whether there are more queries after the page is also
+/// returned.
+///
+/// # Arguments:
+/// - `db`: A connection to the FTL database
+/// - `after_id`: Only load queries with an ID greater than this. If this is
+///   `None` then the search will start from the oldest queries
+/// - `limit`: The maximum number of queries to load
+pub fn load_queries_after_id(
+    db: &SqliteConnection,
+    after_id: Option<i64>,
+    env: &Env,
+    limit: usize
+) -> Result<(Vec<FtlDbQuery>, bool), Error> {
+    // Use the Diesel DSL of this table for easy querying
+    use crate::databases::ftl::queries::dsl::*;
+
+    let mut db_query = queries
+        .into_boxed()
+        // Take up to the limit, plus one to check if there are more queries
+        .limit((limit + 1) as i64)
+        .order(id.asc());
+
+    if let Some(after_id) = after_id {
+        db_query = db_query.filter(id.gt(after_id as i32));
+    }
+
+    // Apply filters
+    let db_query = filter_excluded_domains_db(db_query, env)?;
+    let db_query = filter_excluded_clients_db(db_query, env)?;
+    let db_query = filter_setup_vars_setting_db(db_query, env)?;
+
+    let mut results: Vec<FtlDbQuery> = execute_query(db, db_query)?;
+
+    // The limit + 1 query only shows that there are more queries
+    let more = results.len() > limit;
+    results.truncate(limit);
+
+    Ok((results, more))
+}
+
 /// Execute a database query for DNS queries on an FTL database.
 /// The database could be real, or it could be a test database.
 pub fn execute_query(
@@ -100,7 +144,7 @@ pub fn execute_query(
 
 #[cfg(test)]
 mod test {
-    use super::load_queries_from_database;
+    use super::{load_queries_after_id, load_queries_from_database};
     use crate::{
         databases::ftl::connect_to_test_db,
         env::{Config, Env},
@@ -148,4 +192,30 @@ mod test {
         assert_eq!(queries.len(), 2);
         assert_eq!(cursor, expected_cursor);
     }
+
+    /// Keyset pagination loads the queries after the ID, ordered by ID
+    #[test]
+    fn after_id() {
+        let env = Env::Test(Config::default(), HashMap::new());
+
+        let (queries, more) =
+            load_queries_after_id(&connect_to_test_db(), Some(2), &env, 3).unwrap();
+        let ids: Vec<Option<i32>> = queries.iter().map(|query| query.id).collect();
+
+        assert_eq!(ids, vec![Some(3), Some(4), Some(5)]);
+        assert!(more);
+    }
+
+    /// The last page shows that there are no more queries
+    #[test]
+    fn after_id_last_page() {
+        let env = Env::Test(Config::default(), HashMap::new());
+
+        let (queries, more) =
+            load_queries_after_id(&connect_to_test_db(), Some(92), &env, 3).unwrap();
+        let ids: Vec<Option<i32>> = queries.iter().map(|query| query.id).collect();
+
+        assert_eq!(ids, vec![Some(93), Some(94)]);
+        assert!(!more);
+    }
 }
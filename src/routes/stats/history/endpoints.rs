@@ -12,8 +12,13 @@ use crate::{
     databases::ftl::FtlDatabase,
     env::Env,
     ftl::{FtlDnssecType, FtlMemory, FtlQueryReplyType, FtlQueryStatus, FtlQueryType},
-    routes::{auth::User, stats::history::get_history::get_history},
-    util::{Error, ErrorKind, Reply}
+    routes::{
+        auth::User,
+        stats::history::get_history::{get_history, get_history_csv, get_history_db}
+    },
+    util::{
+        reply_timed, AcceptCsv, CsvPageReply, Error, ErrorKind, Reply, RequestTimer, TimeFormat
+    }
 };
 use base64::{decode, encode};
 use failure::ResultExt;
@@ -24,15 +29,67 @@ use rocket::{
 };
 
 /// Get the query history according to the specified parameters
-#[get("/stats/history?<params..>")]
+#[get("/stats/history?<query_type>&<params..>", rank = 2)]
 pub fn history(
+    timer: RequestTimer,
+    _auth: User,
+    ftl_memory: State<FtlMemory>,
+    env: State<Env>,
+    query_type: Option<Result<FtlQueryType, &RawStr>>,
+    params: Form<HistoryParams>,
+    db: FtlDatabase
+) -> Reply {
+    reply_timed(
+        with_query_type(params, query_type)
+            .and_then(|params| get_history(&ftl_memory, &env, params, &db)),
+        timer
+    )
+}
+
+/// Get the query history according to the specified parameters, in CSV format.
+/// This is used instead of the JSON history when the client prefers CSV. The
+/// cursor of the next page is sent in the `X-Pi-hole-Cursor` header.
+#[get("/stats/history?<query_type>&<params..>", rank = 1)]
+pub fn history_csv(
+    _csv: AcceptCsv,
     _auth: User,
     ftl_memory: State<FtlMemory>,
     env: State<Env>,
+    query_type: Option<Result<FtlQueryType, &RawStr>>,
     params: Form<HistoryParams>,
     db: FtlDatabase
+) -> CsvPageReply {
+    get_history_csv(&ftl_memory, &env, with_query_type(params, query_type)?, &db)
+}
+
+/// Add the query type parameter to the history parameters. The query type is
+/// parsed separately so that an invalid query type is a bad request, instead
+/// of being ignored like other invalid optional parameters.
+fn with_query_type(
+    params: Form<HistoryParams>,
+    query_type: Option<Result<FtlQueryType, &RawStr>>
+) -> Result<HistoryParams, Error> {
+    let mut params = params.into_inner();
+
+    params.query_type = query_type
+        .transpose()
+        .map_err(|_| Error::from(ErrorKind::InvalidQueryType))?;
+
+    Ok(params)
+}
+
+/// Get a page of the query history from the database, using the ID of the
+/// last query of the previous page to find the next page
+#[get("/stats/database/history?<after_id>&<limit>")]
+pub fn history_db(
+    timer: RequestTimer,
+    _auth: User,
+    env: State<Env>,
+    after_id: Option<i64>,
+    limit: Option<usize>,
+    db: FtlDatabase
 ) -> Reply {
-    get_history(&ftl_memory, &env, params.into_inner(), &db)
+    reply_timed(get_history_db(&env, after_id, limit, &db), timer)
 }
 
 /// Represents the possible GET parameters on `/stats/history`
@@ -42,14 +99,25 @@ pub struct HistoryParams {
     pub from: Option<u64>,
     pub until: Option<u64>,
     pub domain: Option<String>,
+    pub domain_filter: Option<String>,
     pub client: Option<String>,
     pub upstream: Option<String>,
+    /// This is parsed by the endpoints, see [`with_query_type`]
+    ///
+    /// [`with_query_type`]: fn.with_query_type.html
     pub query_type: Option<FtlQueryType>,
     pub status: Option<FtlQueryStatus>,
+    /// A shortcut for filtering by status: `true` shows only queries with a
+    /// blocked status, and `false` shows only permitted queries
     pub blocked: Option<bool>,
     pub dnssec: Option<FtlDnssecType>,
     pub reply: Option<FtlQueryReplyType>,
-    pub limit: Option<usize>
+    pub limit: Option<usize>,
+    /// This is read by [`RequestTimer`], and is only here so that it is not
+    /// an unknown parameter
+    ///
+    /// [`RequestTimer`]: ../../../util/struct.RequestTimer.html
+    pub time_format: Option<TimeFormat>
 }
 
 impl Default for HistoryParams {
@@ -59,6 +127,7 @@ impl Default for HistoryParams {
             from: None,
             until: None,
             domain: None,
+            domain_filter: None,
             client: None,
             upstream: None,
             query_type: None,
@@ -66,7 +135,8 @@ impl Default for HistoryParams {
             blocked: None,
             dnssec: None,
             reply: None,
-            limit: Some(100)
+            limit: Some(100),
+            time_format: None
         }
     }
 }
@@ -17,7 +17,9 @@ use crate::{
 use diesel::{prelude::*, sqlite::Sqlite};
 use std::{collections::HashSet, iter};
 
-/// Only show queries of the specified client
+/// Only show queries of the specified client. The client is resolved by its
+/// IP address or name, with names compared case insensitively like the
+/// excluded clients.
 pub fn filter_client<'a>(
     queries_iter: Box<dyn Iterator<Item = &'a FtlQuery> + 'a>,
     params: &HistoryParams,
@@ -25,6 +27,8 @@ pub fn filter_client<'a>(
     ftl_lock: &ShmLockGuard<'a>
 ) -> Result<Box<dyn Iterator<Item = &'a FtlQuery> + 'a>, Error> {
     if let Some(ref client_filter) = params.client {
+        let client_filter = client_filter.to_lowercase();
+
         // Find the matching clients. If none are found, return an empty
         // iterator because no query can match the client requested
         let counters = ftl_memory.counters(ftl_lock)?;
@@ -36,9 +40,9 @@ pub fn filter_client<'a>(
             .enumerate()
             .filter_map(|(i, client)| {
                 let ip = client.get_ip(&strings);
-                let name = client.get_name(&strings).unwrap_or_default();
+                let name = client.get_name(&strings).unwrap_or_default().to_lowercase();
 
-                if ip.contains(client_filter) || name.contains(client_filter) {
+                if ip.contains(&client_filter) || name.contains(&client_filter) {
                     Some(i)
                 } else {
                     None
@@ -148,6 +152,45 @@ mod test {
         assert_eq!(filtered_queries, expected_queries);
     }
 
+    /// Client names are matched case insensitively
+    #[test]
+    fn name_case_insensitive() {
+        let queries = test_queries();
+        let expected_queries = vec![&queries[0], &queries[1], &queries[2]];
+        let filtered_queries: Vec<&FtlQuery> = filter_client(
+            Box::new(queries.iter()),
+            &HistoryParams {
+                client: Some("Client1".to_owned()),
+                ..HistoryParams::default()
+            },
+            &test_memory(),
+            &ShmLockGuard::Test
+        )
+        .unwrap()
+        .collect();
+
+        assert_eq!(filtered_queries, expected_queries);
+    }
+
+    /// A client which is not known has no queries, instead of being an error
+    #[test]
+    fn unknown_client() {
+        let queries = test_queries();
+        let filtered_queries: Vec<&FtlQuery> = filter_client(
+            Box::new(queries.iter()),
+            &HistoryParams {
+                client: Some("10.9.9.9".to_owned()),
+                ..HistoryParams::default()
+            },
+            &test_memory(),
+            &ShmLockGuard::Test
+        )
+        .unwrap()
+        .collect();
+
+        assert!(filtered_queries.is_empty());
+    }
+
     /// Only return queries from the specified client name. This test uses
     /// substring matching.
     #[test]
@@ -0,0 +1,152 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// Query History Domain Search Filter
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    databases::ftl::queries,
+    ftl::{FtlMemory, FtlQuery, ShmLockGuard},
+    routes::stats::history::endpoints::HistoryParams,
+    util::Error
+};
+use diesel::{prelude::*, sqlite::Sqlite};
+use std::{collections::HashSet, iter};
+
+/// Only show queries whose domain contains the search text, ignoring case.
+/// Each domain string is lowercased and checked once, and the queries are
+/// then matched by their domain ID.
+pub fn filter_domain_search<'a>(
+    queries_iter: Box<dyn Iterator<Item = &'a FtlQuery> + 'a>,
+    params: &HistoryParams,
+    ftl_memory: &FtlMemory,
+    ftl_lock: &ShmLockGuard<'a>
+) -> Result<Box<dyn Iterator<Item = &'a FtlQuery> + 'a>, Error> {
+    if let Some(ref domain_filter) = params.domain_filter {
+        let domain_filter = domain_filter.to_lowercase();
+
+        // Find the matching domains. If none are found, return an empty
+        // iterator because no query can match the search
+        let counters = ftl_memory.counters(ftl_lock)?;
+        let strings = ftl_memory.strings(ftl_lock)?;
+        let domains = ftl_memory.domains(ftl_lock)?;
+        let domain_ids: HashSet<usize> = domains
+            .iter()
+            .take(counters.total_domains as usize)
+            .enumerate()
+            .filter_map(|(i, domain)| {
+                if domain
+                    .get_domain(&strings)
+                    .to_lowercase()
+                    .contains(&domain_filter)
+                {
+                    Some(i)
+                } else {
+                    None
+                }
+            })
+            .collect();
+
+        if !domain_ids.is_empty() {
+            Ok(Box::new(queries_iter.filter(move |query| {
+                domain_ids.contains(&(query.domain_id as usize))
+            })))
+        } else {
+            Ok(Box::new(iter::empty()))
+        }
+    } else {
+        Ok(queries_iter)
+    }
+}
+
+/// Only show queries whose domain contains the search text in database
+/// results. SQLite's `LIKE` already ignores case.
+pub fn filter_domain_search_db<'a>(
+    db_query: queries::BoxedQuery<'a, Sqlite>,
+    params: &HistoryParams
+) -> queries::BoxedQuery<'a, Sqlite> {
+    // Use the Diesel DSL of this table for easy querying
+    use self::queries::dsl::*;
+
+    if let Some(ref domain_filter) = params.domain_filter {
+        db_query.filter(domain.like(format!("%{}%", domain_filter)))
+    } else {
+        db_query
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{filter_domain_search, filter_domain_search_db};
+    use crate::{
+        databases::ftl::connect_to_test_db,
+        ftl::{FtlQuery, ShmLockGuard},
+        routes::stats::history::{
+            database::execute_query,
+            endpoints::HistoryParams,
+            testing::{test_memory, test_queries}
+        }
+    };
+    use diesel::prelude::*;
+
+    /// Domains are matched by substring, ignoring case
+    #[test]
+    fn case_insensitive() {
+        let queries = test_queries();
+        let expected_queries = vec![&queries[3]];
+        let filtered_queries: Vec<&FtlQuery> = filter_domain_search(
+            Box::new(queries.iter()),
+            &HistoryParams {
+                domain_filter: Some("MAIN2".to_owned()),
+                ..HistoryParams::default()
+            },
+            &test_memory(),
+            &ShmLockGuard::Test
+        )
+        .unwrap()
+        .collect();
+
+        assert_eq!(filtered_queries, expected_queries);
+    }
+
+    /// No queries are returned if no domain matches
+    #[test]
+    fn no_match() {
+        let queries = test_queries();
+        let filtered_queries: Vec<&FtlQuery> = filter_domain_search(
+            Box::new(queries.iter()),
+            &HistoryParams {
+                domain_filter: Some("doubleclick".to_owned()),
+                ..HistoryParams::default()
+            },
+            &test_memory(),
+            &ShmLockGuard::Test
+        )
+        .unwrap()
+        .collect();
+
+        assert!(filtered_queries.is_empty());
+    }
+
+    /// Only queries with domains containing the search text are returned,
+    /// ignoring case. This is a database filter.
+    #[test]
+    fn database() {
+        use crate::databases::ftl::queries::dsl::*;
+
+        let params = HistoryParams {
+            domain_filter: Some("GOOG".to_owned()),
+            ..HistoryParams::default()
+        };
+
+        let db_query = filter_domain_search_db(queries.into_boxed(), &params);
+        let filtered_queries = execute_query(&connect_to_test_db(), db_query).unwrap();
+
+        assert_eq!(filtered_queries.len(), 1);
+        assert_eq!(filtered_queries[0].domain, "google.com");
+    }
+}
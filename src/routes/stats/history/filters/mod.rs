@@ -12,6 +12,7 @@ mod blocked;
 mod client;
 mod dnssec;
 mod domain;
+mod domain_search;
 mod exclude_clients;
 mod exclude_domains;
 mod private;
@@ -23,6 +24,7 @@ mod time;
 mod upstream;
 
 pub use self::{
-    blocked::*, client::*, dnssec::*, domain::*, exclude_clients::*, exclude_domains::*,
-    private::*, query_type::*, reply::*, setup_vars::*, status::*, time::*, upstream::*
+    blocked::*, client::*, dnssec::*, domain::*, domain_search::*, exclude_clients::*,
+    exclude_domains::*, private::*, query_type::*, reply::*, setup_vars::*, status::*, time::*,
+    upstream::*
 };
@@ -17,7 +17,9 @@ use crate::{
 use diesel::{prelude::*, sqlite::Sqlite};
 use std::{collections::HashSet, iter};
 
-/// Only show queries from the specified upstream
+/// Only show queries from the specified upstream. Cached and blocked queries
+/// were not forwarded, so they are only shown by the `blocklist` and `cache`
+/// pseudo-upstreams.
 pub fn filter_upstream<'a>(
     queries_iter: Box<dyn Iterator<Item = &'a FtlQuery> + 'a>,
     params: &HistoryParams,
@@ -60,7 +62,7 @@ pub fn filter_upstream<'a>(
 
             if !upstream_ids.is_empty() {
                 Ok(Box::new(queries_iter.filter(move |query| {
-                    upstream_ids.contains(&(query.upstream_id as usize))
+                    is_forwarded(query) && upstream_ids.contains(&(query.upstream_id as usize))
                 })))
             } else {
                 Ok(Box::new(iter::empty()))
@@ -71,6 +73,15 @@ pub fn filter_upstream<'a>(
     }
 }
 
+/// Check if the query was forwarded to an upstream. Externally blocked
+/// queries were blocked by the upstream they were forwarded to.
+fn is_forwarded(query: &FtlQuery) -> bool {
+    match query.status {
+        FtlQueryStatus::Forward | FtlQueryStatus::ExternalBlock => true,
+        _ => false
+    }
+}
+
 /// Only show queries from the specified upstream in database results
 pub fn filter_upstream_db<'a>(
     db_query: queries::BoxedQuery<'a, Sqlite>,
@@ -182,6 +193,27 @@ mod test {
         assert_eq!(filtered_queries, expected_queries);
     }
 
+    /// Cached and blocked queries are not returned, even though their upstream
+    /// ID is the ID of the upstream
+    #[test]
+    fn forwarded_only() {
+        let queries = test_queries();
+        let expected_queries = vec![&queries[0], &queries[1], &queries[2], &queries[8]];
+        let filtered_queries: Vec<&FtlQuery> = filter_upstream(
+            Box::new(queries.iter()),
+            &HistoryParams {
+                upstream: Some("8.8.8.8".to_owned()),
+                ..HistoryParams::default()
+            },
+            &test_memory(),
+            &ShmLockGuard::Test
+        )
+        .unwrap()
+        .collect();
+
+        assert_eq!(filtered_queries, expected_queries);
+    }
+
     /// Only queries with an upstream similar to the input are returned. This is
     /// a database filter.
     #[test]
@@ -18,31 +18,121 @@ use crate::{
     databases::ftl::FtlDatabase,
     env::Env,
     ftl::{FtlMemory, FtlQuery},
-    routes::stats::history::database::load_queries_from_database,
-    settings::{ConfigEntry, FtlConfEntry, FtlPrivacyLevel},
-    util::{reply_data, Reply}
+    routes::stats::{
+        common::limit_results,
+        history::database::{load_queries_after_id, load_queries_from_database}
+    },
+    settings::{ConfigEntry, FtlConfEntry, FtlPrivacyLevel, SetupVarsEntry},
+    util::{reply_csv, CsvPage, CsvPageReply, Error}
 };
 use diesel::sqlite::SqliteConnection;
 use rocket_contrib::json::JsonValue;
 use std::time::{SystemTime, UNIX_EPOCH};
 
+/// The columns of the CSV history. These match the fields of the JSON queries.
+const HISTORY_CSV_COLUMNS: [&str; 9] = [
+    "timestamp",
+    "type",
+    "status",
+    "domain",
+    "client",
+    "upstream",
+    "dnssec",
+    "reply",
+    "response_time"
+];
+
 /// Get the query history according to the specified parameters
 pub fn get_history(
     ftl_memory: &FtlMemory,
     env: &Env,
     params: HistoryParams,
     db: &FtlDatabase
-) -> Reply {
+) -> Result<JsonValue, Error> {
+    let (cursor, history) = load_history(ftl_memory, env, params, db)?;
+
+    Ok(json!({
+        "cursor": cursor,
+        "history": history
+    }))
+}
+
+/// Get the query history according to the specified parameters, in CSV
+/// format. Like the JSON history, only one page of up to `limit` queries is
+/// returned. The cursor of the next page is sent in the `X-Pi-hole-Cursor`
+/// header, and can be passed back as the `cursor` parameter.
+pub fn get_history_csv(
+    ftl_memory: &FtlMemory,
+    env: &Env,
+    params: HistoryParams,
+    db: &FtlDatabase
+) -> CsvPageReply {
+    let (cursor, history) = load_history(ftl_memory, env, params, db)?;
+
+    Ok(CsvPage {
+        csv: reply_csv(&HISTORY_CSV_COLUMNS, &history)?,
+        cursor
+    })
+}
+
+/// Get a page of the query history from the database, starting after the
+/// query with the ID `after_id`. The ID of the last query is returned so the
+/// next page can be loaded, along with whether there are more queries.
+pub fn get_history_db(
+    env: &Env,
+    after_id: Option<i64>,
+    limit: Option<usize>,
+    db: &FtlDatabase
+) -> Result<JsonValue, Error> {
     // Check if query details are private
     if FtlConfEntry::PrivacyLevel.read_as::<FtlPrivacyLevel>(env)? >= FtlPrivacyLevel::Maximum {
-        // `None::<()>` represents `null` in JSON. It needs the type parameter because
-        // it doesn't know what type of Option it is (`Option<T>`)
-        return reply_data(json!({
-            "cursor": None::<()>,
-            "history": []
+        return Ok(json!({
+            "history": [],
+            "last_id": None::<i64>,
+            "more": false
         }));
     }
 
+    let limit = limit_results(limit.unwrap_or(100), env)?;
+    let (db_queries, more) = load_queries_after_id(db as &SqliteConnection, after_id, env, limit)?;
+
+    // Continue from the given ID if this page is empty
+    let last_id = db_queries
+        .last()
+        .and_then(|query| query.id)
+        .map(i64::from)
+        .or(after_id);
+    let history: Vec<JsonValue> = db_queries.into_iter().map(Into::into).collect();
+
+    Ok(json!({
+        "history": history,
+        "last_id": last_id,
+        "more": more
+    }))
+}
+
+/// Load the query history according to the specified parameters. The next
+/// cursor (if there are more queries) and the queries in JSON form are
+/// returned.
+fn load_history(
+    ftl_memory: &FtlMemory,
+    env: &Env,
+    mut params: HistoryParams,
+    db: &FtlDatabase
+) -> Result<(Option<String>, Vec<JsonValue>), Error> {
+    // Check if query details are private
+    if FtlConfEntry::PrivacyLevel.read_as::<FtlPrivacyLevel>(env)? >= FtlPrivacyLevel::Maximum {
+        return Ok((None, Vec::new()));
+    }
+
+    // Without a time range, only show the queries in the default window (if
+    // there is one). The window does not cause a database search, unlike a
+    // time range from the parameters.
+    let has_time_range = params.from.is_some() || params.until.is_some();
+    if !has_time_range {
+        params.from = get_default_window_start(env)?;
+    }
+
     let lock = ftl_memory.lock()?;
     let counters = ftl_memory.counters(&lock)?;
     let queries = ftl_memory.queries(&lock)?;
@@ -84,6 +174,7 @@ pub fn get_history(
     let queries_iter = filter_query_type(queries_iter, &params);
     let queries_iter = filter_upstream(queries_iter, &params, ftl_memory, &lock)?;
     let queries_iter = filter_domain(queries_iter, &params, ftl_memory, &lock)?;
+    let queries_iter = filter_domain_search(queries_iter, &params, ftl_memory, &lock)?;
     let queries_iter = filter_client(queries_iter, &params, ftl_memory, &lock)?;
     let queries_iter = filter_status(queries_iter, &params);
     let queries_iter = filter_blocked(queries_iter, &params);
@@ -93,7 +184,7 @@ pub fn get_history(
     let queries_iter = filter_excluded_clients(queries_iter, env, ftl_memory, &lock)?;
 
     // Get the limit
-    let limit = params.limit.unwrap_or(100);
+    let limit = limit_results(params.limit.unwrap_or(100), env)?;
 
     // Apply the limit (plus one to get the cursor) and collect the queries
     let history: Vec<&FtlQuery> = queries_iter.take(limit + 1).collect();
@@ -139,14 +230,14 @@ pub fn get_history(
             // Only take up to the limit this time, not including the last query,
             // because it was just used to get the cursor
             .take(limit)
-            .map(map_query_to_json(ftl_memory, &lock)?)
+            .map(map_query_to_json(ftl_memory, &lock, env)?)
             .collect();
 
     // If there are not enough queries to reach the limit (next cursor is null),
     // there is a specified timestamp, and the timespan is not entirely within
     // the last 24 hours, then search the database for more queries.
     let history = if next_cursor.is_none()
-        && (params.from.is_some() || params.until.is_some())
+        && has_time_range
         && !is_within_24_hours(params.from, params.until)
     {
         // Load queries from the database
@@ -165,10 +256,24 @@ pub fn get_history(
         history
     };
 
-    reply_data(json!({
-        "cursor": next_cursor,
-        "history": history
-    }))
+    Ok((next_cursor, history))
+}
+
+/// Get the start of the default history window, which covers the most recent
+/// `API_HISTORY_DEFAULT_WINDOW` seconds. A window of 0 is unlimited.
+fn get_default_window_start(env: &Env) -> Result<Option<u64>, Error> {
+    let window = SetupVarsEntry::ApiHistoryDefaultWindow.read_as::<u64>(env)?;
+
+    if window == 0 {
+        return Ok(None);
+    }
+
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Current time is older than epoch")
+        .as_secs();
+
+    Ok(Some(now.saturating_sub(window)))
 }
 
 /// Check if the timespan is completely within the last 24 hours
@@ -198,14 +303,17 @@ fn is_within_24_hours(from: Option<u64>, until: Option<u64>) -> bool {
 mod test {
     use crate::{
         env::PiholeFile,
-        ftl::ShmLockGuard,
+        ftl::{FtlQueryType, ShmLockGuard},
         routes::stats::history::{
             map_query_to_json::map_query_to_json,
-            testing::{test_memory, test_queries}
+            testing::{test_env, test_memory, test_queries}
         },
+        setup,
         testing::TestBuilder
     };
+    use rocket::http::{ContentType, Header, Status};
     use rocket_contrib::json::JsonValue;
+    use std::collections::HashMap;
 
     /// The default behavior lists the first 100 non-private queries sorted by
     /// most recent
@@ -220,7 +328,7 @@ mod test {
         let history: Vec<JsonValue> = expected_queries
             .iter()
             .rev()
-            .map(map_query_to_json(&ftl_memory, &ShmLockGuard::Test).unwrap())
+            .map(map_query_to_json(&ftl_memory, &ShmLockGuard::Test, &test_env()).unwrap())
             .collect();
 
         TestBuilder::new()
@@ -228,8 +336,10 @@ mod test {
             .ftl_memory(ftl_memory)
             .need_database(true)
             .expect_json(json!({
-                "history": history,
-                "cursor": None::<()>
+                "data": {
+                    "history": history,
+                    "cursor": None::<()>
+                }
             }))
             .test();
     }
@@ -247,7 +357,7 @@ mod test {
             .iter()
             .rev()
             .take(5)
-            .map(map_query_to_json(&ftl_memory, &ShmLockGuard::Test).unwrap())
+            .map(map_query_to_json(&ftl_memory, &ShmLockGuard::Test, &test_env()).unwrap())
             .collect();
 
         TestBuilder::new()
@@ -255,12 +365,272 @@ mod test {
             .ftl_memory(ftl_memory)
             .need_database(true)
             .expect_json(json!({
-                "history": history,
-                "cursor": "eyJpZCI6bnVsbCwiZGJfaWQiOjk3fQ=="
+                "data": {
+                    "history": history,
+                    "cursor": "eyJpZCI6bnVsbCwiZGJfaWQiOjk3fQ=="
+                }
+            }))
+            .test();
+    }
+
+    /// Without a time range, only queries in the default window are shown.
+    /// The test queries are all older than the window.
+    #[test]
+    fn default_window() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/history")
+            .ftl_memory(test_memory())
+            .file(PiholeFile::SetupVars, "API_HISTORY_DEFAULT_WINDOW=3600")
+            .need_database(true)
+            .expect_json(json!({
+                "data": {
+                    "history": [],
+                    "cursor": None::<()>
+                }
+            }))
+            .test();
+    }
+
+    /// A time range in the parameters overrides the default window
+    #[test]
+    fn default_window_overridden() {
+        let ftl_memory = test_memory();
+        let mut expected_queries = test_queries();
+
+        // The private query should be ignored
+        expected_queries.remove(8);
+
+        let history: Vec<JsonValue> = expected_queries
+            .iter()
+            .rev()
+            .take(5)
+            .map(map_query_to_json(&ftl_memory, &ShmLockGuard::Test, &test_env()).unwrap())
+            .collect();
+
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/history?from=0&limit=5")
+            .ftl_memory(ftl_memory)
+            .file(PiholeFile::SetupVars, "API_HISTORY_DEFAULT_WINDOW=3600")
+            .need_database(true)
+            .expect_json(json!({
+                "data": {
+                    "history": history,
+                    "cursor": "eyJpZCI6bnVsbCwiZGJfaWQiOjk3fQ=="
+                }
+            }))
+            .test();
+    }
+
+    /// The cursor from a previous page continues the history from where that
+    /// page stopped, and the cursor is null once the end is reached
+    #[test]
+    fn cursor_next_page() {
+        let ftl_memory = test_memory();
+        let expected_queries = test_queries();
+
+        // Queries 1 through 3 are the remaining queries after the first page
+        let history: Vec<JsonValue> = expected_queries
+            .iter()
+            .take(3)
+            .rev()
+            .map(map_query_to_json(&ftl_memory, &ShmLockGuard::Test, &test_env()).unwrap())
+            .collect();
+
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/history?limit=5&cursor=eyJpZCI6bnVsbCwiZGJfaWQiOjk3fQ==")
+            .ftl_memory(ftl_memory)
+            .need_database(true)
+            .expect_json(json!({
+                "data": {
+                    "history": history,
+                    "cursor": None::<()>
+                }
+            }))
+            .test();
+    }
+
+    /// A cursor which references a query no longer in memory returns an empty
+    /// page instead of an error
+    #[test]
+    fn stale_cursor() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/history?cursor=eyJpZCI6MTAwMCwiZGJfaWQiOm51bGx9")
+            .ftl_memory(test_memory())
+            .need_database(true)
+            .expect_json(json!({
+                "data": {
+                    "history": [],
+                    "cursor": None::<()>
+                }
+            }))
+            .test();
+    }
+
+    /// Query types can be filtered by name, and combined with other filters
+    #[test]
+    fn query_type_name_and_client() {
+        let ftl_memory = test_memory();
+        let expected_queries = test_queries();
+
+        // Queries 6 and 5 are the AAAA queries from 192.168.1.11
+        let history: Vec<JsonValue> = [5, 4]
+            .iter()
+            .map(|&i| &expected_queries[i])
+            .map(map_query_to_json(&ftl_memory, &ShmLockGuard::Test, &test_env()).unwrap())
+            .collect();
+
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/history?query_type=aaaa&client=192.168.1.11")
+            .ftl_memory(ftl_memory)
+            .need_database(true)
+            .expect_json(json!({
+                "data": {
+                    "history": history,
+                    "cursor": None::<()>
+                }
+            }))
+            .test();
+    }
+
+    /// Query types can be filtered by number
+    #[test]
+    fn query_type_number() {
+        let ftl_memory = test_memory();
+        let expected_queries = test_queries();
+
+        let history: Vec<JsonValue> = expected_queries
+            .iter()
+            .rev()
+            .filter(|query| query.query_type == FtlQueryType::AAAA)
+            .map(map_query_to_json(&ftl_memory, &ShmLockGuard::Test, &test_env()).unwrap())
+            .collect();
+
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/history?query_type=2")
+            .ftl_memory(ftl_memory)
+            .need_database(true)
+            .expect_json(json!({
+                "data": {
+                    "history": history,
+                    "cursor": None::<()>
+                }
+            }))
+            .test();
+    }
+
+    /// The blocked shortcut only shows queries with a blocked status
+    #[test]
+    fn blocked_only() {
+        let ftl_memory = test_memory();
+        let expected_queries = test_queries();
+
+        let history: Vec<JsonValue> = expected_queries
+            .iter()
+            .rev()
+            .filter(|query| query.is_blocked() && !query.is_private)
+            .map(map_query_to_json(&ftl_memory, &ShmLockGuard::Test, &test_env()).unwrap())
+            .collect();
+
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/history?blocked=true")
+            .ftl_memory(ftl_memory)
+            .need_database(true)
+            .expect_json(json!({
+                "data": {
+                    "history": history,
+                    "cursor": None::<()>
+                }
+            }))
+            .test();
+    }
+
+    /// The blocked shortcut can show only permitted queries, and is combined
+    /// with other filters
+    #[test]
+    fn permitted_and_client() {
+        let ftl_memory = test_memory();
+        let expected_queries = test_queries();
+
+        // Client 1 is 192.168.1.11
+        let history: Vec<JsonValue> = expected_queries
+            .iter()
+            .rev()
+            .filter(|query| !query.is_blocked() && !query.is_private && query.client_id == 1)
+            .map(map_query_to_json(&ftl_memory, &ShmLockGuard::Test, &test_env()).unwrap())
+            .collect();
+
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/history?blocked=false&client=192.168.1.11")
+            .ftl_memory(ftl_memory)
+            .need_database(true)
+            .expect_json(json!({
+                "data": {
+                    "history": history,
+                    "cursor": None::<()>
+                }
+            }))
+            .test();
+    }
+
+    /// An unknown query type is a bad request, and the valid types are listed
+    #[test]
+    fn query_type_invalid() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/history?query_type=MX")
+            .ftl_memory(test_memory())
+            .need_database(true)
+            .expect_status(Status::BadRequest)
+            .expect_json(json!({
+                "error": {
+                    "key": "invalid_query_type",
+                    "message": "Invalid query type",
+                    "data": {
+                        "options": ["A", "AAAA", "ANY", "SRV", "SOA", "PTR", "TXT"]
+                    }
+                }
             }))
             .test();
     }
 
+    /// The history is in CSV format if the client prefers CSV. The cursor of
+    /// the next page is in a header.
+    #[test]
+    fn csv() {
+        let client = setup::test(HashMap::new(), test_memory(), HashMap::new(), true);
+        let mut response = client
+            .get("/admin/api/stats/history?limit=2")
+            .header(Header::new("X-Pi-hole-Authenticate", "test_key"))
+            .header(Header::new("Accept", "text/csv"))
+            .dispatch();
+
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.content_type(), Some(ContentType::CSV));
+        assert_eq!(
+            response.body_string().unwrap(),
+            "timestamp,type,status,domain,client,upstream,dnssec,reply,response_time\n\
+             263586,2,6,\"domain5.com\",\"192.168.1.12\",\"google-public-dns-b.google.com\",0,4,1\n\
+             263585,1,5,\"domain4.com\",\"192.168.1.12\",,0,4,1\n"
+        );
+        assert_eq!(
+            response.headers().get_one("X-Pi-hole-Cursor"),
+            Some("eyJpZCI6bnVsbCwiZGJfaWQiOjEwMH0=")
+        );
+    }
+
+    /// The last CSV page has no cursor header
+    #[test]
+    fn csv_last_page() {
+        let client = setup::test(HashMap::new(), test_memory(), HashMap::new(), true);
+        let response = client
+            .get("/admin/api/stats/history")
+            .header(Header::new("X-Pi-hole-Authenticate", "test_key"))
+            .header(Header::new("Accept", "text/csv"))
+            .dispatch();
+
+        assert_eq!(response.status(), Status::Ok);
+        assert_eq!(response.headers().get_one("X-Pi-hole-Cursor"), None);
+    }
+
     /// Maximum privacy shows no queries
     #[test]
     fn privacy_max() {
@@ -270,8 +640,70 @@ mod test {
             .ftl_memory(test_memory())
             .need_database(true)
             .expect_json(json!({
-                "history": [],
-                "cursor": None::<()>
+                "data": {
+                    "history": [],
+                    "cursor": None::<()>
+                }
+            }))
+            .test();
+    }
+
+    /// A page of database queries continues after the given ID
+    #[test]
+    fn database_page() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/database/history?after_id=92&limit=1")
+            .need_database(true)
+            .expect_json(json!({
+                "data": {
+                    "history": [
+                        {
+                            "timestamp": 177_180,
+                            "type": 6,
+                            "status": 3,
+                            "status_name": "cached",
+                            "domain": "1.1.1.10.in-addr.arpa",
+                            "client": "127.0.0.1",
+                            "upstream": None::<()>,
+                            "dnssec": 5,
+                            "reply": 0,
+                            "reply_name": "unknown",
+                            "response_time": 0
+                        }
+                    ],
+                    "last_id": 93,
+                    "more": true
+                }
+            }))
+            .test();
+    }
+
+    /// The last page of database queries shows there are no more queries
+    #[test]
+    fn database_last_page() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/database/history?after_id=93")
+            .need_database(true)
+            .expect_json(json!({
+                "data": {
+                    "history": [
+                        {
+                            "timestamp": 177_180,
+                            "type": 6,
+                            "status": 2,
+                            "status_name": "forwarded",
+                            "domain": "4.4.8.8.in-addr.arpa",
+                            "client": "127.0.0.1",
+                            "upstream": "8.8.4.4",
+                            "dnssec": 5,
+                            "reply": 0,
+                            "reply_name": "unknown",
+                            "response_time": 0
+                        }
+                    ],
+                    "last_id": 94,
+                    "more": false
+                }
             }))
             .test();
     }
@@ -284,29 +716,37 @@ mod test {
             .ftl_memory(test_memory())
             .need_database(true)
             .expect_json(json!({
-                "history": [
-                    {
-                        "timestamp": 177_180,
-                        "type": 6,
-                        "status": 2,
-                        "domain": "4.4.8.8.in-addr.arpa",
-                        "client": "127.0.0.1",
-                        "dnssec": 5,
-                        "reply": 0,
-                        "response_time": 0
-                    },
-                    {
-                        "timestamp": 177_180,
-                        "type": 6,
-                        "status": 3,
-                        "domain": "1.1.1.10.in-addr.arpa",
-                        "client": "127.0.0.1",
-                        "dnssec": 5,
-                        "reply": 0,
-                        "response_time": 0
-                    }
-                ],
-                "cursor": None::<()>
+                "data": {
+                    "history": [
+                        {
+                            "timestamp": 177_180,
+                            "type": 6,
+                            "status": 2,
+                            "status_name": "forwarded",
+                            "domain": "4.4.8.8.in-addr.arpa",
+                            "client": "127.0.0.1",
+                            "upstream": "8.8.4.4",
+                            "dnssec": 5,
+                            "reply": 0,
+                            "reply_name": "unknown",
+                            "response_time": 0
+                        },
+                        {
+                            "timestamp": 177_180,
+                            "type": 6,
+                            "status": 3,
+                            "status_name": "cached",
+                            "domain": "1.1.1.10.in-addr.arpa",
+                            "client": "127.0.0.1",
+                            "upstream": None::<()>,
+                            "dnssec": 5,
+                            "reply": 0,
+                            "reply_name": "unknown",
+                            "response_time": 0
+                        }
+                    ],
+                    "cursor": None::<()>
+                }
             }))
             .test();
     }
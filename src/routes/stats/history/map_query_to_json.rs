@@ -9,19 +9,27 @@
 // Please see LICENSE file for your rights under this license.
 
 use crate::{
-    ftl::{FtlMemory, FtlQuery, ShmLockGuard},
+    env::Env,
+    ftl::{FtlMemory, FtlQuery, FtlQueryStatus, ShmLockGuard},
+    routes::dns::list::List,
     util::Error
 };
+use regex::Regex;
 use rocket_contrib::json::JsonValue;
 
 /// Create a function to map `FtlQuery` structs to JSON `Value` structs.
+/// Queries blocked by a regex include the ID and pattern of the regex which
+/// matched the domain. The upstream is null unless the query was forwarded.
 pub fn map_query_to_json<'a>(
     ftl_memory: &'a FtlMemory,
-    ftl_lock: &ShmLockGuard<'a>
+    ftl_lock: &ShmLockGuard<'a>,
+    env: &Env
 ) -> Result<impl Fn(&FtlQuery) -> JsonValue + 'a, Error> {
     let domains = ftl_memory.domains(ftl_lock)?;
     let clients = ftl_memory.clients(ftl_lock)?;
+    let upstreams = ftl_memory.upstreams(ftl_lock)?;
     let strings = ftl_memory.strings(ftl_lock)?;
+    let regexes = get_regexes(env)?;
 
     Ok(move |query: &FtlQuery| {
         let domain = domains[query.domain_id as usize].get_domain(&strings);
@@ -32,6 +40,18 @@ pub fn map_query_to_json<'a>(
             .get_name(&strings)
             .unwrap_or_else(|| client.get_ip(&strings));
 
+        // The upstream is shown by name if it has one, like the client
+        let upstream = match query.status {
+            FtlQueryStatus::Forward | FtlQueryStatus::ExternalBlock => {
+                upstreams.get(query.upstream_id as usize).map(|upstream| {
+                    upstream
+                        .get_name(&strings)
+                        .unwrap_or_else(|| upstream.get_ip(&strings))
+                })
+            }
+            _ => None
+        };
+
         // Check if response was received (response time should be smaller than 30min)
         let response_time = if query.response_time < 18_000_000 {
             query.response_time
@@ -39,33 +59,78 @@ pub fn map_query_to_json<'a>(
             0
         };
 
-        json!({
+        let mut json = json!({
             "timestamp": query.timestamp,
             "type": query.query_type as u8,
             "status": query.status as u8,
+            "status_name": query.status.get_name(),
             "domain": domain,
             "client": client,
+            "upstream": upstream,
             "dnssec": query.dnssec_type as u8,
             "reply": query.reply_type as u8,
+            "reply_name": query.reply_type.get_name(),
             "response_time": response_time
-        })
+        });
+
+        if query.status == FtlQueryStatus::Wildcard {
+            if let Some((id, (pattern, _))) = regexes.iter().enumerate().find(|(_, (_, regex))| {
+                regex.as_ref().map_or(false, |regex| regex.is_match(domain))
+            }) {
+                json["regex"] = json!({ "id": id, "pattern": pattern }).into();
+            }
+        }
+
+        json
     })
 }
 
+/// Get the enabled regexes with their patterns. A regex's ID is its index in
+/// the enabled regexes, which is the order FTL compiles them in. Regexes which
+/// do not compile are kept so the IDs still line up, but never match.
+fn get_regexes(env: &Env) -> Result<Vec<(String, Option<Regex>)>, Error> {
+    Ok(List::Regex
+        .get(env)?
+        .into_iter()
+        .map(|pattern| {
+            let regex = Regex::new(&pattern).ok();
+
+            (pattern, regex)
+        })
+        .collect())
+}
+
 #[cfg(test)]
 mod test {
     use super::map_query_to_json;
     use crate::{
+        env::{Config, Env, PiholeFile},
         ftl::ShmLockGuard,
-        routes::stats::history::testing::{test_memory, test_queries}
+        routes::stats::history::testing::{test_memory, test_queries},
+        testing::TestEnvBuilder
     };
 
+    /// An environment with a regex list. Only the last enabled regex matches
+    /// the regex-blocked test query.
+    fn regex_env() -> Env {
+        Env::Test(
+            Config::default(),
+            TestEnvBuilder::new()
+                .file(
+                    PiholeFile::Regexlist,
+                    "^domain1\\.com$\n#^domain3\n(\n^domain3\\.com$\n"
+                )
+                .build()
+        )
+    }
+
     /// Verify that queries are mapped to JSON correctly
     #[test]
     fn test_map_query_to_json() {
         let query = test_queries()[0];
         let ftl_memory = test_memory();
-        let map_function = map_query_to_json(&ftl_memory, &ShmLockGuard::Test).unwrap();
+        let map_function =
+            map_query_to_json(&ftl_memory, &ShmLockGuard::Test, &regex_env()).unwrap();
         let mapped_query = map_function(&query);
 
         assert_eq!(
@@ -74,12 +139,41 @@ mod test {
                 "timestamp": 263_581,
                 "type": 1,
                 "status": 2,
+                "status_name": "forwarded",
                 "domain": "domain1.com",
                 "client": "client1",
+                "upstream": "google-public-dns-a.google.com",
                 "dnssec": 1,
                 "reply": 3,
+                "reply_name": "cname",
                 "response_time": 1
             })
         );
     }
+
+    /// Regex-blocked queries include the ID and pattern of the matching regex.
+    /// Disabled regexes do not count towards the IDs.
+    #[test]
+    fn regex_match() {
+        let query = test_queries()[5];
+        let ftl_memory = test_memory();
+        let map_function =
+            map_query_to_json(&ftl_memory, &ShmLockGuard::Test, &regex_env()).unwrap();
+        let mapped_query = map_function(&query);
+        let expected: serde_json::Value = json!({ "id": 2, "pattern": "^domain3\\.com$" }).into();
+
+        assert_eq!(mapped_query["regex"], expected);
+    }
+
+    /// Queries which were not forwarded have no upstream
+    #[test]
+    fn not_forwarded() {
+        let query = test_queries()[4];
+        let ftl_memory = test_memory();
+        let map_function =
+            map_query_to_json(&ftl_memory, &ShmLockGuard::Test, &regex_env()).unwrap();
+        let mapped_query = map_function(&query);
+
+        assert_eq!(mapped_query["upstream"], serde_json::Value::Null);
+    }
 }
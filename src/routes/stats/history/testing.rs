@@ -8,9 +8,13 @@
 // This file is copyright under the latest version of the EUPL.
 // Please see LICENSE file for your rights under this license.
 
-use crate::ftl::{
-    FtlClient, FtlCounters, FtlDnssecType, FtlDomain, FtlMemory, FtlQuery, FtlQueryReplyType,
-    FtlQueryStatus, FtlQueryType, FtlRegexMatch, FtlSettings, FtlUpstream, MAGIC_BYTE
+use crate::{
+    env::{Config, Env},
+    ftl::{
+        FtlClient, FtlCounters, FtlDnssecType, FtlDomain, FtlMemory, FtlQuery, FtlQueryReplyType,
+        FtlQueryStatus, FtlQueryType, FtlRegexMatch, FtlSettings, FtlUpstream, MAGIC_BYTE
+    },
+    testing::TestEnvBuilder
 };
 use std::collections::HashMap;
 
@@ -172,3 +176,8 @@ pub fn test_strings() -> HashMap<usize, String> {
 
     strings
 }
+
+/// An environment without any files, for mapping the test queries to JSON
+pub fn test_env() -> Env {
+    Env::Test(Config::default(), TestEnvBuilder::new().build())
+}
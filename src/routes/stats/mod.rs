@@ -8,21 +8,42 @@
 // This file is copyright under the latest version of the EUPL.
 // Please see LICENSE file for your rights under this license.
 
+mod active_clients;
+mod client_details;
+mod client_over_time;
+mod client_query_types;
 mod clients;
+mod clients_last_seen;
 mod common;
+mod domain_clients;
+mod flush;
+mod gravity_info;
 mod history;
+mod over_time_block_percentage;
 mod over_time_clients;
 mod over_time_history;
+mod over_time_query_types;
+mod over_time_reply_types;
+mod over_time_upstreams;
+mod privacy_level;
 mod query_types;
+mod rate_limited_clients;
 mod recent_blocked;
 mod summary;
+mod top_blocked_clients;
+mod top_blocked_domains;
 mod top_clients;
 mod top_domains;
+mod unique_counts;
 mod upstreams;
 
 pub mod database;
 
 pub use self::{
-    clients::*, history::*, over_time_clients::*, over_time_history::*, query_types::*,
-    recent_blocked::*, summary::*, top_clients::*, top_domains::*, upstreams::*
+    active_clients::*, client_details::*, client_over_time::*, client_query_types::*, clients::*,
+    clients_last_seen::*, domain_clients::*, flush::*, gravity_info::*, history::*,
+    over_time_block_percentage::*, over_time_clients::*, over_time_history::*,
+    over_time_query_types::*, over_time_reply_types::*, over_time_upstreams::*, privacy_level::*,
+    query_types::*, rate_limited_clients::*, recent_blocked::*, summary::*, top_blocked_clients::*,
+    top_blocked_domains::*, top_clients::*, top_domains::*, unique_counts::*, upstreams::*
 };
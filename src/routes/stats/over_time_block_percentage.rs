@@ -0,0 +1,97 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// Block Percentage Over Time Endpoint
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    ftl::FtlMemory,
+    routes::stats::over_time_history::get_over_time_history,
+    util::{reply_timed, Error, Reply, RequestTimer}
+};
+use rocket::State;
+
+/// Get the percentage of blocked queries over time. The slots are the same as
+/// in `/stats/overTime/history`.
+#[get("/stats/overTime/block_percentage?<interval>")]
+pub fn over_time_block_percentage(
+    timer: RequestTimer,
+    ftl_memory: State<FtlMemory>,
+    interval: Option<usize>
+) -> Reply {
+    reply_timed(get_block_percentage(&ftl_memory, interval), timer)
+}
+
+/// Get the block percentage of each overTime slot
+fn get_block_percentage(
+    ftl_memory: &FtlMemory,
+    interval: Option<usize>
+) -> Result<Vec<BlockPercentageItem>, Error> {
+    Ok(get_over_time_history(ftl_memory, interval)?
+        .into_iter()
+        .map(|item| BlockPercentageItem {
+            timestamp: item.timestamp,
+            // Slots without queries have no blocked queries
+            percentage: if item.total_queries == 0 {
+                0.0
+            } else {
+                item.blocked_queries as f64 / item.total_queries as f64 * 100.0
+            }
+        })
+        .collect())
+}
+
+#[derive(Serialize)]
+#[cfg_attr(test, derive(PartialEq, Debug))]
+pub struct BlockPercentageItem {
+    pub timestamp: u64,
+    pub percentage: f64
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        ftl::{FtlCounters, FtlMemory, FtlOverTime, FtlSettings},
+        testing::TestBuilder
+    };
+    use std::collections::HashMap;
+
+    /// Data for testing over_time_block_percentage
+    fn test_data() -> FtlMemory {
+        FtlMemory::Test {
+            over_time: vec![
+                FtlOverTime::new(1, 4, 1, 0, 3, [0; 7]),
+                FtlOverTime::new(2, 0, 0, 0, 0, [0; 7]),
+                FtlOverTime::new(3, 2, 2, 0, 0, [0; 7]),
+            ],
+            counters: FtlCounters::default(),
+            clients: Vec::new(),
+            upstreams: Vec::new(),
+            strings: HashMap::new(),
+            domains: Vec::new(),
+            queries: Vec::new(),
+            settings: FtlSettings::default()
+        }
+    }
+
+    /// The percentage is calculated per slot, and slots without queries have a
+    /// percentage of zero
+    #[test]
+    fn default_params() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/overTime/block_percentage")
+            .ftl_memory(test_data())
+            .expect_json(json!({
+                "data": [
+                    { "timestamp": 1, "percentage": 25.0 },
+                    { "timestamp": 2, "percentage": 0.0 },
+                    { "timestamp": 3, "percentage": 100.0 }
+                ]
+            }))
+            .test();
+    }
+}
@@ -15,23 +15,33 @@ use crate::{
         auth::User,
         stats::{
             clients::{filter_ftl_clients, ClientParams},
-            common::get_current_over_time_slot
+            common::{anonymize_clients, get_current_over_time_slot}
         }
     },
     settings::{ConfigEntry, FtlConfEntry, FtlPrivacyLevel},
-    util::{reply_data, Reply}
+    util::{reply_timed, Error, Reply, RequestTimer}
 };
 use rocket::State;
 use std::cmp::Ordering;
 
 /// Get the client queries over time
 #[get("/stats/overTime/clients")]
-pub fn over_time_clients(_auth: User, ftl_memory: State<FtlMemory>, env: State<Env>) -> Reply {
+pub fn over_time_clients(
+    timer: RequestTimer,
+    _auth: User,
+    ftl_memory: State<FtlMemory>,
+    env: State<Env>
+) -> Reply {
+    reply_timed(get_over_time_clients(&ftl_memory, &env), timer)
+}
+
+/// Get the number of queries of each client in each overTime slot
+fn get_over_time_clients(ftl_memory: &FtlMemory, env: &Env) -> Result<OverTimeClients, Error> {
     // Check if client details are private
     if FtlConfEntry::PrivacyLevel.read_as::<FtlPrivacyLevel>(&env)?
         >= FtlPrivacyLevel::HideDomainsAndClients
     {
-        return reply_data(OverTimeClients {
+        return Ok(OverTimeClients {
             over_time: Vec::new(),
             clients: Vec::new()
         });
@@ -45,10 +55,10 @@ pub fn over_time_clients(_auth: User, ftl_memory: State<FtlMemory>, env: State<E
 
     // Filter out clients which should not be considered
     let clients = filter_ftl_clients(
-        &ftl_memory,
+        ftl_memory,
         &lock,
         &ftl_clients,
-        &env,
+        env,
         ClientParams::default()
     )?;
 
@@ -80,12 +90,19 @@ pub fn over_time_clients(_auth: User, ftl_memory: State<FtlMemory>, env: State<E
         .collect();
 
     // Convert clients into the output format
-    let clients: Vec<ClientReply> = clients
+    let mut clients: Vec<ClientReply> = clients
         .into_iter()
         .map(|client| client.as_reply(&strings))
         .collect();
 
-    reply_data(OverTimeClients { over_time, clients })
+    anonymize_clients(
+        clients
+            .iter_mut()
+            .map(|client| (&mut client.name, &mut client.ip)),
+        env
+    )?;
+
+    Ok(OverTimeClients { over_time, clients })
 }
 
 /// Represents an overTime client item, which holds time and client data for an
@@ -180,17 +197,19 @@ mod test {
             .ftl_memory(test_data())
             .file(PiholeFile::SetupVars, "API_EXCLUDE_CLIENTS=client1")
             .expect_json(json!({
-                "clients": [
-                    { "name": "",        "ip": "10.1.1.2" },
-                    { "name": "client3", "ip": "10.1.1.3" },
-                    { "name": "",        "ip": "10.1.1.4" },
-                    { "name": "",        "ip": "10.1.1.5" }
-                ],
-                "over_time": [
-                    { "timestamp": 1, "data": [1, 1, 0, 0] },
-                    { "timestamp": 2, "data": [0, 0, 1, 1] },
-                    { "timestamp": 3, "data": [0, 0, 0, 0] },
-                ]
+                "data": {
+                    "clients": [
+                        { "name": "",        "ip": "10.1.1.2" },
+                        { "name": "client3", "ip": "10.1.1.3" },
+                        { "name": "",        "ip": "10.1.1.4" },
+                        { "name": "",        "ip": "10.1.1.5" }
+                    ],
+                    "over_time": [
+                        { "timestamp": 1, "data": [1, 1, 0, 0] },
+                        { "timestamp": 2, "data": [0, 0, 1, 1] },
+                        { "timestamp": 3, "data": [0, 0, 0, 0] },
+                    ]
+                }
             }))
             .test();
     }
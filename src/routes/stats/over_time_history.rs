@@ -9,15 +9,38 @@
 // Please see LICENSE file for your rights under this license.
 
 use crate::{
-    ftl::FtlMemory,
-    routes::stats::common::get_current_over_time_slot,
-    util::{reply_data, Reply}
+    ftl::{FtlMemory, FtlQuery, FtlQueryStatus},
+    routes::stats::common::{check_interval, get_current_over_time_slot, group_over_time},
+    util::{reply_timed, Error, Reply, RequestTimer}
 };
 use rocket::State;
 
-/// Get the query history over time (separated into blocked and not blocked)
-#[get("/stats/overTime/history")]
-pub fn over_time_history(ftl_memory: State<FtlMemory>) -> Reply {
+/// Get the query history over time (separated into blocked and not blocked).
+/// If an interval (in seconds) is given, the queries are grouped into slots of
+/// that size instead of FTL's overTime slots.
+#[get("/stats/overTime/history?<interval>")]
+pub fn over_time_history(
+    timer: RequestTimer,
+    ftl_memory: State<FtlMemory>,
+    interval: Option<usize>
+) -> Reply {
+    reply_timed(get_over_time_history(&ftl_memory, interval), timer)
+}
+
+/// Get the query history over time, either in FTL's overTime slots or in
+/// slots of `interval` seconds
+pub fn get_over_time_history(
+    ftl_memory: &FtlMemory,
+    interval: Option<usize>
+) -> Result<Vec<OverTimeItem>, Error> {
+    match interval {
+        Some(interval) => over_time_history_interval(ftl_memory, interval),
+        None => over_time_history_impl(ftl_memory)
+    }
+}
+
+/// Get the query history over time using FTL's overTime slots
+fn over_time_history_impl(ftl_memory: &FtlMemory) -> Result<Vec<OverTimeItem>, Error> {
     let lock = ftl_memory.lock()?;
     let over_time = ftl_memory.over_time(&lock)?;
 
@@ -37,7 +60,57 @@ pub fn over_time_history(ftl_memory: State<FtlMemory>) -> Reply {
         })
         .collect();
 
-    reply_data(over_time_data)
+    Ok(over_time_data)
+}
+
+/// Get the query history over time by grouping the queries into slots of
+/// `interval` seconds. The interval must evenly divide a day.
+fn over_time_history_interval(
+    ftl_memory: &FtlMemory,
+    interval: usize
+) -> Result<Vec<OverTimeItem>, Error> {
+    check_interval(interval)?;
+
+    let lock = ftl_memory.lock()?;
+    let counters = ftl_memory.counters(&lock)?;
+    let queries = ftl_memory.queries(&lock)?;
+
+    Ok(group_queries_over_time(
+        queries.iter().take(counters.total_queries as usize),
+        interval as u64
+    ))
+}
+
+/// Count the total and blocked queries in slots of `interval` seconds. The
+/// slots are aligned to the interval, so slots made from different sets of
+/// queries line up. Queries with an unknown status are skipped.
+pub fn group_queries_over_time<'a>(
+    queries: impl Iterator<Item = &'a FtlQuery>,
+    interval: u64
+) -> Vec<OverTimeItem> {
+    group_over_time(
+        queries
+            .filter(|query| query.status != FtlQueryStatus::Unknown)
+            .map(|query| (query.timestamp as u64, query.is_blocked())),
+        interval,
+        || (0, 0),
+        |(total_queries, blocked_queries), is_blocked| {
+            *total_queries += 1;
+
+            if is_blocked {
+                *blocked_queries += 1;
+            }
+        }
+    )
+    .into_iter()
+    .map(
+        |(timestamp, (total_queries, blocked_queries))| OverTimeItem {
+            timestamp,
+            total_queries,
+            blocked_queries
+        }
+    )
+    .collect()
 }
 
 #[derive(Serialize)]
@@ -51,11 +124,21 @@ pub struct OverTimeItem {
 #[cfg(test)]
 mod test {
     use crate::{
-        ftl::{FtlCounters, FtlMemory, FtlOverTime, FtlSettings},
-        testing::TestBuilder
+        ftl::{FtlCounters, FtlMemory, FtlOverTime, FtlQuery, FtlQueryStatus, FtlSettings},
+        testing::{test_query, TestBuilder}
     };
+    use rocket::http::Status;
     use std::collections::HashMap;
 
+    /// Shorthand for making `FtlQuery` structs
+    fn query(timestamp: i64, status: FtlQueryStatus) -> FtlQuery {
+        FtlQuery {
+            timestamp,
+            status,
+            ..test_query()
+        }
+    }
+
     /// Data for testing over_time_history
     fn test_data() -> FtlMemory {
         FtlMemory::Test {
@@ -65,13 +148,20 @@ mod test {
                 FtlOverTime::new(3, 0, 1, 0, 0, [0; 7]),
             ],
             counters: FtlCounters {
+                total_queries: 5,
                 ..FtlCounters::default()
             },
             clients: Vec::new(),
             upstreams: Vec::new(),
             strings: HashMap::new(),
             domains: Vec::new(),
-            queries: Vec::new(),
+            queries: vec![
+                query(1000, FtlQueryStatus::Forward),
+                query(1100, FtlQueryStatus::Gravity),
+                query(1150, FtlQueryStatus::Unknown),
+                query(2500, FtlQueryStatus::Cache),
+                query(4000, FtlQueryStatus::Forward),
+            ],
             settings: FtlSettings::default()
         }
     }
@@ -83,11 +173,116 @@ mod test {
         TestBuilder::new()
             .endpoint("/admin/api/stats/overTime/history")
             .ftl_memory(test_data())
-            .expect_json(json!([
-                { "timestamp": 1, "total_queries": 1, "blocked_queries": 0 },
-                { "timestamp": 2, "total_queries": 1, "blocked_queries": 1 },
-                { "timestamp": 3, "total_queries": 0, "blocked_queries": 1 }
-            ]))
+            .expect_json(json!({
+                "data": [
+                    { "timestamp": 1, "total_queries": 1, "blocked_queries": 0 },
+                    { "timestamp": 2, "total_queries": 1, "blocked_queries": 1 },
+                    { "timestamp": 3, "total_queries": 0, "blocked_queries": 1 }
+                ]
+            }))
+            .test();
+    }
+
+    /// The queries are grouped into slots of the given interval, including
+    /// empty slots
+    #[test]
+    fn interval() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/overTime/history?interval=1200")
+            .ftl_memory(test_data())
+            .expect_json(json!({
+                "data": [
+                    { "timestamp": 600,  "total_queries": 2, "blocked_queries": 1 },
+                    { "timestamp": 1800, "total_queries": 0, "blocked_queries": 0 },
+                    { "timestamp": 3000, "total_queries": 1, "blocked_queries": 0 },
+                    { "timestamp": 4200, "total_queries": 1, "blocked_queries": 0 }
+                ]
+            }))
+            .test();
+    }
+
+    /// An interval which does not evenly divide a day is a bad request
+    #[test]
+    fn invalid_interval() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/overTime/history?interval=7")
+            .ftl_memory(test_data())
+            .expect_status(Status::BadRequest)
+            .expect_json(json!({
+                "error": {
+                    "key": "bad_request",
+                    "message": "Bad request",
+                    "data": None::<()>
+                }
+            }))
+            .test();
+    }
+
+    /// An interval shorter than FTL's 10 minute slots is a bad request, even
+    /// though it evenly divides a day
+    #[test]
+    fn interval_below_slot() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/overTime/history?interval=300")
+            .ftl_memory(test_data())
+            .expect_status(Status::BadRequest)
+            .expect_json(json!({
+                "error": {
+                    "key": "bad_request",
+                    "message": "Bad request",
+                    "data": None::<()>
+                }
+            }))
+            .test();
+    }
+
+    /// The timestamps are formatted as RFC 3339 strings when requested
+    #[test]
+    fn rfc3339_time_format() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/overTime/history?interval=1200&time_format=rfc3339")
+            .ftl_memory(test_data())
+            .expect_json(json!({
+                "data": [
+                    {
+                        "timestamp": "1970-01-01T00:10:00Z",
+                        "total_queries": 2,
+                        "blocked_queries": 1
+                    },
+                    {
+                        "timestamp": "1970-01-01T00:30:00Z",
+                        "total_queries": 0,
+                        "blocked_queries": 0
+                    },
+                    {
+                        "timestamp": "1970-01-01T00:50:00Z",
+                        "total_queries": 1,
+                        "blocked_queries": 0
+                    },
+                    {
+                        "timestamp": "1970-01-01T01:10:00Z",
+                        "total_queries": 1,
+                        "blocked_queries": 0
+                    }
+                ]
+            }))
+            .test();
+    }
+
+    /// An unknown time format is a bad request
+    #[test]
+    fn invalid_time_format() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/overTime/history?time_format=iso")
+            .ftl_memory(test_data())
+            .expect_status(Status::BadRequest)
+            .expect_json(json!({
+                "error": {
+                    "key": "bad_request",
+                    "message": "Bad request",
+                    "data": None::<()>
+                }
+            }))
             .test();
     }
 }
@@ -0,0 +1,195 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// Query Types Over Time Endpoint
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    ftl::{FtlMemory, FtlQueryType, OVERTIME_INTERVAL},
+    routes::{
+        auth::User,
+        stats::common::{check_interval, group_over_time}
+    },
+    util::{reply_timed, Error, Reply, RequestTimer}
+};
+use rocket::State;
+
+/// Get the number of queries of each query type over time. The queries are
+/// grouped into slots of `interval` seconds, which defaults to the size of
+/// FTL's overTime slots.
+#[get("/stats/overTime/query_types?<interval>")]
+pub fn over_time_query_types(
+    timer: RequestTimer,
+    _auth: User,
+    ftl_memory: State<FtlMemory>,
+    interval: Option<usize>
+) -> Reply {
+    reply_timed(
+        get_over_time_query_types(&ftl_memory, interval.unwrap_or(OVERTIME_INTERVAL)),
+        timer
+    )
+}
+
+/// Count the queries of each query type in slots of `interval` seconds. The
+/// slots are aligned the same way as the query history slots.
+fn get_over_time_query_types(
+    ftl_memory: &FtlMemory,
+    interval: usize
+) -> Result<OverTimeQueryTypes, Error> {
+    check_interval(interval)?;
+
+    let lock = ftl_memory.lock()?;
+    let counters = ftl_memory.counters(&lock)?;
+    let queries = ftl_memory.queries(&lock)?;
+
+    // The data of each slot is indexed by the query type's position in the
+    // query type list. Query type values start at 1.
+    let query_types = FtlQueryType::variants();
+
+    // Count the queries of each query type in each slot
+    let over_time = group_over_time(
+        queries
+            .iter()
+            .take(counters.total_queries as usize)
+            .map(|query| (query.timestamp as u64, query.query_type as usize - 1)),
+        interval as u64,
+        || vec![0; query_types.len()],
+        |data, index| data[index] += 1
+    )
+    .into_iter()
+    .map(|(timestamp, data)| OverTimeQueryTypeItem { timestamp, data })
+    .collect();
+
+    Ok(OverTimeQueryTypes {
+        query_types: query_types
+            .iter()
+            .map(|query_type| query_type.get_name())
+            .collect(),
+        over_time
+    })
+}
+
+/// Represents an overTime query type item, which holds the query count of
+/// each query type in an overTime interval. The counts are in the same order
+/// as the query types.
+#[derive(Serialize)]
+pub struct OverTimeQueryTypeItem {
+    pub timestamp: u64,
+    pub data: Vec<usize>
+}
+
+/// Represents the reply format for the overTime query types endpoint
+#[derive(Serialize)]
+pub struct OverTimeQueryTypes {
+    pub query_types: Vec<String>,
+    pub over_time: Vec<OverTimeQueryTypeItem>
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        ftl::{FtlCounters, FtlMemory, FtlQuery, FtlQueryType, FtlSettings},
+        testing::{test_query, TestBuilder}
+    };
+    use rocket::http::Status;
+    use std::collections::HashMap;
+
+    /// Shorthand for making `FtlQuery` structs
+    fn query(timestamp: i64, query_type: FtlQueryType) -> FtlQuery {
+        FtlQuery {
+            timestamp,
+            query_type,
+            ..test_query()
+        }
+    }
+
+    /// There are 6 queries with a mix of query types
+    fn test_data() -> FtlMemory {
+        FtlMemory::Test {
+            clients: Vec::new(),
+            domains: Vec::new(),
+            over_time: Vec::new(),
+            strings: HashMap::new(),
+            upstreams: Vec::new(),
+            queries: vec![
+                query(1000, FtlQueryType::A),
+                query(1100, FtlQueryType::AAAA),
+                query(1150, FtlQueryType::A),
+                query(1190, FtlQueryType::PTR),
+                query(2500, FtlQueryType::PTR),
+                query(3000, FtlQueryType::TXT),
+            ],
+            counters: FtlCounters {
+                total_queries: 6,
+                ..FtlCounters::default()
+            },
+            settings: FtlSettings::default()
+        }
+    }
+
+    /// The query types are listed once, in order of their IDs
+    fn query_types() -> Vec<&'static str> {
+        vec!["A", "AAAA", "ANY", "SRV", "SOA", "PTR", "TXT"]
+    }
+
+    /// The queries of each query type are counted in FTL's overTime slots by
+    /// default
+    #[test]
+    fn default_interval() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/overTime/query_types")
+            .ftl_memory(test_data())
+            .expect_json(json!({
+                "data": {
+                    "query_types": query_types(),
+                    "over_time": [
+                        { "timestamp": 900,  "data": [2, 1, 0, 0, 0, 1, 0] },
+                        { "timestamp": 1500, "data": [0, 0, 0, 0, 0, 0, 0] },
+                        { "timestamp": 2100, "data": [0, 0, 0, 0, 0, 0, 0] },
+                        { "timestamp": 2700, "data": [0, 0, 0, 0, 0, 1, 0] },
+                        { "timestamp": 3300, "data": [0, 0, 0, 0, 0, 0, 1] }
+                    ]
+                }
+            }))
+            .test();
+    }
+
+    /// The queries are grouped into slots of the given interval
+    #[test]
+    fn interval() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/overTime/query_types?interval=1800")
+            .ftl_memory(test_data())
+            .expect_json(json!({
+                "data": {
+                    "query_types": query_types(),
+                    "over_time": [
+                        { "timestamp": 900,  "data": [2, 1, 0, 0, 0, 1, 0] },
+                        { "timestamp": 2700, "data": [0, 0, 0, 0, 0, 1, 1] }
+                    ]
+                }
+            }))
+            .test();
+    }
+
+    /// An interval which does not evenly divide a day is a bad request
+    #[test]
+    fn invalid_interval() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/overTime/query_types?interval=7")
+            .ftl_memory(test_data())
+            .expect_status(Status::BadRequest)
+            .expect_json(json!({
+                "error": {
+                    "key": "bad_request",
+                    "message": "Bad request",
+                    "data": None::<()>
+                }
+            }))
+            .test();
+    }
+}
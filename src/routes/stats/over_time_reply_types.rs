@@ -0,0 +1,204 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// Reply Types Over Time Endpoint
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    ftl::{FtlMemory, FtlQueryReplyType, OVERTIME_INTERVAL},
+    routes::{
+        auth::User,
+        stats::common::{check_interval, group_over_time}
+    },
+    util::{reply_timed, Error, Reply, RequestTimer}
+};
+use rocket::State;
+
+/// Get the number of queries of each reply type over time. The queries are
+/// grouped into slots of `interval` seconds, which defaults to the size of
+/// FTL's overTime slots.
+#[get("/stats/overTime/reply_types?<interval>")]
+pub fn over_time_reply_types(
+    timer: RequestTimer,
+    _auth: User,
+    ftl_memory: State<FtlMemory>,
+    interval: Option<usize>
+) -> Reply {
+    reply_timed(
+        get_over_time_reply_types(&ftl_memory, interval.unwrap_or(OVERTIME_INTERVAL)),
+        timer
+    )
+}
+
+/// Count the queries of each reply type in slots of `interval` seconds. The
+/// slots are aligned the same way as the query history slots. Reply types
+/// which are not known are counted as `other`.
+fn get_over_time_reply_types(
+    ftl_memory: &FtlMemory,
+    interval: usize
+) -> Result<OverTimeReplyTypes, Error> {
+    check_interval(interval)?;
+
+    let lock = ftl_memory.lock()?;
+    let counters = ftl_memory.counters(&lock)?;
+    let queries = ftl_memory.queries(&lock)?;
+
+    // The data of each slot is indexed by the reply type's ordinal value
+    let reply_types = FtlQueryReplyType::variants();
+    let other_index = FtlQueryReplyType::OTHER as usize;
+
+    // Count the queries of each reply type in each slot
+    let over_time = group_over_time(
+        queries
+            .iter()
+            .take(counters.total_queries as usize)
+            .map(|query| {
+                (
+                    query.timestamp as u64,
+                    (query.reply_type as usize).min(other_index)
+                )
+            }),
+        interval as u64,
+        || vec![0; reply_types.len()],
+        |data, index| data[index] += 1
+    )
+    .into_iter()
+    .map(|(timestamp, data)| OverTimeReplyTypeItem { timestamp, data })
+    .collect();
+
+    Ok(OverTimeReplyTypes {
+        reply_types: reply_types
+            .iter()
+            .map(|reply_type| reply_type.get_name())
+            .collect(),
+        over_time
+    })
+}
+
+/// Represents an overTime reply type item, which holds the query count of
+/// each reply type in an overTime interval. The counts are in the same order
+/// as the reply types.
+#[derive(Serialize)]
+pub struct OverTimeReplyTypeItem {
+    pub timestamp: u64,
+    pub data: Vec<usize>
+}
+
+/// Represents the reply format for the overTime reply types endpoint
+#[derive(Serialize)]
+pub struct OverTimeReplyTypes {
+    pub reply_types: Vec<&'static str>,
+    pub over_time: Vec<OverTimeReplyTypeItem>
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        ftl::{FtlCounters, FtlMemory, FtlQuery, FtlQueryReplyType, FtlSettings},
+        testing::{test_query, TestBuilder}
+    };
+    use rocket::http::Status;
+    use std::collections::HashMap;
+
+    /// Shorthand for making `FtlQuery` structs
+    fn query(timestamp: i64, reply_type: FtlQueryReplyType) -> FtlQuery {
+        FtlQuery {
+            timestamp,
+            reply_type,
+            ..test_query()
+        }
+    }
+
+    /// There are 6 queries with a mix of reply types
+    fn test_data() -> FtlMemory {
+        FtlMemory::Test {
+            clients: Vec::new(),
+            domains: Vec::new(),
+            over_time: Vec::new(),
+            strings: HashMap::new(),
+            upstreams: Vec::new(),
+            queries: vec![
+                query(1000, FtlQueryReplyType::IP),
+                query(1100, FtlQueryReplyType::NXDOMAIN),
+                query(1150, FtlQueryReplyType::IP),
+                query(1190, FtlQueryReplyType::CNAME),
+                query(2500, FtlQueryReplyType::NODATA),
+                query(3000, FtlQueryReplyType::Unknown),
+            ],
+            counters: FtlCounters {
+                total_queries: 6,
+                ..FtlCounters::default()
+            },
+            settings: FtlSettings::default()
+        }
+    }
+
+    /// The reply types are listed once, in order of their IDs
+    fn reply_types() -> Vec<&'static str> {
+        vec![
+            "unknown", "nodata", "nxdomain", "cname", "ip", "domain", "rrname", "servfail",
+            "refused", "notimp", "other",
+        ]
+    }
+
+    /// The queries of each reply type are counted in FTL's overTime slots by
+    /// default
+    #[test]
+    fn default_interval() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/overTime/reply_types")
+            .ftl_memory(test_data())
+            .expect_json(json!({
+                "data": {
+                    "reply_types": reply_types(),
+                    "over_time": [
+                        { "timestamp": 900,  "data": [0, 0, 1, 1, 2, 0, 0, 0, 0, 0, 0] },
+                        { "timestamp": 1500, "data": [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0] },
+                        { "timestamp": 2100, "data": [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0] },
+                        { "timestamp": 2700, "data": [0, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0] },
+                        { "timestamp": 3300, "data": [1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0] }
+                    ]
+                }
+            }))
+            .test();
+    }
+
+    /// The queries are grouped into slots of the given interval
+    #[test]
+    fn interval() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/overTime/reply_types?interval=1800")
+            .ftl_memory(test_data())
+            .expect_json(json!({
+                "data": {
+                    "reply_types": reply_types(),
+                    "over_time": [
+                        { "timestamp": 900,  "data": [0, 0, 1, 1, 2, 0, 0, 0, 0, 0, 0] },
+                        { "timestamp": 2700, "data": [1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0] }
+                    ]
+                }
+            }))
+            .test();
+    }
+
+    /// An interval which does not evenly divide a day is a bad request
+    #[test]
+    fn invalid_interval() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/overTime/reply_types?interval=7")
+            .ftl_memory(test_data())
+            .expect_status(Status::BadRequest)
+            .expect_json(json!({
+                "error": {
+                    "key": "bad_request",
+                    "message": "Bad request",
+                    "data": None::<()>
+                }
+            }))
+            .test();
+    }
+}
@@ -0,0 +1,256 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// Upstreams Over Time Endpoint
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    ftl::{FtlMemory, FtlQueryStatus, OVERTIME_INTERVAL},
+    routes::{
+        auth::User,
+        stats::common::{check_interval, group_over_time}
+    },
+    util::{reply_timed, Error, Reply, RequestTimer}
+};
+use rocket::State;
+use std::iter;
+
+/// The name of the pseudo-upstream for queries which were not forwarded
+const CACHE_BLOCKED_UPSTREAM: &str = "cache/blocked";
+
+/// Get the number of queries forwarded to each upstream over time. The
+/// queries are grouped into slots of `interval` seconds, which defaults to
+/// the size of FTL's overTime slots.
+#[get("/stats/overTime/upstreams?<interval>")]
+pub fn over_time_upstreams(
+    timer: RequestTimer,
+    _auth: User,
+    ftl_memory: State<FtlMemory>,
+    interval: Option<usize>
+) -> Reply {
+    reply_timed(
+        get_over_time_upstreams(&ftl_memory, interval.unwrap_or(OVERTIME_INTERVAL)),
+        timer
+    )
+}
+
+/// Count the queries of each upstream in slots of `interval` seconds. The
+/// slots are aligned the same way as the query history slots. Cached and
+/// blocked queries are counted under a `cache/blocked` pseudo-upstream, which
+/// is always the first upstream.
+fn get_over_time_upstreams(
+    ftl_memory: &FtlMemory,
+    interval: usize
+) -> Result<OverTimeUpstreams, Error> {
+    check_interval(interval)?;
+
+    let lock = ftl_memory.lock()?;
+    let counters = ftl_memory.counters(&lock)?;
+    let strings = ftl_memory.strings(&lock)?;
+    let ftl_upstreams = ftl_memory.upstreams(&lock)?;
+    let queries = ftl_memory.queries(&lock)?;
+
+    // FTL allocates more upstreams than it uses. Every upstream in use is
+    // listed, so the data of each slot is indexed by upstream ID (offset by
+    // one for the pseudo-upstream).
+    let upstream_count = counters.total_upstreams as usize;
+
+    // Count the queries of each upstream in each slot
+    let over_time = group_over_time(
+        queries
+            .iter()
+            .take(counters.total_queries as usize)
+            .filter(|query| query.status != FtlQueryStatus::Unknown)
+            .filter_map(|query| {
+                let index = if query.status == FtlQueryStatus::Forward {
+                    query.upstream_id as usize + 1
+                } else {
+                    0
+                };
+
+                // Skip queries with an upstream which is not in use
+                if index > upstream_count {
+                    None
+                } else {
+                    Some((query.timestamp as u64, index))
+                }
+            }),
+        interval as u64,
+        || vec![0; upstream_count + 1],
+        |data, index| data[index] += 1
+    )
+    .into_iter()
+    .map(|(timestamp, data)| OverTimeUpstreamItem { timestamp, data })
+    .collect();
+
+    let upstreams = iter::once(OverTimeUpstream {
+        name: CACHE_BLOCKED_UPSTREAM.to_owned(),
+        ip: CACHE_BLOCKED_UPSTREAM.to_owned()
+    })
+    .chain(
+        ftl_upstreams
+            .iter()
+            .take(upstream_count)
+            .map(|upstream| OverTimeUpstream {
+                name: upstream.get_name(&strings).unwrap_or_default().to_owned(),
+                ip: upstream.get_ip(&strings).to_owned()
+            })
+    )
+    .collect();
+
+    Ok(OverTimeUpstreams {
+        upstreams,
+        over_time
+    })
+}
+
+/// Represents an upstream in the overTime upstreams reply
+#[derive(Serialize)]
+#[cfg_attr(test, derive(Debug, PartialEq))]
+pub struct OverTimeUpstream {
+    pub name: String,
+    pub ip: String
+}
+
+/// Represents an overTime upstream item, which holds the query count of each
+/// upstream in an overTime interval. The counts are in the same order as the
+/// upstreams.
+#[derive(Serialize)]
+#[cfg_attr(test, derive(Debug, PartialEq))]
+pub struct OverTimeUpstreamItem {
+    pub timestamp: u64,
+    pub data: Vec<usize>
+}
+
+/// Represents the reply format for the overTime upstreams endpoint
+#[derive(Serialize)]
+#[cfg_attr(test, derive(Debug, PartialEq))]
+pub struct OverTimeUpstreams {
+    pub upstreams: Vec<OverTimeUpstream>,
+    pub over_time: Vec<OverTimeUpstreamItem>
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        ftl::{FtlCounters, FtlMemory, FtlQuery, FtlQueryStatus, FtlSettings, FtlUpstream},
+        testing::{test_query, TestBuilder}
+    };
+    use rocket::http::Status;
+    use std::collections::HashMap;
+
+    /// Shorthand for making `FtlQuery` structs
+    fn query(timestamp: i64, status: FtlQueryStatus, upstream_id: i32) -> FtlQuery {
+        FtlQuery {
+            timestamp,
+            upstream_id,
+            status,
+            ..test_query()
+        }
+    }
+
+    /// There are 2 upstreams, one with a name, and 7 queries. FTL allocated
+    /// space for a third upstream.
+    fn test_data() -> FtlMemory {
+        let mut strings = HashMap::new();
+        strings.insert(1, "8.8.8.8".to_owned());
+        strings.insert(2, "google-public-dns-a.google.com".to_owned());
+        strings.insert(3, "1.1.1.1".to_owned());
+
+        FtlMemory::Test {
+            clients: Vec::new(),
+            domains: Vec::new(),
+            over_time: Vec::new(),
+            strings,
+            upstreams: vec![
+                FtlUpstream::new(3, 0, 1, Some(2)),
+                FtlUpstream::new(1, 0, 3, None),
+                FtlUpstream::new(0, 0, 0, None),
+            ],
+            queries: vec![
+                query(1000, FtlQueryStatus::Forward, 0),
+                query(1100, FtlQueryStatus::Gravity, 0),
+                query(1150, FtlQueryStatus::Forward, 1),
+                query(1190, FtlQueryStatus::Cache, 0),
+                query(2500, FtlQueryStatus::Forward, 0),
+                query(3000, FtlQueryStatus::Forward, 0),
+                query(3100, FtlQueryStatus::Unknown, 1),
+            ],
+            counters: FtlCounters {
+                total_upstreams: 2,
+                total_queries: 7,
+                ..FtlCounters::default()
+            },
+            settings: FtlSettings::default()
+        }
+    }
+
+    /// The queries of each upstream are counted in FTL's overTime slots by
+    /// default, with cached and blocked queries under the pseudo-upstream
+    #[test]
+    fn default_interval() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/overTime/upstreams")
+            .ftl_memory(test_data())
+            .expect_json(json!({
+                "data": {
+                    "upstreams": [
+                        { "name": "cache/blocked",                  "ip": "cache/blocked" },
+                        { "name": "google-public-dns-a.google.com", "ip": "8.8.8.8" },
+                        { "name": "",                               "ip": "1.1.1.1" }
+                    ],
+                    "over_time": [
+                        { "timestamp": 900,  "data": [2, 1, 1] },
+                        { "timestamp": 1500, "data": [0, 0, 0] },
+                        { "timestamp": 2100, "data": [0, 0, 0] },
+                        { "timestamp": 2700, "data": [0, 1, 0] },
+                        { "timestamp": 3300, "data": [0, 1, 0] }
+                    ]
+                }
+            }))
+            .test();
+    }
+
+    /// The queries are grouped into slots of the given interval
+    #[test]
+    fn interval() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/overTime/upstreams?interval=1800")
+            .ftl_memory(test_data())
+            .expect_json(json!({
+                "data": {
+                    "upstreams": [
+                        { "name": "cache/blocked",                  "ip": "cache/blocked" },
+                        { "name": "google-public-dns-a.google.com", "ip": "8.8.8.8" },
+                        { "name": "",                               "ip": "1.1.1.1" }
+                    ],
+                    "over_time": [
+                        { "timestamp": 900,  "data": [2, 1, 1] },
+                        { "timestamp": 2700, "data": [0, 2, 0] }
+                    ]
+                }
+            }))
+            .test();
+    }
+
+    /// An interval which does not evenly divide a day is a bad request
+    #[test]
+    fn invalid_interval() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/overTime/upstreams?interval=7")
+            .ftl_memory(test_data())
+            .expect_status(Status::BadRequest)
+            .expect_json(json!({
+                "error": {
+                    "key": "bad_request",
+                    "message": "Bad request",
+                    "data": None::<()>
+                }
+            }))
+            .test();
+    }
+}
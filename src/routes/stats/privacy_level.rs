@@ -0,0 +1,67 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// Privacy Level Endpoint
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    env::Env,
+    settings::{ConfigEntry, FtlConfEntry, FtlPrivacyLevel},
+    util::{reply_timed, Error, Reply, RequestTimer}
+};
+use rocket::State;
+
+/// Get the privacy level used by FTL, which determines what data is available
+#[get("/stats/privacy_level")]
+pub fn privacy_level(timer: RequestTimer, env: State<Env>) -> Reply {
+    reply_timed(get_privacy_level(&env), timer)
+}
+
+/// Get the privacy level and its name
+fn get_privacy_level(env: &Env) -> Result<PrivacyLevelReply, Error> {
+    let privacy_level = FtlConfEntry::PrivacyLevel.read_as::<FtlPrivacyLevel>(env)?;
+
+    Ok(PrivacyLevelReply {
+        level: privacy_level.get_level(),
+        name: privacy_level.get_name()
+    })
+}
+
+/// Represents the reply structure for the privacy level
+#[derive(Serialize)]
+pub struct PrivacyLevelReply {
+    pub level: usize,
+    pub name: &'static str
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{env::PiholeFile, testing::TestBuilder};
+
+    /// The default privacy level shows everything
+    #[test]
+    fn default() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/privacy_level")
+            .expect_json(json!({
+                "data": { "level": 0, "name": "show_all" }
+            }))
+            .test();
+    }
+
+    /// The privacy level is read from the FTL config
+    #[test]
+    fn hide_clients() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/privacy_level")
+            .file(PiholeFile::FtlConfig, "PRIVACYLEVEL=2")
+            .expect_json(json!({
+                "data": { "level": 2, "name": "hide_domains_and_clients" }
+            }))
+            .test();
+    }
+}
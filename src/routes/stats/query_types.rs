@@ -11,14 +11,14 @@
 use crate::{
     ftl::{FtlMemory, FtlQueryType},
     routes::auth::User,
-    util::{reply_result, Error, Reply}
+    util::{reply_timed, Error, Reply, RequestTimer}
 };
 use rocket::State;
 
 /// Get the query types
 #[get("/stats/query_types")]
-pub fn query_types(_auth: User, ftl_memory: State<FtlMemory>) -> Reply {
-    reply_result(query_types_impl(&ftl_memory))
+pub fn query_types(timer: RequestTimer, _auth: User, ftl_memory: State<FtlMemory>) -> Reply {
+    reply_timed(query_types_impl(&ftl_memory), timer)
 }
 
 /// Get the query types
@@ -0,0 +1,276 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// Rate Limited Clients Endpoint
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    env::Env,
+    ftl::{FtlClient, FtlMemory, FtlQuery, FtlStrings},
+    routes::{
+        auth::User,
+        settings::RateLimit,
+        stats::{
+            clients::{filter_ftl_clients, ClientParams},
+            common::anonymize_clients
+        }
+    },
+    settings::{ConfigEntry, FtlConfEntry},
+    util::{reply_timed, Error, Reply, RequestTimer}
+};
+use rocket::State;
+use std::{
+    collections::HashMap,
+    time::{SystemTime, UNIX_EPOCH}
+};
+
+/// Represents a client which is over FTL's rate limit
+#[derive(Serialize)]
+#[cfg_attr(test, derive(Debug, PartialEq))]
+pub struct RateLimitedClientReply {
+    name: String,
+    ip: String,
+    /// The number of queries the client has made in the last rate limit
+    /// interval
+    query_rate: usize
+}
+
+/// Get the clients which are currently over the rate limit
+#[get("/stats/clients/rate_limited")]
+pub fn rate_limited_clients(
+    timer: RequestTimer,
+    _auth: User,
+    ftl_memory: State<FtlMemory>,
+    env: State<Env>
+) -> Reply {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Current time is older than epoch")
+        .as_secs();
+
+    reply_timed(get_rate_limited_clients(&ftl_memory, &env, now), timer)
+}
+
+/// Get the visible clients which have made more queries than the rate limit
+/// allows in the last rate limit interval before `now`, with the most queries
+/// first. Version 4 of FTL's shared memory does not flag rate limited
+/// clients, so the queries are counted the same way FTL counts them.
+fn get_rate_limited_clients(
+    ftl_memory: &FtlMemory,
+    env: &Env,
+    now: u64
+) -> Result<Vec<RateLimitedClientReply>, Error> {
+    let rate_limit: RateLimit = FtlConfEntry::RateLimit.read_as(env)?;
+    let lock = ftl_memory.lock()?;
+    let counters = ftl_memory.counters(&lock)?;
+    let strings = ftl_memory.strings(&lock)?;
+    let clients = ftl_memory.clients(&lock)?;
+    let queries = ftl_memory.queries(&lock)?;
+
+    let query_rates = get_query_rates(
+        &queries,
+        counters.total_queries as usize,
+        &clients,
+        &strings,
+        now.saturating_sub(rate_limit.interval as u64)
+    );
+
+    let mut limited_clients: Vec<RateLimitedClientReply> =
+        filter_ftl_clients(ftl_memory, &lock, &clients, env, ClientParams::default())?
+            .into_iter()
+            .filter_map(|client| {
+                let query_rate = *query_rates.get(client.get_ip(&strings))?;
+
+                if query_rate <= rate_limit.count {
+                    return None;
+                }
+
+                let reply = client.as_reply(&strings);
+
+                Some(RateLimitedClientReply {
+                    name: reply.name,
+                    ip: reply.ip,
+                    query_rate
+                })
+            })
+            .collect();
+
+    limited_clients.sort_by(|a, b| b.query_rate.cmp(&a.query_rate));
+    anonymize_clients(
+        limited_clients
+            .iter_mut()
+            .map(|client| (&mut client.name, &mut client.ip)),
+        env
+    )?;
+
+    Ok(limited_clients)
+}
+
+/// Count each client's queries made after `since`, by client IP
+fn get_query_rates<'a>(
+    queries: &[FtlQuery],
+    total_queries: usize,
+    clients: &[FtlClient],
+    strings: &'a FtlStrings,
+    since: u64
+) -> HashMap<&'a str, usize> {
+    let mut query_rates: HashMap<usize, usize> = HashMap::new();
+
+    for query in queries
+        .iter()
+        .take(total_queries)
+        .filter(|query| query.timestamp as u64 > since)
+    {
+        *query_rates.entry(query.client_id as usize).or_insert(0) += 1;
+    }
+
+    // Map the counts to the client IPs, so they can be matched with the
+    // filtered clients
+    query_rates
+        .into_iter()
+        .filter_map(|(client_id, query_rate)| {
+            clients
+                .get(client_id)
+                .map(|client| (client.get_ip(strings), query_rate))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::{get_rate_limited_clients, RateLimitedClientReply};
+    use crate::{
+        env::{Config, Env, PiholeFile},
+        ftl::{FtlClient, FtlCounters, FtlMemory, FtlQuery, FtlSettings},
+        testing::{test_query, TestBuilder, TestEnvBuilder}
+    };
+    use std::collections::HashMap;
+
+    const NOW: u64 = 1_550_000_000;
+
+    /// Shorthand for making `FtlQuery` structs
+    fn query(client_id: i32, timestamp: u64) -> FtlQuery {
+        FtlQuery {
+            timestamp: timestamp as i64,
+            client_id,
+            ..test_query()
+        }
+    }
+
+    /// There are 4 clients, one of them hidden. In the last 10 seconds, the
+    /// first client made 3 queries, the third made 4, and the hidden client
+    /// made 3. The second client made 2 queries 10 seconds ago and 1 query a
+    /// minute ago.
+    fn test_data() -> FtlMemory {
+        let mut strings = HashMap::new();
+        strings.insert(1, "10.1.1.1".to_owned());
+        strings.insert(2, "client1".to_owned());
+        strings.insert(3, "10.1.1.2".to_owned());
+        strings.insert(4, "10.1.1.3".to_owned());
+        strings.insert(5, "0.0.0.0".to_owned());
+
+        FtlMemory::Test {
+            clients: vec![
+                FtlClient::new(3, 0, 1, Some(2)),
+                FtlClient::new(3, 0, 3, None),
+                FtlClient::new(4, 0, 4, None),
+                FtlClient::new(3, 0, 5, None),
+            ],
+            domains: Vec::new(),
+            over_time: Vec::new(),
+            strings,
+            upstreams: Vec::new(),
+            queries: vec![
+                query(1, NOW - 60),
+                query(0, NOW - 5),
+                query(1, NOW - 10),
+                query(2, NOW - 8),
+                query(0, NOW - 3),
+                query(2, NOW - 2),
+                query(3, NOW - 2),
+                query(1, NOW - 10),
+                query(2, NOW - 1),
+                query(3, NOW - 1),
+                query(0, NOW),
+                query(3, NOW),
+                query(2, NOW),
+            ],
+            counters: FtlCounters {
+                total_clients: 4,
+                total_queries: 13,
+                ..FtlCounters::default()
+            },
+            settings: FtlSettings::default()
+        }
+    }
+
+    /// Get the rate limited clients using the environment files
+    fn rate_limited(env_builder: TestEnvBuilder) -> Vec<RateLimitedClientReply> {
+        let env = Env::Test(Config::default(), env_builder.build());
+
+        get_rate_limited_clients(&test_data(), &env, NOW).unwrap()
+    }
+
+    /// Clients with more queries than the limit in the interval are rate
+    /// limited, with the most queries first. Hidden clients are not shown.
+    #[test]
+    fn over_limit() {
+        assert_eq!(
+            rate_limited(TestEnvBuilder::new().file(PiholeFile::FtlConfig, "RATE_LIMIT=2/10\n")),
+            vec![
+                RateLimitedClientReply {
+                    name: "".to_owned(),
+                    ip: "10.1.1.3".to_owned(),
+                    query_rate: 4
+                },
+                RateLimitedClientReply {
+                    name: "client1".to_owned(),
+                    ip: "10.1.1.1".to_owned(),
+                    query_rate: 3
+                },
+            ]
+        );
+    }
+
+    /// Queries before the interval are not counted
+    #[test]
+    fn longer_interval() {
+        assert_eq!(
+            rate_limited(TestEnvBuilder::new().file(PiholeFile::FtlConfig, "RATE_LIMIT=3/60\n"))
+                .into_iter()
+                .map(|client| client.ip)
+                .collect::<Vec<String>>(),
+            vec!["10.1.1.3"]
+        );
+    }
+
+    /// Excluded clients are not shown
+    #[test]
+    fn excluded_clients() {
+        assert_eq!(
+            rate_limited(
+                TestEnvBuilder::new()
+                    .file(PiholeFile::FtlConfig, "RATE_LIMIT=2/10\n")
+                    .file(PiholeFile::SetupVars, "API_EXCLUDE_CLIENTS=10.1.1.3")
+            )
+            .into_iter()
+            .map(|client| client.ip)
+            .collect::<Vec<String>>(),
+            vec!["10.1.1.1"]
+        );
+    }
+
+    /// No clients are listed when none are over the limit
+    #[test]
+    fn none_limited() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/clients/rate_limited")
+            .ftl_memory(test_data())
+            .expect_json(json!({ "data": [] }))
+            .test();
+    }
+}
@@ -10,36 +10,53 @@
 
 use crate::{
     env::Env,
-    ftl::FtlMemory,
-    routes::auth::User,
+    ftl::{CachedStrings, FtlMemory},
+    routes::{auth::User, stats::common::get_excluded_domains},
     settings::{ConfigEntry, FtlConfEntry, FtlPrivacyLevel},
-    util::{reply_data, Reply}
+    util::{reply_timed, Error, Reply, RequestTimer}
 };
 use rocket::{request::Form, State};
+use std::collections::HashSet;
 
-/// Get the `num` most recently blocked domains
+/// The maximum number of recently blocked domains which can be requested
+const MAX_RECENT_BLOCKED: usize = 100;
+
+/// Get the `count` most recently blocked domains
 #[get("/stats/recent_blocked?<params..>")]
 pub fn recent_blocked(
+    timer: RequestTimer,
     _auth: User,
     ftl_memory: State<FtlMemory>,
     env: State<Env>,
     params: Form<RecentBlockedParams>
 ) -> Reply {
-    get_recent_blocked(&ftl_memory, &env, params.num.unwrap_or(1))
+    // `num` is the older name of the `count` parameter
+    let count = params.count.or(params.num).unwrap_or(1);
+
+    reply_timed(
+        get_recent_blocked(&ftl_memory, &env, count.min(MAX_RECENT_BLOCKED)),
+        timer
+    )
 }
 
 /// Represents the possible GET parameters on `/stats/recent_blocked`
 #[derive(FromForm)]
 pub struct RecentBlockedParams {
+    count: Option<usize>,
     num: Option<usize>
 }
 
-/// Get `num`-many most recently blocked domains
-pub fn get_recent_blocked(ftl_memory: &FtlMemory, env: &Env, num: usize) -> Reply {
+/// Get `num`-many most recently blocked domains. Domains in
+/// `SetupVarsEntry::ApiExcludeDomains` are skipped.
+pub fn get_recent_blocked(
+    ftl_memory: &FtlMemory,
+    env: &Env,
+    num: usize
+) -> Result<Vec<String>, Error> {
     // Check if client details are private
     if FtlConfEntry::PrivacyLevel.read_as::<FtlPrivacyLevel>(&env)? >= FtlPrivacyLevel::HideDomains
     {
-        return reply_data([0; 0]);
+        return Ok(Vec::new());
     }
 
     let lock = ftl_memory.lock()?;
@@ -48,7 +65,13 @@ pub fn get_recent_blocked(ftl_memory: &FtlMemory, env: &Env, num: usize) -> Repl
     let strings = ftl_memory.strings(&lock)?;
     let domains = ftl_memory.domains(&lock)?;
 
-    let recent_blocked: Vec<&str> = queries
+    // Recently blocked domains are often blocked many times in a row
+    let strings = CachedStrings::new(&strings);
+
+    let excluded_domains = get_excluded_domains(env)?;
+    let excluded_domains: HashSet<&str> = excluded_domains.iter().map(String::as_str).collect();
+
+    let recent_blocked: Vec<String> = queries
         .iter()
         // Get the most recent queries first
         .rev()
@@ -56,18 +79,22 @@ pub fn get_recent_blocked(ftl_memory: &FtlMemory, env: &Env, num: usize) -> Repl
         .skip(queries.len() - counters.total_queries as usize)
         // Only get blocked queries
         .filter(|query| query.is_blocked())
-        // Get up to num queries
+        // Only use the domain
+        .map(|query| domains[query.domain_id as usize].get_domain_cached(&strings))
+        // Skip excluded domains
+        .filter(|domain| !excluded_domains.contains(domain.to_lowercase().as_str()))
+        // Get up to num domains
         .take(num)
-        // Only return the domain
-        .map(|query| domains[query.domain_id as usize].get_domain(&strings))
+        .map(str::to_owned)
         .collect();
 
-    reply_data(recent_blocked)
+    Ok(recent_blocked)
 }
 
 #[cfg(test)]
 mod test {
     use crate::{
+        env::PiholeFile,
         ftl::{
             FtlCounters, FtlDnssecType, FtlDomain, FtlMemory, FtlQuery, FtlQueryReplyType,
             FtlQueryStatus, FtlQueryType, FtlRegexMatch, FtlSettings, MAGIC_BYTE
@@ -159,7 +186,9 @@ mod test {
         TestBuilder::new()
             .endpoint("/admin/api/stats/recent_blocked")
             .ftl_memory(test_memory())
-            .expect_json(json!(["domain5.com"]))
+            .expect_json(json!({
+                "data": ["domain5.com"]
+            }))
             .test();
     }
 
@@ -169,7 +198,64 @@ mod test {
         TestBuilder::new()
             .endpoint("/admin/api/stats/recent_blocked?num=3")
             .ftl_memory(test_memory())
-            .expect_json(json!(["domain5.com", "domain4.com", "domain3.com"]))
+            .expect_json(json!({
+                "data": ["domain5.com", "domain4.com", "domain3.com"]
+            }))
+            .test();
+    }
+
+    /// The `count` parameter returns that many most recently blocked domains,
+    /// including duplicates
+    #[test]
+    fn count() {
+        let mut ftl_memory = test_memory();
+
+        if let FtlMemory::Test {
+            ref mut queries,
+            ref mut counters,
+            ..
+        } = ftl_memory
+        {
+            queries.push(query!(7, Gravity, 1));
+            counters.total_queries += 1;
+        }
+
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/recent_blocked?count=5")
+            .ftl_memory(ftl_memory)
+            .expect_json(json!({
+                "data": [
+                    "domain2.com",
+                    "domain5.com",
+                    "domain4.com",
+                    "domain3.com",
+                    "domain2.com"
+                ]
+            }))
+            .test();
+    }
+
+    /// No more than the maximum number of domains are returned
+    #[test]
+    fn maximum() {
+        let mut ftl_memory = test_memory();
+
+        if let FtlMemory::Test {
+            ref mut queries,
+            ref mut counters,
+            ..
+        } = ftl_memory
+        {
+            *queries = (1..=150).map(|id| query!(id, Gravity, 1)).collect();
+            counters.total_queries = 150;
+        }
+
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/recent_blocked?count=150")
+            .ftl_memory(ftl_memory)
+            .expect_json(json!({
+                "data": vec!["domain2.com"; 100]
+            }))
             .test();
     }
 
@@ -180,12 +266,45 @@ mod test {
         TestBuilder::new()
             .endpoint("/admin/api/stats/recent_blocked?num=10")
             .ftl_memory(test_memory())
-            .expect_json(json!([
-                "domain5.com",
-                "domain4.com",
-                "domain3.com",
-                "domain2.com"
-            ]))
+            .expect_json(json!({
+                "data": [
+                    "domain5.com",
+                    "domain4.com",
+                    "domain3.com",
+                    "domain2.com"
+                ]
+            }))
+            .test();
+    }
+
+    /// Excluded domains are skipped, so older blocked domains take their
+    /// place
+    #[test]
+    fn excluded_domains() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/recent_blocked?count=2")
+            .ftl_memory(test_memory())
+            .file(
+                PiholeFile::SetupVars,
+                "API_EXCLUDE_DOMAINS=DOMAIN5.com,domain3.com"
+            )
+            .expect_json(json!({
+                "data": ["domain4.com", "domain2.com"]
+            }))
+            .test();
+    }
+
+    /// If every blocked domain is excluded, no domains are returned
+    #[test]
+    fn all_excluded() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/recent_blocked?count=5")
+            .ftl_memory(test_memory())
+            .file(
+                PiholeFile::SetupVars,
+                "API_EXCLUDE_DOMAINS=domain2.com,domain3.com,domain4.com,domain5.com"
+            )
+            .expect_json(json!({ "data": [] }))
             .test();
     }
 }
@@ -9,18 +9,86 @@
 // Please see LICENSE file for your rights under this license.
 
 use crate::{
+    databases::gravity::GravityCountCache,
     env::Env,
-    ftl::{FtlMemory, FtlQueryType},
+    ftl::{FtlCounters, FtlDnssecType, FtlMemoryGuard, FtlQueryStatus, FtlQueryType},
     settings::{ConfigEntry, FtlConfEntry, FtlPrivacyLevel, SetupVarsEntry},
-    util::{reply_data, Reply}
+    util::{reply_tagged, reply_timed, Error, IfNoneMatch, RequestTimer, TaggedReply}
 };
 use rocket::State;
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher}
+};
 
-/// Get the summary data
+/// Get the summary data. The reply is tagged with a weak ETag, so clients
+/// which poll the summary can send `If-None-Match` and get a 304 reply when
+/// nothing changed.
 #[get("/stats/summary")]
-pub fn get_summary(ftl_memory: State<FtlMemory>, env: State<Env>) -> Reply {
-    let lock = ftl_memory.lock()?;
-    let counters = ftl_memory.counters(&lock)?;
+pub fn get_summary(
+    timer: RequestTimer,
+    ftl_memory: Result<FtlMemoryGuard, Error>,
+    env: State<Env>,
+    gravity_count: State<GravityCountCache>,
+    if_none_match: IfNoneMatch
+) -> Result<TaggedReply, Error> {
+    let ftl_memory = ftl_memory?;
+    let etag = summary_etag(&ftl_memory.counters()?, &env, &gravity_count)?;
+
+    Ok(reply_tagged(etag, &if_none_match, || {
+        reply_timed(get_summary_impl(&ftl_memory, &env, &gravity_count), timer)
+    }))
+}
+
+/// Compute the weak ETag of the summary. It is a hash of the counters which
+/// the summary is made from, along with the settings and gravity count which
+/// also show up in it, so it changes whenever a reported value changes. The
+/// DNSSEC status of a query is only set when its reply arrives, which also
+/// changes the reply counters.
+fn summary_etag(
+    counters: &FtlCounters,
+    env: &Env,
+    gravity_count: &GravityCountCache
+) -> Result<String, Error> {
+    let mut hasher = DefaultHasher::new();
+
+    [
+        counters.total_queries,
+        counters.blocked_queries,
+        counters.cached_queries,
+        counters.forwarded_queries,
+        counters.total_clients,
+        counters.total_domains,
+        counters.gravity_size,
+        counters.reply_count_ip,
+        counters.reply_count_cname,
+        counters.reply_count_domain,
+        counters.reply_count_nodata,
+        counters.reply_count_nxdomain
+    ]
+    .hash(&mut hasher);
+    counters.query_type_counters.hash(&mut hasher);
+
+    FtlConfEntry::PrivacyLevel.read(env)?.hash(&mut hasher);
+    SetupVarsEntry::BlockingEnabled
+        .is_true(env)?
+        .hash(&mut hasher);
+    gravity_count.get(env).hash(&mut hasher);
+
+    Ok(format!("W/\"{:016x}\"", hasher.finish()))
+}
+
+/// Implementation of [`get_summary`]. The counters are required, but if
+/// another section of shared memory can not be read, the fields made from it
+/// are `null` and a warning is added instead of failing the whole summary.
+///
+/// [`get_summary`]: fn.get_summary.html
+fn get_summary_impl(
+    ftl_memory: &FtlMemoryGuard,
+    env: &Env,
+    gravity_count: &GravityCountCache
+) -> Result<Summary, Error> {
+    let counters = ftl_memory.counters()?;
 
     let percent_blocked = if counters.total_queries == 0 {
         0.0
@@ -28,42 +96,77 @@ pub fn get_summary(ftl_memory: State<FtlMemory>, env: State<Env>) -> Reply {
         (counters.blocked_queries * 100) as f64 / counters.total_queries as f64
     };
 
-    let (total_clients, active_clients) = {
+    let mut warnings = Vec::new();
+
+    let client_counts = {
         if FtlConfEntry::PrivacyLevel.read_as::<FtlPrivacyLevel>(&env)?
             >= FtlPrivacyLevel::HideDomainsAndClients
         {
             // If clients are supposed to be hidden, pretend there are no clients
-            (0, 0)
+            Some((0, 0))
         } else {
             // Only show active clients, and ignore hidden clients
-            let clients = ftl_memory.clients(&lock)?;
-            let strings = ftl_memory.strings(&lock)?;
-
-            let hidden_client_count = clients
-                .iter()
-                .filter(|client| client.get_ip(&strings) == "0.0.0.0")
-                .count();
-
-            let active_client_count = clients
-                .iter()
-                .filter(|client| client.query_count > 0 && client.get_ip(&strings) != "0.0.0.0")
-                .count();
-
-            (
-                counters.total_clients as usize - hidden_client_count,
-                active_client_count
-            )
+            let client_counts = ftl_memory.clients().and_then(|clients| {
+                let strings = ftl_memory.strings()?;
+
+                let hidden_client_count = clients
+                    .iter()
+                    .filter(|client| client.get_ip(&strings) == "0.0.0.0")
+                    .count();
+
+                let active_client_count = clients
+                    .iter()
+                    .filter(|client| client.query_count > 0 && client.get_ip(&strings) != "0.0.0.0")
+                    .count();
+
+                Ok((
+                    counters.total_clients as usize - hidden_client_count,
+                    active_client_count
+                ))
+            });
+
+            partial_section("clients", client_counts, &mut warnings)
         }
     };
 
+    // Count the queries by their DNSSEC status and query status
+    let query_counts = ftl_memory.queries().map(|queries| {
+        let mut dnssec = DnssecTypes::default();
+        let mut query_status = QueryStatuses::default();
+
+        for query in queries.iter().take(counters.total_queries as usize) {
+            match query.dnssec_type {
+                FtlDnssecType::Unspecified => dnssec.unspecified += 1,
+                FtlDnssecType::Secure => dnssec.secure += 1,
+                FtlDnssecType::Insecure => dnssec.insecure += 1,
+                FtlDnssecType::Bogus => dnssec.bogus += 1,
+                FtlDnssecType::Abandoned => dnssec.abandoned += 1,
+                FtlDnssecType::Unknown => dnssec.unknown += 1
+            }
+
+            query_status.add(query.status, 1);
+        }
+
+        (dnssec, query_status)
+    });
+    let (dnssec, query_status) = match partial_section("queries", query_counts, &mut warnings) {
+        Some((dnssec, query_status)) => (Some(dnssec), Some(query_status)),
+        None => (None, None)
+    };
+    let (total_clients, active_clients) = match client_counts {
+        Some((total_clients, active_clients)) => (Some(total_clients), Some(active_clients)),
+        None => (None, None)
+    };
+
     let status = if SetupVarsEntry::BlockingEnabled.is_true(&env)? {
         "enabled"
     } else {
         "disabled"
     };
 
-    reply_data(Summary {
+    Ok(Summary {
         gravity_size: counters.gravity_size as usize,
+        gravity_domains_blocked: gravity_count.get(&env),
         total_queries: TotalQueries {
             A: counters.query_type(FtlQueryType::A),
             AAAA: counters.query_type(FtlQueryType::AAAA),
@@ -85,17 +188,38 @@ pub fn get_summary(ftl_memory: State<FtlMemory>, env: State<Env>) -> Reply {
             NODATA: counters.reply_count_nodata as usize,
             NXDOMAIN: counters.reply_count_nxdomain as usize
         },
+        dnssec,
+        query_status,
         total_clients,
         active_clients,
-        status
+        status,
+        warnings
     })
 }
 
+/// Get the value of a section of the summary. If it could not be read, a
+/// warning naming the section is added and `None` is returned, so the section
+/// is `null` in the summary.
+fn partial_section<T>(
+    section: &str,
+    result: Result<T, Error>,
+    warnings: &mut Vec<String>
+) -> Option<T> {
+    match result {
+        Ok(value) => Some(value),
+        Err(e) => {
+            warnings.push(format!("Unable to read {}: {}", section, e));
+            None
+        }
+    }
+}
+
 /// Represents the response of summary endpoints
 #[derive(Serialize)]
 #[cfg_attr(test, derive(PartialEq, Debug))]
 pub struct Summary {
     pub gravity_size: usize,
+    pub gravity_domains_blocked: Option<usize>,
     pub total_queries: TotalQueries,
     pub blocked_queries: usize,
     pub percent_blocked: f64,
@@ -103,9 +227,14 @@ pub struct Summary {
     pub forwarded_queries: usize,
     pub cached_queries: usize,
     pub reply_types: ReplyTypes,
-    pub total_clients: usize,
-    pub active_clients: usize,
-    pub status: &'static str
+    pub dnssec: Option<DnssecTypes>,
+    pub query_status: Option<QueryStatuses>,
+    pub total_clients: Option<usize>,
+    pub active_clients: Option<usize>,
+    pub status: &'static str,
+    /// Sections which could not be read, and are `null` in the summary
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>
 }
 
 /// Part of the summary response
@@ -134,16 +263,72 @@ pub struct ReplyTypes {
     pub NXDOMAIN: usize
 }
 
+/// Part of the summary response
+#[derive(Serialize, Default)]
+#[cfg_attr(test, derive(PartialEq, Debug))]
+pub struct DnssecTypes {
+    pub unspecified: usize,
+    pub secure: usize,
+    pub insecure: usize,
+    pub bogus: usize,
+    pub abandoned: usize,
+    pub unknown: usize
+}
+
+/// Part of the summary response. Queries are grouped by what blocked or
+/// answered them.
+#[derive(Serialize, Default)]
+#[cfg_attr(test, derive(PartialEq, Debug))]
+pub struct QueryStatuses {
+    pub gravity: usize,
+    pub regex: usize,
+    pub blacklist: usize,
+    pub cached: usize,
+    pub forwarded: usize,
+    pub other: usize
+}
+
+impl QueryStatuses {
+    /// Add a number of queries with the status to its group
+    pub fn add(&mut self, status: FtlQueryStatus, count: usize) {
+        match status {
+            FtlQueryStatus::Gravity => self.gravity += count,
+            // Queries blocked by a regex have the wildcard status
+            FtlQueryStatus::Wildcard => self.regex += count,
+            FtlQueryStatus::Blacklist => self.blacklist += count,
+            FtlQueryStatus::Cache => self.cached += count,
+            FtlQueryStatus::Forward => self.forwarded += count,
+            FtlQueryStatus::Unknown | FtlQueryStatus::ExternalBlock => self.other += count
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
+    use super::{partial_section, summary_etag};
     use crate::{
-        env::PiholeFile,
-        ftl::{FtlClient, FtlCounters, FtlMemory, FtlSettings},
-        testing::TestBuilder
+        databases::gravity::GravityCountCache,
+        env::{Config, Env, PiholeFile},
+        ftl::{
+            FtlClient, FtlCounters, FtlDnssecType, FtlMemory, FtlQuery, FtlQueryStatus, FtlSettings
+        },
+        testing::{test_query, TestBuilder, TestEnvBuilder},
+        util::{Error, ErrorKind}
     };
+    use rocket::http::{ContentType, Header, Method, Status};
     use std::collections::HashMap;
 
-    /// There are 6 clients, two inactive, one hidden, and two with names.
+    /// Shorthand for making `FtlQuery` structs
+    fn query(status: FtlQueryStatus, dnssec_type: FtlDnssecType) -> FtlQuery {
+        FtlQuery {
+            status,
+            dnssec_type,
+            ..test_query()
+        }
+    }
+
+    /// There are 6 clients, two inactive, one hidden, and two with names. There
+    /// are 7 queries with various statuses and DNSSEC statuses.
     fn test_data() -> FtlMemory {
         let mut strings = HashMap::new();
         strings.insert(1, "10.1.1.1".to_owned());
@@ -168,7 +353,15 @@ mod test {
             over_time: Vec::new(),
             strings,
             upstreams: Vec::new(),
-            queries: Vec::new(),
+            queries: vec![
+                query(FtlQueryStatus::Gravity, FtlDnssecType::Unspecified),
+                query(FtlQueryStatus::Wildcard, FtlDnssecType::Unspecified),
+                query(FtlQueryStatus::Cache, FtlDnssecType::Unspecified),
+                query(FtlQueryStatus::Cache, FtlDnssecType::Secure),
+                query(FtlQueryStatus::Forward, FtlDnssecType::Secure),
+                query(FtlQueryStatus::Forward, FtlDnssecType::Insecure),
+                query(FtlQueryStatus::Forward, FtlDnssecType::Bogus),
+            ],
             counters: FtlCounters {
                 gravity_size: 100_000,
                 total_queries: 7,
@@ -195,32 +388,52 @@ mod test {
             .endpoint("/admin/api/stats/summary")
             .ftl_memory(test_data())
             .file(PiholeFile::SetupVars, "BLOCKING_ENABLED=true")
+            .file(PiholeFile::Gravity, "domain1.com\ndomain2.com\n")
             .expect_json(json!({
-                "gravity_size": 100_000,
-                "total_queries": {
-                    "A": 3,
-                    "AAAA": 4,
-                    "ANY": 1,
-                    "SRV": 0,
-                    "SOA": 0,
-                    "PTR": 3,
-                    "TXT": 0
-                },
-                "blocked_queries": 2,
-                "percent_blocked": 28.571_428_571_428_573,
-                "unique_domains": 6,
-                "forwarded_queries": 3,
-                "cached_queries": 2,
-                "reply_types": {
-                    "IP": 3,
-                    "CNAME": 3,
-                    "DOMAIN": 1,
-                    "NODATA": 1,
-                    "NXDOMAIN": 2
-                },
-                "total_clients": 5,
-                "active_clients": 4,
-                "status": "enabled"
+                "data": {
+                    "gravity_size": 100_000,
+                    "gravity_domains_blocked": 2,
+                    "total_queries": {
+                        "A": 3,
+                        "AAAA": 4,
+                        "ANY": 1,
+                        "SRV": 0,
+                        "SOA": 0,
+                        "PTR": 3,
+                        "TXT": 0
+                    },
+                    "blocked_queries": 2,
+                    "percent_blocked": 28.571_428_571_428_573,
+                    "unique_domains": 6,
+                    "forwarded_queries": 3,
+                    "cached_queries": 2,
+                    "reply_types": {
+                        "IP": 3,
+                        "CNAME": 3,
+                        "DOMAIN": 1,
+                        "NODATA": 1,
+                        "NXDOMAIN": 2
+                    },
+                    "dnssec": {
+                        "unspecified": 3,
+                        "secure": 2,
+                        "insecure": 1,
+                        "bogus": 1,
+                        "abandoned": 0,
+                        "unknown": 0
+                    },
+                    "query_status": {
+                        "gravity": 1,
+                        "regex": 1,
+                        "blacklist": 0,
+                        "cached": 2,
+                        "forwarded": 3,
+                        "other": 0
+                    },
+                    "total_clients": 5,
+                    "active_clients": 4,
+                    "status": "enabled"
+                }
             }))
             .test();
     }
@@ -233,32 +446,183 @@ mod test {
             .file(PiholeFile::SetupVars, "BLOCKING_ENABLED=false")
             .file(PiholeFile::FtlConfig, "PRIVACYLEVEL=2")
             .expect_json(json!({
-                "gravity_size": 100_000,
-                "total_queries": {
-                    "A": 3,
-                    "AAAA": 4,
-                    "ANY": 1,
-                    "SRV": 0,
-                    "SOA": 0,
-                    "PTR": 3,
-                    "TXT": 0
-                },
-                "blocked_queries": 2,
-                "percent_blocked": 28.571_428_571_428_573,
-                "unique_domains": 6,
-                "forwarded_queries": 3,
-                "cached_queries": 2,
-                "reply_types": {
-                    "IP": 3,
-                    "CNAME": 3,
-                    "DOMAIN": 1,
-                    "NODATA": 1,
-                    "NXDOMAIN": 2
-                },
-                "total_clients": 0,
-                "active_clients": 0,
-                "status": "disabled"
+                "data": {
+                    "gravity_size": 100_000,
+                    "gravity_domains_blocked": None::<usize>,
+                    "total_queries": {
+                        "A": 3,
+                        "AAAA": 4,
+                        "ANY": 1,
+                        "SRV": 0,
+                        "SOA": 0,
+                        "PTR": 3,
+                        "TXT": 0
+                    },
+                    "blocked_queries": 2,
+                    "percent_blocked": 28.571_428_571_428_573,
+                    "unique_domains": 6,
+                    "forwarded_queries": 3,
+                    "cached_queries": 2,
+                    "reply_types": {
+                        "IP": 3,
+                        "CNAME": 3,
+                        "DOMAIN": 1,
+                        "NODATA": 1,
+                        "NXDOMAIN": 2
+                    },
+                    "dnssec": {
+                        "unspecified": 3,
+                        "secure": 2,
+                        "insecure": 1,
+                        "bogus": 1,
+                        "abandoned": 0,
+                        "unknown": 0
+                    },
+                    "query_status": {
+                        "gravity": 1,
+                        "regex": 1,
+                        "blacklist": 0,
+                        "cached": 2,
+                        "forwarded": 3,
+                        "other": 0
+                    },
+                    "total_clients": 0,
+                    "active_clients": 0,
+                    "status": "disabled"
+                }
             }))
             .test();
     }
+
+    /// Get the counters of the test data
+    fn test_counters() -> FtlCounters {
+        match test_data() {
+            FtlMemory::Test { counters, .. } => counters,
+            _ => unreachable!()
+        }
+    }
+
+    /// Get the ETag of the test data, with blocking enabled
+    fn test_etag(counters: &FtlCounters) -> String {
+        let env = Env::Test(
+            Config::default(),
+            TestEnvBuilder::new()
+                .file(PiholeFile::SetupVars, "BLOCKING_ENABLED=true")
+                .build()
+        );
+
+        summary_etag(counters, &env, &GravityCountCache::new()).unwrap()
+    }
+
+    /// The ETag is weak, and only changes when the counters change
+    #[test]
+    fn etag_changes_with_counters() {
+        let mut counters = test_counters();
+        let etag = test_etag(&counters);
+
+        assert!(etag.starts_with("W/\""));
+        assert_eq!(etag, test_etag(&counters));
+
+        counters.blocked_queries += 1;
+        assert_ne!(etag, test_etag(&counters));
+    }
+
+    /// The ETag changes when the blocking status changes
+    #[test]
+    fn etag_changes_with_status() {
+        let counters = test_counters();
+        let env = Env::Test(
+            Config::default(),
+            TestEnvBuilder::new()
+                .file(PiholeFile::SetupVars, "BLOCKING_ENABLED=false")
+                .build()
+        );
+
+        assert_ne!(
+            test_etag(&counters),
+            summary_etag(&counters, &env, &GravityCountCache::new()).unwrap()
+        );
+    }
+
+    /// A matching If-None-Match header gets a 304 reply without any data
+    #[test]
+    fn not_modified() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/summary")
+            .ftl_memory(test_data())
+            .file(PiholeFile::SetupVars, "BLOCKING_ENABLED=true")
+            .header(Header::new("If-None-Match", test_etag(&test_counters())))
+            .expect_status(Status::NotModified)
+            .test();
+    }
+
+    /// HEAD requests get the same headers, including the ETag, but no body
+    #[test]
+    fn head() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/summary")
+            .method(Method::Head)
+            .ftl_memory(test_data())
+            .file(PiholeFile::SetupVars, "BLOCKING_ENABLED=true")
+            .expect_header(Header::new("ETag", test_etag(&test_counters())))
+            .expect_header(ContentType::JSON)
+            .test();
+    }
+
+    /// Shared memory from an incompatible version of FTL is not read
+    #[test]
+    fn shared_memory_version_mismatch() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/summary")
+            .ftl_memory(FtlMemory::Test {
+                clients: Vec::new(),
+                domains: Vec::new(),
+                over_time: Vec::new(),
+                strings: HashMap::new(),
+                upstreams: Vec::new(),
+                queries: Vec::new(),
+                counters: FtlCounters::default(),
+                settings: FtlSettings {
+                    version: 3,
+                    ..FtlSettings::default()
+                }
+            })
+            .file(PiholeFile::SetupVars, "BLOCKING_ENABLED=true")
+            .expect_status(Status::ServiceUnavailable)
+            .expect_json(json!({
+                "error": {
+                    "key": "shared_memory_version",
+                    "message": "Incompatible version of shared memory. Found 3, expected 4",
+                    "data": {
+                        "found": 3,
+                        "expected": 4
+                    }
+                }
+            }))
+            .test();
+    }
+
+    /// A section which can be read is used as is, without a warning
+    #[test]
+    fn partial_section_read() {
+        let mut warnings = Vec::new();
+
+        assert_eq!(partial_section("clients", Ok(5), &mut warnings), Some(5));
+        assert!(warnings.is_empty());
+    }
+
+    /// A section which can not be read is missing, and a warning is added
+    #[test]
+    fn partial_section_unreadable() {
+        let mut warnings = Vec::new();
+        let result: Result<usize, Error> = Err(Error::from(ErrorKind::SharedMemoryOpen(
+            "/FTL-queries".to_owned()
+        )));
+
+        assert_eq!(partial_section("queries", result, &mut warnings), None);
+        assert_eq!(
+            warnings,
+            vec!["Unable to read queries: Failed to open shared memory: /FTL-queries"]
+        );
+    }
 }
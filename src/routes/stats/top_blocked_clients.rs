@@ -0,0 +1,257 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// Top Blocked Clients Endpoint
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    env::Env,
+    ftl::{ClientReply, FtlMemory},
+    routes::{
+        auth::User,
+        stats::{
+            clients::{filter_ftl_clients, ClientParams},
+            common::{anonymize_clients, limit_results}
+        }
+    },
+    util::{reply_timed, Error, Reply, RequestTimer}
+};
+use rocket::State;
+use std::{cmp::Ordering, collections::HashMap};
+
+/// A client ranked by its blocked queries
+#[derive(Serialize)]
+pub struct TopBlockedClientReply {
+    pub client: ClientReply,
+    pub blocked: usize,
+    pub total: usize,
+    /// The share of the client's queries which were blocked, from 0 to 1
+    pub ratio: f64
+}
+
+/// Get the clients with the most blocked queries
+#[get("/stats/top_blocked_clients?<count>")]
+pub fn top_blocked_clients(
+    timer: RequestTimer,
+    _auth: User,
+    ftl_memory: State<FtlMemory>,
+    env: State<Env>,
+    count: Option<usize>
+) -> Reply {
+    reply_timed(
+        get_top_blocked_clients(&ftl_memory, &env, count.unwrap_or(10)),
+        timer
+    )
+}
+
+/// Tally the blocked and total queries of each client, and rank the clients
+/// by their blocked queries, then by their block ratio. Clients without
+/// queries are not shown.
+fn get_top_blocked_clients(
+    ftl_memory: &FtlMemory,
+    env: &Env,
+    count: usize
+) -> Result<Vec<TopBlockedClientReply>, Error> {
+    let lock = ftl_memory.lock()?;
+    let counters = ftl_memory.counters(&lock)?;
+    let strings = ftl_memory.strings(&lock)?;
+    let clients = ftl_memory.clients(&lock)?;
+    let queries = ftl_memory.queries(&lock)?;
+
+    // Count the blocked and total queries by client ID
+    let mut client_counts: HashMap<usize, (usize, usize)> = HashMap::new();
+
+    for query in queries.iter().take(counters.total_queries as usize) {
+        let (blocked, total) = client_counts
+            .entry(query.client_id as usize)
+            .or_insert((0, 0));
+
+        if query.is_blocked() {
+            *blocked += 1;
+        }
+
+        *total += 1;
+    }
+
+    // Map the counts to the client IPs, so they can be matched with the
+    // filtered clients
+    let client_counts: HashMap<&str, (usize, usize)> = client_counts
+        .into_iter()
+        .filter_map(|(client_id, counts)| {
+            clients
+                .get(client_id)
+                .map(|client| (client.get_ip(&strings), counts))
+        })
+        .collect();
+
+    let mut top_clients: Vec<TopBlockedClientReply> =
+        filter_ftl_clients(ftl_memory, &lock, &clients, env, ClientParams::default())?
+            .into_iter()
+            .filter_map(|client| {
+                let (blocked, total) = *client_counts.get(client.get_ip(&strings))?;
+
+                Some(TopBlockedClientReply {
+                    client: client.as_reply(&strings),
+                    blocked,
+                    total,
+                    ratio: blocked as f64 / total as f64
+                })
+            })
+            .collect();
+
+    top_clients.sort_by(|a, b| {
+        b.blocked
+            .cmp(&a.blocked)
+            .then(b.ratio.partial_cmp(&a.ratio).unwrap_or(Ordering::Equal))
+    });
+    top_clients.truncate(limit_results(count, env)?);
+
+    anonymize_clients(
+        top_clients
+            .iter_mut()
+            .map(|item| (&mut item.client.name, &mut item.client.ip)),
+        env
+    )?;
+
+    Ok(top_clients)
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        env::PiholeFile,
+        ftl::{FtlClient, FtlCounters, FtlMemory, FtlQuery, FtlQueryStatus, FtlSettings},
+        testing::{test_query, TestBuilder}
+    };
+    use std::collections::HashMap;
+
+    /// Shorthand for making `FtlQuery` structs
+    fn query(client_id: i32, status: FtlQueryStatus) -> FtlQuery {
+        FtlQuery {
+            client_id,
+            status,
+            ..test_query()
+        }
+    }
+
+    /// There are 5 clients, one of them hidden and one without queries. The
+    /// first and second clients have two blocked queries each, but the second
+    /// client has a higher block ratio.
+    fn test_data() -> FtlMemory {
+        let mut strings = HashMap::new();
+        strings.insert(1, "10.1.1.1".to_owned());
+        strings.insert(2, "client1".to_owned());
+        strings.insert(3, "10.1.1.2".to_owned());
+        strings.insert(4, "10.1.1.3".to_owned());
+        strings.insert(5, "10.1.1.4".to_owned());
+        strings.insert(6, "0.0.0.0".to_owned());
+
+        FtlMemory::Test {
+            clients: vec![
+                FtlClient::new(4, 2, 1, Some(2)),
+                FtlClient::new(2, 2, 3, None),
+                FtlClient::new(1, 0, 4, None),
+                FtlClient::new(0, 0, 5, None),
+                FtlClient::new(1, 1, 6, None),
+            ],
+            domains: Vec::new(),
+            over_time: Vec::new(),
+            strings,
+            upstreams: Vec::new(),
+            queries: vec![
+                query(0, FtlQueryStatus::Gravity),
+                query(0, FtlQueryStatus::Forward),
+                query(1, FtlQueryStatus::Blacklist),
+                query(0, FtlQueryStatus::Cache),
+                query(2, FtlQueryStatus::Forward),
+                query(1, FtlQueryStatus::Wildcard),
+                query(0, FtlQueryStatus::ExternalBlock),
+                query(4, FtlQueryStatus::Gravity),
+            ],
+            counters: FtlCounters {
+                total_clients: 5,
+                total_queries: 8,
+                ..FtlCounters::default()
+            },
+            settings: FtlSettings::default()
+        }
+    }
+
+    /// Clients are ranked by blocked queries, then by block ratio. Hidden
+    /// clients and clients without queries are not shown.
+    #[test]
+    fn default_params() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/top_blocked_clients")
+            .ftl_memory(test_data())
+            .expect_json(json!({
+                "data": [
+                    {
+                        "client": { "name": "", "ip": "10.1.1.2" },
+                        "blocked": 2,
+                        "total": 2,
+                        "ratio": 1.0
+                    },
+                    {
+                        "client": { "name": "client1", "ip": "10.1.1.1" },
+                        "blocked": 2,
+                        "total": 4,
+                        "ratio": 0.5
+                    },
+                    {
+                        "client": { "name": "", "ip": "10.1.1.3" },
+                        "blocked": 0,
+                        "total": 1,
+                        "ratio": 0.0
+                    }
+                ]
+            }))
+            .test();
+    }
+
+    /// The count limits the number of clients
+    #[test]
+    fn count() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/top_blocked_clients?count=1")
+            .ftl_memory(test_data())
+            .expect_json(json!({
+                "data": [
+                    {
+                        "client": { "name": "", "ip": "10.1.1.2" },
+                        "blocked": 2,
+                        "total": 2,
+                        "ratio": 1.0
+                    }
+                ]
+            }))
+            .test();
+    }
+
+    /// Excluded clients are not shown
+    #[test]
+    fn excluded_clients() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/top_blocked_clients")
+            .ftl_memory(test_data())
+            .file(
+                PiholeFile::SetupVars,
+                "API_EXCLUDE_CLIENTS=10.1.1.2,10.1.1.3"
+            )
+            .expect_json(json!({
+                "data": [
+                    {
+                        "client": { "name": "client1", "ip": "10.1.1.1" },
+                        "blocked": 2,
+                        "total": 4,
+                        "ratio": 0.5
+                    }
+                ]
+            }))
+            .test();
+    }
+}
@@ -0,0 +1,288 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// Top Blocked Domains By Reason Endpoint
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    env::Env,
+    ftl::{FtlMemory, FtlQueryStatus},
+    routes::{
+        auth::User,
+        stats::{
+            common::{get_excluded_domains, get_hidden_domain, limit_results},
+            top_domains::{
+                check_privacy_level_top_domains, check_query_log_show_top_domains,
+                TopDomainItemReply
+            }
+        }
+    },
+    util::{reply_timed, Error, ErrorKind, Reply, RequestTimer}
+};
+use rocket::State;
+use std::collections::HashMap;
+
+/// Represents the reply structure for the top blocked domains of each
+/// blocking reason
+#[derive(Serialize, Default)]
+#[cfg_attr(test, derive(Debug, PartialEq))]
+pub struct TopBlockedDomainsReply {
+    pub gravity: Vec<TopDomainItemReply>,
+    pub regex: Vec<TopDomainItemReply>,
+    pub blacklist: Vec<TopDomainItemReply>
+}
+
+/// Get the top blocked domains of each blocking reason. Only `group_by=reason`
+/// is supported.
+#[get("/stats/top_blocked_domains?<group_by>&<limit>")]
+pub fn top_blocked_domains(
+    timer: RequestTimer,
+    _auth: User,
+    ftl_memory: State<FtlMemory>,
+    env: State<Env>,
+    group_by: String,
+    limit: Option<usize>
+) -> Reply {
+    reply_timed(
+        get_top_blocked_domains(&ftl_memory, &env, &group_by, limit.unwrap_or(10)),
+        timer
+    )
+}
+
+/// Get the top blocked domains of gravity, the regexes, and the blacklist.
+/// The queries are scanned once, counting the blocked queries of each domain
+/// by the status they were blocked with. Queries blocked upstream are not
+/// counted, as they were not blocked by a list.
+fn get_top_blocked_domains(
+    ftl_memory: &FtlMemory,
+    env: &Env,
+    group_by: &str,
+    limit: usize
+) -> Result<TopBlockedDomainsReply, Error> {
+    if group_by != "reason" {
+        return Err(Error::from(ErrorKind::BadRequest));
+    }
+
+    let limit = limit_results(limit, env)?;
+
+    let lock = ftl_memory.lock()?;
+    let counters = ftl_memory.counters(&lock)?;
+
+    // Check if we are allowed to share the blocked domains
+    if check_query_log_show_top_domains(env, true)?.is_some()
+        || check_privacy_level_top_domains(env, true, 0)?.is_some()
+    {
+        return Ok(TopBlockedDomainsReply::default());
+    }
+
+    let domains = ftl_memory.domains(&lock)?;
+    let strings = ftl_memory.strings(&lock)?;
+    let queries = ftl_memory.queries(&lock)?;
+
+    // Count the blocked queries of each domain, by domain ID
+    let mut gravity: HashMap<usize, usize> = HashMap::new();
+    let mut regex: HashMap<usize, usize> = HashMap::new();
+    let mut blacklist: HashMap<usize, usize> = HashMap::new();
+
+    for query in queries.iter().take(counters.total_queries as usize) {
+        let counts = match query.status {
+            FtlQueryStatus::Gravity => &mut gravity,
+            FtlQueryStatus::Wildcard => &mut regex,
+            FtlQueryStatus::Blacklist => &mut blacklist,
+            _ => continue
+        };
+
+        *counts.entry(query.domain_id as usize).or_insert(0) += 1;
+    }
+
+    let excluded_domains = get_excluded_domains(env)?;
+    let hidden_domain = get_hidden_domain();
+
+    // Rank the domains of a reason, skipping excluded and hidden domains
+    let rank = |counts: HashMap<usize, usize>| -> Vec<TopDomainItemReply> {
+        let mut top_domains: Vec<TopDomainItemReply> = counts
+            .into_iter()
+            .filter_map(|(domain_id, count)| {
+                let domain = domains.get(domain_id)?.get_domain(&strings);
+
+                if domain == hidden_domain || excluded_domains.contains(&domain.to_lowercase()) {
+                    return None;
+                }
+
+                Some(TopDomainItemReply {
+                    domain: domain.to_owned(),
+                    count
+                })
+            })
+            .collect();
+
+        top_domains.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.domain.cmp(&b.domain)));
+        top_domains.truncate(limit);
+        top_domains
+    };
+
+    Ok(TopBlockedDomainsReply {
+        gravity: rank(gravity),
+        regex: rank(regex),
+        blacklist: rank(blacklist)
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        env::PiholeFile,
+        ftl::{
+            FtlCounters, FtlDomain, FtlMemory, FtlQuery, FtlQueryStatus, FtlRegexMatch, FtlSettings
+        },
+        testing::{test_query, TestBuilder}
+    };
+    use rocket::http::Status;
+    use std::collections::HashMap;
+
+    /// Shorthand for making `FtlQuery` structs
+    fn query(domain_id: i32, status: FtlQueryStatus) -> FtlQuery {
+        FtlQuery {
+            domain_id,
+            status,
+            ..test_query()
+        }
+    }
+
+    /// There are 5 domains, one of them hidden. Some domains are blocked for
+    /// more than one reason.
+    fn test_data() -> FtlMemory {
+        let mut strings = HashMap::new();
+        strings.insert(1, "ads.example.com".to_owned());
+        strings.insert(2, "tracker.example.com".to_owned());
+        strings.insert(3, "example.net".to_owned());
+        strings.insert(4, "hidden".to_owned());
+        strings.insert(5, "github.com".to_owned());
+
+        FtlMemory::Test {
+            domains: vec![
+                FtlDomain::new(3, 3, 1, FtlRegexMatch::Blocked),
+                FtlDomain::new(2, 2, 2, FtlRegexMatch::NotBlocked),
+                FtlDomain::new(2, 2, 3, FtlRegexMatch::NotBlocked),
+                FtlDomain::new(1, 1, 4, FtlRegexMatch::NotBlocked),
+                FtlDomain::new(2, 0, 5, FtlRegexMatch::NotBlocked),
+            ],
+            clients: Vec::new(),
+            over_time: Vec::new(),
+            strings,
+            upstreams: Vec::new(),
+            queries: vec![
+                query(0, FtlQueryStatus::Gravity),
+                query(0, FtlQueryStatus::Wildcard),
+                query(0, FtlQueryStatus::Gravity),
+                query(1, FtlQueryStatus::Gravity),
+                query(1, FtlQueryStatus::Blacklist),
+                query(2, FtlQueryStatus::Blacklist),
+                query(2, FtlQueryStatus::ExternalBlock),
+                query(3, FtlQueryStatus::Gravity),
+                query(4, FtlQueryStatus::Forward),
+                query(4, FtlQueryStatus::Cache),
+            ],
+            counters: FtlCounters {
+                total_queries: 10,
+                blocked_queries: 8,
+                total_domains: 5,
+                ..FtlCounters::default()
+            },
+            settings: FtlSettings::default()
+        }
+    }
+
+    /// The blocked domains are ranked separately for each blocking reason.
+    /// Permitted, hidden, and externally blocked queries are not counted.
+    #[test]
+    fn group_by_reason() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/top_blocked_domains?group_by=reason")
+            .ftl_memory(test_data())
+            .expect_json(json!({
+                "data": {
+                    "gravity": [
+                        { "domain": "ads.example.com", "count": 2 },
+                        { "domain": "tracker.example.com", "count": 1 }
+                    ],
+                    "regex": [{ "domain": "ads.example.com", "count": 1 }],
+                    "blacklist": [
+                        { "domain": "example.net", "count": 1 },
+                        { "domain": "tracker.example.com", "count": 1 }
+                    ]
+                }
+            }))
+            .test();
+    }
+
+    /// The limit applies to each reason
+    #[test]
+    fn limit() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/top_blocked_domains?group_by=reason&limit=1")
+            .ftl_memory(test_data())
+            .expect_json(json!({
+                "data": {
+                    "gravity": [{ "domain": "ads.example.com", "count": 2 }],
+                    "regex": [{ "domain": "ads.example.com", "count": 1 }],
+                    "blacklist": [{ "domain": "example.net", "count": 1 }]
+                }
+            }))
+            .test();
+    }
+
+    /// Excluded domains are not shown
+    #[test]
+    fn excluded() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/top_blocked_domains?group_by=reason")
+            .ftl_memory(test_data())
+            .file(PiholeFile::SetupVars, "API_EXCLUDE_DOMAINS=ADS.example.com")
+            .expect_json(json!({
+                "data": {
+                    "gravity": [{ "domain": "tracker.example.com", "count": 1 }],
+                    "regex": [],
+                    "blacklist": [
+                        { "domain": "example.net", "count": 1 },
+                        { "domain": "tracker.example.com", "count": 1 }
+                    ]
+                }
+            }))
+            .test();
+    }
+
+    /// Privacy level 1 does not show any domains
+    #[test]
+    fn privacy() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/top_blocked_domains?group_by=reason")
+            .ftl_memory(test_data())
+            .file(PiholeFile::FtlConfig, "PRIVACYLEVEL=1")
+            .expect_json(json!({
+                "data": { "gravity": [], "regex": [], "blacklist": [] }
+            }))
+            .test();
+    }
+
+    /// Only grouping by reason is supported
+    #[test]
+    fn invalid_group_by() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/top_blocked_domains?group_by=client")
+            .ftl_memory(test_data())
+            .expect_status(Status::BadRequest)
+            .expect_json(json!({
+                "error": {
+                    "key": "bad_request",
+                    "message": "Bad request",
+                    "data": None::<()>
+                }
+            }))
+            .test();
+    }
+}
@@ -10,25 +10,32 @@
 
 use crate::{
     env::Env,
-    ftl::{FtlClient, FtlMemory},
+    ftl::{FtlClient, FtlMemoryGuard},
     routes::{
         auth::User,
-        stats::common::{remove_excluded_clients, remove_hidden_clients}
+        stats::common::{
+            anonymize_clients, check_time_range, get_custom_dns_names, limit_results,
+            remove_excluded_clients, remove_hidden_clients
+        }
     },
     settings::{ConfigEntry, FtlConfEntry, FtlPrivacyLevel},
-    util::{reply_result, Error, Reply}
+    util::{reply_timed, Error, Reply, RequestTimer}
 };
 use rocket::{request::Form, State};
 
 /// Get the top clients
 #[get("/stats/top_clients?<params..>")]
 pub fn top_clients(
+    timer: RequestTimer,
     _auth: User,
-    ftl_memory: State<FtlMemory>,
+    ftl_memory: Result<FtlMemoryGuard, Error>,
     env: State<Env>,
     params: Form<TopClientParams>
 ) -> Reply {
-    reply_result(get_top_clients(&ftl_memory, &env, params.into_inner()))
+    reply_timed(
+        get_top_clients(&ftl_memory?, &env, params.into_inner()),
+        timer
+    )
 }
 
 /// Represents the possible GET parameters on `/stats/top_clients`
@@ -37,7 +44,11 @@ pub struct TopClientParams {
     pub limit: Option<usize>,
     pub inactive: Option<bool>,
     pub ascending: Option<bool>,
-    pub blocked: Option<bool>
+    pub blocked: Option<bool>,
+    pub from: Option<u64>,
+    pub until: Option<u64>,
+    /// Add each client's share of the visible clients' queries
+    pub as_percentage: Option<bool>
 }
 
 /// Represents the reply structure for top (blocked) clients
@@ -57,25 +68,69 @@ pub struct TopClientsReply {
 pub struct TopClientItemReply {
     pub name: String,
     pub ip: String,
-    pub count: usize
+    pub count: usize,
+    /// The percentage of the visible clients' queries made by this client
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub percentage: Option<f64>
 }
 
 /// Get the top clients according to the parameters
 fn get_top_clients(
-    ftl_memory: &FtlMemory,
+    ftl_memory: &FtlMemoryGuard,
     env: &Env,
     params: TopClientParams
 ) -> Result<TopClientsReply, Error> {
     // Resolve the parameters
-    let limit = params.limit.unwrap_or(10);
+    let limit = limit_results(params.limit.unwrap_or(10), env)?;
     let inactive = params.inactive.unwrap_or(false);
     let ascending = params.ascending.unwrap_or(false);
     let blocked = params.blocked.unwrap_or(false);
+    let as_percentage = params.as_percentage.unwrap_or(false);
+
+    // The time window must not be inverted
+    if let (Some(from), Some(until)) = (params.from, params.until) {
+        check_time_range(from, until)?;
+    }
+
+    let counters = ftl_memory.counters()?;
+    let strings = ftl_memory.strings()?;
+    let clients = ftl_memory.clients()?;
+
+    // If a time window was given, count the queries in the window instead of
+    // using the precomputed client counts
+    let window_clients = if params.from.is_some() || params.until.is_some() {
+        Some(count_clients_in_window(
+            ftl_memory,
+            &clients,
+            params.from,
+            params.until
+        )?)
+    } else {
+        None
+    };
 
-    let lock = ftl_memory.lock()?;
-    let counters = ftl_memory.counters(&lock)?;
+    // Get an array of valid client references (FTL allocates more than it uses)
+    let mut clients: Vec<&FtlClient> = match window_clients {
+        Some(ref window_clients) => window_clients.iter().collect(),
+        None => clients
+            .iter()
+            .take(counters.total_clients as usize)
+            .collect()
+    };
 
-    let total_count = if blocked {
+    let total_count = if window_clients.is_some() {
+        // Only the queries in the time window are counted
+        clients
+            .iter()
+            .map(|client| {
+                if blocked {
+                    client.blocked_count
+                } else {
+                    client.query_count
+                }
+            })
+            .sum::<i32>()
+    } else if blocked {
         counters.blocked_queries
     } else {
         counters.total_queries
@@ -88,15 +143,6 @@ fn get_top_clients(
         return Ok(reply);
     }
 
-    let strings = ftl_memory.strings(&lock)?;
-    let clients = ftl_memory.clients(&lock)?;
-
-    // Get an array of valid client references (FTL allocates more than it uses)
-    let mut clients: Vec<&FtlClient> = clients
-        .iter()
-        .take(counters.total_clients as usize)
-        .collect();
-
     // Ignore inactive clients by default (retain active clients)
     if !inactive {
         if blocked {
@@ -108,7 +154,20 @@ fn get_top_clients(
 
     // Remove excluded and hidden clients
     remove_excluded_clients(&mut clients, env, &strings)?;
-    remove_hidden_clients(&mut clients, &strings);
+    remove_hidden_clients(&mut clients, &strings, env)?;
+
+    // The percentages are of the remaining clients' queries, before the limit
+    // is applied
+    let visible_count = clients
+        .iter()
+        .map(|client| {
+            if blocked {
+                client.blocked_count
+            } else {
+                client.query_count
+            }
+        })
+        .sum::<i32>() as usize;
 
     // Sort the clients (descending by default)
     match (ascending, blocked) {
@@ -123,38 +182,115 @@ fn get_top_clients(
         clients.split_off(limit);
     }
 
+    // Clients without a name fall back to their name in the custom DNS list
+    let custom_dns_names = get_custom_dns_names(env)?;
+
     // Map the clients into the output format
-    let top_clients: Vec<TopClientItemReply> = clients
+    let mut top_clients: Vec<TopClientItemReply> = clients
         .into_iter()
         .map(|client| {
-            let name = client.get_name(&strings).unwrap_or_default().to_owned();
             let ip = client.get_ip(&strings).to_owned();
+            let name = client
+                .get_name(&strings)
+                .map(ToOwned::to_owned)
+                .or_else(|| custom_dns_names.get(&ip).cloned())
+                .unwrap_or_default();
             let count = if blocked {
                 client.blocked_count
             } else {
                 client.query_count
             } as usize;
+            let percentage = if as_percentage {
+                Some(get_percentage(count, visible_count))
+            } else {
+                None
+            };
 
-            TopClientItemReply { name, ip, count }
+            TopClientItemReply {
+                name,
+                ip,
+                count,
+                percentage
+            }
         })
         .collect();
 
+    anonymize_clients(
+        top_clients
+            .iter_mut()
+            .map(|client| (&mut client.name, &mut client.ip)),
+        env
+    )?;
+
     // Output format changes when getting top blocked clients
     if blocked {
         Ok(TopClientsReply {
             top_clients,
             total_queries: None,
-            blocked_queries: Some(counters.blocked_queries as usize)
+            blocked_queries: Some(total_count)
         })
     } else {
         Ok(TopClientsReply {
             top_clients,
-            total_queries: Some(counters.total_queries as usize),
+            total_queries: Some(total_count),
             blocked_queries: None
         })
     }
 }
 
+/// Get `count` as a percentage of `total`. If the total is zero, so is the
+/// percentage.
+fn get_percentage(count: usize, total: usize) -> f64 {
+    if total == 0 {
+        0.0
+    } else {
+        count as f64 / total as f64 * 100.0
+    }
+}
+
+/// Get copies of the valid clients, with their query counts replaced by the
+/// number of their queries in the time window. Unbounded ends of the window
+/// include all queries on that side.
+fn count_clients_in_window(
+    ftl_memory: &FtlMemoryGuard,
+    clients: &[FtlClient],
+    from: Option<u64>,
+    until: Option<u64>
+) -> Result<Vec<FtlClient>, Error> {
+    let counters = ftl_memory.counters()?;
+    let queries = ftl_memory.queries()?;
+    let from = from.unwrap_or(0);
+    let until = until.unwrap_or(u64::max_value());
+
+    // Start each client with no queries
+    let mut window_clients: Vec<FtlClient> = clients
+        .iter()
+        .take(counters.total_clients as usize)
+        .map(|client| {
+            let mut client = *client;
+            client.query_count = 0;
+            client.blocked_count = 0;
+            client
+        })
+        .collect();
+
+    for query in queries
+        .iter()
+        .take(counters.total_queries as usize)
+        .filter(|query| query.timestamp as u64 >= from && query.timestamp as u64 <= until)
+    {
+        if let Some(client) = window_clients.get_mut(query.client_id as usize) {
+            client.query_count += 1;
+
+            if query.is_blocked() {
+                client.blocked_count += 1;
+            }
+        }
+    }
+
+    Ok(window_clients)
+}
+
 /// Check the privacy level to see if clients are allowed to be shared. If not,
 /// then only return the relevant count (total or blocked queries).
 pub fn check_privacy_level_top_clients(
@@ -187,12 +323,25 @@ pub fn check_privacy_level_top_clients(
 mod test {
     use crate::{
         env::PiholeFile,
-        ftl::{FtlClient, FtlCounters, FtlMemory, FtlSettings},
-        testing::TestBuilder
+        ftl::{FtlClient, FtlCounters, FtlMemory, FtlQuery, FtlQueryStatus, FtlSettings},
+        testing::{test_query, TestBuilder}
     };
+    use rocket::http::Status;
     use std::collections::HashMap;
 
-    /// There are 6 clients, two inactive, one hidden, and two with names.
+    /// Shorthand for making `FtlQuery` structs
+    fn query(client_id: i32, timestamp: i64, status: FtlQueryStatus) -> FtlQuery {
+        FtlQuery {
+            timestamp,
+            client_id,
+            status,
+            ..test_query()
+        }
+    }
+
+    /// There are 6 clients, two inactive, one hidden, and two with names. Only
+    /// the most recent queries are in memory, which are used when filtering by
+    /// time.
     fn test_data() -> FtlMemory {
         let mut strings = HashMap::new();
         strings.insert(1, "10.1.1.1".to_owned());
@@ -217,7 +366,13 @@ mod test {
             over_time: Vec::new(),
             strings,
             upstreams: Vec::new(),
-            queries: Vec::new(),
+            queries: vec![
+                query(0, 1, FtlQueryStatus::Forward),
+                query(0, 2, FtlQueryStatus::Gravity),
+                query(0, 3, FtlQueryStatus::Forward),
+                query(1, 2, FtlQueryStatus::Forward),
+                query(3, 5, FtlQueryStatus::Forward),
+            ],
             counters: FtlCounters {
                 total_queries: 100,
                 blocked_queries: 15,
@@ -235,13 +390,15 @@ mod test {
             .endpoint("/admin/api/stats/top_clients")
             .ftl_memory(test_data())
             .expect_json(json!({
-                "top_clients": [
-                    { "name": "",        "ip": "10.1.1.4", "count": 40 },
-                    { "name": "client1", "ip": "10.1.1.1", "count": 30 },
-                    { "name": "",        "ip": "10.1.1.2", "count": 20 },
-                    { "name": "client3", "ip": "10.1.1.3", "count": 10 }
-                ],
-                "total_queries": 100
+                "data": {
+                    "top_clients": [
+                        { "name": "",        "ip": "10.1.1.4", "count": 40 },
+                        { "name": "client1", "ip": "10.1.1.1", "count": 30 },
+                        { "name": "",        "ip": "10.1.1.2", "count": 20 },
+                        { "name": "client3", "ip": "10.1.1.3", "count": 10 }
+                    ],
+                    "total_queries": 100
+                }
             }))
             .test();
     }
@@ -254,11 +411,13 @@ mod test {
             .endpoint("/admin/api/stats/top_clients?blocked=true")
             .ftl_memory(test_data())
             .expect_json(json!({
-                "top_clients": [
-                    { "name": "client1", "ip": "10.1.1.1", "count": 10 },
-                    { "name": "",        "ip": "10.1.1.2", "count": 5 }
-                ],
-                "blocked_queries": 15
+                "data": {
+                    "top_clients": [
+                        { "name": "client1", "ip": "10.1.1.1", "count": 10 },
+                        { "name": "",        "ip": "10.1.1.2", "count": 5 }
+                    ],
+                    "blocked_queries": 15
+                }
             }))
             .test();
     }
@@ -270,11 +429,13 @@ mod test {
             .endpoint("/admin/api/stats/top_clients?limit=2")
             .ftl_memory(test_data())
             .expect_json(json!({
-                "top_clients": [
-                    { "name": "",        "ip": "10.1.1.4", "count": 40 },
-                    { "name": "client1", "ip": "10.1.1.1", "count": 30 }
-                ],
-                "total_queries": 100
+                "data": {
+                    "top_clients": [
+                        { "name": "",        "ip": "10.1.1.4", "count": 40 },
+                        { "name": "client1", "ip": "10.1.1.1", "count": 30 }
+                    ],
+                    "total_queries": 100
+                }
             }))
             .test();
     }
@@ -286,13 +447,15 @@ mod test {
             .endpoint("/admin/api/stats/top_clients?ascending=true")
             .ftl_memory(test_data())
             .expect_json(json!({
-                "top_clients": [
-                    { "name": "client3", "ip": "10.1.1.3", "count": 10 },
-                    { "name": "",        "ip": "10.1.1.2", "count": 20 },
-                    { "name": "client1", "ip": "10.1.1.1", "count": 30 },
-                    { "name": "",        "ip": "10.1.1.4", "count": 40 }
-                ],
-                "total_queries": 100
+                "data": {
+                    "top_clients": [
+                        { "name": "client3", "ip": "10.1.1.3", "count": 10 },
+                        { "name": "",        "ip": "10.1.1.2", "count": 20 },
+                        { "name": "client1", "ip": "10.1.1.1", "count": 30 },
+                        { "name": "",        "ip": "10.1.1.4", "count": 40 }
+                    ],
+                    "total_queries": 100
+                }
             }))
             .test();
     }
@@ -305,8 +468,10 @@ mod test {
             .ftl_memory(test_data())
             .file(PiholeFile::FtlConfig, "PRIVACYLEVEL=2")
             .expect_json(json!({
-                "top_clients": [],
-                "total_queries": 100
+                "data": {
+                    "top_clients": [],
+                    "total_queries": 100
+                }
             }))
             .test();
     }
@@ -320,8 +485,10 @@ mod test {
             .ftl_memory(test_data())
             .file(PiholeFile::FtlConfig, "PRIVACYLEVEL=2")
             .expect_json(json!({
-                "top_clients": [],
-                "blocked_queries": 15
+                "data": {
+                    "top_clients": [],
+                    "blocked_queries": 15
+                }
             }))
             .test();
     }
@@ -333,14 +500,41 @@ mod test {
             .endpoint("/admin/api/stats/top_clients?inactive=true")
             .ftl_memory(test_data())
             .expect_json(json!({
-                "top_clients": [
-                    { "name": "",        "ip": "10.1.1.4", "count": 40 },
-                    { "name": "client1", "ip": "10.1.1.1", "count": 30 },
-                    { "name": "",        "ip": "10.1.1.2", "count": 20 },
-                    { "name": "client3", "ip": "10.1.1.3", "count": 10 },
-                    { "name": "",        "ip": "10.1.1.5", "count":  0 }
-                ],
-                "total_queries": 100
+                "data": {
+                    "top_clients": [
+                        { "name": "",        "ip": "10.1.1.4", "count": 40 },
+                        { "name": "client1", "ip": "10.1.1.1", "count": 30 },
+                        { "name": "",        "ip": "10.1.1.2", "count": 20 },
+                        { "name": "client3", "ip": "10.1.1.3", "count": 10 },
+                        { "name": "",        "ip": "10.1.1.5", "count":  0 }
+                    ],
+                    "total_queries": 100
+                }
+            }))
+            .test();
+    }
+
+    /// Clients without a name use their name from the custom DNS list, if
+    /// there is one
+    #[test]
+    fn custom_dns_names() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/top_clients")
+            .ftl_memory(test_data())
+            .file(
+                PiholeFile::CustomList,
+                "# Custom DNS records\n10.1.1.4 laptop.lan laptop\n10.1.1.1 desktop.lan"
+            )
+            .expect_json(json!({
+                "data": {
+                    "top_clients": [
+                        { "name": "laptop.lan", "ip": "10.1.1.4", "count": 40 },
+                        { "name": "client1",    "ip": "10.1.1.1", "count": 30 },
+                        { "name": "",           "ip": "10.1.1.2", "count": 20 },
+                        { "name": "client3",    "ip": "10.1.1.3", "count": 10 }
+                    ],
+                    "total_queries": 100
+                }
             }))
             .test();
     }
@@ -356,11 +550,127 @@ mod test {
                 "API_EXCLUDE_CLIENTS=client3,10.1.1.2"
             )
             .expect_json(json!({
-                "top_clients": [
-                    { "name": "",        "ip": "10.1.1.4", "count": 40 },
-                    { "name": "client1", "ip": "10.1.1.1", "count": 30 }
-                ],
-                "total_queries": 100
+                "data": {
+                    "top_clients": [
+                        { "name": "",        "ip": "10.1.1.4", "count": 40 },
+                        { "name": "client1", "ip": "10.1.1.1", "count": 30 }
+                    ],
+                    "total_queries": 100
+                }
+            }))
+            .test();
+    }
+
+    /// Only queries in the time window are counted
+    #[test]
+    fn time_window() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/top_clients?from=2&until=3")
+            .ftl_memory(test_data())
+            .expect_json(json!({
+                "data": {
+                    "top_clients": [
+                        { "name": "client1", "ip": "10.1.1.1", "count": 2 },
+                        { "name": "",        "ip": "10.1.1.2", "count": 1 }
+                    ],
+                    "total_queries": 3
+                }
+            }))
+            .test();
+    }
+
+    /// Only blocked queries in the time window are counted
+    #[test]
+    fn time_window_blocked() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/top_clients?blocked=true&from=2&until=3")
+            .ftl_memory(test_data())
+            .expect_json(json!({
+                "data": {
+                    "top_clients": [
+                        { "name": "client1", "ip": "10.1.1.1", "count": 1 }
+                    ],
+                    "blocked_queries": 1
+                }
+            }))
+            .test();
+    }
+
+    /// Each client's share of the visible clients' queries is added. The
+    /// limit does not change the total.
+    #[test]
+    fn as_percentage() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/top_clients?as_percentage=true&limit=2")
+            .ftl_memory(test_data())
+            .expect_json(json!({
+                "data": {
+                    "top_clients": [
+                        { "name": "",        "ip": "10.1.1.4", "count": 40, "percentage": 40.0 },
+                        { "name": "client1", "ip": "10.1.1.1", "count": 30, "percentage": 30.0 }
+                    ],
+                    "total_queries": 100
+                }
+            }))
+            .test();
+    }
+
+    /// Excluded clients are not part of the total
+    #[test]
+    fn as_percentage_excluded() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/top_clients?as_percentage=true")
+            .ftl_memory(test_data())
+            .file(
+                PiholeFile::SetupVars,
+                "API_EXCLUDE_CLIENTS=client3,10.1.1.4"
+            )
+            .expect_json(json!({
+                "data": {
+                    "top_clients": [
+                        { "name": "client1", "ip": "10.1.1.1", "count": 30, "percentage": 60.0 },
+                        { "name": "",        "ip": "10.1.1.2", "count": 20, "percentage": 40.0 }
+                    ],
+                    "total_queries": 100
+                }
+            }))
+            .test();
+    }
+
+    /// The percentages are zero if there are no queries
+    #[test]
+    fn as_percentage_no_queries() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/top_clients?as_percentage=true&inactive=true&from=10")
+            .ftl_memory(test_data())
+            .expect_json(json!({
+                "data": {
+                    "top_clients": [
+                        { "name": "client1", "ip": "10.1.1.1", "count": 0, "percentage": 0.0 },
+                        { "name": "",        "ip": "10.1.1.2", "count": 0, "percentage": 0.0 },
+                        { "name": "client3", "ip": "10.1.1.3", "count": 0, "percentage": 0.0 },
+                        { "name": "",        "ip": "10.1.1.4", "count": 0, "percentage": 0.0 },
+                        { "name": "",        "ip": "10.1.1.5", "count": 0, "percentage": 0.0 }
+                    ],
+                    "total_queries": 0
+                }
+            }))
+            .test();
+    }
+
+    /// An inverted time window is a bad request
+    #[test]
+    fn time_window_inverted() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/top_clients?from=3&until=2")
+            .ftl_memory(test_data())
+            .expect_status(Status::BadRequest)
+            .expect_json(json!({
+                "error": {
+                    "key": "bad_request",
+                    "message": "Bad request",
+                    "data": None::<()>
+                }
             }))
             .test();
     }
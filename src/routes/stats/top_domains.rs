@@ -10,25 +10,29 @@
 
 use crate::{
     env::{Env, PiholeFile},
-    ftl::{FtlDomain, FtlMemory},
+    ftl::{CachedStrings, FtlDomain, FtlMemory},
     routes::{
         auth::User,
-        stats::common::{remove_excluded_domains, remove_hidden_domains}
+        stats::common::{limit_results, remove_excluded_domains, remove_hidden_domains}
     },
     settings::{ConfigEntry, FtlConfEntry, FtlPrivacyLevel, SetupVarsEntry},
-    util::{reply_result, Error, Reply}
+    util::{reply_timed, Error, Reply, RequestTimer}
 };
 use rocket::{request::Form, State};
 
 /// Return the top domains
 #[get("/stats/top_domains?<params..>")]
 pub fn top_domains(
+    timer: RequestTimer,
     _auth: User,
     ftl_memory: State<FtlMemory>,
     env: State<Env>,
     params: Form<TopDomainParams>
 ) -> Reply {
-    reply_result(get_top_domains(&ftl_memory, &env, params.into_inner()))
+    reply_timed(
+        get_top_domains(&ftl_memory, &env, params.into_inner()),
+        timer
+    )
 }
 
 /// Represents the possible GET parameters for top (blocked) domains requests
@@ -37,7 +41,10 @@ pub struct TopDomainParams {
     pub limit: Option<usize>,
     pub audit: Option<bool>,
     pub ascending: Option<bool>,
-    pub blocked: Option<bool>
+    pub blocked: Option<bool>,
+    /// Domains with fewer queries than this are not shown. It is applied
+    /// before the limit.
+    pub min_count: Option<usize>
 }
 
 /// Represents the reply structure for top (blocked) domains
@@ -59,6 +66,12 @@ pub struct TopDomainItemReply {
     pub count: usize
 }
 
+/// Get the minimum count of the top domains. Domains without queries are never
+/// shown, so the minimum count is at least 1.
+pub fn get_min_count(params: &TopDomainParams) -> usize {
+    params.min_count.unwrap_or(1).max(1)
+}
+
 /// Get the top domains (blocked or not)
 fn get_top_domains(
     ftl_memory: &FtlMemory,
@@ -66,10 +79,11 @@ fn get_top_domains(
     params: TopDomainParams
 ) -> Result<TopDomainsReply, Error> {
     // Resolve the parameters
-    let limit = params.limit.unwrap_or(10);
+    let limit = limit_results(params.limit.unwrap_or(10), env)?;
     let audit = params.audit.unwrap_or(false);
     let ascending = params.ascending.unwrap_or(false);
     let blocked = params.blocked.unwrap_or(false);
+    let min_count = get_min_count(&params);
 
     let lock = ftl_memory.lock()?;
     let counters = ftl_memory.counters(&lock)?;
@@ -107,13 +121,17 @@ fn get_top_domains(
     remove_excluded_domains(&mut domains, env, &strings)?;
     remove_hidden_domains(&mut domains, &strings);
 
-    // Remove domains with a count of 0
+    // Remove domains with less than the minimum count
     if blocked {
-        domains.retain(|domain| domain.blocked_count > 0);
+        domains.retain(|domain| domain.blocked_count as usize >= min_count);
     } else {
-        domains.retain(|domain| (domain.query_count - domain.blocked_count) > 0);
+        domains.retain(|domain| (domain.query_count - domain.blocked_count) as usize >= min_count);
     }
 
+    // Cache the domain names, which are read by the audit check and again to
+    // make the reply
+    let cached_strings = CachedStrings::new(&strings);
+
     // If audit flag is true, only include unaudited domains
     if audit {
         let audited_domains = env.read_file_lines(PiholeFile::AuditLog)?;
@@ -121,7 +139,8 @@ fn get_top_domains(
         // Get a vector of references to strings, to better compare with the domains
         let audited_domains: Vec<&str> = audited_domains.iter().map(String::as_str).collect();
 
-        domains.retain(|domain| !audited_domains.contains(&domain.get_domain(&strings)));
+        domains
+            .retain(|domain| !audited_domains.contains(&domain.get_domain_cached(&cached_strings)));
     }
 
     // Sort the domains (descending by default)
@@ -145,7 +164,7 @@ fn get_top_domains(
     let top_domains: Vec<TopDomainItemReply> = domains
         .iter()
         .map(|domain| {
-            let name = domain.get_domain(&strings).to_owned();
+            let name = domain.get_domain_cached(&cached_strings).to_owned();
             let count = if blocked {
                 domain.blocked_count
             } else {
@@ -283,11 +302,13 @@ mod test {
             .endpoint("/admin/api/stats/top_domains")
             .ftl_memory(test_data())
             .expect_json(json!({
-                "top_domains": [
-                    { "domain": "github.com", "count": 20 },
-                    { "domain": "example.net", "count": 1 }
-                ],
-                "total_queries": 39
+                "data": {
+                    "top_domains": [
+                        { "domain": "github.com", "count": 20 },
+                        { "domain": "example.net", "count": 1 }
+                    ],
+                    "total_queries": 39
+                }
             }))
             .test();
     }
@@ -299,10 +320,30 @@ mod test {
             .endpoint("/admin/api/stats/top_domains?limit=1")
             .ftl_memory(test_data())
             .expect_json(json!({
-                "top_domains": [
-                    { "domain": "github.com", "count": 20 }
-                ],
-                "total_queries": 39
+                "data": {
+                    "top_domains": [
+                        { "domain": "github.com", "count": 20 }
+                    ],
+                    "total_queries": 39
+                }
+            }))
+            .test();
+    }
+
+    /// Domains with fewer queries than the minimum count are not shown, and
+    /// the limit is applied after them
+    #[test]
+    fn min_count() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/top_domains?min_count=2&ascending=true&limit=1")
+            .ftl_memory(test_data())
+            .expect_json(json!({
+                "data": {
+                    "top_domains": [
+                        { "domain": "github.com", "count": 20 }
+                    ],
+                    "total_queries": 39
+                }
             }))
             .test();
     }
@@ -315,11 +356,13 @@ mod test {
             .endpoint("/admin/api/stats/top_domains?blocked=true")
             .ftl_memory(test_data())
             .expect_json(json!({
-                "top_domains": [
-                    { "domain": "example.com", "count": 10 },
-                    { "domain": "example.net", "count": 9 }
-                ],
-                "blocked_queries": 21
+                "data": {
+                    "top_domains": [
+                        { "domain": "example.com", "count": 10 },
+                        { "domain": "example.net", "count": 9 }
+                    ],
+                    "blocked_queries": 21
+                }
             }))
             .test();
     }
@@ -332,11 +375,13 @@ mod test {
             .endpoint("/admin/api/stats/top_domains?ascending=true")
             .ftl_memory(test_data())
             .expect_json(json!({
-                "top_domains": [
-                    { "domain": "example.net", "count": 1 },
-                    { "domain": "github.com", "count": 20 }
-                ],
-                "total_queries": 39
+                "data": {
+                    "top_domains": [
+                        { "domain": "example.net", "count": 1 },
+                        { "domain": "github.com", "count": 20 }
+                    ],
+                    "total_queries": 39
+                }
             }))
             .test();
     }
@@ -350,10 +395,12 @@ mod test {
             .ftl_memory(test_data())
             .file(PiholeFile::AuditLog, "example.net")
             .expect_json(json!({
-                "top_domains": [
-                    { "domain": "github.com", "count": 20 }
-                ],
-                "total_queries": 39
+                "data": {
+                    "top_domains": [
+                        { "domain": "github.com", "count": 20 }
+                    ],
+                    "total_queries": 39
+                }
             }))
             .test();
     }
@@ -366,10 +413,48 @@ mod test {
             .ftl_memory(test_data())
             .file(PiholeFile::SetupVars, "API_EXCLUDE_DOMAINS=example.net")
             .expect_json(json!({
-                "top_domains": [
-                    { "domain": "github.com", "count": 20 }
-                ],
-                "total_queries": 39
+                "data": {
+                    "top_domains": [
+                        { "domain": "github.com", "count": 20 }
+                    ],
+                    "total_queries": 39
+                }
+            }))
+            .test();
+    }
+
+    /// Show blocked domains, but no hidden, inactive, or excluded domains
+    #[test]
+    fn excluded_blocked() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/top_domains?blocked=true")
+            .ftl_memory(test_data())
+            .file(PiholeFile::SetupVars, "API_EXCLUDE_DOMAINS=example.net")
+            .expect_json(json!({
+                "data": {
+                    "top_domains": [
+                        { "domain": "example.com", "count": 10 }
+                    ],
+                    "blocked_queries": 21
+                }
+            }))
+            .test();
+    }
+
+    /// Excluded domains are matched regardless of case
+    #[test]
+    fn excluded_case_insensitive() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/top_domains")
+            .ftl_memory(test_data())
+            .file(PiholeFile::SetupVars, "API_EXCLUDE_DOMAINS=GitHub.com")
+            .expect_json(json!({
+                "data": {
+                    "top_domains": [
+                        { "domain": "example.net", "count": 1 }
+                    ],
+                    "total_queries": 39
+                }
             }))
             .test();
     }
@@ -0,0 +1,117 @@
+// Pi-hole: A black hole for Internet advertisements
+// (c) 2019 Pi-hole, LLC (https://pi-hole.net)
+// Network-wide ad blocking via your own hardware.
+//
+// API
+// Unique Domain And Client Counts Endpoint
+//
+// This file is copyright under the latest version of the EUPL.
+// Please see LICENSE file for your rights under this license.
+
+use crate::{
+    env::Env,
+    ftl::FtlMemory,
+    routes::auth::User,
+    settings::{ConfigEntry, FtlConfEntry, FtlPrivacyLevel},
+    util::{reply_timed, Error, Reply, RequestTimer}
+};
+use rocket::State;
+
+/// Get the number of unique domains and clients which FTL has seen
+#[get("/stats/unique_counts")]
+pub fn unique_counts(
+    timer: RequestTimer,
+    _auth: User,
+    ftl_memory: State<FtlMemory>,
+    env: State<Env>
+) -> Reply {
+    reply_timed(get_unique_counts(&ftl_memory, &env), timer)
+}
+
+/// Get the unique counts from FTL's counters, which avoids scanning the
+/// domain and client arrays. If clients are hidden by the privacy level, there
+/// are no unique clients, the same as in the summary.
+fn get_unique_counts(ftl_memory: &FtlMemory, env: &Env) -> Result<UniqueCounts, Error> {
+    let lock = ftl_memory.lock()?;
+    let counters = ftl_memory.counters(&lock)?;
+
+    let unique_clients = if FtlConfEntry::PrivacyLevel.read_as::<FtlPrivacyLevel>(env)?
+        >= FtlPrivacyLevel::HideDomainsAndClients
+    {
+        0
+    } else {
+        counters.total_clients as usize
+    };
+
+    Ok(UniqueCounts {
+        unique_domains: counters.total_domains as usize,
+        unique_clients
+    })
+}
+
+/// Represents the reply structure for the unique counts
+#[derive(Serialize)]
+pub struct UniqueCounts {
+    pub unique_domains: usize,
+    pub unique_clients: usize
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        env::PiholeFile,
+        ftl::{FtlCounters, FtlMemory, FtlSettings},
+        testing::TestBuilder
+    };
+    use std::collections::HashMap;
+
+    /// FTL has seen 12 domains and 3 clients
+    fn test_data() -> FtlMemory {
+        FtlMemory::Test {
+            clients: Vec::new(),
+            domains: Vec::new(),
+            over_time: Vec::new(),
+            strings: HashMap::new(),
+            upstreams: Vec::new(),
+            queries: Vec::new(),
+            counters: FtlCounters {
+                total_domains: 12,
+                total_clients: 3,
+                ..FtlCounters::default()
+            },
+            settings: FtlSettings::default()
+        }
+    }
+
+    /// The counts come from FTL's counters
+    #[test]
+    fn unique_counts() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/unique_counts")
+            .ftl_memory(test_data())
+            .file(PiholeFile::FtlConfig, "")
+            .expect_json(json!({
+                "data": {
+                    "unique_domains": 12,
+                    "unique_clients": 3
+                }
+            }))
+            .test();
+    }
+
+    /// There are no unique clients if clients are hidden
+    #[test]
+    fn hidden_clients() {
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/unique_counts")
+            .ftl_memory(test_data())
+            .file(PiholeFile::FtlConfig, "PRIVACYLEVEL=2")
+            .expect_json(json!({
+                "data": {
+                    "unique_domains": 12,
+                    "unique_clients": 0
+                }
+            }))
+            .test();
+    }
+}
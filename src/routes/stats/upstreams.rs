@@ -9,30 +9,43 @@
 // Please see LICENSE file for your rights under this license.
 
 use crate::{
-    ftl::{FtlMemory, FtlUpstream},
+    ftl::{FtlMemory, FtlQuery, FtlQueryStatus, FtlUpstream},
     routes::auth::User,
-    util::{reply_data, Reply}
+    util::{reply_timed, Error, Reply, RequestTimer}
 };
 use rocket::State;
+use std::collections::HashMap;
 
 /// Get the upstreams
 #[get("/stats/upstreams")]
-pub fn upstreams(_auth: User, ftl_memory: State<FtlMemory>) -> Reply {
+pub fn upstreams(timer: RequestTimer, _auth: User, ftl_memory: State<FtlMemory>) -> Reply {
+    reply_timed(get_upstreams(&ftl_memory), timer)
+}
+
+/// Get the upstreams, with the blocklist and cache first
+fn get_upstreams(ftl_memory: &FtlMemory) -> Result<UpstreamsReply, Error> {
     let lock = ftl_memory.lock()?;
     let ftl_upstreams = ftl_memory.upstreams(&lock)?;
     let strings = ftl_memory.strings(&lock)?;
     let counters = ftl_memory.counters(&lock)?;
+    let queries = ftl_memory.queries(&lock)?;
 
-    // Get an array of valid upstream references (FTL allocates more than it uses)
-    let mut ftl_upstreams: Vec<&FtlUpstream> = ftl_upstreams
+    // Get the response times of each upstream, by upstream ID
+    let mut response_times =
+        get_response_times(&queries[..(counters.total_queries as usize).min(queries.len())]);
+
+    // Get an array of valid upstream references (FTL allocates more than it uses),
+    // along with their IDs
+    let mut ftl_upstreams: Vec<(usize, &FtlUpstream)> = ftl_upstreams
         .iter()
         .take(counters.total_upstreams as usize)
+        .enumerate()
         // Remove upstreams with a zero count
-        .filter(|upstream| upstream.query_count > 0)
+        .filter(|(_, upstream)| upstream.query_count > 0)
         .collect();
 
     // Sort the upstreams (descending by count)
-    ftl_upstreams.sort_by(|a, b| b.query_count.cmp(&a.query_count));
+    ftl_upstreams.sort_by(|(_, a), (_, b)| b.query_count.cmp(&a.query_count));
 
     let mut upstreams: Vec<UpstreamItemReply> = Vec::with_capacity(ftl_upstreams.len() + 2);
 
@@ -40,40 +53,102 @@ pub fn upstreams(_auth: User, ftl_memory: State<FtlMemory>) -> Reply {
     upstreams.push(UpstreamItemReply {
         name: "blocklist".to_owned(),
         ip: "blocklist".to_owned(),
-        count: counters.blocked_queries as usize
+        count: counters.blocked_queries as usize,
+        response_times: None
     });
     upstreams.push(UpstreamItemReply {
         name: "cache".to_owned(),
         ip: "cache".to_owned(),
-        count: counters.cached_queries as usize
+        count: counters.cached_queries as usize,
+        response_times: None
     });
 
     // Map the upstreams into the output format
-    upstreams.extend(ftl_upstreams.into_iter().map(|upstream| {
+    upstreams.extend(ftl_upstreams.into_iter().map(|(id, upstream)| {
         let ip = upstream.get_ip(&strings).to_owned();
         let name = upstream.get_name(&strings).unwrap_or_default().to_owned();
 
         UpstreamItemReply {
             name,
             ip,
-            count: upstream.query_count as usize
+            count: upstream.query_count as usize,
+            response_times: response_times.remove(&id)
         }
     }));
 
-    reply_data(UpstreamsReply {
+    Ok(UpstreamsReply {
         upstreams,
         forwarded_queries: counters.forwarded_queries as usize,
         total_queries: counters.total_queries as usize
     })
 }
 
+/// Get the minimum, maximum, and mean response times of each upstream, by
+/// upstream ID. Only forwarded queries which received a reply are used, so
+/// cached and blocked queries do not affect the response times.
+fn get_response_times(queries: &[FtlQuery]) -> HashMap<usize, ResponseTimesReply> {
+    // The min, max, sum, and count of the response times of each upstream.
+    // These are in units of 1/10 milliseconds, like in `FtlQuery`.
+    let mut totals: HashMap<usize, (u64, u64, u64, u64)> = HashMap::new();
+
+    for query in queries.iter().filter(|query| {
+        // Check if a response was received (the response time is 0 until the
+        // reply arrives, and should be smaller than 30min)
+        query.status == FtlQueryStatus::Forward
+            && query.response_time > 0
+            && query.response_time < 18_000_000
+    }) {
+        let response_time = query.response_time as u64;
+        let entry = totals.entry(query.upstream_id as usize).or_insert((
+            response_time,
+            response_time,
+            0,
+            0
+        ));
+
+        entry.0 = entry.0.min(response_time);
+        entry.1 = entry.1.max(response_time);
+        entry.2 += response_time;
+        entry.3 += 1;
+    }
+
+    // Convert the totals into milliseconds
+    totals
+        .into_iter()
+        .map(|(id, (min, max, sum, count))| {
+            (
+                id,
+                ResponseTimesReply {
+                    response_time_min: min as f64 / 10.0,
+                    response_time_max: max as f64 / 10.0,
+                    response_time_mean: sum as f64 / count as f64 / 10.0
+                }
+            )
+        })
+        .collect()
+}
+
 /// Represents the reply structure for returning upstream item data
 #[derive(Serialize)]
 #[cfg_attr(test, derive(Debug, PartialEq))]
 pub struct UpstreamItemReply {
     pub name: String,
     pub ip: String,
-    pub count: usize
+    pub count: usize,
+    /// The response times are only known for real upstreams which have
+    /// answered queries, and are left out otherwise
+    #[serde(flatten)]
+    pub response_times: Option<ResponseTimesReply>
+}
+
+/// Represents the reply structure for the response times of an upstream, in
+/// milliseconds
+#[derive(Serialize)]
+#[cfg_attr(test, derive(Debug, PartialEq))]
+pub struct ResponseTimesReply {
+    pub response_time_min: f64,
+    pub response_time_max: f64,
+    pub response_time_mean: f64
 }
 
 /// Represents the reply structure for upstreams endpoints
@@ -88,11 +163,21 @@ pub struct UpstreamsReply {
 #[cfg(test)]
 mod test {
     use crate::{
-        ftl::{FtlCounters, FtlMemory, FtlSettings, FtlUpstream},
-        testing::TestBuilder
+        ftl::{FtlCounters, FtlMemory, FtlQuery, FtlQueryStatus, FtlSettings, FtlUpstream},
+        testing::{test_query, TestBuilder}
     };
     use std::collections::HashMap;
 
+    /// Shorthand for making `FtlQuery` structs
+    fn query(upstream_id: i32, status: FtlQueryStatus, response_time: u64) -> FtlQuery {
+        FtlQuery {
+            response_time: response_time as libc::c_ulong,
+            upstream_id,
+            status,
+            ..test_query()
+        }
+    }
+
     fn test_upstream_data() -> (Vec<FtlUpstream>, HashMap<usize, String>) {
         let mut strings = HashMap::new();
         strings.insert(1, "8.8.8.8".to_owned());
@@ -134,15 +219,17 @@ mod test {
                 settings: FtlSettings::default()
             })
             .expect_json(json!({
-                "upstreams": [
-                    { "name": "blocklist", "ip": "blocklist", "count": 0 },
-                    { "name": "cache", "ip": "cache", "count": 0 },
-                    { "name": "google-public-dns-a.google.com", "ip": "8.8.8.8", "count": 10 },
-                    { "name": "google-public-dns-b.google.com", "ip": "8.8.4.4", "count": 4 },
-                    { "name": "", "ip": "1.1.1.1", "count": 3 }
-                ],
-                "total_queries": 17,
-                "forwarded_queries": 17
+                "data": {
+                    "upstreams": [
+                        { "name": "blocklist", "ip": "blocklist", "count": 0 },
+                        { "name": "cache", "ip": "cache", "count": 0 },
+                        { "name": "google-public-dns-a.google.com", "ip": "8.8.8.8", "count": 10 },
+                        { "name": "google-public-dns-b.google.com", "ip": "8.8.4.4", "count": 4 },
+                        { "name": "", "ip": "1.1.1.1", "count": 3 }
+                    ],
+                    "total_queries": 17,
+                    "forwarded_queries": 17
+                }
             }))
             .test();
     }
@@ -173,15 +260,126 @@ mod test {
                 settings: FtlSettings::default()
             })
             .expect_json(json!({
-                "upstreams": [
-                    { "name": "blocklist", "ip": "blocklist", "count": 1 },
-                    { "name": "cache", "ip": "cache", "count": 1 },
-                    { "name": "google-public-dns-a.google.com", "ip": "8.8.8.8", "count": 10 },
-                    { "name": "google-public-dns-b.google.com", "ip": "8.8.4.4", "count": 4 },
-                    { "name": "", "ip": "1.1.1.1", "count": 3 }
+                "data": {
+                    "upstreams": [
+                        { "name": "blocklist", "ip": "blocklist", "count": 1 },
+                        { "name": "cache", "ip": "cache", "count": 1 },
+                        { "name": "google-public-dns-a.google.com", "ip": "8.8.8.8", "count": 10 },
+                        { "name": "google-public-dns-b.google.com", "ip": "8.8.4.4", "count": 4 },
+                        { "name": "", "ip": "1.1.1.1", "count": 3 }
+                    ],
+                    "total_queries": 19,
+                    "forwarded_queries": 17
+                }
+            }))
+            .test();
+    }
+
+    /// The response times of each upstream are calculated from its forwarded
+    /// queries. Cached queries and upstreams without replies are ignored.
+    #[test]
+    fn response_times() {
+        let (upstreams, strings) = test_upstream_data();
+
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/upstreams")
+            .ftl_memory(FtlMemory::Test {
+                upstreams,
+                strings,
+                counters: FtlCounters {
+                    total_upstreams: 3,
+                    total_queries: 4,
+                    forwarded_queries: 3,
+                    cached_queries: 1,
+                    ..FtlCounters::default()
+                },
+                clients: Vec::new(),
+                domains: Vec::new(),
+                over_time: Vec::new(),
+                queries: vec![
+                    query(0, FtlQueryStatus::Forward, 10),
+                    query(0, FtlQueryStatus::Forward, 30),
+                    query(0, FtlQueryStatus::Cache, 5),
+                    query(1, FtlQueryStatus::Forward, 25),
                 ],
-                "total_queries": 19,
-                "forwarded_queries": 17
+                settings: FtlSettings::default()
+            })
+            .expect_json(json!({
+                "data": {
+                    "upstreams": [
+                        { "name": "blocklist", "ip": "blocklist", "count": 0 },
+                        { "name": "cache", "ip": "cache", "count": 1 },
+                        {
+                            "name": "google-public-dns-a.google.com",
+                            "ip": "8.8.8.8",
+                            "count": 10,
+                            "response_time_min": 1.0,
+                            "response_time_max": 3.0,
+                            "response_time_mean": 2.0
+                        },
+                        {
+                            "name": "google-public-dns-b.google.com",
+                            "ip": "8.8.4.4",
+                            "count": 4,
+                            "response_time_min": 2.5,
+                            "response_time_max": 2.5,
+                            "response_time_mean": 2.5
+                        },
+                        { "name": "", "ip": "1.1.1.1", "count": 3 }
+                    ],
+                    "total_queries": 4,
+                    "forwarded_queries": 3
+                }
+            }))
+            .test();
+    }
+
+    /// Forwarded queries which have not been answered yet do not count
+    /// towards the response times
+    #[test]
+    fn unanswered_queries() {
+        let (upstreams, strings) = test_upstream_data();
+
+        TestBuilder::new()
+            .endpoint("/admin/api/stats/upstreams")
+            .ftl_memory(FtlMemory::Test {
+                upstreams,
+                strings,
+                counters: FtlCounters {
+                    total_upstreams: 3,
+                    total_queries: 3,
+                    forwarded_queries: 3,
+                    ..FtlCounters::default()
+                },
+                clients: Vec::new(),
+                domains: Vec::new(),
+                over_time: Vec::new(),
+                queries: vec![
+                    query(0, FtlQueryStatus::Forward, 10),
+                    query(0, FtlQueryStatus::Forward, 30),
+                    query(0, FtlQueryStatus::Forward, 0),
+                ],
+                settings: FtlSettings::default()
+            })
+            .expect_json(json!({
+                "data": {
+                    "upstreams": [
+                        { "name": "blocklist", "ip": "blocklist", "count": 0 },
+                        { "name": "cache", "ip": "cache", "count": 0 },
+                        {
+                            "name": "google-public-dns-a.google.com",
+                            "ip": "8.8.8.8",
+                            "count": 10,
+                            "response_time_min": 1.0,
+                            "response_time_max": 3.0,
+                            "response_time_mean": 2.0
+                        },
+                        { "name": "google-public-dns-b.google.com", "ip": "8.8.4.4", "count": 4 },
+                        { "name": "", "ip": "1.1.1.1", "count": 3 }
+                    ],
+                    "total_queries": 3,
+                    "forwarded_queries": 3
+                }
             }))
             .test();
     }
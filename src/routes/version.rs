@@ -43,26 +43,32 @@ fn read_api_version() -> Version {
     }
 }
 
-/// Read FTL version information from FTL's API
-fn read_ftl_version(ftl: &FtlConnectionType) -> Result<Version, Error> {
+/// Read FTL version information from FTL's API. If FTL can not be reached,
+/// the fields are all `None`.
+fn read_ftl_version(ftl: &FtlConnectionType) -> Result<FtlVersion, Error> {
     let mut con = ftl.connect("version")?;
     let mut str_buffer = [0u8; 4096];
 
-    // Ignore the version and date strings
-    let _hash_tag = con.read_str(&mut str_buffer)?.to_owned();
+    // Ignore the date string
+    let version = con.read_str(&mut str_buffer)?.to_owned();
     let tag = con.read_str(&mut str_buffer)?.to_owned();
     let branch = con.read_str(&mut str_buffer)?.to_owned();
     let hash = con.read_str(&mut str_buffer)?.to_owned();
     let _date = con.read_str(&mut str_buffer)?.to_owned();
     con.expect_eom()?;
 
-    Ok(Version { tag, branch, hash })
+    Ok(FtlVersion {
+        version: Some(version),
+        tag: Some(tag),
+        branch: Some(branch),
+        hash: Some(hash)
+    })
 }
 
 /// Read Web version information from the `VERSION` file in the web assets.
 fn read_web_version() -> Result<Version, Error> {
-    let version_raw = WebAssets::get("VERSION").ok_or(ErrorKind::Unknown)?;
-    let version_str = str::from_utf8(&version_raw).context(ErrorKind::Unknown)?;
+    let version_raw = WebAssets::get("VERSION").ok_or(ErrorKind::VersionParse)?;
+    let version_str = str::from_utf8(&version_raw).context(ErrorKind::VersionParse)?;
 
     parse_web_version(version_str)
 }
@@ -74,7 +80,7 @@ fn parse_web_version(version_str: &str) -> Result<Version, Error> {
     let version_split: Vec<&str> = version_str.trim_end_matches('\n').split(' ').collect();
 
     if version_split.len() != 3 {
-        return Err(Error::from(ErrorKind::Unknown));
+        return Err(Error::from(ErrorKind::VersionParse));
     }
 
     Ok(Version {
@@ -116,7 +122,7 @@ fn parse_git_version(git_version: &str, branch: &str) -> Result<Version, Error>
 
     // Could include "-dirty", which would make the length equal 4
     if split.len() < 3 {
-        return Err(Error::from(ErrorKind::Unknown));
+        return Err(Error::from(ErrorKind::VersionParse));
     }
 
     // Only set the tag if this is the tagged commit (we are 0 commits after the
@@ -139,14 +145,28 @@ struct Version {
     hash: String
 }
 
+/// FTL's version information, which includes its full version string. The
+/// fields are null when FTL can not be reached.
+#[cfg_attr(test, derive(Debug, PartialEq))]
+#[derive(Serialize, Default)]
+struct FtlVersion {
+    version: Option<String>,
+    tag: Option<String>,
+    branch: Option<String>,
+    hash: Option<String>
+}
+
 #[cfg(test)]
 mod tests {
-    use super::{parse_git_version, parse_web_version, read_ftl_version, Version};
+    use super::{
+        parse_git_version, parse_web_version, read_api_version, read_ftl_version, read_web_version,
+        FtlVersion, Version
+    };
     use crate::{
         env::{Config, Env, PiholeFile},
         ftl::FtlConnectionType,
         routes::version::read_core_version,
-        testing::{write_eom, TestEnvBuilder},
+        testing::{write_eom, TestBuilder, TestEnvBuilder},
         util::ErrorKind
     };
     use rmp::encode;
@@ -169,10 +189,11 @@ mod tests {
 
         assert_eq!(
             read_ftl_version(&ftl).map_err(|e| e.kind()),
-            Ok(Version {
-                tag: "".to_owned(),
-                branch: "tweak/version-api".to_owned(),
-                hash: "4d5da59".to_owned()
+            Ok(FtlVersion {
+                version: Some("vDev-4d5da59".to_owned()),
+                tag: Some("".to_owned()),
+                branch: Some("tweak/version-api".to_owned()),
+                hash: Some("4d5da59".to_owned())
             })
         )
     }
@@ -194,14 +215,40 @@ mod tests {
 
         assert_eq!(
             read_ftl_version(&ftl).map_err(|e| e.kind()),
-            Ok(Version {
-                tag: "v4.0".to_owned(),
-                branch: "master".to_owned(),
-                hash: "abcdefg".to_owned()
+            Ok(FtlVersion {
+                version: Some("v4.0".to_owned()),
+                tag: Some("v4.0".to_owned()),
+                branch: Some("master".to_owned()),
+                hash: Some("abcdefg".to_owned())
             })
         )
     }
 
+    /// When FTL can not be reached, its version has null fields, but the rest
+    /// of the versions are still reported
+    #[test]
+    fn test_ftl_unreachable() {
+        TestBuilder::new()
+            .endpoint("/admin/api/version")
+            .should_auth(false)
+            .expect_json(json!({
+                "core": {
+                    "tag": "",
+                    "branch": "",
+                    "hash": ""
+                },
+                "web": read_web_version().unwrap_or_default(),
+                "ftl": {
+                    "version": None::<String>,
+                    "tag": None::<String>,
+                    "branch": None::<String>,
+                    "hash": None::<String>
+                },
+                "api": read_api_version()
+            }))
+            .test();
+    }
+
     #[test]
     fn test_parse_web_version_dev() {
         assert_eq!(
@@ -230,7 +277,7 @@ mod tests {
     fn test_parse_web_version_invalid() {
         assert_eq!(
             parse_web_version("invalid data").map_err(|e| e.kind()),
-            Err(ErrorKind::Unknown)
+            Err(ErrorKind::VersionParse)
         );
     }
 
@@ -290,7 +337,7 @@ mod tests {
 
         assert_eq!(
             read_core_version(&test_env).map_err(|e| e.kind()),
-            Err(ErrorKind::Unknown)
+            Err(ErrorKind::VersionParse)
         );
     }
 
@@ -322,7 +369,7 @@ mod tests {
     fn test_parse_git_version_invalid() {
         assert_eq!(
             parse_git_version("invalid data", "branch").map_err(|e| e.kind()),
-            Err(ErrorKind::Unknown)
+            Err(ErrorKind::VersionParse)
         );
     }
 
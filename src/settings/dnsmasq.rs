@@ -46,6 +46,32 @@ pub fn generate_dnsmasq_config(env: &Env) -> Result<(), Error> {
     Ok(())
 }
 
+/// Check if the dnsmasq config is generated from the entry, so the config has
+/// to be generated again when the entry changes
+pub fn is_dnsmasq_entry(entry: &SetupVarsEntry) -> bool {
+    match entry {
+        SetupVarsEntry::PiholeDns(_)
+        | SetupVarsEntry::QueryLogging
+        | SetupVarsEntry::DnsFqdnRequired
+        | SetupVarsEntry::DnsBogusPriv
+        | SetupVarsEntry::Dnssec
+        | SetupVarsEntry::HostRecord
+        | SetupVarsEntry::DnsmasqListening
+        | SetupVarsEntry::PiholeInterface
+        | SetupVarsEntry::ConditionalForwarding
+        | SetupVarsEntry::ConditionalForwardingIp
+        | SetupVarsEntry::ConditionalForwardingDomain
+        | SetupVarsEntry::ConditionalForwardingReverse
+        | SetupVarsEntry::DhcpActive
+        | SetupVarsEntry::DhcpLeasetime
+        | SetupVarsEntry::DhcpStart
+        | SetupVarsEntry::DhcpEnd
+        | SetupVarsEntry::DhcpRouter
+        | SetupVarsEntry::DhcpIpv6 => true,
+        _ => false
+    }
+}
+
 /// Open the dnsmasq config and truncate it
 fn open_config(env: &Env) -> Result<BufWriter<File>, Error> {
     env.write_file(PiholeFile::DnsmasqConfig, false)
@@ -70,6 +96,9 @@ fn write_servers(config_file: &mut BufWriter<File>, env: &Env) -> Result<(), Err
             break;
         }
 
+        // dnsmasq does not use brackets around IPv6 addresses with a port
+        let dns = dns.replace('[', "").replace(']', "");
+
         writeln!(config_file, "server={}", dns).context(ErrorKind::DnsmasqConfigWrite)?;
     }
 
@@ -154,11 +183,15 @@ fn write_dns_options(config_file: &mut BufWriter<File>, env: &Env) -> Result<(),
     if SetupVarsEntry::ConditionalForwarding.is_true(env)? {
         let ip = SetupVarsEntry::ConditionalForwardingIp.read(env)?;
 
+        // Each domain is forwarded to the same server
+        for domain in SetupVarsEntry::ConditionalForwardingDomain.read_list(env)? {
+            writeln!(config_file, "server=/{}/{}", domain, ip)
+                .context(ErrorKind::DnsmasqConfigWrite)?;
+        }
+
         writeln!(
             config_file,
-            "server=/{}/{}\nserver=/{}/{}",
-            SetupVarsEntry::ConditionalForwardingDomain.read(env)?,
-            ip,
+            "server=/{}/{}",
             SetupVarsEntry::ConditionalForwardingReverse.read(env)?,
             ip
         )
@@ -278,6 +311,17 @@ mod tests {
         );
     }
 
+    /// IPv6 servers in bracket notation are written without the brackets
+    #[test]
+    fn ipv6_servers() {
+        test_config(
+            "server=2001:4860:4860::8888\nserver=2001:db8::1#5353\n",
+            "PIHOLE_DNS_1=2001:4860:4860::8888\n\
+             PIHOLE_DNS_2=[2001:db8::1]#5353",
+            write_servers
+        );
+    }
+
     /// Confirm that non-sequential DNS servers are ignored, that is, stop at
     /// the first empty server
     #[test]
@@ -346,6 +390,28 @@ mod tests {
         );
     }
 
+    /// Each conditional forwarding domain is forwarded to the router, in order
+    #[test]
+    fn conditional_forwarding_multiple_domains() {
+        test_config(
+            "interface=eth0\n\
+             server=/lan/192.168.1.1\n\
+             server=/home.arpa/192.168.1.1\n\
+             server=/1.168.192.in-addr.arpa/192.168.1.1\n",
+            "DNS_FQDN_REQUIRED=false\n\
+             DNS_BOGUS_PRIV=false\n\
+             DNSSEC=false\n\
+             HOSTRECORD=\n\
+             DNSMASQ_LISTENING=single\n\
+             PIHOLE_INTERFACE=eth0\n\
+             CONDITIONAL_FORWARDING=true\n\
+             CONDITIONAL_FORWARDING_IP=192.168.1.1\n\
+             CONDITIONAL_FORWARDING_DOMAIN=lan,home.arpa\n\
+             CONDITIONAL_FORWARDING_REVERSE=1.168.192.in-addr.arpa",
+            write_dns_options
+        );
+    }
+
     /// No DHCP settings should be written if DHCP is inactive
     #[test]
     fn dhcp_inactive() {
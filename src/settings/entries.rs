@@ -111,6 +111,13 @@ pub trait ConfigEntry {
             return Err(Error::from(ErrorKind::InvalidSettingValue));
         }
 
+        self.write_unvalidated(value, env)
+    }
+
+    /// Write a value to the config file without validating it. This is used
+    /// for values which users are not allowed to set directly, such as the
+    /// web password.
+    fn write_unvalidated(&self, value: &str, env: &Env) -> Result<(), Error> {
         // Read specified file, removing any line matching the setting we are writing
         let key = self.key();
         let entry_equals = format!("{}=", key);
@@ -157,11 +164,26 @@ pub trait ConfigEntry {
     }
 }
 
+/// The blocking modes supported by FTL
+pub const BLOCKING_MODES: [&str; 4] = ["NULL", "IP-AAAA-NODATA", "IP", "NXDOMAIN"];
+
 /// setupVars.conf file entries
 #[derive(Eq, PartialEq, Hash, Copy, Clone, Debug)]
 pub enum SetupVarsEntry {
+    ApiAnonymizeClients,
+    ApiAuthMaxAttempts,
+    ApiAuthWindow,
+    ApiBindAddress,
+    ApiCorsOrigins,
     ApiExcludeClients,
     ApiExcludeDomains,
+    ApiGravityCountTtl,
+    ApiHideLoopback,
+    ApiHistoryDefaultWindow,
+    ApiMaxResults,
+    ApiMetricsLocalhostOnly,
+    ApiPort,
+    ApiSessionTimeout,
     ApiQueryLogShow,
     BlockingEnabled,
     DnsBogusPriv,
@@ -178,6 +200,7 @@ pub enum SetupVarsEntry {
     DhcpRouter,
     DnsmasqListening,
     Dnssec,
+    FtlSocketPath,
     HostRecord,
     Ipv4Address,
     Ipv6Address,
@@ -197,8 +220,20 @@ impl ConfigEntry for SetupVarsEntry {
 
     fn key(&self) -> Cow<str> {
         match self {
+            SetupVarsEntry::ApiAnonymizeClients => Cow::Borrowed("API_ANONYMIZE_CLIENTS"),
+            SetupVarsEntry::ApiAuthMaxAttempts => Cow::Borrowed("API_AUTH_MAX_ATTEMPTS"),
+            SetupVarsEntry::ApiAuthWindow => Cow::Borrowed("API_AUTH_WINDOW"),
+            SetupVarsEntry::ApiBindAddress => Cow::Borrowed("API_BIND_ADDRESS"),
+            SetupVarsEntry::ApiCorsOrigins => Cow::Borrowed("API_CORS_ORIGINS"),
             SetupVarsEntry::ApiExcludeClients => Cow::Borrowed("API_EXCLUDE_CLIENTS"),
             SetupVarsEntry::ApiExcludeDomains => Cow::Borrowed("API_EXCLUDE_DOMAINS"),
+            SetupVarsEntry::ApiGravityCountTtl => Cow::Borrowed("API_GRAVITY_COUNT_TTL"),
+            SetupVarsEntry::ApiHideLoopback => Cow::Borrowed("API_HIDE_LOOPBACK"),
+            SetupVarsEntry::ApiHistoryDefaultWindow => Cow::Borrowed("API_HISTORY_DEFAULT_WINDOW"),
+            SetupVarsEntry::ApiMaxResults => Cow::Borrowed("API_MAX_RESULTS"),
+            SetupVarsEntry::ApiMetricsLocalhostOnly => Cow::Borrowed("API_METRICS_LOCALHOST_ONLY"),
+            SetupVarsEntry::ApiPort => Cow::Borrowed("API_PORT"),
+            SetupVarsEntry::ApiSessionTimeout => Cow::Borrowed("API_SESSION_TIMEOUT"),
             SetupVarsEntry::ApiQueryLogShow => Cow::Borrowed("API_QUERY_LOG_SHOW"),
             SetupVarsEntry::BlockingEnabled => Cow::Borrowed("BLOCKING_ENABLED"),
             SetupVarsEntry::DnsBogusPriv => Cow::Borrowed("DNS_BOGUS_PRIV"),
@@ -219,6 +254,7 @@ impl ConfigEntry for SetupVarsEntry {
             SetupVarsEntry::DhcpRouter => Cow::Borrowed("DHCP_ROUTER"),
             SetupVarsEntry::DnsmasqListening => Cow::Borrowed("DNSMASQ_LISTENING"),
             SetupVarsEntry::Dnssec => Cow::Borrowed("DNSSEC"),
+            SetupVarsEntry::FtlSocketPath => Cow::Borrowed("FTL_SOCKET_PATH"),
             SetupVarsEntry::HostRecord => Cow::Borrowed("HOSTRECORD"),
             SetupVarsEntry::Ipv4Address => Cow::Borrowed("IPV4_ADDRESS"),
             SetupVarsEntry::Ipv6Address => Cow::Borrowed("IPV6_ADDRESS"),
@@ -234,10 +270,24 @@ impl ConfigEntry for SetupVarsEntry {
 
     fn value_type(&self) -> ValueType {
         match self {
+            SetupVarsEntry::ApiAnonymizeClients => ValueType::Boolean,
+            SetupVarsEntry::ApiAuthMaxAttempts => ValueType::PositiveInteger,
+            SetupVarsEntry::ApiAuthWindow => ValueType::PositiveInteger,
+            SetupVarsEntry::ApiBindAddress => ValueType::IpAddress,
+            SetupVarsEntry::ApiCorsOrigins => {
+                ValueType::Array(&[ValueType::Url, ValueType::String(&["*"])])
+            }
             SetupVarsEntry::ApiExcludeClients => {
                 ValueType::Array(&[ValueType::Hostname, ValueType::Ipv4, ValueType::Ipv6])
             }
             SetupVarsEntry::ApiExcludeDomains => ValueType::Array(&[ValueType::Hostname]),
+            SetupVarsEntry::ApiGravityCountTtl => ValueType::Integer,
+            SetupVarsEntry::ApiHideLoopback => ValueType::Boolean,
+            SetupVarsEntry::ApiHistoryDefaultWindow => ValueType::Integer,
+            SetupVarsEntry::ApiMaxResults => ValueType::PositiveInteger,
+            SetupVarsEntry::ApiMetricsLocalhostOnly => ValueType::Boolean,
+            SetupVarsEntry::ApiPort => ValueType::PortNumber,
+            SetupVarsEntry::ApiSessionTimeout => ValueType::PositiveInteger,
             SetupVarsEntry::ApiQueryLogShow => {
                 ValueType::String(&["all", "permittedonly", "blockedonly", "nothing"])
             }
@@ -245,7 +295,7 @@ impl ConfigEntry for SetupVarsEntry {
             SetupVarsEntry::DnsBogusPriv => ValueType::Boolean,
             SetupVarsEntry::DnsFqdnRequired => ValueType::Boolean,
             SetupVarsEntry::ConditionalForwarding => ValueType::Boolean,
-            SetupVarsEntry::ConditionalForwardingDomain => ValueType::Hostname,
+            SetupVarsEntry::ConditionalForwardingDomain => ValueType::Array(&[ValueType::Hostname]),
             SetupVarsEntry::ConditionalForwardingIp => ValueType::Ipv4,
             SetupVarsEntry::ConditionalForwardingReverse => ValueType::ConditionalForwardingReverse,
             SetupVarsEntry::DhcpActive => ValueType::Boolean,
@@ -256,10 +306,11 @@ impl ConfigEntry for SetupVarsEntry {
             SetupVarsEntry::DhcpRouter => ValueType::Ipv4,
             SetupVarsEntry::DnsmasqListening => ValueType::String(&["all", "local", "single"]),
             SetupVarsEntry::Dnssec => ValueType::Boolean,
+            SetupVarsEntry::FtlSocketPath => ValueType::Path,
             SetupVarsEntry::HostRecord => ValueType::Domain,
             SetupVarsEntry::Ipv4Address => ValueType::Ipv4Mask,
             SetupVarsEntry::Ipv6Address => ValueType::Ipv6,
-            SetupVarsEntry::PiholeDns(_) => ValueType::IPv4OptionalPort,
+            SetupVarsEntry::PiholeDns(_) => ValueType::DnsServer,
             SetupVarsEntry::PiholeDomain => ValueType::Hostname,
             SetupVarsEntry::PiholeInterface => ValueType::Interface,
             SetupVarsEntry::QueryLogging => ValueType::Boolean,
@@ -271,8 +322,20 @@ impl ConfigEntry for SetupVarsEntry {
 
     fn get_default(&self) -> &str {
         match self {
+            SetupVarsEntry::ApiAnonymizeClients => "false",
+            SetupVarsEntry::ApiAuthMaxAttempts => "5",
+            SetupVarsEntry::ApiAuthWindow => "60",
+            SetupVarsEntry::ApiBindAddress => "",
+            SetupVarsEntry::ApiCorsOrigins => "",
             SetupVarsEntry::ApiExcludeClients => "",
             SetupVarsEntry::ApiExcludeDomains => "",
+            SetupVarsEntry::ApiGravityCountTtl => "60",
+            SetupVarsEntry::ApiHideLoopback => "false",
+            SetupVarsEntry::ApiHistoryDefaultWindow => "0",
+            SetupVarsEntry::ApiMaxResults => "10000",
+            SetupVarsEntry::ApiMetricsLocalhostOnly => "true",
+            SetupVarsEntry::ApiPort => "",
+            SetupVarsEntry::ApiSessionTimeout => "1800",
             SetupVarsEntry::ApiQueryLogShow => "all",
             SetupVarsEntry::BlockingEnabled => "true",
             SetupVarsEntry::DnsBogusPriv => "true",
@@ -289,6 +352,7 @@ impl ConfigEntry for SetupVarsEntry {
             SetupVarsEntry::DhcpRouter => "",
             SetupVarsEntry::DnsmasqListening => "local",
             SetupVarsEntry::Dnssec => "false",
+            SetupVarsEntry::FtlSocketPath => "/var/run/pihole/FTL.sock",
             SetupVarsEntry::HostRecord => "",
             SetupVarsEntry::Ipv4Address => "",
             SetupVarsEntry::Ipv6Address => "",
@@ -304,6 +368,68 @@ impl ConfigEntry for SetupVarsEntry {
 }
 
 impl SetupVarsEntry {
+    /// Get every entry which does not take a parameter. `PiholeDns` entries
+    /// are numbered, so they are not included.
+    pub fn variants() -> &'static [SetupVarsEntry] {
+        &[
+            SetupVarsEntry::ApiAnonymizeClients,
+            SetupVarsEntry::ApiAuthMaxAttempts,
+            SetupVarsEntry::ApiAuthWindow,
+            SetupVarsEntry::ApiBindAddress,
+            SetupVarsEntry::ApiCorsOrigins,
+            SetupVarsEntry::ApiExcludeClients,
+            SetupVarsEntry::ApiExcludeDomains,
+            SetupVarsEntry::ApiGravityCountTtl,
+            SetupVarsEntry::ApiHideLoopback,
+            SetupVarsEntry::ApiHistoryDefaultWindow,
+            SetupVarsEntry::ApiMaxResults,
+            SetupVarsEntry::ApiMetricsLocalhostOnly,
+            SetupVarsEntry::ApiPort,
+            SetupVarsEntry::ApiSessionTimeout,
+            SetupVarsEntry::ApiQueryLogShow,
+            SetupVarsEntry::BlockingEnabled,
+            SetupVarsEntry::DnsBogusPriv,
+            SetupVarsEntry::DnsFqdnRequired,
+            SetupVarsEntry::ConditionalForwarding,
+            SetupVarsEntry::ConditionalForwardingDomain,
+            SetupVarsEntry::ConditionalForwardingIp,
+            SetupVarsEntry::ConditionalForwardingReverse,
+            SetupVarsEntry::DhcpActive,
+            SetupVarsEntry::DhcpEnd,
+            SetupVarsEntry::DhcpIpv6,
+            SetupVarsEntry::DhcpLeasetime,
+            SetupVarsEntry::DhcpStart,
+            SetupVarsEntry::DhcpRouter,
+            SetupVarsEntry::DnsmasqListening,
+            SetupVarsEntry::Dnssec,
+            SetupVarsEntry::FtlSocketPath,
+            SetupVarsEntry::HostRecord,
+            SetupVarsEntry::Ipv4Address,
+            SetupVarsEntry::Ipv6Address,
+            SetupVarsEntry::PiholeDomain,
+            SetupVarsEntry::PiholeInterface,
+            SetupVarsEntry::QueryLogging,
+            SetupVarsEntry::WebPassword,
+            SetupVarsEntry::WebLayout,
+            SetupVarsEntry::WebLanguage
+        ]
+    }
+
+    /// Find the entry with the key, such as `PIHOLE_DNS_2`
+    pub fn from_key(key: &str) -> Option<SetupVarsEntry> {
+        if key.starts_with("PIHOLE_DNS_") {
+            return key["PIHOLE_DNS_".len()..]
+                .parse()
+                .ok()
+                .map(SetupVarsEntry::PiholeDns);
+        }
+
+        SetupVarsEntry::variants()
+            .iter()
+            .find(|entry| entry.key() == key)
+            .cloned()
+    }
+
     /// Delete all `SetupVarsEntry::PiholeDns` entries
     pub fn delete_upstream_dns(env: &Env) -> Result<(), Error> {
         let entries: Vec<String> = env
@@ -350,6 +476,7 @@ pub enum FtlConfEntry {
     MaxLogAge,
     PrivacyLevel,
     QueryDisplay,
+    RateLimit,
     RegexDebugMode,
     ResolveIpv4,
     ResolveIpv6,
@@ -373,8 +500,9 @@ impl ConfigEntry for FtlConfEntry {
             FtlConfEntry::MaxLogAge => "MAXLOGAGE",
             FtlConfEntry::PrivacyLevel => "PRIVACYLEVEL",
             FtlConfEntry::QueryDisplay => "QUERY_DISPLAY",
+            FtlConfEntry::RateLimit => "RATE_LIMIT",
             FtlConfEntry::RegexDebugMode => "REGEX_DEBUGMODE",
-            FtlConfEntry::ResolveIpv4 => "RESOLVE_IPV6",
+            FtlConfEntry::ResolveIpv4 => "RESOLVE_IPV4",
             FtlConfEntry::ResolveIpv6 => "RESOLVE_IPV6",
             FtlConfEntry::SocketListening => "SOCKET_LISTENING"
         })
@@ -383,9 +511,7 @@ impl ConfigEntry for FtlConfEntry {
     fn value_type(&self) -> ValueType {
         match self {
             FtlConfEntry::AaaaQueryAnalysis => ValueType::YesNo,
-            FtlConfEntry::BlockingMode => {
-                ValueType::String(&["NULL", "IP-AAAA-NODATA", "IP", "NXDOMAIN"])
-            }
+            FtlConfEntry::BlockingMode => ValueType::String(&BLOCKING_MODES),
             FtlConfEntry::DbFile => ValueType::Path,
             FtlConfEntry::DbInterval => ValueType::Decimal,
             FtlConfEntry::FtlPort => ValueType::PortNumber,
@@ -394,6 +520,7 @@ impl ConfigEntry for FtlConfEntry {
             FtlConfEntry::MaxLogAge => ValueType::Decimal,
             FtlConfEntry::PrivacyLevel => ValueType::String(&["0", "1", "2", "3", "4"]),
             FtlConfEntry::QueryDisplay => ValueType::YesNo,
+            FtlConfEntry::RateLimit => ValueType::RateLimit,
             FtlConfEntry::RegexDebugMode => ValueType::Boolean,
             FtlConfEntry::ResolveIpv4 => ValueType::YesNo,
             FtlConfEntry::ResolveIpv6 => ValueType::YesNo,
@@ -413,6 +540,7 @@ impl ConfigEntry for FtlConfEntry {
             FtlConfEntry::MaxLogAge => "24.0",
             FtlConfEntry::PrivacyLevel => "0",
             FtlConfEntry::QueryDisplay => "yes",
+            FtlConfEntry::RateLimit => "1000/60",
             FtlConfEntry::RegexDebugMode => "false",
             FtlConfEntry::ResolveIpv4 => "yes",
             FtlConfEntry::ResolveIpv6 => "yes",
@@ -421,6 +549,37 @@ impl ConfigEntry for FtlConfEntry {
     }
 }
 
+impl FtlConfEntry {
+    /// Get every entry
+    pub fn variants() -> &'static [FtlConfEntry] {
+        &[
+            FtlConfEntry::AaaaQueryAnalysis,
+            FtlConfEntry::BlockingMode,
+            FtlConfEntry::DbFile,
+            FtlConfEntry::DbInterval,
+            FtlConfEntry::FtlPort,
+            FtlConfEntry::IgnoreLocalHost,
+            FtlConfEntry::MaxDbDays,
+            FtlConfEntry::MaxLogAge,
+            FtlConfEntry::PrivacyLevel,
+            FtlConfEntry::QueryDisplay,
+            FtlConfEntry::RateLimit,
+            FtlConfEntry::RegexDebugMode,
+            FtlConfEntry::ResolveIpv4,
+            FtlConfEntry::ResolveIpv6,
+            FtlConfEntry::SocketListening
+        ]
+    }
+
+    /// Find the entry with the key
+    pub fn from_key(key: &str) -> Option<FtlConfEntry> {
+        FtlConfEntry::variants()
+            .iter()
+            .find(|entry| entry.key() == key)
+            .cloned()
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::{ConfigEntry, SetupVarsEntry};
@@ -14,8 +14,8 @@ mod privacy_level;
 mod value_type;
 
 pub use self::{
-    dnsmasq::generate_dnsmasq_config,
-    entries::{ConfigEntry, FtlConfEntry, SetupVarsEntry},
+    dnsmasq::{generate_dnsmasq_config, is_dnsmasq_entry},
+    entries::{ConfigEntry, FtlConfEntry, SetupVarsEntry, BLOCKING_MODES},
     privacy_level::FtlPrivacyLevel,
     value_type::ValueType
 };
@@ -12,7 +12,7 @@ use crate::util::{Error, ErrorKind};
 use std::str::FromStr;
 
 /// The privacy levels used by FTL
-#[derive(PartialOrd, PartialEq)]
+#[derive(PartialOrd, PartialEq, Copy, Clone)]
 pub enum FtlPrivacyLevel {
     ShowAll,
     HideDomains,
@@ -35,3 +35,21 @@ impl FromStr for FtlPrivacyLevel {
         }
     }
 }
+
+impl FtlPrivacyLevel {
+    /// Get the numeric value of the privacy level, as used by FTL
+    pub fn get_level(self) -> usize {
+        self as usize
+    }
+
+    /// Get the name of the privacy level
+    pub fn get_name(self) -> &'static str {
+        match self {
+            FtlPrivacyLevel::ShowAll => "show_all",
+            FtlPrivacyLevel::HideDomains => "hide_domains",
+            FtlPrivacyLevel::HideDomainsAndClients => "hide_domains_and_clients",
+            FtlPrivacyLevel::Maximum => "maximum",
+            FtlPrivacyLevel::NoStats => "no_stats"
+        }
+    }
+}
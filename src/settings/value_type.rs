@@ -11,7 +11,7 @@
 use get_if_addrs::get_if_addrs;
 use regex::Regex;
 use std::{
-    net::{Ipv4Addr, Ipv6Addr},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr},
     path::Path,
     str::FromStr
 };
@@ -25,22 +25,34 @@ pub enum ValueType {
     Array(&'static [ValueType]),
     ConditionalForwardingReverse,
     Decimal,
+    /// An upstream DNS server: an IPv4 address with an optional port, or an
+    /// IPv6 address, optionally in bracket notation with a port
+    DnsServer,
     Domain,
     #[allow(dead_code)]
     Filename,
     Hostname,
     Integer,
     Interface,
+    /// Any IPv4 or IPv6 address, including the unspecified addresses, such as
+    /// an address to listen on
+    IpAddress,
     Ipv4,
     IPv4OptionalPort,
     Ipv4Mask,
     Ipv6,
+    MacAddress,
     Path,
     PortNumber,
+    PositiveInteger,
+    /// A query rate limit, as `count/interval`: two positive integers
+    RateLimit,
     YesNo,
     WebPassword,
     String(&'static [&'static str]),
-    LanguageCode
+    LanguageCode,
+    /// An HTTP(S) or file URL, such as the address of an adlist
+    Url
 }
 
 impl ValueType {
@@ -73,6 +85,26 @@ impl ValueType {
                 let decimal_re = Regex::new(r"^(\d)+(\.)?(\d)*$").unwrap();
                 decimal_re.is_match(value)
             }
+            ValueType::DnsServer => {
+                if ValueType::IPv4OptionalPort.is_valid(value) || ValueType::Ipv6.is_valid(value) {
+                    return true;
+                }
+
+                // Check for bracket notation, ex. [2001:db8::1]#5353
+                let close = match value.find(']') {
+                    Some(close) if value.starts_with('[') => close,
+                    _ => return false
+                };
+                let ip = &value[1..close];
+                let port = &value[close + 1..];
+
+                ValueType::Ipv6.is_valid(ip)
+                    && (port.is_empty()
+                        || (port.starts_with('#')
+                            && port[1..]
+                                .parse::<usize>()
+                                .map_or(false, |port| port <= 65535)))
+            }
             ValueType::Domain => {
                 // Like a hostname, but must be fully qualified
                 let split: Vec<&str> = value.split('.').collect();
@@ -123,12 +155,19 @@ impl ValueType {
                     .iter()
                     .any(|interface| interface.name == value)
             }
+            ValueType::IpAddress => IpAddr::from_str(value).is_ok(),
             ValueType::Ipv4 => {
                 // Valid and in allowable range
                 // (4 octets)
                 // Test if valid address falls within permitted ranges
                 is_ipv4_valid(value)
             }
+            ValueType::MacAddress => {
+                // Six pairs of hexadecimal digits, colon delimited
+                Regex::new("^([0-9a-fA-F]{2}:){5}[0-9a-fA-F]{2}$")
+                    .unwrap()
+                    .is_match(value)
+            }
             ValueType::IPv4OptionalPort => {
                 // Valid, in allowable range, with optional port
                 // (4 octets, with port from 0 to 65535, colon delimited)
@@ -180,6 +219,22 @@ impl ValueType {
                     false
                 }
             }
+            ValueType::PositiveInteger => {
+                // An integer greater than zero
+                ValueType::Integer.is_valid(value)
+                    && value.parse::<usize>().map_or(false, |number| number > 0)
+            }
+            ValueType::RateLimit => {
+                let mut parts = value.splitn(2, '/');
+
+                match (parts.next(), parts.next()) {
+                    (Some(count), Some(interval)) => {
+                        ValueType::PositiveInteger.is_valid(count)
+                            && ValueType::PositiveInteger.is_valid(interval)
+                    }
+                    _ => false
+                }
+            }
             ValueType::YesNo => match value {
                 "yes" | "no" => true,
                 _ => false
@@ -190,6 +245,9 @@ impl ValueType {
             }
             ValueType::String(strings) => strings.contains(&value),
             ValueType::LanguageCode => Regex::new("^[a-zA-Z]+(-[a-zA-Z]+)*$")
+                .unwrap()
+                .is_match(value),
+            ValueType::Url => Regex::new(r"^(https?://[^\s/?#]+|file://)[^\s#]*$")
                 .unwrap()
                 .is_match(value)
         }
@@ -238,11 +296,18 @@ mod tests {
                 true
             ),
             (ValueType::Decimal, "3.14", true),
+            (ValueType::DnsServer, "8.8.8.8", true),
+            (ValueType::DnsServer, "8.8.8.8:5353", true),
+            (ValueType::DnsServer, "2001:4860:4860::8888", true),
+            (ValueType::DnsServer, "[2001:db8::1]", true),
+            (ValueType::DnsServer, "[2001:db8::1]#5353", true),
             (ValueType::Domain, "domain.com", true),
             (ValueType::Filename, "c3po", true),
             (ValueType::Hostname, "localhost", true),
             (ValueType::Integer, "8675309", true),
             (ValueType::Interface, &available_interface, true),
+            (ValueType::IpAddress, "0.0.0.0", true),
+            (ValueType::IpAddress, "::1", true),
             (ValueType::Ipv4, "192.168.2.9", true),
             (ValueType::IPv4OptionalPort, "192.168.4.5:80", true),
             (ValueType::IPv4OptionalPort, "192.168.3.3", true),
@@ -252,10 +317,19 @@ mod tests {
                 "f7c4:12f8:4f5a:8454:5241:cf80:d61c:3e2c",
                 true
             ),
+            (ValueType::MacAddress, "aa:BB:cc:11:22:33", true),
             (ValueType::Path, "/tmp/directory/file.ext", true),
             (ValueType::PortNumber, "9000", true),
+            (ValueType::PositiveInteger, "10000", true),
+            (ValueType::RateLimit, "1000/60", true),
             (ValueType::YesNo, "yes", true),
             (ValueType::String(&["boxed", ""]), "boxed", true),
+            (
+                ValueType::Url,
+                "https://raw.githubusercontent.com/StevenBlack/hosts/master/hosts",
+                true
+            ),
+            (ValueType::Url, "file:///etc/pihole/local.list", true),
         ];
 
         for (setting, value, result) in tests {
@@ -291,6 +365,10 @@ mod tests {
             ),
             (ValueType::Decimal, "3/4", false),
             (ValueType::Decimal, "3.14.15.26", false),
+            (ValueType::DnsServer, "[2001:db8::1]#65536", false),
+            (ValueType::DnsServer, "[2001:db8::1]:5353", false),
+            (ValueType::DnsServer, "[2001:db8::1", false),
+            (ValueType::DnsServer, "[8.8.8.8]#53", false),
             (ValueType::Domain, "D0#A!N", false),
             (ValueType::Filename, "c3p0/", false),
             (ValueType::Hostname, ".localhost", false),
@@ -300,6 +378,7 @@ mod tests {
             (ValueType::Integer, "9.9", false),
             (ValueType::Integer, "10m3", false),
             (ValueType::Interface, "/dev/net/ev9d9", false),
+            (ValueType::IpAddress, "192.168.0.300", false),
             (ValueType::Ipv4, "192.168.0.3/24", false),
             (ValueType::Ipv4, "192.168.0.2:53", false),
             (ValueType::IPv4OptionalPort, "192.168.4.5 port 1000", false),
@@ -307,10 +386,20 @@ mod tests {
             (ValueType::Ipv4Mask, "192.168.2.9", false),
             (ValueType::Ipv4Mask, "192.168.1.1/qwfp", false),
             (ValueType::Ipv6, "192.168.0.3", false),
+            (ValueType::MacAddress, "aa:bb:cc:11:22", false),
+            (ValueType::MacAddress, "aa-bb-cc-11-22-33", false),
             (ValueType::Path, "~/tmp/directory/file.ext", false),
             (ValueType::PortNumber, "65536", false),
+            (ValueType::PositiveInteger, "0", false),
+            (ValueType::PositiveInteger, "-5", false),
+            (ValueType::RateLimit, "1000", false),
+            (ValueType::RateLimit, "1000/0", false),
+            (ValueType::RateLimit, "10/60/5", false),
             (ValueType::YesNo, "true", false),
             (ValueType::String(&["boxed", ""]), "lan", false),
+            (ValueType::Url, "example.com/hosts", false),
+            (ValueType::Url, "https://example.com/my hosts", false),
+            (ValueType::Url, "ftp://example.com/hosts", false),
         ];
 
         for (setting, value, result) in tests {
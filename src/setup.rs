@@ -9,18 +9,23 @@
 // Please see LICENSE file for your rights under this license.
 
 use crate::{
-    databases::{ftl::FtlDatabase, load_databases},
+    bind::bind_address,
+    compression::Compression,
+    cors::cors_fairing,
+    databases::{ftl::FtlDatabase, gravity::GravityCountCache, load_databases},
     env::{Config, Env},
     ftl::{FtlConnectionType, FtlMemory},
+    metrics::RequestMetrics,
+    reverse_dns::ReverseDnsCache,
     routes::{
         auth::{self, AuthData},
-        dns, settings, stats, version, web
+        dns::{self, BlockingTimer},
+        health, metrics, settings, stats, version, web
     },
     settings::{ConfigEntry, SetupVarsEntry},
     util::{Error, ErrorKind}
 };
 use rocket::config::{ConfigBuilder, Environment};
-use rocket_cors::Cors;
 
 #[cfg(test)]
 use crate::{databases::load_test_databases, env::PiholeFile};
@@ -33,6 +38,11 @@ use tempfile::NamedTempFile;
 
 const CONFIG_LOCATION: &str = "/etc/pihole/API.toml";
 
+#[catch(400)]
+fn bad_request() -> Error {
+    Error::from(ErrorKind::BadRequest)
+}
+
 #[catch(404)]
 fn not_found() -> Error {
     Error::from(ErrorKind::NotFound)
@@ -43,28 +53,44 @@ fn unauthorized() -> Error {
     Error::from(ErrorKind::Unauthorized)
 }
 
+#[catch(422)]
+fn unprocessable_entity() -> Error {
+    Error::from(ErrorKind::UnprocessableEntity)
+}
+
+#[catch(429)]
+fn too_many_requests() -> Error {
+    Error::from(ErrorKind::TooManyRequests)
+}
+
+#[catch(500)]
+fn internal_server_error() -> Error {
+    Error::from(ErrorKind::Unknown)
+}
+
 /// Run the API normally (connect to FTL over the socket)
 pub fn start() -> Result<(), Error> {
     let config = Config::parse(CONFIG_LOCATION)?;
     let env = Env::Production(config);
     let key = SetupVarsEntry::WebPassword.read(&env)?;
+    let (address, port) = bind_address(&env)?;
 
     setup(
         rocket::custom(
             ConfigBuilder::new(Environment::Production)
-                .address(env.config().address())
-                .port(env.config().port() as u16)
+                .address(address)
+                .port(port)
                 .log_level(env.config().log_level()?)
                 .extra("databases", load_databases(&env)?)
                 .finalize()
                 .unwrap()
         ),
-        FtlConnectionType::Socket,
+        FtlConnectionType::socket(&env)?,
         FtlMemory::production(),
         env,
         key,
         true
-    )
+    )?
     .launch();
 
     Ok(())
@@ -80,20 +106,23 @@ pub fn test(
 ) -> Client {
     use toml;
 
-    Client::new(setup(
-        rocket::custom(
-            ConfigBuilder::new(Environment::Development)
-                .log_level(LoggingLevel::Debug)
-                .extra("databases", load_test_databases())
-                .finalize()
-                .unwrap()
-        ),
-        FtlConnectionType::Test(ftl_data),
-        ftl_memory,
-        Env::Test(toml::from_str("").unwrap(), env_data),
-        "test_key".to_owned(),
-        needs_database
-    ))
+    Client::new(
+        setup(
+            rocket::custom(
+                ConfigBuilder::new(Environment::Development)
+                    .log_level(LoggingLevel::Debug)
+                    .extra("databases", load_test_databases())
+                    .finalize()
+                    .unwrap()
+            ),
+            FtlConnectionType::Test(ftl_data),
+            ftl_memory,
+            Env::Test(toml::from_str("").unwrap(), env_data),
+            "test_key".to_owned(),
+            needs_database
+        )
+        .unwrap()
+    )
     .unwrap()
 }
 
@@ -105,11 +134,12 @@ fn setup(
     env: Env,
     api_key: String,
     needs_database: bool
-) -> rocket::Rocket {
-    // Set up CORS
-    let cors = Cors {
-        allow_credentials: true,
-        ..Cors::default()
+) -> Result<rocket::Rocket, Error> {
+    // Attach the CORS handler if any origins are allowed. Preflight requests
+    // are answered by the handler.
+    let server = match cors_fairing(&env)? {
+        Some(cors) => server.attach(cors),
+        None => server
     };
 
     // Attach the databases if required
@@ -122,12 +152,24 @@ fn setup(
     // Create a scheduler for scheduling work (ex. disable for 10 minutes)
     let scheduler = task_scheduler::Scheduler::new();
 
+    // Record request metrics. The fairing and the metrics endpoint share them.
+    let request_metrics = RequestMetrics::default();
+
     // Set up the server
-    server
-        // Attach CORS handler
-        .attach(cors)
+    Ok(server
+        // Compress large responses
+        .attach(Compression)
+        // Record the count and latency of requests
+        .attach(request_metrics.clone())
         // Add custom error handlers
-        .register(catchers![not_found, unauthorized])
+        .register(catchers![
+            bad_request,
+            not_found,
+            unauthorized,
+            unprocessable_entity,
+            too_many_requests,
+            internal_server_error
+        ])
         // Manage the FTL socket configuration
         .manage(ftl_socket)
         // Manage the FTL shared memory configuration
@@ -138,28 +180,61 @@ fn setup(
         .manage(AuthData::new(api_key))
         // Manage the scheduler
         .manage(scheduler)
+        // Manage the timer for re-enabling blocking
+        .manage(BlockingTimer::new())
+        // Manage the gravity domain count cache
+        .manage(GravityCountCache::new())
+        // Manage the reverse DNS cache
+        .manage(ReverseDnsCache::new())
+        // Manage the request metrics
+        .manage(request_metrics)
         // Mount the web interface
         .mount("/", routes![
             web::web_interface_redirect,
             web::web_interface_index,
             web::web_interface
         ])
-        // Mount the API
+        // Mount the metrics endpoint
+        .mount("/", routes![metrics::metrics])
+        // Mount the API. Rocket answers HEAD requests using the matching GET
+        // route, with the same headers but no body.
         .mount("/admin/api", routes![
             version::version,
+            health::health,
             auth::check,
             auth::logout,
+            auth::rotate,
             stats::get_summary,
             stats::top_domains,
             stats::top_clients,
+            stats::top_blocked_clients,
+            stats::top_blocked_domains,
+            stats::unique_counts,
             stats::upstreams,
             stats::query_types,
             stats::history,
+            stats::history_csv,
+            stats::history_db,
             stats::recent_blocked,
+            stats::flush,
+            stats::gravity_info,
             stats::clients,
+            stats::active_clients,
+            stats::clients_last_seen,
+            stats::rate_limited_clients,
+            stats::domain_clients,
+            stats::client_details,
+            stats::client_query_types,
+            stats::client_over_time,
             stats::over_time_history,
+            stats::over_time_block_percentage,
+            stats::privacy_level,
             stats::over_time_clients,
+            stats::over_time_upstreams,
+            stats::over_time_query_types,
+            stats::over_time_reply_types,
             stats::database::get_summary_db,
+            stats::database::clients_db,
             stats::database::over_time_clients_db,
             stats::database::over_time_history_db,
             stats::database::query_types_db,
@@ -174,17 +249,97 @@ fn setup(
             dns::add_whitelist,
             dns::add_blacklist,
             dns::add_regexlist,
+            dns::add_whitelist_bulk,
+            dns::add_blacklist_bulk,
+            dns::add_regexlist_bulk,
+            dns::import_whitelist,
+            dns::import_blacklist,
             dns::delete_whitelist,
             dns::delete_blacklist,
             dns::delete_regexlist,
+            dns::patch_whitelist,
+            dns::patch_blacklist,
+            dns::patch_regexlist,
+            dns::move_domain,
+            dns::search,
+            dns::get_audit,
+            dns::export_lists,
+            settings::get_adlists,
+            settings::add_adlist,
+            settings::delete_adlist,
+            settings::put_batch,
             settings::get_dhcp,
             settings::put_dhcp,
+            settings::get_dhcp_leases,
+            settings::add_static_lease,
+            settings::delete_static_lease,
             settings::get_dns,
             settings::put_dns,
+            settings::get_dns_records,
+            settings::add_dns_record,
+            settings::delete_dns_record,
+            settings::get_cname_records,
+            settings::add_cname_record,
+            settings::delete_cname_record,
             settings::get_ftldb,
             settings::get_ftl,
+            settings::get_blocking_mode,
+            settings::put_blocking_mode,
+            settings::get_db_interval,
+            settings::put_db_interval,
+            settings::optimize_database,
+            settings::get_max_db_days,
+            settings::put_max_db_days,
+            settings::get_query_logging,
+            settings::put_query_logging,
+            settings::get_rate_limit,
+            settings::put_rate_limit,
             settings::get_network,
+            settings::validate_setup_vars,
+            settings::validate_ftl_config,
             settings::get_web,
             settings::put_web
-        ])
+        ]))
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{env::PiholeFile, testing::TestBuilder};
+    use rocket::http::{Method, Status};
+
+    /// Unknown routes get the JSON error body instead of an HTML page
+    #[test]
+    fn not_found() {
+        TestBuilder::new()
+            .endpoint("/admin/api/does/not/exist")
+            .expect_status(Status::NotFound)
+            .expect_json(json!({
+                "error": {
+                    "key": "not_found",
+                    "message": "Not found",
+                    "data": None::<()>
+                }
+            }))
+            .test();
+    }
+
+    /// A body which does not match the expected structure gets the JSON error
+    /// body
+    #[test]
+    fn unprocessable_entity() {
+        TestBuilder::new()
+            .endpoint("/admin/api/settings/ftl/max_db_days")
+            .method(Method::Put)
+            .file_expect(PiholeFile::FtlConfig, "", "")
+            .body(json!({ "days": "ten" }))
+            .expect_status(Status::UnprocessableEntity)
+            .expect_json(json!({
+                "error": {
+                    "key": "unprocessable_entity",
+                    "message": "Unprocessable entity",
+                    "data": None::<()>
+                }
+            }))
+            .test();
+    }
 }
@@ -10,7 +10,10 @@
 
 use crate::{
     env::PiholeFile,
-    ftl::{FtlCounters, FtlMemory, FtlSettings},
+    ftl::{
+        FtlCounters, FtlDnssecType, FtlMemory, FtlQuery, FtlQueryReplyType, FtlQueryStatus,
+        FtlQueryType, FtlSettings, MAGIC_BYTE
+    },
     setup
 };
 use rocket::http::{ContentType, Header, Method, Status};
@@ -26,6 +29,29 @@ pub fn write_eom(data: &mut Vec<u8>) {
     data.push(0xc1);
 }
 
+/// Make a complete, forwarded A query from client 0 for domain 0, at
+/// timestamp 1. Tests set the fields they use with struct update syntax.
+pub fn test_query() -> FtlQuery {
+    FtlQuery {
+        magic: MAGIC_BYTE,
+        id: 1,
+        database_id: 0,
+        timestamp: 1,
+        time_index: 1,
+        response_time: 1,
+        domain_id: 0,
+        client_id: 0,
+        upstream_id: 0,
+        query_type: FtlQueryType::A,
+        status: FtlQueryStatus::Forward,
+        reply_type: FtlQueryReplyType::IP,
+        dnssec_type: FtlDnssecType::Unspecified,
+        is_complete: true,
+        is_private: false,
+        ad_bit: false
+    }
+}
+
 /// Builds the data needed to create a `Env::Test`
 pub struct TestEnvBuilder {
     test_files: Vec<TestFile<NamedTempFile>>
@@ -145,6 +171,7 @@ pub struct TestBuilder {
     test_config_builder: TestEnvBuilder,
     expected_json: serde_json::Value,
     expected_status: Status,
+    expected_headers: Vec<Header<'static>>,
     needs_database: bool
 }
 
@@ -174,6 +201,7 @@ impl TestBuilder {
             })
             .into(),
             expected_status: Status::Ok,
+            expected_headers: Vec::new(),
             needs_database: false
         }
     }
@@ -240,6 +268,11 @@ impl TestBuilder {
         self
     }
 
+    pub fn expect_header<H: Into<Header<'static>>>(mut self, header: H) -> Self {
+        self.expected_headers.push(header.into());
+        self
+    }
+
     pub fn need_database(mut self, need_database: bool) -> Self {
         self.needs_database = need_database;
         self
@@ -285,18 +318,41 @@ impl TestBuilder {
         // Check the status
         assert_eq!(self.expected_status, response.status());
 
-        // Check that something was returned
-        let body = response.body_string();
-        assert!(body.is_some());
-
-        let body_str = body.unwrap();
-        println!("Body:\n{}", body_str);
+        // Check the headers
+        for header in &self.expected_headers {
+            assert_eq!(
+                response.headers().get_one(header.name().as_str()),
+                Some(header.value())
+            );
+        }
 
-        // Check that it is correct JSON
-        let parsed: serde_json::Value = serde_json::from_str(&body_str).unwrap();
+        let body = response.body_string();
 
-        // Check that is is the same as the expected JSON
-        assert_eq!(self.expected_json, parsed);
+        if self.expected_status == Status::NotModified || self.method == Method::Head {
+            // Not Modified and HEAD replies do not have a body
+            assert!(body.map_or(true, |body| body.is_empty()));
+        } else {
+            // Check that something was returned
+            assert!(body.is_some());
+
+            let body_str = body.unwrap();
+            println!("Body:\n{}", body_str);
+
+            // Check that it is correct JSON
+            let mut parsed: serde_json::Value = serde_json::from_str(&body_str).unwrap();
+
+            // Timed replies include the processing time, which can not be known
+            // ahead of time, so only check that it is a number
+            if let Some(took_ms) = parsed
+                .as_object_mut()
+                .and_then(|reply| reply.remove("took_ms"))
+            {
+                assert!(took_ms.is_number());
+            }
+
+            // Check that is is the same as the expected JSON
+            assert_eq!(self.expected_json, parsed);
+        }
 
         // Check the files against the expected data
         let mut buffer = String::new();
@@ -8,11 +8,12 @@
 // This file is copyright under the latest version of the EUPL.
 // Please see LICENSE file for your rights under this license.
 
+use crate::ftl::FtlQueryType;
 use failure::{Backtrace, Context, Fail};
 use rocket::{
-    http::Status,
-    request,
-    response::{self, Responder, Response},
+    http::{ContentType, RawStr, Status},
+    request::{self, FromFormValue, FromRequest},
+    response::{self, content::Content, Responder, Response},
     Outcome, Request
 };
 use rocket_contrib::json::JsonValue;
@@ -20,12 +21,20 @@ use serde::Serialize;
 use shmem;
 use std::{
     env,
-    fmt::{self, Display}
+    fmt::{self, Display},
+    time::Instant
 };
 
 /// Type alias for the most common return type of the API methods
 pub type Reply = Result<SetStatus<JsonValue>, Error>;
 
+/// Type alias for the return type of API methods which reply in CSV format
+pub type CsvReply = Result<Content<String>, Error>;
+
+/// Type alias for the return type of paginated API methods which reply in CSV
+/// format
+pub type CsvPageReply = Result<CsvPage, Error>;
+
 /// The most general reply builder. It takes in data/errors and status to
 /// construct the JSON reply.
 pub fn reply<D: Serialize>(data: Result<D, Error>, status: Status) -> Reply {
@@ -34,7 +43,7 @@ pub fn reply<D: Serialize>(data: Result<D, Error>, status: Status) -> Reply {
         Err(e) => {
             // Only print out the error if it's not a common error
             match e.kind() {
-                ErrorKind::Unauthorized | ErrorKind::NotFound => (),
+                ErrorKind::Unauthorized | ErrorKind::NotFound | ErrorKind::TooManyRequests => (),
                 _ => e.print_stacktrace()
             }
 
@@ -86,6 +95,291 @@ pub fn reply_success() -> Reply {
     reply(Ok(json!({ "status": "success" })), Status::Ok)
 }
 
+/// Create a reply from a Result like [`reply_result`], but with the data in
+/// an envelope which includes the processing time:
+/// `{ "data": ..., "took_ms": ... }`. Errors are replied as usual.
+///
+/// [`reply_result`]: fn.reply_result.html
+pub fn reply_timed<D: Serialize>(data: Result<D, Error>, timer: RequestTimer) -> Reply {
+    match data {
+        Ok(data) => {
+            let mut data = json!(data);
+            timer.time_format.format_timestamps(&mut data);
+
+            reply_data(json!({
+                "data": data,
+                "took_ms": timer.elapsed_ms()
+            }))
+        }
+        Err(error) => reply_error(error)
+    }
+}
+
+/// Create a CSV reply from a list of JSON objects. The first line holds the
+/// column names, and each object becomes a line with the values of those
+/// columns. String values are quoted so that commas in them do not break the
+/// format, and missing values are left empty.
+pub fn reply_csv(columns: &[&str], records: &[JsonValue]) -> CsvReply {
+    let mut csv = columns.join(",");
+    csv.push('\n');
+
+    for record in records {
+        let values: Vec<String> = columns
+            .iter()
+            .map(|&column| match record.get(column) {
+                Some(serde_json::Value::String(value)) => {
+                    format!("\"{}\"", value.replace('"', "\"\""))
+                }
+                Some(serde_json::Value::Null) | None => String::new(),
+                Some(value) => value.to_string()
+            })
+            .collect();
+
+        csv.push_str(&values.join(","));
+        csv.push('\n');
+    }
+
+    Ok(Content(ContentType::CSV, csv))
+}
+
+/// A request guard which succeeds if the client prefers a CSV reply, according
+/// to the `Accept` header. Otherwise, the request is forwarded.
+pub struct AcceptCsv;
+
+impl<'a, 'r> FromRequest<'a, 'r> for AcceptCsv {
+    type Error = ();
+
+    fn from_request(request: &'a Request<'r>) -> request::Outcome<Self, ()> {
+        let prefers_csv = request.accept().map_or(false, |accept| {
+            let media_type = accept.preferred().media_type();
+            media_type.top() == "text" && media_type.sub() == "csv"
+        });
+
+        if prefers_csv {
+            Outcome::Success(AcceptCsv)
+        } else {
+            Outcome::Forward(())
+        }
+    }
+}
+
+/// A request guard which records when the API started handling the request.
+/// It should be the first guard of a route so that the other guards are
+/// included in the time. It also reads the `time_format` parameter, since
+/// the timestamps of timed replies are formatted with it. An invalid time
+/// format is a bad request. See [`reply_timed`].
+///
+/// [`reply_timed`]: fn.reply_timed.html
+pub struct RequestTimer {
+    start: Instant,
+    time_format: TimeFormat
+}
+
+impl RequestTimer {
+    /// Get the number of milliseconds since the request started being handled
+    fn elapsed_ms(&self) -> f64 {
+        let elapsed = self.start.elapsed();
+
+        elapsed.as_secs() as f64 * 1000.0 + elapsed.subsec_nanos() as f64 / 1_000_000.0
+    }
+}
+
+impl<'a, 'r> FromRequest<'a, 'r> for RequestTimer {
+    type Error = Error;
+
+    fn from_request(request: &'a Request<'r>) -> request::Outcome<Self, Error> {
+        let start = Instant::now();
+        let time_format = match request.get_query_value("time_format") {
+            Some(Ok(time_format)) => time_format,
+            Some(Err(_)) => return Error::from(ErrorKind::BadRequest).into_outcome(),
+            None => TimeFormat::Unix
+        };
+
+        Outcome::Success(RequestTimer { start, time_format })
+    }
+}
+
+/// The keys of the reply fields which hold Unix timestamps
+const TIMESTAMP_KEYS: &[&str] = &["timestamp", "updated", "last_seen"];
+
+/// The formats which timestamps can be replied in, set by the `time_format`
+/// parameter
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum TimeFormat {
+    /// Seconds since the Unix epoch (the default)
+    Unix,
+    /// RFC 3339 date and time strings in UTC, such as `2019-01-01T00:00:00Z`
+    Rfc3339
+}
+
+impl TimeFormat {
+    /// Format the timestamps of the reply data. Unix timestamps are kept as
+    /// they are.
+    fn format_timestamps(self, value: &mut serde_json::Value) {
+        if self == TimeFormat::Unix {
+            return;
+        }
+
+        match value {
+            serde_json::Value::Array(items) => {
+                for item in items {
+                    self.format_timestamps(item);
+                }
+            }
+            serde_json::Value::Object(fields) => {
+                for (key, item) in fields.iter_mut() {
+                    match item.as_u64() {
+                        Some(timestamp) if TIMESTAMP_KEYS.contains(&key.as_str()) => {
+                            *item = serde_json::Value::String(format_rfc3339(timestamp));
+                        }
+                        _ => self.format_timestamps(item)
+                    }
+                }
+            }
+            _ => ()
+        }
+    }
+}
+
+impl<'v> FromFormValue<'v> for TimeFormat {
+    type Error = &'v RawStr;
+
+    fn from_form_value(form_value: &'v RawStr) -> Result<Self, Self::Error> {
+        match form_value.as_str() {
+            "unix" => Ok(TimeFormat::Unix),
+            "rfc3339" => Ok(TimeFormat::Rfc3339),
+            _ => Err(form_value)
+        }
+    }
+}
+
+/// Format a Unix timestamp as an RFC 3339 date and time in UTC
+fn format_rfc3339(timestamp: u64) -> String {
+    let seconds = timestamp % 86400;
+
+    // Convert the days since the epoch into a date, see
+    // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let days = timestamp / 86400 + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = era * 400 + year_of_era + if month <= 2 { 1 } else { 0 };
+
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    )
+}
+
+/// A request guard which holds the entity tags of the request's
+/// `If-None-Match` header, for conditional requests. See [`reply_tagged`].
+///
+/// [`reply_tagged`]: fn.reply_tagged.html
+pub struct IfNoneMatch(Vec<String>);
+
+impl IfNoneMatch {
+    /// Check if the ETag matches one of the request's entity tags. Tags are
+    /// compared weakly, so a `W/` prefix does not matter.
+    fn matches(&self, etag: &str) -> bool {
+        let etag = etag.trim_start_matches("W/");
+
+        self.0
+            .iter()
+            .any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag)
+    }
+}
+
+impl<'a, 'r> FromRequest<'a, 'r> for IfNoneMatch {
+    type Error = ();
+
+    fn from_request(request: &'a Request<'r>) -> request::Outcome<Self, ()> {
+        Outcome::Success(IfNoneMatch(
+            request
+                .headers()
+                .get("If-None-Match")
+                .flat_map(|value| value.split(','))
+                .map(|tag| tag.trim().to_owned())
+                .collect()
+        ))
+    }
+}
+
+/// A reply with an ETag header, created by [`reply_tagged`]
+///
+/// [`reply_tagged`]: fn.reply_tagged.html
+pub enum TaggedReply {
+    /// The client does not have the current data, so it is sent
+    Modified(String, Reply),
+    /// The client already has the current data, so only the ETag is sent
+    NotModified(String)
+}
+
+/// Create a reply with an ETag. If the ETag matches the request's
+/// `If-None-Match` header, the reply is an empty 304 Not Modified instead, and
+/// `reply` is not called.
+pub fn reply_tagged<F: FnOnce() -> Reply>(
+    etag: String,
+    if_none_match: &IfNoneMatch,
+    reply: F
+) -> TaggedReply {
+    if if_none_match.matches(&etag) {
+        TaggedReply::NotModified(etag)
+    } else {
+        TaggedReply::Modified(etag, reply())
+    }
+}
+
+impl<'r> Responder<'r> for TaggedReply {
+    fn respond_to(self, request: &Request) -> response::Result<'r> {
+        match self {
+            TaggedReply::Modified(etag, Ok(reply)) => {
+                Ok(Response::build_from(reply.respond_to(request)?)
+                    .raw_header("ETag", etag)
+                    .finalize())
+            }
+            // Errors are not tagged, since they are not the data being tagged
+            TaggedReply::Modified(_, Err(error)) => error.respond_to(request),
+            TaggedReply::NotModified(etag) => Ok(Response::build()
+                .status(Status::NotModified)
+                .raw_header("ETag", etag)
+                .finalize())
+        }
+    }
+}
+
+/// A page of a CSV reply. CSV has no room for the cursor of the next page, so
+/// it is sent in the `X-Pi-hole-Cursor` header when there are more records.
+pub struct CsvPage {
+    pub csv: Content<String>,
+    pub cursor: Option<String>
+}
+
+impl<'r> Responder<'r> for CsvPage {
+    fn respond_to(self, request: &Request) -> response::Result<'r> {
+        let mut response = Response::build_from(self.csv.respond_to(request)?);
+
+        if let Some(cursor) = self.cursor {
+            response.raw_header("X-Pi-hole-Cursor", cursor);
+        }
+
+        Ok(response.finalize())
+    }
+}
+
 /// Wraps `ErrorKind` to provide context via `Context`.
 ///
 /// See https://boats.gitlab.io/failure/error-errorkind.html
@@ -112,12 +406,26 @@ pub enum ErrorKind {
     NotFound,
     #[fail(display = "Item already exists")]
     AlreadyExists,
+    #[fail(display = "A local DNS record already exists for the domain")]
+    ConflictingDnsRecord(String, String),
     #[fail(display = "Invalid domain")]
     InvalidDomain,
+    #[fail(display = "Invalid regex: {}", _0)]
+    InvalidRegex(String),
+    #[fail(display = "Invalid list entry: {}", _0)]
+    InvalidListEntry(String),
     #[fail(display = "Bad request")]
     BadRequest,
+    #[fail(display = "Invalid query type")]
+    InvalidQueryType,
     #[fail(display = "Unauthorized")]
     Unauthorized,
+    #[fail(display = "Too many requests")]
+    TooManyRequests,
+    #[fail(display = "Unprocessable entity")]
+    UnprocessableEntity,
+    #[fail(display = "Payload too large")]
+    PayloadTooLarge,
     #[fail(display = "Error reading from {}", _0)]
     FileRead(String),
     #[fail(display = "Error writing to {}", _0)]
@@ -126,6 +434,12 @@ pub enum ErrorKind {
     ConfigParsingError,
     #[fail(display = "Invalid setting value")]
     InvalidSettingValue,
+    #[fail(display = "Invalid setting value at index {}", _0)]
+    InvalidSettingListValue(usize),
+    #[fail(display = "Invalid setting value")]
+    InvalidSettingOption(&'static [&'static str]),
+    #[fail(display = "Invalid setting: {}", _0)]
+    InvalidSetting(String),
     #[fail(display = "Failed to restart the DNS server")]
     RestartDnsError,
     #[fail(display = "Failed to reload the DNS server")]
@@ -148,7 +462,19 @@ pub enum ErrorKind {
     )]
     SharedMemoryVersion(usize, usize),
     #[fail(display = "Error while interacting with the FTL database")]
-    FtlDatabase
+    FtlDatabase,
+    #[fail(display = "Failed to parse version information")]
+    VersionParse,
+    #[fail(display = "FTL did not acknowledge the flush")]
+    FtlFlushError,
+    #[fail(display = "Invalid CORS configuration: {}", _0)]
+    CorsConfig(String),
+    #[fail(display = "Invalid API bind configuration: {}", _0)]
+    BindConfig(String),
+    #[fail(display = "Invalid FTL socket path: {}", _0)]
+    InvalidFtlSocketPath(String),
+    #[fail(display = "The FTL database does not exist")]
+    FtlDatabaseMissing
 }
 
 impl Error {
@@ -216,26 +542,43 @@ impl ErrorKind {
         match self {
             ErrorKind::Unknown => "unknown",
             ErrorKind::GravityError => "gravity_error",
-            ErrorKind::FtlConnectionFail => "ftl_connection_fail",
+            // FTL not running is reported the same way, whether the socket or
+            // the shared memory is used
+            ErrorKind::FtlConnectionFail | ErrorKind::SharedMemoryOpen(_) => "ftl_unavailable",
             ErrorKind::FtlReadError => "ftl_read_error",
             ErrorKind::FtlEomError => "ftl_eom_error",
             ErrorKind::NotFound => "not_found",
             ErrorKind::AlreadyExists => "already_exists",
+            ErrorKind::ConflictingDnsRecord(_, _) => "conflicting_dns_record",
             ErrorKind::InvalidDomain => "invalid_domain",
+            ErrorKind::InvalidRegex(_) => "invalid_regex",
+            ErrorKind::InvalidListEntry(_) => "invalid_list_entry",
             ErrorKind::BadRequest => "bad_request",
+            ErrorKind::InvalidQueryType => "invalid_query_type",
             ErrorKind::Unauthorized => "unauthorized",
+            ErrorKind::TooManyRequests => "too_many_requests",
+            ErrorKind::UnprocessableEntity => "unprocessable_entity",
+            ErrorKind::PayloadTooLarge => "payload_too_large",
             ErrorKind::FileRead(_) => "file_read",
             ErrorKind::FileWrite(_) => "file_write",
             ErrorKind::ConfigParsingError => "config_parsing_error",
             ErrorKind::InvalidSettingValue => "invalid_setting_value",
+            ErrorKind::InvalidSettingListValue(_) => "invalid_setting_list_value",
+            ErrorKind::InvalidSettingOption(_) => "invalid_setting_option",
+            ErrorKind::InvalidSetting(_) => "invalid_setting",
             ErrorKind::RestartDnsError => "restart_dns_error",
             ErrorKind::ReloadDnsError => "reload_dns_error",
             ErrorKind::DnsmasqConfigWrite => "dnsmasq_config_write",
-            ErrorKind::SharedMemoryOpen(_) => "shared_memory_open",
             ErrorKind::SharedMemoryRead => "shared_memory_read",
             ErrorKind::SharedMemoryLock => "shared_memory_lock",
             ErrorKind::SharedMemoryVersion(_, _) => "shared_memory_version",
-            ErrorKind::FtlDatabase => "ftl_database"
+            ErrorKind::FtlDatabase => "ftl_database",
+            ErrorKind::VersionParse => "version_parse",
+            ErrorKind::FtlFlushError => "ftl_flush_error",
+            ErrorKind::CorsConfig(_) => "cors_config",
+            ErrorKind::BindConfig(_) => "bind_config",
+            ErrorKind::InvalidFtlSocketPath(_) => "invalid_ftl_socket_path",
+            ErrorKind::FtlDatabaseMissing => "ftl_database_missing"
         }
     }
 
@@ -243,14 +586,27 @@ impl ErrorKind {
     pub fn status(&self) -> Status {
         match self {
             ErrorKind::NotFound => Status::NotFound,
-            ErrorKind::AlreadyExists => Status::Conflict,
-            ErrorKind::InvalidDomain | ErrorKind::BadRequest | ErrorKind::InvalidSettingValue => {
-                Status::BadRequest
-            }
+            ErrorKind::AlreadyExists | ErrorKind::ConflictingDnsRecord(_, _) => Status::Conflict,
+            ErrorKind::InvalidDomain
+            | ErrorKind::InvalidRegex(_)
+            | ErrorKind::InvalidListEntry(_)
+            | ErrorKind::BadRequest
+            | ErrorKind::InvalidQueryType
+            | ErrorKind::InvalidSettingValue
+            | ErrorKind::InvalidSettingListValue(_)
+            | ErrorKind::InvalidSettingOption(_)
+            | ErrorKind::InvalidSetting(_) => Status::BadRequest,
             ErrorKind::Unauthorized => Status::Unauthorized,
+            ErrorKind::TooManyRequests => Status::TooManyRequests,
+            ErrorKind::UnprocessableEntity => Status::UnprocessableEntity,
+            ErrorKind::PayloadTooLarge => Status::PayloadTooLarge,
+            ErrorKind::FtlFlushError
+            | ErrorKind::FtlConnectionFail
+            | ErrorKind::SharedMemoryOpen(_)
+            | ErrorKind::SharedMemoryVersion(_, _)
+            | ErrorKind::FtlDatabaseMissing => Status::ServiceUnavailable,
             ErrorKind::Unknown
             | ErrorKind::GravityError
-            | ErrorKind::FtlConnectionFail
             | ErrorKind::FtlReadError
             | ErrorKind::FtlEomError
             | ErrorKind::FileRead(_)
@@ -259,11 +615,13 @@ impl ErrorKind {
             | ErrorKind::RestartDnsError
             | ErrorKind::ReloadDnsError
             | ErrorKind::DnsmasqConfigWrite
-            | ErrorKind::SharedMemoryOpen(_)
             | ErrorKind::SharedMemoryRead
             | ErrorKind::SharedMemoryLock
-            | ErrorKind::SharedMemoryVersion(_, _)
-            | ErrorKind::FtlDatabase => Status::InternalServerError
+            | ErrorKind::FtlDatabase
+            | ErrorKind::VersionParse
+            | ErrorKind::CorsConfig(_)
+            | ErrorKind::BindConfig(_)
+            | ErrorKind::InvalidFtlSocketPath(_) => Status::InternalServerError
         }
     }
 
@@ -272,6 +630,23 @@ impl ErrorKind {
         match self {
             ErrorKind::FileRead(file) => Some(json!({ "file": file })),
             ErrorKind::FileWrite(file) => Some(json!({ "file": file })),
+            ErrorKind::InvalidListEntry(entry) => Some(json!({ "entry": entry })),
+            ErrorKind::ConflictingDnsRecord(domain, ip) => Some(json!({
+                "record": { "domain": domain, "ip": ip }
+            })),
+            ErrorKind::InvalidSettingListValue(index) => Some(json!({ "index": index })),
+            ErrorKind::InvalidSettingOption(options) => Some(json!({ "options": options })),
+            ErrorKind::InvalidSetting(key) => Some(json!({ "key": key })),
+            ErrorKind::SharedMemoryVersion(found, expected) => Some(json!({
+                "found": found,
+                "expected": expected
+            })),
+            ErrorKind::InvalidQueryType => Some(json!({
+                "options": FtlQueryType::variants()
+                    .iter()
+                    .map(|query_type| query_type.get_name())
+                    .collect::<Vec<String>>()
+            })),
             _ => None
         }
     }
@@ -339,3 +714,32 @@ impl<'r, R: Responder<'r>> Responder<'r> for SetStatus<R> {
             .finalize())
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::{format_rfc3339, ErrorKind};
+    use rocket::http::Status;
+
+    /// Timestamps are formatted as dates and times in UTC, including leap
+    /// days
+    #[test]
+    fn rfc3339() {
+        assert_eq!(format_rfc3339(0), "1970-01-01T00:00:00Z");
+        assert_eq!(format_rfc3339(951_827_696), "2000-02-29T12:34:56Z");
+        assert_eq!(format_rfc3339(1_546_300_799), "2018-12-31T23:59:59Z");
+        assert_eq!(format_rfc3339(1_546_300_800), "2019-01-01T00:00:00Z");
+    }
+
+    /// FTL not running is reported as unavailable, whether it was reached
+    /// through the socket or the shared memory
+    #[test]
+    fn ftl_unavailable() {
+        for kind in &[
+            ErrorKind::FtlConnectionFail,
+            ErrorKind::SharedMemoryOpen("/FTL-queries".to_owned())
+        ] {
+            assert_eq!(kind.key(), "ftl_unavailable");
+            assert_eq!(kind.status(), Status::ServiceUnavailable);
+        }
+    }
+}